@@ -3,9 +3,10 @@
 //! Compiles eBPF program and generates Rust bindings at build time:
 //! 1. Compiles C eBPF code to BPF bytecode
 //! 2. Generates Rust skeleton for safe interaction
-//! 3. Compiles protobuf definitions for gRPC
+//! 3. Compiles protobuf definitions for gRPC (`grpc` feature only)
+//! 4. Embeds the git SHA for version reporting
 
-use std::{env, ffi::OsStr, path::PathBuf};
+use std::{env, ffi::OsStr, path::PathBuf, process::Command};
 
 use libbpf_cargo::SkeletonBuilder;
 
@@ -26,13 +27,41 @@ fn main() {
         .build_and_generate(&out)
         .expect("Failed to build BPF skeleton");
 
-    // Generate gRPC service code from protobuf
+    #[cfg(feature = "grpc")]
+    compile_protos();
+
+    // Embed the git SHA; AEGIS_GIT_SHA overrides it for builds without a checkout
+    let git_sha = env::var("AEGIS_GIT_SHA")
+        .ok()
+        .or_else(git_sha)
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=AEGIS_GIT_SHA={}", git_sha);
+    println!("cargo:rerun-if-env-changed=AEGIS_GIT_SHA");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+
+    println!("cargo:rerun-if-changed={}", BPF_SOURCE);
+}
+
+/// Generates the gRPC service code from protobuf.
+#[cfg(feature = "grpc")]
+fn compile_protos() {
     tonic_prost_build::configure()
         .build_server(true)
         .build_client(false)
         .compile_protos(&["../proto/session.proto"], &["../proto"])
         .expect("Failed to compile protobuf. Ensure protoc is installed.");
-
     println!("cargo:rerun-if-changed=../proto/session.proto");
-    println!("cargo:rerun-if-changed={}", BPF_SOURCE);
+}
+
+/// Returns the short SHA of the current git checkout, if any.
+fn git_sha() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = String::from_utf8(output.stdout).ok()?;
+    Some(sha.trim().to_string())
 }
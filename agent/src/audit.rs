@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// One mutating RPC as recorded in the audit trail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditEvent<'a> {
+    /// Caller: `cn=<name>`, `ip=<addr>`, `uid=<n>` or `local`
+    pub actor: &'a str,
+    /// Operation, e.g. `activate_session` or `flush_all`
+    pub op: &'a str,
+    /// What was changed: a session tuple, an IP change, an IP or `*`
+    pub tuple: &'a str,
+    /// Outcome, e.g. `added`, `failed` or `denied`
+    pub result: &'a str,
+}
+
+/// Destination for audit events.
+pub trait AuditSink: Send + Sync {
+    /// Records one event. Errors are reported by the caller; the RPC itself
+    /// has already been applied.
+    fn record(&self, event: &AuditEvent) -> Result<()>;
+}
+
+/// Writes audit events to the agent's log at `info` with `audit = true`, so
+/// a subscriber can route them separately.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogSink;
+
+impl AuditSink for LogSink {
+    fn record(&self, event: &AuditEvent) -> Result<()> {
+        info!(
+            audit = true,
+            peer = %event.actor,
+            operation = %event.op,
+            resource = %event.tuple,
+            result = %event.result,
+            "Audit record"
+        );
+        Ok(())
+    }
+}
+
+/// Appends one JSON object per event to a file.
+///
+/// The file is opened with `O_APPEND` and each line is handed to the kernel
+/// in a single write followed by a flush, so records from concurrent RPCs
+/// never interleave and a crash loses at most the record being written.
+pub struct FileSink {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it with mode 0600 if missing.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("Failed to open audit file: {}", path.display()))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, event: &AuditEvent) -> Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let line = format_event(event, timestamp_ms);
+
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit file lock poisoned"))?;
+        writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush())
+            .context("Failed to write audit record")
+    }
+}
+
+/// Formats an event as one JSON line.
+fn format_event(event: &AuditEvent, timestamp_ms: u128) -> String {
+    format!(
+        "{{\"timestamp_ms\":{},\"actor\":{},\"op\":{},\"tuple\":{},\"result\":{}}}\n",
+        timestamp_ms,
+        json_string(event.actor),
+        json_string(event.op),
+        json_string(event.tuple),
+        json_string(event.result)
+    )
+}
+
+/// Quotes `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(
+            json_string("10.0.0.1 -> 10.0.0.2:80"),
+            "\"10.0.0.1 -> 10.0.0.2:80\""
+        );
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+
+    #[test]
+    fn test_file_sink_appends_events() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("audit.jsonl");
+        let sink = FileSink::open(&path).unwrap();
+
+        sink.record(&AuditEvent {
+            actor: "cn=controller",
+            op: "activate_session",
+            tuple: "10.0.0.1 -> 172.21.0.10:443",
+            result: "added",
+        })
+        .unwrap();
+        // A second sink on the same file appends instead of truncating
+        FileSink::open(&path)
+            .unwrap()
+            .record(&AuditEvent {
+                actor: "ip=10.0.0.5",
+                op: "block_ip",
+                tuple: "198.51.100.7",
+                result: "ok",
+            })
+            .unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"timestamp_ms\":"), "{}", lines[0]);
+        assert!(
+            lines[0].ends_with(
+                "\"actor\":\"cn=controller\",\"op\":\"activate_session\",\
+                 \"tuple\":\"10.0.0.1 -> 172.21.0.10:443\",\"result\":\"added\"}"
+            ),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].ends_with(
+                "\"actor\":\"ip=10.0.0.5\",\"op\":\"block_ip\",\
+                 \"tuple\":\"198.51.100.7\",\"result\":\"ok\"}"
+            ),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn test_file_sink_missing_dir_fails() {
+        assert!(FileSink::open(Path::new("/nonexistent/dir/audit.jsonl")).is_err());
+    }
+}
@@ -1,12 +1,26 @@
 #[cfg(test)]
 mod benchmarks {
+    use crate::bpf::Bpf;
     use crate::bpf::agent_skel::types::{session_key, session_val};
-    use crate::config::Config;
+    use crate::config::{Config, FragmentPolicy};
+    #[cfg(feature = "grpc")]
+    use crate::grpc_server::session::LoginEvent;
+    #[cfg(feature = "grpc")]
+    use crate::grpc_server::session::session_manager_server::SessionManager;
+    #[cfg(feature = "grpc")]
+    use crate::grpc_server::{RuleChange, SessionManagerService};
+    use crate::ip;
+    #[cfg(feature = "grpc")]
+    use crate::types::SessionKey;
     use bytemuck;
     use libbpf_rs::skel::{OpenSkel, SkelBuilder};
     use libbpf_rs::{MapCore, MapFlags, ProgramInput};
     use std::mem::MaybeUninit;
-    use std::time::Instant;
+    #[cfg(feature = "grpc")]
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    #[cfg(feature = "grpc")]
+    use tokio::sync::{Mutex, broadcast};
 
     /// Helper function to create a TCP packet with specified source and destination
     fn create_tcp_packet(src_ip: [u8; 4], dst_ip: [u8; 4], dst_port: u16) -> [u8; 64] {
@@ -41,10 +55,95 @@ mod benchmarks {
         packet[35] = 0x90; // Src port (low)
         packet[36] = (dst_port >> 8) as u8; // Dst port (high)
         packet[37] = (dst_port & 0xFF) as u8; // Dst port (low)
+        packet[46] = 0x50; // Data offset 5 (20 bytes)
 
         packet
     }
 
+    /// Helper function to create a TCP packet with the given flags byte (e.g. 0x02 = SYN)
+    fn create_tcp_packet_with_flags(
+        src_ip: [u8; 4],
+        dst_ip: [u8; 4],
+        dst_port: u16,
+        flags: u8,
+    ) -> [u8; 64] {
+        let mut packet = create_tcp_packet(src_ip, dst_ip, dst_port);
+        packet[47] = flags;
+        packet
+    }
+
+    /// Helper function to create a TCP packet carrying VLAN tags.
+    /// Tags are given outermost first as (TPID, VLAN ID), e.g. (0x88A8, 100) then (0x8100, 200) for QinQ.
+    fn create_vlan_tcp_packet(
+        src_ip: [u8; 4],
+        dst_ip: [u8; 4],
+        dst_port: u16,
+        tags: &[(u16, u16)],
+    ) -> Vec<u8> {
+        let untagged = create_tcp_packet(src_ip, dst_ip, dst_port);
+        let mut packet = Vec::with_capacity(untagged.len() + tags.len() * 4);
+
+        // MAC addresses
+        packet.extend_from_slice(&untagged[..12]);
+
+        // Each tag: TPID then TCI; the inner EtherType follows the last tag
+        for &(tpid, vlan_id) in tags {
+            packet.extend_from_slice(&tpid.to_be_bytes());
+            packet.extend_from_slice(&vlan_id.to_be_bytes());
+        }
+
+        // Original EtherType, IPv4 and TCP headers
+        packet.extend_from_slice(&untagged[12..]);
+
+        packet
+    }
+
+    /// Helper function to create a TCP packet whose IPv4 header carries `options`.
+    /// `options` must be a multiple of 4 bytes and at most 40 bytes.
+    fn create_tcp_packet_with_ip_options(
+        src_ip: [u8; 4],
+        dst_ip: [u8; 4],
+        dst_port: u16,
+        options: &[u8],
+    ) -> Vec<u8> {
+        assert!(options.len() % 4 == 0 && options.len() <= 40);
+        let plain = create_tcp_packet(src_ip, dst_ip, dst_port);
+        let mut packet = Vec::with_capacity(plain.len() + options.len());
+
+        // Ethernet and fixed IPv4 header, then options, then TCP
+        packet.extend_from_slice(&plain[..34]);
+        packet.extend_from_slice(options);
+        packet.extend_from_slice(&plain[34..]);
+
+        let ihl = 5 + options.len() / 4;
+        packet[14] = 0x40 | ihl as u8; // Version 4, IHL
+        let total_len = u16::from_be_bytes([packet[16], packet[17]]) + options.len() as u16;
+        packet[16..18].copy_from_slice(&total_len.to_be_bytes());
+
+        packet
+    }
+
+    /// Helper function to create a fragment of a TCP datagram.
+    /// `more_fragments` sets MF and `offset` is in 8-byte units; only offset 0 carries the TCP header.
+    fn create_tcp_fragment(
+        src_ip: [u8; 4],
+        dst_ip: [u8; 4],
+        dst_port: u16,
+        id: u16,
+        more_fragments: bool,
+        offset: u16,
+    ) -> [u8; 64] {
+        let mut packet = create_tcp_packet(src_ip, dst_ip, dst_port);
+        packet[18..20].copy_from_slice(&id.to_be_bytes());
+        let flags_offset = if more_fragments { 0x2000 } else { 0 } | (offset & 0x1FFF);
+        packet[20..22].copy_from_slice(&flags_offset.to_be_bytes());
+        if offset != 0 {
+            // Later fragments carry payload where the TCP header would be
+            packet[34..].fill(0xAB);
+        }
+        packet
+    }
+
     /// Helper function to generate a random-looking IP address (deterministic for reproducibility)
     fn generate_ip(seed: u32) -> [u8; 4] {
         // Simple LCG pseudo-random number generator for deterministic IPs
@@ -83,14 +182,18 @@ mod benchmarks {
             let dest_port = base_port.wrapping_add((i % 1000) as u16);
 
             let key = session_key {
-                src_ip: src_ip.to_be(),
-                dest_ip: dest_ip.to_be(),
+                src_ip: ip::host_to_network(src_ip),
+                dest_ip: ip::host_to_network(dest_ip),
                 dest_port: dest_port.to_be(),
+                src_port: crate::bpf::ANY_SRC_PORT,
             };
 
             let val = session_val {
                 created_at_ns: 1000000000,
                 last_seen_ns: 1000000000,
+                epoch: 0,
+                src_mac: 0,
+                flags: 0,
             };
 
             skel.maps
@@ -106,6 +209,27 @@ mod benchmarks {
         println!("Pre-filled session map with {} entries", count);
     }
 
+    /// Helper to sum one per-CPU counter (see `enum aegis_counter` in `aegis.h`)
+    fn read_counter(skel: &crate::bpf::agent_skel::AegisSkel, index: u32) -> u64 {
+        skel.maps
+            .counters
+            .lookup_percpu(&index.to_ne_bytes(), MapFlags::ANY)
+            .expect("Failed to read counter")
+            .unwrap_or_default()
+            .iter()
+            .map(|value| bytemuck::pod_read_unaligned::<u64>(value))
+            .sum()
+    }
+
+    /// Helper to pick the latency at percentile `p` (0-100) from sorted samples
+    fn percentile(sorted: &[Duration], p: f64) -> Duration {
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+        sorted[rank.min(sorted.len() - 1)]
+    }
+
     #[test]
     #[ignore]
     fn benchmark_attack_scenario_dropped_packets() {
@@ -125,10 +249,10 @@ mod benchmarks {
 
         let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
         rodata.CONTROLLER_PORT = config.controller_port.to_be();
-        rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
         rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
 
         let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
 
         // Fill the map with legitimate sessions
         let map_size = 5000;
@@ -216,10 +340,10 @@ mod benchmarks {
 
         let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
         rodata.CONTROLLER_PORT = config.controller_port.to_be();
-        rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
         rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
 
         let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
 
         // Fill the map with legitimate sessions
         let map_size = 5000;
@@ -311,10 +435,10 @@ mod benchmarks {
 
         let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
         rodata.CONTROLLER_PORT = config.controller_port.to_be();
-        rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
         rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
 
         let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
 
         // Fill the map with legitimate sessions
         let map_size = 5000;
@@ -410,10 +534,10 @@ mod benchmarks {
 
         let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
         rodata.CONTROLLER_PORT = config.controller_port.to_be();
-        rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
         rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
 
         let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
 
         // Benchmark insertions
         let num_ops = 5000;
@@ -421,14 +545,18 @@ mod benchmarks {
 
         for i in 0..num_ops {
             let key = session_key {
-                src_ip: (0x0A000001u32 + i).to_be(),
-                dest_ip: (0x0A010001u32 + i).to_be(),
+                src_ip: ip::host_to_network(0x0A000001 + i),
+                dest_ip: ip::host_to_network(0x0A010001 + i),
                 dest_port: (8000 + (i % 1000) as u16).to_be(),
+                src_port: crate::bpf::ANY_SRC_PORT,
             };
 
             let val = session_val {
                 created_at_ns: 1000000000,
                 last_seen_ns: 1000000000,
+                epoch: 0,
+                src_mac: 0,
+                flags: 0,
             };
 
             skel.maps
@@ -449,9 +577,10 @@ mod benchmarks {
 
         for i in 0..num_ops {
             let key = session_key {
-                src_ip: (0x0A000001u32 + i).to_be(),
-                dest_ip: (0x0A010001u32 + i).to_be(),
+                src_ip: ip::host_to_network(0x0A000001 + i),
+                dest_ip: ip::host_to_network(0x0A010001 + i),
                 dest_port: (8000 + (i % 1000) as u16).to_be(),
+                src_port: crate::bpf::ANY_SRC_PORT,
             };
 
             let _result = skel
@@ -469,9 +598,10 @@ mod benchmarks {
 
         for i in 0..num_ops {
             let key = session_key {
-                src_ip: (0x0A000001u32 + i).to_be(),
-                dest_ip: (0x0A010001u32 + i).to_be(),
+                src_ip: ip::host_to_network(0x0A000001 + i),
+                dest_ip: ip::host_to_network(0x0A010001 + i),
                 dest_port: (8000 + (i % 1000) as u16).to_be(),
+                src_port: crate::bpf::ANY_SRC_PORT,
             };
 
             let _result = skel.maps.session.delete(bytemuck::bytes_of(&key));
@@ -521,10 +651,10 @@ mod benchmarks {
 
             let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
             rodata.CONTROLLER_PORT = config.controller_port.to_be();
-            rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
             rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
 
             let skel = open_skel.load().expect("Failed to load");
+            Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
 
             fill_session_map(&skel, size, 0x0A000001, 8000);
 
@@ -554,4 +684,880 @@ mod benchmarks {
 
         println!(" Scalability benchmark complete\n");
     }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn benchmark_submit_session_throughput() {
+        println!("\nBENCHMARK: gRPC submit_session End-to-End");
+
+        let config = Config::default();
+
+        // Keep the skeleton alive for the whole test, like Bpf::new does
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let open_object = Box::leak(Box::new(MaybeUninit::uninit()));
+        let mut open_skel = skel_builder.open(open_object).expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+        let skel = Arc::new(std::sync::Mutex::new(skel));
+
+        // Same callback shape main.rs wires into the service
+        let skel_rules = skel.clone();
+        let modify_rules = Arc::new(Mutex::new(
+            move |is_add: bool,
+                  key: SessionKey,
+                  epoch: u64,
+                  _src_mac: u64|
+                  -> anyhow::Result<RuleChange> {
+                let skel = skel_rules.lock().unwrap();
+                let key = session_key::from(key);
+                if is_add {
+                    let val = session_val {
+                        created_at_ns: 1000000000,
+                        last_seen_ns: 1000000000,
+                        epoch,
+                        src_mac: 0,
+                        flags: 0,
+                    };
+                    skel.maps.session.update(
+                        bytemuck::bytes_of(&key),
+                        bytemuck::bytes_of(&val),
+                        MapFlags::ANY,
+                    )?;
+                } else {
+                    skel.maps.session.delete(bytemuck::bytes_of(&key))?;
+                }
+                Ok(RuleChange::default())
+            },
+        ));
+        let update_ip = Arc::new(Mutex::new(|_: u32, _: u32| -> anyhow::Result<usize> {
+            Ok(0)
+        }));
+        let (monitor_tx, _) = broadcast::channel(config.broadcast_channel_size);
+
+        let service = Arc::new(SessionManagerService::new(
+            modify_rules,
+            update_ip,
+            monitor_tx,
+        ));
+
+        let num_clients = 8;
+        let requests_per_client = 2_000;
+        let base_ip = 0x0A000001u32; // 10.0.0.1
+
+        println!(
+            " Running {} concurrent clients x {} requests each",
+            num_clients, requests_per_client
+        );
+
+        let start = Instant::now();
+        let mut handles = Vec::new();
+        for client in 0..num_clients {
+            let service = service.clone();
+            handles.push(tokio::spawn(async move {
+                let mut latencies = Vec::with_capacity(requests_per_client);
+                for i in 0..requests_per_client {
+                    let src_bytes = generate_ip((client * requests_per_client + i) as u32);
+                    let event = LoginEvent {
+                        src_ip: u32::from_be_bytes(src_bytes),
+                        dst_ip: base_ip.wrapping_add(10000 + client as u32),
+                        dst_port: 8000 + (i % 1000) as u32,
+                        activate: true,
+                        src_port: 0,
+                        epoch: 0,
+                        src_mac: 0,
+                    };
+
+                    let op_start = Instant::now();
+                    let ack = service
+                        .submit_session(crate::grpc_server::controller_request(event))
+                        .await
+                        .expect("submit_session failed");
+                    latencies.push(op_start.elapsed());
+
+                    assert!(ack.into_inner().success, "Session should be accepted");
+                }
+                latencies
+            }));
+        }
+
+        let mut latencies = Vec::new();
+        for handle in handles {
+            latencies.extend(handle.await.expect("Client task panicked"));
+        }
+        let elapsed = start.elapsed();
+        latencies.sort();
+
+        let total_ops = latencies.len();
+        let throughput = total_ops as f64 / elapsed.as_secs_f64();
+
+        println!(" SUBMIT SESSION RESULTS");
+        println!("  Clients:          {}", num_clients);
+        println!("  Requests:         {}", total_ops);
+        println!("  Throughput:       {:.0} ops/sec", throughput);
+        println!(
+            "  p50 Latency:      {:.2} µs",
+            percentile(&latencies, 50.0).as_nanos() as f64 / 1000.0
+        );
+        println!(
+            "  p90 Latency:      {:.2} µs",
+            percentile(&latencies, 90.0).as_nanos() as f64 / 1000.0
+        );
+        println!(
+            "  p99 Latency:      {:.2} µs",
+            percentile(&latencies, 99.0).as_nanos() as f64 / 1000.0
+        );
+        println!(
+            "  Max Latency:      {:.2} µs",
+            percentile(&latencies, 100.0).as_nanos() as f64 / 1000.0
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_wildcard_port_session() {
+        println!("\nBENCHMARK: Wildcard Port Session (Fallback Lookup)");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        // Single wildcard session: 10.0.0.1 -> 10.0.0.2 on any port
+        let src_ip = 0x0A000001u32;
+        let dest_ip = 0x0A000002u32;
+        let key = session_key {
+            src_ip: ip::host_to_network(src_ip),
+            dest_ip: ip::host_to_network(dest_ip),
+            dest_port: crate::bpf::WILDCARD_PORT,
+            src_port: crate::bpf::ANY_SRC_PORT,
+        };
+        let val = session_val {
+            created_at_ns: 1000000000,
+            last_seen_ns: 1000000000,
+            epoch: 0,
+            src_mac: 0,
+            flags: 0,
+        };
+        skel.maps
+            .session
+            .update(
+                bytemuck::bytes_of(&key),
+                bytemuck::bytes_of(&val),
+                MapFlags::ANY,
+            )
+            .expect("Failed to insert wildcard session");
+
+        let prog = &skel.progs.xdp_drop_prog;
+        let repeats_per_packet = 10_000;
+        let ports = [22u16, 80, 443, 8080, 65535];
+        let mut total_avg_latency = 0.0;
+
+        for &port in &ports {
+            let packet = create_tcp_packet(ip_to_bytes(src_ip), ip_to_bytes(dest_ip), port);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = repeats_per_packet;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            total_avg_latency += result.duration.as_nanos() as f64;
+
+            // Verify packet was accepted (XDP_PASS = 2)
+            assert_eq!(result.return_value, 2, "Port {} should be accepted", port);
+        }
+
+        // A different source must still be dropped
+        let packet = create_tcp_packet(ip_to_bytes(src_ip + 1), ip_to_bytes(dest_ip), 80);
+        let mut test_args = ProgramInput::default();
+        test_args.data_in = Some(&packet);
+        let result = prog.test_run(test_args).expect("Test run failed");
+        assert_eq!(
+            result.return_value, 1,
+            "Unauthorized source should be dropped"
+        );
+
+        let global_avg_ns = total_avg_latency / ports.len() as f64;
+
+        println!(" WILDCARD PORT RESULTS");
+        println!("  Average Latency:  {:.2} ns/packet", global_avg_ns);
+        println!("  Ports Tested:     {:?} (all accepted)", ports);
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_strict_tcp_flags() {
+        println!("\nBENCHMARK: Strict TCP Flag Validation");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+        rodata.STRICT_TCP = true;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        let base_ip = 0x0A000001u32;
+        fill_session_map(&skel, 1, base_ip, 8000);
+
+        let src_bytes = ip_to_bytes(base_ip);
+        let dst_bytes = ip_to_bytes(base_ip + 10000);
+        let prog = &skel.progs.xdp_drop_prog;
+
+        // (flags, description, expected verdict)
+        let cases = [
+            (0x02u8, "SYN", 2),
+            (0x12, "SYN+ACK", 2),
+            (0x10, "ACK", 2),
+            (0x11, "FIN+ACK", 2),
+            (0x00, "NULL", 1),
+            (0x03, "SYN+FIN", 1),
+            (0x06, "SYN+RST", 1),
+            (0x01, "FIN", 1),
+            (0x29, "XMAS (FIN+PSH+URG)", 1),
+        ];
+
+        for (flags, name, expected) in cases {
+            let packet = create_tcp_packet_with_flags(src_bytes, dst_bytes, 8000, flags);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = 10_000;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value, expected,
+                "Unexpected verdict for {}",
+                name
+            );
+
+            println!(
+                "  {:20} → {} ({:.2} ns/packet)",
+                name,
+                if expected == 2 { "PASS" } else { "DROP" },
+                result.duration.as_nanos() as f64
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_strict_tcp_new_session() {
+        println!("\nBENCHMARK: Strict TCP First Packet of a New Session");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+        rodata.STRICT_TCP = true;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        let base_ip = 0x0A000001u32;
+        let src_bytes = ip_to_bytes(base_ip);
+        let dst_bytes = ip_to_bytes(base_ip + 10000);
+        let prog = &skel.progs.xdp_drop_prog;
+
+        // (flags, description, expected verdict) for a session that has not carried traffic
+        let cases = [
+            (0x02u8, "SYN", 2),
+            (0x10, "ACK", 1),
+            (0x12, "SYN+ACK", 1),
+            (0x11, "FIN+ACK", 1),
+            (0x04, "RST", 1),
+            (0x14, "RST+ACK", 1),
+            (0x18, "PSH+ACK", 1),
+        ];
+
+        for (flags, name, expected) in cases {
+            // Re-insert so every case sees a session that has not passed a packet
+            fill_session_map(&skel, 1, base_ip, 8000);
+
+            let packet = create_tcp_packet_with_flags(src_bytes, dst_bytes, 8000, flags);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = 10_000;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value, expected,
+                "Unexpected verdict for {} on a new session",
+                name
+            );
+
+            println!(
+                "  {:20} → {} ({:.2} ns/packet)",
+                name,
+                if expected == 2 { "PASS" } else { "DROP" },
+                result.duration.as_nanos() as f64
+            );
+        }
+
+        // Once the opening SYN has passed, the rest of the handshake flows
+        fill_session_map(&skel, 1, base_ip, 8000);
+        for (flags, name) in [(0x02u8, "SYN"), (0x10, "ACK"), (0x11, "FIN+ACK")] {
+            let packet = create_tcp_packet_with_flags(src_bytes, dst_bytes, 8000, flags);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value, 2,
+                "{} should pass on an established session",
+                name
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_truncated_and_malformed_packets() {
+        println!("\nBENCHMARK: Truncated and Malformed Packets");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        // Authorize the flow so every drop below comes from header validation
+        let base_ip = 0x0A000001u32;
+        fill_session_map(&skel, 1, base_ip, 8000);
+
+        let valid = create_tcp_packet(ip_to_bytes(base_ip), ip_to_bytes(base_ip + 10000), 8000);
+        let mut udp = valid;
+        udp[23] = 0x11; // Protocol (UDP)
+        let vlan = create_vlan_tcp_packet(
+            ip_to_bytes(base_ip),
+            ip_to_bytes(base_ip + 10000),
+            8000,
+            &[(0x8100, 100)],
+        );
+
+        let mut bad_version = valid;
+        bad_version[14] = 0x65; // Version 6, IHL 5
+        let mut short_ihl = valid;
+        short_ihl[14] = 0x44; // Version 4, IHL 4 (16 bytes)
+        let mut short_tot_len = valid;
+        short_tot_len[17] = 0x10; // Total length 16, less than the header
+        let mut short_doff = valid;
+        short_doff[46] = 0x40; // Data offset 4 (16 bytes)
+
+        // The kernel rejects test runs below the 14-byte Ethernet header, so
+        // the shortest case is an Ethernet header with nothing after it.
+        // (packet, description, expected verdict)
+        let cases: [(&[u8], &str, u32); 11] = [
+            (&valid, "Valid TCP", 2),
+            (&valid[..14], "Ethernet only", 1),
+            (&valid[..24], "Truncated IPv4 header", 1),
+            (&valid[..44], "Truncated TCP header", 1),
+            (&udp[..38], "Truncated UDP header", 1),
+            (&vlan[..16], "Truncated VLAN tag", 1),
+            (&vlan[..28], "Truncated IPv4 after VLAN", 1),
+            (&bad_version, "IPv4 EtherType, version 6", 1),
+            (&short_ihl, "IHL below 5", 1),
+            (&short_tot_len, "Total length below IHL", 1),
+            (&short_doff, "TCP data offset below 5", 1),
+        ];
+
+        let prog = &skel.progs.xdp_drop_prog;
+        for (packet, name, expected) in cases {
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(packet);
+            test_args.repeat = 10_000;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value,
+                expected,
+                "Unexpected verdict for {} ({} bytes)",
+                name,
+                packet.len()
+            );
+
+            println!(
+                "  {:28} {:3} bytes → {} ({:.2} ns/packet)",
+                name,
+                packet.len(),
+                if expected == 2 { "PASS" } else { "DROP" },
+                result.duration.as_nanos() as f64
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_ip_options() {
+        println!("\nBENCHMARK: IPv4 Headers with Options");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        let base_ip = 0x0A000001u32;
+        fill_session_map(&skel, 1, base_ip, 8000);
+
+        let src_bytes = ip_to_bytes(base_ip);
+        let dst_bytes = ip_to_bytes(base_ip + 10000);
+        let prog = &skel.progs.xdp_drop_prog;
+
+        // NOPs padded to 4 bytes, a router alert, and the 40-byte maximum
+        let option_sets: [(&[u8], &str); 3] = [
+            (&[0x01, 0x01, 0x01, 0x00], "NOP padding"),
+            (&[0x94, 0x04, 0x00, 0x00], "Router Alert"),
+            (&[0x01; 40], "40 bytes of NOP"),
+        ];
+
+        // Authorized port passes and unauthorized port drops, with or without options
+        for (port, expected) in [(8000u16, 2u32), (8001, 1)] {
+            let plain = create_tcp_packet(src_bytes, dst_bytes, port);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&plain);
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value, expected,
+                "Plain packet to port {}",
+                port
+            );
+
+            for (options, name) in option_sets {
+                let packet = create_tcp_packet_with_ip_options(src_bytes, dst_bytes, port, options);
+                let mut test_args = ProgramInput::default();
+                test_args.data_in = Some(&packet);
+                test_args.repeat = 10_000;
+
+                let result = prog.test_run(test_args).expect("Test run failed");
+                assert_eq!(
+                    result.return_value, expected,
+                    "{} to port {} should match the optionless verdict",
+                    name, port
+                );
+
+                println!(
+                    "  {:16} port {} → {} ({:.2} ns/packet)",
+                    name,
+                    port,
+                    if expected == 2 { "PASS" } else { "DROP" },
+                    result.duration.as_nanos() as f64
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_ip_fragments() {
+        println!("\nBENCHMARK: IPv4 Fragment Policies");
+
+        let config = Config::default();
+
+        for policy in [FragmentPolicy::Drop, FragmentPolicy::Track] {
+            let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+            let mut open_object = MaybeUninit::uninit();
+            let mut open_skel = skel_builder
+                .open(&mut open_object)
+                .expect("Failed to open skel");
+
+            let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+            rodata.CONTROLLER_PORT = config.controller_port.to_be();
+            rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+            rodata.FRAGMENT_POLICY = policy as u8;
+
+            let skel = open_skel.load().expect("Failed to load");
+            Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+            let base_ip = 0x0A000001u32;
+            fill_session_map(&skel, 1, base_ip, 8000);
+
+            let src_bytes = ip_to_bytes(base_ip);
+            let dst_bytes = ip_to_bytes(base_ip + 10000);
+            let prog = &skel.progs.xdp_drop_prog;
+            let tracked = if policy == FragmentPolicy::Track {
+                2
+            } else {
+                1
+            };
+
+            // (packet, description, expected verdict), run in order since
+            // first fragments populate the fragment map
+            let cases = [
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8000, 7, false, 185),
+                    "tail before first fragment",
+                    1u32,
+                ),
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8000, 7, true, 0),
+                    "authorized first fragment (MF)",
+                    2,
+                ),
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8000, 7, true, 185),
+                    "middle fragment",
+                    tracked,
+                ),
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8000, 7, false, 370),
+                    "last fragment",
+                    tracked,
+                ),
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8001, 8, true, 0),
+                    "unauthorized first fragment (MF)",
+                    1,
+                ),
+                (
+                    create_tcp_fragment(src_bytes, dst_bytes, 8001, 8, false, 185),
+                    "unauthorized last fragment",
+                    1,
+                ),
+            ];
+
+            for (packet, name, expected) in cases {
+                let mut test_args = ProgramInput::default();
+                test_args.data_in = Some(&packet);
+                test_args.repeat = 10_000;
+
+                let result = prog.test_run(test_args).expect("Test run failed");
+                assert_eq!(
+                    result.return_value, expected,
+                    "Unexpected verdict for {} with policy {}",
+                    name, policy
+                );
+
+                println!(
+                    "  {:5} {:34} → {} ({:.2} ns/packet)",
+                    policy,
+                    name,
+                    if expected == 2 { "PASS" } else { "DROP" },
+                    result.duration.as_nanos() as f64
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_lazy_update_suppression() {
+        println!("\nBENCHMARK: Lazy Timestamp Update Suppression");
+
+        let config = Config::default();
+        let repeat = 100_000u32;
+
+        // (lazy_update_timeout_ns, expected applied, expected suppressed).
+        // A 60s window only lets the first packet of a new session write;
+        // a zero window writes on every packet.
+        let cases = [
+            (60_000_000_000u64, 1u64, repeat as u64 - 1),
+            (0, repeat as u64, 0),
+        ];
+
+        for (timeout_ns, applied, suppressed) in cases {
+            let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+            let mut open_object = MaybeUninit::uninit();
+            let mut open_skel = skel_builder
+                .open(&mut open_object)
+                .expect("Failed to open skel");
+
+            let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+            rodata.CONTROLLER_PORT = config.controller_port.to_be();
+            rodata.LAZY_UPDATE_TIMEOUT = timeout_ns;
+
+            let skel = open_skel.load().expect("Failed to load");
+            Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+            let base_ip = 0x0A000001u32;
+            fill_session_map(&skel, 1, base_ip, 8000);
+
+            let packet =
+                create_tcp_packet(ip_to_bytes(base_ip), ip_to_bytes(base_ip + 10000), 8000);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = repeat;
+
+            let result = skel
+                .progs
+                .xdp_drop_prog
+                .test_run(test_args)
+                .expect("Test run failed");
+            assert_eq!(result.return_value, 2, "Session packet should pass");
+
+            let counted = (read_counter(&skel, 0), read_counter(&skel, 1));
+            assert_eq!(
+                counted,
+                (applied, suppressed),
+                "Unexpected (applied, suppressed) for a {}ns window",
+                timeout_ns
+            );
+
+            println!(
+                "  window {:>14} ns → {} applied, {} suppressed ({:.2} ns/packet)",
+                timeout_ns,
+                counted.0,
+                counted.1,
+                result.duration.as_nanos() as f64
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_always_allowed_port() {
+        println!("\nBENCHMARK: Always-Allowed Port (No Session)");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        // Allow SSH without any session entry
+        let allowed_port = 22u16;
+        skel.maps
+            .allowed_ports
+            .update(
+                bytemuck::bytes_of(&allowed_port.to_be()),
+                &[1u8],
+                MapFlags::ANY,
+            )
+            .expect("Failed to allow port");
+
+        let num_unique_packets = 100;
+        let repeats_per_packet = 10_000;
+        let prog = &skel.progs.xdp_drop_prog;
+        let mut total_avg_latency = 0.0;
+
+        for i in 0..num_unique_packets {
+            let src_ip = generate_ip(i as u32 * 7777);
+            let packet = create_tcp_packet(src_ip, [192, 168, 1, 100], allowed_port);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = repeats_per_packet;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+            total_avg_latency += result.duration.as_nanos() as f64;
+
+            // Verify packet was accepted (XDP_PASS = 2)
+            assert_eq!(result.return_value, 2, "Always-allowed port should pass");
+        }
+
+        // Any other port without a session is still dropped
+        let packet = create_tcp_packet([10, 0, 0, 1], [192, 168, 1, 100], 2222);
+        let mut test_args = ProgramInput::default();
+        test_args.data_in = Some(&packet);
+        let result = prog.test_run(test_args).expect("Test run failed");
+        assert_eq!(result.return_value, 1, "Other ports should be dropped");
+
+        let global_avg_ns = total_avg_latency / num_unique_packets as f64;
+
+        println!(" ALWAYS-ALLOWED PORT RESULTS");
+        println!("  Average Latency:  {:.2} ns/packet", global_avg_ns);
+        println!(
+            "  Packets Tested:   {} (all accepted)",
+            num_unique_packets * repeats_per_packet as usize
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_grpc_port_from_controller() {
+        println!("\nBENCHMARK: gRPC Port From Controller (No Session)");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.GRPC_PORT = config.grpc_server_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        let controller_ip = config.controller_ip.octets();
+        let repeats = 100_000;
+        let prog = &skel.progs.xdp_drop_prog;
+
+        // Controller -> agent gRPC port passes without any session entry
+        let packet = create_tcp_packet(controller_ip, [192, 168, 1, 100], config.grpc_server_port);
+        let mut test_args = ProgramInput::default();
+        test_args.data_in = Some(&packet);
+        test_args.repeat = repeats;
+        let result = prog.test_run(test_args).expect("Test run failed");
+        assert_eq!(
+            result.return_value, 2,
+            "Controller traffic to the gRPC port should pass"
+        );
+        let avg_ns = result.duration.as_nanos() as f64;
+
+        // Any other source hitting the gRPC port is still dropped
+        let packet = create_tcp_packet([10, 0, 0, 1], [192, 168, 1, 100], config.grpc_server_port);
+        let mut test_args = ProgramInput::default();
+        test_args.data_in = Some(&packet);
+        let result = prog.test_run(test_args).expect("Test run failed");
+        assert_eq!(
+            result.return_value, 1,
+            "Non-controller traffic to the gRPC port should be dropped"
+        );
+
+        println!(" GRPC PORT RESULTS");
+        println!("  Average Latency:  {:.2} ns/packet", avg_ns);
+        println!("  Packets Tested:   {} (all accepted)", repeats);
+    }
+
+    #[test]
+    #[ignore]
+    fn benchmark_vlan_tagged_session() {
+        println!("\nBENCHMARK: VLAN-Tagged Session Traffic");
+
+        let config = Config::default();
+
+        let skel_builder = crate::bpf::agent_skel::AegisSkelBuilder::default();
+        let mut open_object = MaybeUninit::uninit();
+        let mut open_skel = skel_builder
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = config.controller_port.to_be();
+        rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip).unwrap();
+
+        // Single session: 10.0.0.1 -> 10.0.0.2:8080
+        let src_ip = 0x0A000001u32;
+        let dest_ip = 0x0A000002u32;
+        let dest_port = 8080u16;
+        let key = session_key {
+            src_ip: ip::host_to_network(src_ip),
+            dest_ip: ip::host_to_network(dest_ip),
+            dest_port: dest_port.to_be(),
+            src_port: crate::bpf::ANY_SRC_PORT,
+        };
+        let val = session_val {
+            created_at_ns: 1000000000,
+            last_seen_ns: 1000000000,
+            epoch: 0,
+            src_mac: 0,
+            flags: 0,
+        };
+        skel.maps
+            .session
+            .update(
+                bytemuck::bytes_of(&key),
+                bytemuck::bytes_of(&val),
+                MapFlags::ANY,
+            )
+            .expect("Failed to insert session");
+
+        let prog = &skel.progs.xdp_drop_prog;
+        let repeats_per_packet = 10_000;
+        let cases: [(&str, &[(u16, u16)]); 3] = [
+            ("untagged", &[]),
+            ("802.1Q", &[(0x8100, 100)]),
+            ("QinQ", &[(0x88A8, 100), (0x8100, 200)]),
+        ];
+
+        for (name, tags) in cases {
+            let packet =
+                create_vlan_tcp_packet(ip_to_bytes(src_ip), ip_to_bytes(dest_ip), dest_port, tags);
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            test_args.repeat = repeats_per_packet;
+
+            let result = prog.test_run(test_args).expect("Test run failed");
+
+            // Verify packet was accepted (XDP_PASS = 2)
+            assert_eq!(
+                result.return_value, 2,
+                "{} session packet should pass",
+                name
+            );
+            println!(
+                "  {:<9} PASS  {:.2} ns/packet",
+                name,
+                result.duration.as_nanos() as f64
+            );
+
+            // Same tags without a session must still be dropped
+            let packet = create_vlan_tcp_packet(
+                ip_to_bytes(src_ip + 1),
+                ip_to_bytes(dest_ip),
+                dest_port,
+                tags,
+            );
+            let mut test_args = ProgramInput::default();
+            test_args.data_in = Some(&packet);
+            let result = prog.test_run(test_args).expect("Test run failed");
+            assert_eq!(
+                result.return_value, 1,
+                "{} packet without session should be dropped",
+                name
+            );
+        }
+    }
 }
@@ -1,31 +1,585 @@
+#[doc(hidden)]
 #[path = "./bpf/aegis.skel.rs"]
 #[rustfmt::skip]
 pub mod agent_skel;
 
-use crate::config::Config;
+use crate::{
+    cgroup,
+    clock::{Clock, MonotonicClock},
+    config::{AttachHook, Config, XdpMode},
+    iface, ip,
+    types::{SessionKey, SessionVal},
+};
 use agent_skel::{
     AegisSkel, AegisSkelBuilder,
     types::{session_key, session_val},
 };
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use bytemuck::{Pod, Zeroable};
 use libbpf_rs::{
-    Link, MapCore, MapFlags,
+    MapCore, MapFlags, MapHandle, MapType, TC_INGRESS, TcHookBuilder, Xdp, XdpFlags,
     skel::{OpenSkel, SkelBuilder},
 };
-use nix::time::{ClockId, clock_gettime};
-use std::{fs, path::Path};
+use nix::{
+    mount::{MsFlags, mount},
+    sys::statfs::statfs,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    net::Ipv4Addr,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
 use tracing::{debug, error, info, warn};
 
-// Pin paths
-const BPF_FS_PATH: &str = "/sys/fs/bpf/aegis";
-const MAP_PIN_PATH: &str = "/sys/fs/bpf/aegis/session";
-const LINK_PIN_PATH: &str = "/sys/fs/bpf/aegis/xdp_link";
+// Pin names, relative to the configured pin prefix
+const MAP_PIN_NAME: &str = "session";
+const BLOCKLIST_PIN_NAME: &str = "blocklist";
+const LINK_PIN_NAME: &str = "xdp_link";
+
+/// Handle and priority of the tc filter, fixed so a re-attach replaces it.
+const TC_HANDLE: u32 = 1;
+const TC_PRIORITY: u32 = 1;
+
+/// `statfs` magic of a BPF filesystem (`BPF_FS_MAGIC` in `linux/magic.h`).
+const BPF_FS_MAGIC: u32 = 0xcafe_4a11;
+
+/// Standard bpffs mount point, the only place `--mount-bpffs` will mount.
+const BPF_FS_MOUNT_POINT: &str = "/sys/fs/bpf";
+
+/// Reserved destination port meaning "any port" (mirrors `WILDCARD_PORT` in `aegis.h`).
+/// Port 0 is not a valid TCP/UDP destination, so it never collides with a real rule.
+pub const WILDCARD_PORT: u16 = 0;
+
+/// Reserved source port meaning "any source port" (mirrors `ANY_SRC_PORT` in `aegis.h`).
+pub const ANY_SRC_PORT: u16 = 0;
+
+/// Reserved source IP meaning "any source" (mirrors `ANY_SRC_IP` in `aegis.h`).
+/// `0.0.0.0` is never a real packet source, so it cannot shadow a session.
+pub const ANY_SRC_IP: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
+
+/// Epoch of sessions added without one: locally, from a seed file, or by a
+/// controller that does not send an epoch.
+pub const NO_EPOCH: u64 = 0;
+
+/// Source MAC of sessions not bound up front; with `network.bind_src_mac`
+/// the XDP program binds them to the MAC of their first packet.
+pub const NO_SRC_MAC: u64 = 0;
+
+/// `session_val` flag set by the XDP program once it passed a packet for the
+/// session (mirrors `SESSION_SEEN` in `aegis.h`).
+pub const SESSION_SEEN: u64 = 1;
+
+/// Key looked up by [`Bpf::probe_session_map`]. No API accepts a `0.0.0.0`
+/// destination, so it never matches a real session.
+const PROBE_KEY: SessionKey = SessionKey {
+    src_ip: ANY_SRC_IP,
+    dest_ip: Ipv4Addr::UNSPECIFIED,
+    dest_port: WILDCARD_PORT,
+    src_port: ANY_SRC_PORT,
+};
+
+/// Kernel BPF capabilities detected at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpfFeatures {
+    /// `BPF_MAP_DELETE_BATCH` on hash maps (Linux 5.6+)
+    pub batch_delete: bool,
+    /// `BPF_MAP_TYPE_RINGBUF` (Linux 5.8+)
+    pub ringbuf: bool,
+    /// Kernel BTF exposed at `/sys/kernel/btf/vmlinux`
+    pub btf: bool,
+    /// `BPF_PROG_TYPE_XDP`
+    pub xdp: bool,
+    /// `BPF_MAP_TYPE_LRU_HASH` (Linux 4.10+), used for fragment tracking
+    pub lru_hash: bool,
+    /// `BPF_MAP_TYPE_PERCPU_ARRAY` (Linux 4.6+), used for datapath counters
+    pub percpu_array: bool,
+}
+
+impl BpfFeatures {
+    /// Fails with an actionable message if a feature the agent cannot work
+    /// without is missing. Optional features only degrade behavior.
+    pub fn check_required(&self) -> Result<()> {
+        let missing: Vec<&str> = [
+            (self.xdp, "BPF_PROG_TYPE_XDP (Linux 4.8+)"),
+            (self.lru_hash, "BPF_MAP_TYPE_LRU_HASH (Linux 4.10+)"),
+            (self.percpu_array, "BPF_MAP_TYPE_PERCPU_ARRAY (Linux 4.6+)"),
+        ]
+        .into_iter()
+        .filter(|(supported, _)| !supported)
+        .map(|(_, name)| name)
+        .collect();
+
+        if !missing.is_empty() {
+            bail!(
+                "Kernel lacks required BPF support: {}. Upgrade the kernel or \
+                 enable CONFIG_BPF_SYSCALL, and make sure the agent has CAP_BPF.",
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BpfFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        write!(
+            f,
+            "batch_delete={} ringbuf={} btf={} xdp={} lru_hash={} percpu_array={}",
+            yes_no(self.batch_delete),
+            yes_no(self.ringbuf),
+            yes_no(self.btf),
+            yes_no(self.xdp),
+            yes_no(self.lru_hash),
+            yes_no(self.percpu_array)
+        )
+    }
+}
+
+/// Probes the running kernel for the BPF features the agent can use.
+///
+/// Probing needs `CAP_BPF`; without it every feature reports as unsupported.
+/// The result is computed once and cached for the life of the process.
+pub fn probe_features() -> BpfFeatures {
+    static FEATURES: OnceLock<BpfFeatures> = OnceLock::new();
+
+    *FEATURES.get_or_init(|| {
+        // SAFETY: libbpf probes take no options and only create and close
+        // temporary kernel objects.
+        let probe_map = |map_type| unsafe {
+            libbpf_sys::libbpf_probe_bpf_map_type(map_type, std::ptr::null()) == 1
+        };
+        let xdp = unsafe {
+            libbpf_sys::libbpf_probe_bpf_prog_type(libbpf_sys::BPF_PROG_TYPE_XDP, std::ptr::null())
+                == 1
+        };
+
+        BpfFeatures {
+            batch_delete: probe_batch_delete(),
+            ringbuf: probe_map(libbpf_sys::BPF_MAP_TYPE_RINGBUF),
+            btf: Path::new("/sys/kernel/btf/vmlinux").exists(),
+            xdp,
+            lru_hash: probe_map(libbpf_sys::BPF_MAP_TYPE_LRU_HASH),
+            percpu_array: probe_map(libbpf_sys::BPF_MAP_TYPE_PERCPU_ARRAY),
+        }
+    })
+}
+
+/// Sysctl that makes the kernel collect run statistics for every BPF program.
+const BPF_STATS_SYSCTL: &str = "/proc/sys/kernel/bpf_stats_enabled";
+
+/// Cumulative run statistics the kernel keeps for a BPF program.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgStats {
+    /// Number of times the program ran
+    pub run_cnt: u64,
+    /// Total time spent in the program in nanoseconds
+    pub run_time_ns: u64,
+}
+
+impl ProgStats {
+    /// Returns the number of runs and the average nanoseconds per run since
+    /// `earlier`, or `None` if the program did not run in between.
+    pub fn since(&self, earlier: &ProgStats) -> Option<(u64, f64)> {
+        let runs = self.run_cnt.checked_sub(earlier.run_cnt)?;
+        if runs == 0 {
+            return None;
+        }
+        let time_ns = self.run_time_ns.saturating_sub(earlier.run_time_ns);
+        Some((runs, time_ns as f64 / runs as f64))
+    }
+}
+
+/// Counter index for applied `last_seen_ns` refreshes (mirrors `COUNTER_REFRESH_APPLIED` in `aegis.h`).
+const COUNTER_REFRESH_APPLIED: u32 = 0;
+
+/// Counter index for refreshes skipped inside the lazy update window
+/// (mirrors `COUNTER_REFRESH_SUPPRESSED` in `aegis.h`).
+const COUNTER_REFRESH_SUPPRESSED: u32 = 1;
+
+/// Counter index for SYNs dropped by `max_conns_per_src`
+/// (mirrors `COUNTER_CONN_CAPPED` in `aegis.h`).
+const COUNTER_CONN_CAPPED: u32 = 2;
+
+/// Counter index for packets dropped by `bind_src_mac`
+/// (mirrors `COUNTER_MAC_MISMATCH` in `aegis.h`).
+const COUNTER_MAC_MISMATCH: u32 = 3;
+
+/// `enforcement` map value that drops what the policy rejects
+/// (mirrors `MODE_ENFORCING` in `aegis.h`).
+const MODE_ENFORCING: u32 = 0;
+
+/// `enforcement` map value that passes every packet (mirrors `MODE_PERMISSIVE` in `aegis.h`).
+const MODE_PERMISSIVE: u32 = 1;
+
+/// Event counters kept by the XDP program, summed over all CPUs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    /// Session hits that rewrote `last_seen_ns`
+    pub refresh_applied: u64,
+    /// Session hits that skipped the rewrite because the last one was
+    /// less than `lazy_update_timeout` ago
+    pub refresh_suppressed: u64,
+    /// New TCP connections dropped because their source already had
+    /// `max_conns_per_src` open to the destination
+    pub conn_capped: u64,
+    /// Session packets dropped because their source MAC differed from the
+    /// one the session was bound to
+    pub mac_mismatch: u64,
+}
+
+impl Counters {
+    /// Returns the increase of each counter since `earlier`.
+    pub fn since(&self, earlier: &Counters) -> Counters {
+        Counters {
+            refresh_applied: self.refresh_applied.saturating_sub(earlier.refresh_applied),
+            refresh_suppressed: self
+                .refresh_suppressed
+                .saturating_sub(earlier.refresh_suppressed),
+            conn_capped: self.conn_capped.saturating_sub(earlier.conn_capped),
+            mac_mismatch: self.mac_mismatch.saturating_sub(earlier.mac_mismatch),
+        }
+    }
+
+    /// Returns the share of session hits whose refresh was suppressed, or
+    /// `None` if there were no hits.
+    pub fn suppressed_ratio(&self) -> Option<f64> {
+        let total = self.refresh_applied + self.refresh_suppressed;
+        (total > 0).then(|| self.refresh_suppressed as f64 / total as f64)
+    }
+}
+
+/// Turns on kernel collection of BPF run statistics.
+///
+/// Uses `BPF_ENABLE_STATS` (Linux 5.8+), which keeps statistics on only while
+/// the returned fd is open. On older kernels it falls back to setting
+/// `kernel.bpf_stats_enabled`, which stays on after the agent exits, and
+/// returns `None`. Both need `CAP_SYS_ADMIN`.
+pub fn enable_stats() -> Result<Option<OwnedFd>> {
+    // SAFETY: takes no pointers; a non-negative result is a new fd we own.
+    let fd = unsafe { libbpf_sys::bpf_enable_stats(libbpf_sys::BPF_STATS_RUN_TIME) };
+    if fd >= 0 {
+        // SAFETY: `fd` was just returned by the kernel and is not owned elsewhere.
+        return Ok(Some(unsafe { OwnedFd::from_raw_fd(fd) }));
+    }
+
+    debug!(
+        "BPF_ENABLE_STATS failed ({}), trying {}",
+        std::io::Error::from_raw_os_error(-fd),
+        BPF_STATS_SYSCTL
+    );
+    fs::write(BPF_STATS_SYSCTL, "1")
+        .with_context(|| format!("Failed to enable BPF statistics via {}", BPF_STATS_SYSCTL))?;
+    Ok(None)
+}
+
+/// Checks batch deletion support by deleting from a throwaway hash map.
+fn probe_batch_delete() -> bool {
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: std::mem::size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+        ..Default::default()
+    };
+    let Ok(map) = MapHandle::create(MapType::Hash, Some("aegis_probe"), 4, 1, 1, &opts) else {
+        return false;
+    };
+
+    let key = 0u32.to_ne_bytes();
+    map.update(&key, &[0u8], MapFlags::ANY).is_ok()
+        && map
+            .delete_batch(&key, 1, MapFlags::ANY, MapFlags::ANY)
+            .is_ok()
+}
+
+/// Path of the pinned session map under `pin_prefix`.
+fn map_pin_path(pin_prefix: &Path) -> PathBuf {
+    pin_prefix.join(MAP_PIN_NAME)
+}
+
+/// Path of the pinned blocklist map under `pin_prefix`.
+fn blocklist_pin_path(pin_prefix: &Path) -> PathBuf {
+    pin_prefix.join(BLOCKLIST_PIN_NAME)
+}
+
+/// Path of the XDP link pinned by older agent versions under `pin_prefix`.
+fn link_pin_path(pin_prefix: &Path) -> PathBuf {
+    pin_prefix.join(LINK_PIN_NAME)
+}
+
+/// `GRACE_UNTIL` for a grace window of `grace_sec` starting at `now_ns`.
+fn grace_deadline(now_ns: u64, grace_sec: u64) -> u64 {
+    now_ns.saturating_add(grace_sec.saturating_mul(1_000_000_000))
+}
+
+/// Whether the program drops what the policy rejects in `mode` at `now_ns`,
+/// mirroring `enforce()` in the datapath.
+fn enforces(mode: u32, grace_until: u64, now_ns: u64) -> bool {
+    mode != MODE_PERMISSIVE || (grace_until != 0 && now_ns >= grace_until)
+}
+
+/// Type, key size and value size of a pinned map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MapLayout {
+    map_type: MapType,
+    key_size: u32,
+    value_size: u32,
+}
+
+impl MapLayout {
+    /// Layout of the session map compiled into this build.
+    fn session() -> Self {
+        Self {
+            map_type: MapType::LruHash,
+            key_size: std::mem::size_of::<session_key>() as u32,
+            value_size: std::mem::size_of::<session_val>() as u32,
+        }
+    }
+
+    /// Layout of the blocklist map compiled into this build.
+    fn blocklist() -> Self {
+        Self {
+            map_type: MapType::Hash,
+            key_size: std::mem::size_of::<u32>() as u32,
+            value_size: std::mem::size_of::<u8>() as u32,
+        }
+    }
+
+    /// Layout the kernel reports for `map`.
+    fn of(map: &impl MapCore) -> Self {
+        Self {
+            map_type: map.map_type(),
+            key_size: map.key_size(),
+            value_size: map.value_size(),
+        }
+    }
+}
+
+impl fmt::Display for MapLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} with {}-byte keys and {}-byte values",
+            self.map_type, self.key_size, self.value_size
+        )
+    }
+}
+
+/// Checks that a session map uses this build's type, key and value layout.
+fn check_session_schema(map: &impl MapCore, path: &Path) -> Result<()> {
+    check_layout(MapLayout::of(map), MapLayout::session(), path)
+}
+
+/// Compares a pinned map's `found` layout with the `expected` compiled one.
+fn check_layout(found: MapLayout, expected: MapLayout, path: &Path) -> Result<()> {
+    if found != expected {
+        bail!(
+            "Pinned map {} is {}, expected {}; \
+             it was created by an incompatible agent version",
+            path.display(),
+            found,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Verifies a map pinned by a previous run can be reused as `expected`.
+///
+/// With `force_recreate`, an incompatible map is unpinned so a fresh one is
+/// created on load, dropping the entries it held.
+fn prepare_pinned_map(path: &Path, expected: MapLayout, force_recreate: bool) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let map = MapHandle::from_pinned_path(path)
+        .with_context(|| format!("Failed to open pinned map {}", path.display()))?;
+    let layout = MapLayout::of(&map);
+    let Err(e) = check_layout(layout, expected, path) else {
+        info!("Reusing pinned map {} ({})", path.display(), layout);
+        return Ok(());
+    };
+    if !force_recreate {
+        return Err(e.context(
+            "Refusing to reuse the pinned map; \
+             pass --force-recreate to discard it and its entries",
+        ));
+    }
+
+    warn!("{:#}; recreating it, existing entries are lost", e);
+    fs::remove_file(path).with_context(|| format!("Failed to unpin map {}", path.display()))
+}
+
+/// Reads the name of the loaded program with ID `id`.
+fn prog_name(id: u32) -> Result<String> {
+    // SAFETY: plain syscall wrapper; a non-negative return is a new fd we own.
+    let fd = unsafe { libbpf_sys::bpf_prog_get_fd_by_id(id) };
+    if fd < 0 {
+        return Err(std::io::Error::from_raw_os_error(-fd))
+            .with_context(|| format!("Failed to open BPF program {}", id));
+    }
+    // SAFETY: `fd` was just returned by the kernel and is not owned elsewhere.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut info = libbpf_sys::bpf_prog_info::default();
+    let mut len = std::mem::size_of::<libbpf_sys::bpf_prog_info>() as u32;
+    // SAFETY: `info` and `len` describe a valid, writable bpf_prog_info.
+    let ret = unsafe { libbpf_sys::bpf_prog_get_info_by_fd(fd.as_raw_fd(), &mut info, &mut len) };
+    if ret < 0 {
+        return Err(std::io::Error::from_raw_os_error(-ret))
+            .with_context(|| format!("Failed to read BPF program {} info", id));
+    }
+
+    let name: Vec<u8> = info
+        .name
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    Ok(String::from_utf8_lossy(&name).into_owned())
+}
+
+/// Fails if `found`, the ID and name of the program already attached to
+/// `interface_index` on `hook`, is not an Aegis filter named `ours`.
+/// Attaching would silently replace another tool's program.
+fn check_replaceable(
+    interface_index: i32,
+    hook: AttachHook,
+    found: Option<(u32, String)>,
+    ours: &str,
+) -> Result<()> {
+    match found {
+        None => Ok(()),
+        Some((id, name)) if name == ours => {
+            debug!(
+                "Replacing Aegis filter {} on interface {}",
+                id, interface_index
+            );
+            Ok(())
+        }
+        Some((id, name)) => bail!(
+            "Interface {} already has program {:?} (id {}) on the {} hook; \
+             detach it before starting the agent",
+            interface_index,
+            name,
+            id,
+            hook
+        ),
+    }
+}
+
+/// Verifies `pin_prefix` lives on a BPF filesystem.
+///
+/// The prefix itself may not exist yet, so its nearest existing ancestor is
+/// checked. With `mount_bpffs`, an unmounted `/sys/fs/bpf` is mounted first.
+fn check_bpffs(pin_prefix: &Path, mount_bpffs: bool) -> Result<()> {
+    let mount_point = pin_prefix
+        .ancestors()
+        .find(|dir| dir.exists())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/"));
+
+    if is_bpffs(&mount_point)? {
+        return Ok(());
+    }
+
+    if mount_bpffs && mount_point == Path::new(BPF_FS_MOUNT_POINT) {
+        info!("Mounting bpffs at {}", BPF_FS_MOUNT_POINT);
+        mount(
+            Some("bpf"),
+            BPF_FS_MOUNT_POINT,
+            Some("bpf"),
+            MsFlags::empty(),
+            None::<&str>,
+        )
+        .with_context(|| format!("Failed to mount bpffs at {}", BPF_FS_MOUNT_POINT))?;
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "{} is not a BPF filesystem, so {} cannot hold pinned maps. \
+         Mount it with `mount -t bpf bpf {}` (or pass --mount-bpffs for {}), \
+         or choose a --pin-prefix on an existing bpffs mount.",
+        mount_point.display(),
+        pin_prefix.display(),
+        mount_point.display(),
+        BPF_FS_MOUNT_POINT
+    ))
+}
+
+/// Returns `true` if `path` is on a BPF filesystem.
+fn is_bpffs(path: &Path) -> Result<bool> {
+    let stat = statfs(path).with_context(|| format!("Failed to statfs {}", path.display()))?;
+    Ok(stat.filesystem_type().0 as u32 == BPF_FS_MAGIC)
+}
+
+/// A session as reported by the `dump` subcommand and [`Bpf::get_rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionEntry {
+    pub key: SessionKey,
+    /// Seconds since the session was authorized
+    pub age_sec: u64,
+    /// Seconds left before the session idles out, negative once expired
+    pub time_left_sec: i64,
+}
 
 /// BPF program manager - handles loading and interacting with the XDP firewall..
+///
+/// The XDP program is attached through netlink and stays attached after the
+/// agent exits, so the interface never falls back to an unfiltered state.
+/// With [`AttachHook::Tc`] the tc program is attached to clsact ingress
+/// instead; it stays attached the same way and shares every map. With
+/// `network.cgroup` set, a `connect4` program also filters outbound
+/// connections, see [`cgroup::attach`].
 pub struct Bpf<'a> {
     skel: AegisSkel<'a>,
-    _link: Link,
+    /// Hook the filter is attached to
+    attach_hook: AttachHook,
+    /// Attach mode requested in the configuration, reused on re-attach
+    xdp_mode: XdpMode,
+    /// Cleared when the kernel rejects batch deletion at runtime
+    batch_delete: AtomicBool,
+    /// Running count of sessions in the map, see [`Bpf::approx_len`]
+    session_count: SessionCount,
+    /// `GRACE_UNTIL` compiled into the program, 0 without a grace window
+    grace_until: u64,
+    /// Time source for session timestamps, see [`Clock`]
+    clock: Arc<dyn Clock>,
+}
+
+/// Running count of sessions behind [`Bpf::approx_len`].
+struct SessionCount(AtomicUsize);
+
+impl SessionCount {
+    fn new(count: usize) -> Self {
+        Self(AtomicUsize::new(count))
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, count: usize) {
+        self.0.store(count, Ordering::Relaxed);
+    }
+
+    fn add(&self, count: usize) {
+        self.0.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Stops at zero, since the count may already miss evicted sessions.
+    fn sub(&self, count: usize) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                Some(n.saturating_sub(count))
+            });
+    }
 }
 
 unsafe impl Zeroable for session_key {}
@@ -37,17 +591,51 @@ unsafe impl Pod for session_val {}
 impl<'a> Bpf<'a> {
     /// Creates a new BPF instance and attaches it to the specified interface.
     pub fn new(interface_index: i32, config: &Config) -> Result<Self> {
-        if !Path::new(BPF_FS_PATH).exists() {
-            fs::create_dir_all(BPF_FS_PATH).context("Failed to create BPF FS directory")?;
+        let pin_prefix = config.pin_prefix.as_path();
+        check_bpffs(pin_prefix, config.mount_bpffs)?;
+        if !pin_prefix.exists() {
+            fs::create_dir_all(pin_prefix).context("Failed to create BPF FS directory")?;
         }
 
+        if config.attach_hook == AttachHook::Tc && config.xdp_mode == XdpMode::Offload {
+            bail!("XDP offload mode cannot be used with the tc attach hook");
+        }
+        let redirect_ifindex = match &config.redirect_to {
+            Some(_)
+                if config.attach_hook == AttachHook::Xdp && config.xdp_mode == XdpMode::Offload =>
+            {
+                bail!("Redirecting session traffic is not supported in XDP offload mode");
+            }
+            Some(name) => iface::index_of(name).context("Invalid redirect target")?,
+            None => 0,
+        };
+
+        // Sessions and blocked sources survive restarts and upgrades
+        let map_pin_path = map_pin_path(pin_prefix);
+        prepare_pinned_map(&map_pin_path, MapLayout::session(), config.force_recreate)?;
+        let blocklist_pin_path = blocklist_pin_path(pin_prefix);
+        prepare_pinned_map(
+            &blocklist_pin_path,
+            MapLayout::blocklist(),
+            config.force_recreate,
+        )?;
+
         let skel_builder = AegisSkelBuilder::default();
 
         // Open the BPF skeleton with static lifetime
         let open_object = Box::new_uninit();
         let open_object_ref = Box::leak(open_object);
         let mut open_skel = skel_builder.open(open_object_ref)?;
-        open_skel.maps.session.set_pin_path(MAP_PIN_PATH)?;
+        open_skel.maps.session.set_pin_path(&map_pin_path)?;
+        open_skel.maps.blocklist.set_pin_path(&blocklist_pin_path)?;
+
+        // Offloaded programs must be loaded for the target device
+        if config.attach_hook == AttachHook::Xdp && config.xdp_mode == XdpMode::Offload {
+            open_skel
+                .progs
+                .xdp_drop_prog
+                .set_ifindex(interface_index as u32);
+        }
 
         // Configure BPF global variables before loading
         let rodata = open_skel
@@ -57,8 +645,32 @@ impl<'a> Bpf<'a> {
             .ok_or_else(|| anyhow!("rodata not memory-mapped"))?;
 
         rodata.CONTROLLER_PORT = config.controller_port.to_be();
-        rodata.CONTROLLER_IP = u32::from(config.controller_ip).to_be();
+        for (slot, port) in rodata
+            .CONTROLLER_EXTRA_PORTS
+            .iter_mut()
+            .zip(&config.controller_extra_ports)
+        {
+            *slot = port.to_be();
+        }
+        rodata.CONTROLLER_EXTRA_PORT_COUNT = config.controller_extra_ports.len() as u32;
+        if let Some(net) = config.controller_net {
+            rodata.CONTROLLER_NET = ip::to_network(net.network());
+            rodata.CONTROLLER_NET_MASK = ip::to_network(net.netmask());
+        }
+        rodata.GRPC_PORT = config.grpc_server_port.to_be();
+        rodata.MONITOR_IP = config.grpc_monitor_ip.map_or(0, ip::to_network);
         rodata.LAZY_UPDATE_TIMEOUT = config.lazy_update_timeout;
+        rodata.STRICT_TCP = config.strict_tcp;
+        rodata.BIND_SRC_MAC = config.bind_src_mac;
+        rodata.FRAGMENT_POLICY = config.fragment_policy as u8;
+        rodata.MAX_CONNS_PER_SRC = config.max_conns_per_src;
+        rodata.REDIRECT_IFINDEX = redirect_ifindex;
+        let grace_until = if config.enforcement_grace_sec > 0 {
+            grace_deadline(MonotonicClock.now_ns(), config.enforcement_grace_sec)
+        } else {
+            0
+        };
+        rodata.GRACE_UNTIL = grace_until;
 
         debug!("BPF configuration applied");
 
@@ -69,119 +681,622 @@ impl<'a> Bpf<'a> {
         })?;
         debug!("BPF program loaded into kernel");
 
-        if Path::new(LINK_PIN_PATH).exists() {
-            let _ = fs::remove_file(LINK_PIN_PATH);
+        // Program always-allowed ports before the program sees traffic
+        for port in &config.always_allow_ports {
+            skel.maps
+                .allowed_ports
+                .update(bytemuck::bytes_of(&port.to_be()), &[1u8], MapFlags::ANY)
+                .with_context(|| format!("Failed to allow port {}", port))?;
+        }
+        if !config.always_allow_ports.is_empty() {
+            info!("Always-allowed ports: {:?}", config.always_allow_ports);
         }
 
-        // Attach XDP program to interface
-        debug!("Attaching XDP to interface {}", interface_index);
-        let mut link = skel
-            .progs
-            .xdp_drop_prog
-            .attach_xdp(interface_index)
-            .context("Failed to attach XDP program")?;
+        if let Some(name) = &config.redirect_to {
+            skel.maps
+                .redirect_devmap
+                .update(
+                    &0u32.to_ne_bytes(),
+                    &redirect_ifindex.to_ne_bytes(),
+                    MapFlags::ANY,
+                )
+                .with_context(|| format!("Failed to set redirect target {}", name))?;
+            info!(
+                "Redirecting session traffic to {} (index: {})",
+                name, redirect_ifindex
+            );
+        }
 
-        link.pin(LINK_PIN_PATH).context("Failed to pin XDP link")?;
+        Self::set_controller_ip_in(&skel.maps.controller_ip, config.controller_ip)?;
+        for &controller in &config.controller_extra_ips {
+            let raw_ip = ip::to_network(controller);
+            skel.maps
+                .controllers
+                .update(bytemuck::bytes_of(&raw_ip), &[1u8], MapFlags::ANY)
+                .with_context(|| format!("Failed to add extra controller {}", controller))?;
+        }
+        if !config.controller_extra_ips.is_empty() {
+            info!("Extra controllers: {:?}", config.controller_extra_ips);
+        }
+        if !config.controller_extra_ports.is_empty() {
+            info!(
+                "Extra controller ports: {:?}",
+                config.controller_extra_ports
+            );
+        }
 
-        Ok(Self { skel, _link: link })
-    }
+        // Start permissive so in-flight connections survive until the
+        // controller has pushed its sessions; the agent flips it back later,
+        // and GRACE_UNTIL ends it in the datapath if the agent does not
+        if config.enforcement_grace_sec > 0 {
+            Self::set_enforcing_in(&skel.maps.enforcement, false)?;
+            warn!(
+                "Grace window open: unauthorized traffic passes for the first {}s after attach",
+                config.enforcement_grace_sec
+            );
+        }
 
-    /// Adds a firewall rule to allow traffic for a specific session.
-    pub fn add_rule(&self, dest_ip: u32, src_ip: u32, dest_port: u16) -> Result<()> {
-        let now = Self::get_ktime_ns();
+        // Release the pinned link left behind by older agent versions
+        let link_pin_path = link_pin_path(pin_prefix);
+        if link_pin_path.exists() {
+            let _ = fs::remove_file(link_pin_path);
+        }
 
-        let key = session_key {
-            dest_ip,
-            src_ip,
-            dest_port,
-        };
-        let val = session_val {
-            created_at_ns: now,
-            last_seen_ns: now,
-        };
+        // Attach the filter to the interface
+        debug!(
+            "Attaching {} program to interface {} (XDP mode: {})",
+            config.attach_hook, interface_index, config.xdp_mode
+        );
+        let attached = Self::attach(&skel, interface_index, config.attach_hook, config.xdp_mode)?;
+        info!("Filter attached as {}", attached);
 
-        self.skel.maps.session.update(
-            bytemuck::bytes_of(&key),
-            bytemuck::bytes_of(&val),
-            MapFlags::ANY,
-        )?;
+        // Extend the policy to outbound connections from the configured cgroup
+        match &config.cgroup {
+            Some(path) => {
+                let resolvers = cgroup::dns_resolvers(&config.dns_resolvers)?;
+                if resolvers.is_empty() {
+                    warn!(
+                        "No DNS resolvers configured or in /etc/resolv.conf; {} cannot resolve names",
+                        path.display()
+                    );
+                } else {
+                    info!("Egress DNS allowed to {:?}", resolvers);
+                }
+                Self::set_dns_resolvers_in(&skel.maps.dns_resolvers, &resolvers)?;
+                cgroup::attach(&skel.progs.connect4_prog, path, pin_prefix)?
+            }
+            None => {
+                if cgroup::detach(pin_prefix)? {
+                    info!("Detached the egress filter left by an earlier run");
+                }
+            }
+        }
+
+        let batch_delete = config.batch_delete && probe_features().batch_delete;
+        if !batch_delete {
+            info!("Stale rules will be deleted one key at a time");
+        }
 
-        debug!("Added rule {} -> {}:{}", src_ip, dest_ip, dest_port);
+        Ok(Self {
+            skel,
+            attach_hook: config.attach_hook,
+            xdp_mode: config.xdp_mode,
+            batch_delete: AtomicBool::new(batch_delete),
+            session_count: SessionCount::new(Self::len_in(&skel.maps.session)?),
+            grace_until,
+            clock: Arc::new(MonotonicClock),
+        })
+    }
+
+    /// Replaces the time source used for session timestamps.
+    ///
+    /// Only meant for tests: the XDP program always stamps sessions with the
+    /// kernel monotonic clock, so any other clock drifts from the datapath.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
 
+    /// Attaches the loaded program to `interface_index` again, e.g. after a
+    /// driver reset or after the interface was recreated. Maps and sessions
+    /// are kept.
+    pub fn reattach(&self, interface_index: i32) -> Result<()> {
+        let attached = Self::attach(&self.skel, interface_index, self.attach_hook, self.xdp_mode)?;
+        info!(
+            "Filter re-attached to interface {} as {}",
+            interface_index, attached
+        );
         Ok(())
     }
 
-    /// Removes a firewall rule from the map.
-    pub fn remove_rule(&self, dest_ip: u32, src_ip: u32, dest_port: u16) -> Result<()> {
-        let key = session_key {
-            dest_ip,
-            src_ip,
-            dest_port,
+    /// Attaches the program for `hook` and describes how it was attached.
+    ///
+    /// Refuses to replace a program that is not an Aegis filter; one left by
+    /// an earlier run, or by this agent before a re-attach, is replaced.
+    fn attach(
+        skel: &AegisSkel<'a>,
+        interface_index: i32,
+        hook: AttachHook,
+        mode: XdpMode,
+    ) -> Result<String> {
+        let ours = Self::prog_for(skel, hook).name().to_string_lossy();
+        let found = match Self::is_attached(skel, interface_index, hook)? {
+            Some(id) => Some((id, prog_name(id)?)),
+            None => None,
         };
-        self.skel
-            .maps
-            .session
-            .delete(bytemuck::bytes_of(&key))
-            .map_err(|e| anyhow!(e))
+        check_replaceable(interface_index, hook, found, &ours)?;
+
+        match hook {
+            AttachHook::Xdp => {
+                let mode = Self::attach_xdp(skel, interface_index, mode)?;
+                Ok(format!("XDP in {} mode", mode))
+            }
+            AttachHook::Tc => {
+                Self::attach_tc(skel, interface_index)?;
+                Ok("tc clsact ingress".to_string())
+            }
+        }
     }
 
-    /// Updates all session rules that use the old destination IP to use the new destination IP.
-    pub fn update_dest_ip(&self, old_dest_ip: u32, new_dest_ip: u32) -> Result<usize> {
-        if old_dest_ip == new_dest_ip {
-            info!(
-                "IP unchanged (old: {}, new: {}), skipping update",
-                old_dest_ip, new_dest_ip
-            );
-            return Ok(0);
+    /// Returns the ID of the program attached to `interface_index` on `hook`,
+    /// whoever attached it. On the tc hook only the filter slot this agent
+    /// uses is checked.
+    pub fn is_attached(
+        skel: &AegisSkel<'a>,
+        interface_index: i32,
+        hook: AttachHook,
+    ) -> Result<Option<u32>> {
+        match hook {
+            AttachHook::Xdp => {
+                let id = Xdp::new(skel.progs.xdp_drop_prog.as_fd())
+                    .query_id(interface_index, XdpFlags::NONE)
+                    .context("Failed to query attached XDP program")?;
+                Ok((id != 0).then_some(id))
+            }
+            AttachHook::Tc => {
+                let mut builder = TcHookBuilder::new(skel.progs.tc_drop_prog.as_fd());
+                builder
+                    .ifindex(interface_index)
+                    .handle(TC_HANDLE)
+                    .priority(TC_PRIORITY);
+                // Fails without a clsact qdisc or a filter in our slot
+                Ok(builder.hook(TC_INGRESS).query().ok())
+            }
         }
+    }
 
-        // Find all sessions with the old destination IP
-        let sessions_to_update: Vec<(u32, u16, session_val)> = self
-            .skel
-            .maps
-            .session
-            .keys()
-            .filter_map(|key_bytes| {
-                // Validate key size
-                if key_bytes.len() != std::mem::size_of::<session_key>() {
-                    warn!(
-                        "Invalid session key size: {}, expected {}",
-                        key_bytes.len(),
-                        std::mem::size_of::<session_key>()
-                    );
-                    return None;
-                }
+    /// Program used on `hook`.
+    fn prog_for<'s>(skel: &'s AegisSkel<'a>, hook: AttachHook) -> &'s libbpf_rs::Program<'a> {
+        match hook {
+            AttachHook::Xdp => &skel.progs.xdp_drop_prog,
+            AttachHook::Tc => &skel.progs.tc_drop_prog,
+        }
+    }
 
-                // Validate alignment
-                let is_aligned = (key_bytes.as_ptr() as usize)
-                    .is_multiple_of(std::mem::align_of::<session_key>());
-                if !is_aligned {
-                    warn!(
-                        "Misaligned session key at address {:#x}, expected alignment {}",
-                        key_bytes.as_ptr() as usize,
-                        std::mem::align_of::<session_key>()
-                    );
-                    return None;
+    /// Attaches the tc program to the clsact ingress hook, creating the
+    /// clsact qdisc if the interface does not have one yet. An earlier
+    /// filter from this agent is replaced.
+    fn attach_tc(skel: &AegisSkel<'a>, interface_index: i32) -> Result<()> {
+        let mut builder = TcHookBuilder::new(skel.progs.tc_drop_prog.as_fd());
+        builder
+            .ifindex(interface_index)
+            .replace(true)
+            .handle(TC_HANDLE)
+            .priority(TC_PRIORITY);
+        let mut hook = builder.hook(TC_INGRESS);
+
+        if let Err(e) = hook.create()
+            && e.kind() != libbpf_rs::ErrorKind::AlreadyExists
+        {
+            return Err(e).context("Failed to create clsact qdisc");
+        }
+        hook.attach().context("Failed to attach tc program")?;
+        Ok(())
+    }
+
+    /// Attaches the XDP program in the requested mode and returns the mode used.
+    /// In `Auto` mode native attach is tried first, falling back to SKB mode.
+    fn attach_xdp(skel: &AegisSkel<'a>, interface_index: i32, mode: XdpMode) -> Result<XdpMode> {
+        let xdp = Xdp::new(skel.progs.xdp_drop_prog.as_fd());
+
+        match mode {
+            XdpMode::Auto => match xdp.attach(interface_index, Self::xdp_flags(XdpMode::Native)) {
+                Ok(()) => Ok(XdpMode::Native),
+                Err(e) => {
+                    warn!("Native XDP attach failed ({}), falling back to SKB mode", e);
+                    xdp.attach(interface_index, Self::xdp_flags(XdpMode::Skb))
+                        .context("Failed to attach XDP program in SKB mode")?;
+                    Ok(XdpMode::Skb)
                 }
+            },
+            mode => {
+                xdp.attach(interface_index, Self::xdp_flags(mode))
+                    .with_context(|| format!("Failed to attach XDP program in {} mode", mode))?;
+                Ok(mode)
+            }
+        }
+    }
 
-                let key: &session_key = bytemuck::from_bytes(&key_bytes);
+    /// Maps an attach mode to the kernel's `XDP_FLAGS_*` value.
+    fn xdp_flags(mode: XdpMode) -> XdpFlags {
+        match mode {
+            XdpMode::Auto => XdpFlags::NONE,
+            XdpMode::Native => XdpFlags::DRV_MODE,
+            XdpMode::Skb => XdpFlags::SKB_MODE,
+            XdpMode::Offload => XdpFlags::HW_MODE,
+        }
+    }
 
-                // Only process sessions with the old destination IP
-                if key.dest_ip == old_dest_ip {
-                    if let Ok(Some(val_bytes)) =
-                        self.skel.maps.session.lookup(&key_bytes, MapFlags::ANY)
-                    {
-                        // Validate value size
-                        if val_bytes.len() != std::mem::size_of::<session_val>() {
-                            warn!(
-                                "Invalid session value size: {}, expected {}",
-                                val_bytes.len(),
-                                std::mem::size_of::<session_val>()
-                            );
-                            return None;
-                        }
+    /// Adds a firewall rule to allow traffic for a specific session.
+    /// A `dest_port` of [`WILDCARD_PORT`] allows every port on `dest_ip`, a
+    /// `src_port` of [`ANY_SRC_PORT`] accepts traffic from any source port, and
+    /// a `src_ip` of [`ANY_SRC_IP`] lets every source reach `dest_ip:dest_port`.
+    /// Returns true if the session already existed and was refreshed.
+    pub fn add_rule(&self, key: SessionKey) -> Result<bool> {
+        self.add_rule_in_epoch(key, NO_EPOCH, NO_SRC_MAC)
+    }
 
-                        // Validate alignment
+    /// Like [`Bpf::add_rule`], tagging the session with the controller
+    /// `epoch` that submitted it. Refreshing a session moves it to `epoch`.
+    /// A non-zero `src_mac` binds the session to that MAC up front, see
+    /// [`Bpf::add_bound_rule_to`].
+    pub fn add_rule_in_epoch(&self, key: SessionKey, epoch: u64, src_mac: u64) -> Result<bool> {
+        let existed = Self::add_bound_rule_to(
+            &self.skel.maps.session,
+            key,
+            self.clock.now_ns(),
+            epoch,
+            src_mac,
+        )?;
+        if !existed {
+            self.session_count.add(1);
+        }
+        Ok(existed)
+    }
+
+    /// Removes a firewall rule from the map.
+    /// Returns false if there was no such session.
+    pub fn remove_rule(&self, key: SessionKey) -> Result<bool> {
+        let existed = Self::remove_rule_from(&self.skel.maps.session, key)?;
+        if existed {
+            self.session_count.sub(1);
+        }
+        Ok(existed)
+    }
+
+    /// Number of sessions in the map without walking it, for hot paths such
+    /// as every `SubmitSession`.
+    ///
+    /// Kept up to date by this agent's own changes, and reset to the exact
+    /// count by [`Bpf::map_stats`], which every cleanup cycle calls. Sessions
+    /// evicted by the LRU or changed through the pinned map by a subcommand
+    /// are only reflected after that.
+    pub fn approx_len(&self) -> usize {
+        self.session_count.get()
+    }
+
+    /// Counts the sessions in the map, including expired ones not yet reaped.
+    ///
+    /// Walks the map keys without reading values, so it is cheaper than
+    /// [`Bpf::list_rules`] but still linear in the number of sessions.
+    pub fn len(&self) -> Result<usize> {
+        Self::len_in(&self.skel.maps.session)
+    }
+
+    /// Returns true if the map holds no sessions at all.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    fn len_in(map: &impl MapCore) -> Result<usize> {
+        Ok(map.keys().count())
+    }
+
+    /// Returns the number of sessions in the map and its capacity.
+    ///
+    /// The kernel keeps no element count for hash maps that user space can
+    /// query, so the count walks the keys like [`Bpf::len`]. Once the map is
+    /// full, new sessions evict the least recently used ones.
+    pub fn map_stats(&self) -> Result<(usize, usize)> {
+        let (used, capacity) = Self::map_stats_in(&self.skel.maps.session)?;
+        self.session_count.set(used);
+        Ok((used, capacity))
+    }
+
+    fn map_stats_in(map: &impl MapCore) -> Result<(usize, usize)> {
+        Ok((Self::len_in(map)?, map.max_entries() as usize))
+    }
+
+    /// Checks that the maps still answer: writes the current time to the
+    /// dedicated `probe` map and reads it back, then looks up a key no session
+    /// uses in the session map.
+    ///
+    /// The session map is only read, so probing never evicts a session and
+    /// never shows up in listings or `MonitorSessions`.
+    pub fn probe_session_map(&self) -> Result<()> {
+        Self::probe_in(&self.skel.maps.probe, &self.skel.maps.session)
+    }
+
+    fn probe_in(probe: &impl MapCore, session: &impl MapCore) -> Result<()> {
+        let stamp = MonotonicClock.now_ns().to_ne_bytes();
+        probe
+            .update(&0u32.to_ne_bytes(), &stamp, MapFlags::ANY)
+            .context("Failed to write probe entry")?;
+        let read = probe
+            .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+            .context("Failed to read probe entry")?;
+        if read.as_deref() != Some(&stamp[..]) {
+            bail!("Probe entry read back differently than written");
+        }
+        let raw_key = session_key::from(PROBE_KEY);
+        if session
+            .lookup(bytemuck::bytes_of(&raw_key), MapFlags::ANY)
+            .context("Failed to read session map")?
+            .is_some()
+        {
+            bail!("Session map returned an entry for the probe key");
+        }
+        Ok(())
+    }
+
+    /// Inserts or refreshes a session in `map`, stamped `now` and tagged with
+    /// `epoch`; shared with [`PinnedSessions`]. Returns true if the session
+    /// already existed.
+    ///
+    /// Re-submitting a session only moves `last_seen_ns`, so its age and
+    /// `max_session_age_ns` keep counting from the first submission.
+    fn add_rule_to(map: &impl MapCore, key: SessionKey, now: u64, epoch: u64) -> Result<bool> {
+        Self::add_bound_rule_to(map, key, now, epoch, NO_SRC_MAC)
+    }
+
+    /// Like [`Bpf::add_rule_to`], binding the session to `src_mac` (a MAC in
+    /// its low 48 bits) for `network.bind_src_mac`, replacing any earlier
+    /// binding. With [`NO_SRC_MAC`] the XDP program binds the MAC of the first
+    /// packet that passes instead, which trusts whichever host sends first.
+    fn add_bound_rule_to(
+        map: &impl MapCore,
+        key: SessionKey,
+        now: u64,
+        epoch: u64,
+        src_mac: u64,
+    ) -> Result<bool> {
+        key.check_src_mac(src_mac)?;
+        // The XDP program only tries ANY_SRC_PORT for wildcard lookups
+        if key.is_wildcard() && key.has_src_port() {
+            bail!("A source port cannot be combined with a wildcard destination port");
+        }
+        // Any-source sessions are only looked up per destination port
+        if key.is_any_source() && (key.is_wildcard() || key.has_src_port()) {
+            bail!("An any-source session needs a specific destination port and no source port");
+        }
+
+        let raw_key = session_key::from(key);
+
+        // Refreshing keeps the original creation time, the bound MAC so a
+        // refresh does not let another host claim the session, and the flags
+        // so strict TCP does not treat established connections as new.
+        let old = map.lookup(bytemuck::bytes_of(&raw_key), MapFlags::ANY)?;
+        let existed = old.is_some();
+        let (created_at_ns, bound_mac, flags) = match old {
+            Some(bytes) if bytes.len() == std::mem::size_of::<session_val>() => {
+                let old: session_val = bytemuck::pod_read_unaligned(&bytes);
+                (old.created_at_ns, old.src_mac, old.flags)
+            }
+            _ => (now, NO_SRC_MAC, 0),
+        };
+        let src_mac = if src_mac != NO_SRC_MAC {
+            src_mac
+        } else {
+            bound_mac
+        };
+        let val = session_val {
+            created_at_ns,
+            last_seen_ns: now,
+            epoch,
+            src_mac,
+            flags,
+        };
+
+        map.update(
+            bytemuck::bytes_of(&raw_key),
+            bytemuck::bytes_of(&val),
+            MapFlags::ANY,
+        )?;
+
+        if existed {
+            debug!("Refreshed rule {}", key);
+        } else {
+            debug!("Added rule {}", key);
+        }
+
+        Ok(existed)
+    }
+
+    /// Deletes a session from `map`; shared with [`PinnedSessions`].
+    /// Returns false if there was no such session.
+    fn remove_rule_from(map: &impl MapCore, key: SessionKey) -> Result<bool> {
+        let raw_key = session_key::from(key);
+        match map.delete(bytemuck::bytes_of(&raw_key)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == libbpf_rs::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(anyhow!(e)),
+        }
+    }
+
+    /// Looks up the session stored under exactly `key`, without walking the map.
+    ///
+    /// Only the literal key is checked: a wildcard or any-source session that
+    /// would also admit the flow is not reported.
+    pub fn get_rule(&self, key: SessionKey, timeout_ns: u64) -> Result<Option<SessionEntry>> {
+        Self::get_rule_in(
+            &self.skel.maps.session,
+            key,
+            self.clock.now_ns(),
+            timeout_ns,
+        )
+    }
+
+    fn get_rule_in(
+        map: &impl MapCore,
+        key: SessionKey,
+        now: u64,
+        timeout_ns: u64,
+    ) -> Result<Option<SessionEntry>> {
+        let raw_key = session_key::from(key);
+        let entry = map
+            .lookup(bytemuck::bytes_of(&raw_key), MapFlags::ANY)
+            .with_context(|| format!("Failed to look up session {}", key))?
+            .filter(|bytes| bytes.len() == std::mem::size_of::<session_val>())
+            .map(|bytes| {
+                let val: session_val = bytemuck::pod_read_unaligned(&bytes);
+                Self::session_entry(key, SessionVal::from(val), now, timeout_ns)
+            });
+        Ok(entry)
+    }
+
+    /// Drops all traffic from `ip`, overriding any session that would allow it.
+    pub fn block_ip(&self, ip: Ipv4Addr) -> Result<()> {
+        Self::block_ip_in(&self.skel.maps.blocklist, ip)
+    }
+
+    /// Lifts a block set by [`Bpf::block_ip`].
+    pub fn unblock_ip(&self, ip: Ipv4Addr) -> Result<()> {
+        Self::unblock_ip_in(&self.skel.maps.blocklist, ip)
+    }
+
+    /// Switches between dropping what the policy rejects and passing every
+    /// packet. Sessions are matched and refreshed in both modes.
+    pub fn set_enforcing(&self, enforcing: bool) -> Result<()> {
+        Self::set_enforcing_in(&self.skel.maps.enforcement, enforcing)
+    }
+
+    /// Replaces the controller address the program lets through, e.g. after
+    /// `controller.ip_file` changes.
+    pub fn set_controller_ip(&self, ip: Ipv4Addr) -> Result<()> {
+        Self::set_controller_ip_in(&self.skel.maps.controller_ip, ip)
+    }
+
+    /// Returns false while the program runs in permissive mode, which ends
+    /// at `GRACE_UNTIL` even if the mode was never switched back.
+    pub fn is_enforcing(&self) -> Result<bool> {
+        let mode = self
+            .skel
+            .maps
+            .enforcement
+            .lookup(&0u32.to_ne_bytes(), MapFlags::ANY)
+            .context("Failed to read enforcement mode")?
+            .ok_or_else(|| anyhow!("Enforcement mode entry missing"))?;
+        Ok(enforces(
+            bytemuck::pod_read_unaligned::<u32>(&mode),
+            self.grace_until,
+            MonotonicClock.now_ns(),
+        ))
+    }
+
+    fn set_enforcing_in(map: &impl MapCore, enforcing: bool) -> Result<()> {
+        let mode = if enforcing {
+            MODE_ENFORCING
+        } else {
+            MODE_PERMISSIVE
+        };
+        map.update(&0u32.to_ne_bytes(), &mode.to_ne_bytes(), MapFlags::ANY)
+            .context("Failed to set enforcement mode")
+    }
+
+    pub(crate) fn set_controller_ip_in(map: &impl MapCore, ip: Ipv4Addr) -> Result<()> {
+        let raw_ip = ip::to_network(ip);
+        map.update(
+            &0u32.to_ne_bytes(),
+            bytemuck::bytes_of(&raw_ip),
+            MapFlags::ANY,
+        )
+        .with_context(|| format!("Failed to set controller IP {}", ip))
+    }
+
+    /// Lets the egress filter's cgroup reach port 53 on each of `resolvers`.
+    pub(crate) fn set_dns_resolvers_in(map: &impl MapCore, resolvers: &[Ipv4Addr]) -> Result<()> {
+        for &resolver in resolvers {
+            let raw_ip = ip::to_network(resolver);
+            map.update(bytemuck::bytes_of(&raw_ip), &[1u8], MapFlags::ANY)
+                .with_context(|| format!("Failed to add DNS resolver {}", resolver))?;
+        }
+        Ok(())
+    }
+
+    fn block_ip_in(map: &impl MapCore, ip: Ipv4Addr) -> Result<()> {
+        let raw_ip = ip::to_network(ip);
+        map.update(bytemuck::bytes_of(&raw_ip), &[1u8], MapFlags::ANY)
+            .with_context(|| format!("Failed to block {}", ip))?;
+        warn!("Blocked all traffic from {}", ip);
+        Ok(())
+    }
+
+    fn unblock_ip_in(map: &impl MapCore, ip: Ipv4Addr) -> Result<()> {
+        let raw_ip = ip::to_network(ip);
+        map.delete(bytemuck::bytes_of(&raw_ip))
+            .with_context(|| format!("Failed to unblock {} (not blocked?)", ip))?;
+        info!("Unblocked {}", ip);
+        Ok(())
+    }
+
+    /// Updates all session rules that use the old destination IP to use the new destination IP.
+    pub fn update_dest_ip(&self, old_dest: Ipv4Addr, new_dest: Ipv4Addr) -> Result<usize> {
+        if old_dest == new_dest {
+            info!(
+                "IP unchanged (old: {}, new: {}), skipping update",
+                old_dest, new_dest
+            );
+            return Ok(0);
+        }
+        let (old_dest_ip, new_dest_ip) = (ip::to_network(old_dest), ip::to_network(new_dest));
+
+        // Find all sessions with the old destination IP
+        let sessions_to_update: Vec<(session_key, session_val)> = self
+            .skel
+            .maps
+            .session
+            .keys()
+            .filter_map(|key_bytes| {
+                // Validate key size
+                if key_bytes.len() != std::mem::size_of::<session_key>() {
+                    warn!(
+                        "Invalid session key size: {}, expected {}",
+                        key_bytes.len(),
+                        std::mem::size_of::<session_key>()
+                    );
+                    return None;
+                }
+
+                // Validate alignment
+                let is_aligned = (key_bytes.as_ptr() as usize)
+                    .is_multiple_of(std::mem::align_of::<session_key>());
+                if !is_aligned {
+                    warn!(
+                        "Misaligned session key at address {:#x}, expected alignment {}",
+                        key_bytes.as_ptr() as usize,
+                        std::mem::align_of::<session_key>()
+                    );
+                    return None;
+                }
+
+                let key: &session_key = bytemuck::from_bytes(&key_bytes);
+
+                // Only process sessions with the old destination IP
+                if key.dest_ip == old_dest_ip {
+                    if let Ok(Some(val_bytes)) =
+                        self.skel.maps.session.lookup(&key_bytes, MapFlags::ANY)
+                    {
+                        // Validate value size
+                        if val_bytes.len() != std::mem::size_of::<session_val>() {
+                            warn!(
+                                "Invalid session value size: {}, expected {}",
+                                val_bytes.len(),
+                                std::mem::size_of::<session_val>()
+                            );
+                            return None;
+                        }
+
+                        // Validate alignment
                         let is_aligned = (val_bytes.as_ptr() as usize)
                             .is_multiple_of(std::mem::align_of::<session_val>());
                         if !is_aligned {
@@ -194,7 +1309,7 @@ impl<'a> Bpf<'a> {
                         }
 
                         let val: &session_val = bytemuck::from_bytes(&val_bytes);
-                        Some((key.src_ip, key.dest_port, *val))
+                        Some((*key, *val))
                     } else {
                         None
                     }
@@ -214,13 +1329,8 @@ impl<'a> Bpf<'a> {
 
             let mut successful_updates = 0;
 
-            for (src_ip, dest_port, val) in sessions_to_update {
+            for (old_key, val) in sessions_to_update {
                 // Remove the old rule
-                let old_key = session_key {
-                    dest_ip: old_dest_ip,
-                    src_ip,
-                    dest_port,
-                };
                 if let Err(e) = self.skel.maps.session.delete(bytemuck::bytes_of(&old_key)) {
                     warn!("Failed to delete old rule: {}", e);
                     continue;
@@ -229,8 +1339,7 @@ impl<'a> Bpf<'a> {
                 // Add the new rule with the same session value
                 let new_key = session_key {
                     dest_ip: new_dest_ip,
-                    src_ip,
-                    dest_port,
+                    ..old_key
                 };
                 if let Err(e) = self.skel.maps.session.update(
                     bytemuck::bytes_of(&new_key),
@@ -263,6 +1372,8 @@ impl<'a> Bpf<'a> {
                     successful_updates, total_to_update
                 );
             }
+            // A moved session may have replaced one already on the new IP
+            self.session_count.set(self.len()?);
 
             Ok(successful_updates)
         } else {
@@ -270,20 +1381,104 @@ impl<'a> Bpf<'a> {
         }
     }
 
-    /// Removes all stale firewall rules from the map.
+    /// Removes all stale firewall rules from the map: sessions idle for longer
+    /// than `timeout_ns`, and sessions older than `max_age_ns` no matter how
+    /// active they are. A `max_age_ns` of 0 disables the age cap.
     /// Returns the number of rules cleaned up.
-    pub fn cleanup_ebpf_rules(&self, timeout_ns: u64) -> Result<usize> {
-        let now = Self::get_ktime_ns();
+    pub fn cleanup_ebpf_rules(&self, timeout_ns: u64, max_age_ns: u64) -> Result<usize> {
+        let stale_keys = Self::stale_keys_in(
+            &self.skel.maps.session,
+            self.clock.now_ns(),
+            timeout_ns,
+            max_age_ns,
+        );
 
-        let stale_keys: Vec<Vec<u8>> = self
-            .skel
-            .maps
-            .session
-            .keys()
+        let removed = self.delete_keys(&stale_keys)?;
+        if removed > 0 {
+            debug!("Reaped {} stale session rules", removed);
+        }
+
+        Ok(removed)
+    }
+
+    /// Forgets connections tracked for `max_conns_per_src` that have seen no
+    /// packet for longer than `idle_ns`, e.g. because the destination reset
+    /// them, and recounts the open connections of every (source, destination)
+    /// pair. Returns the number of connections expired.
+    pub fn expire_idle_conns(&self, idle_ns: u64) -> Result<usize> {
+        Self::expire_idle_conns_in(
+            &self.skel.maps.flows,
+            &self.skel.maps.conn_counts,
+            self.clock.now_ns(),
+            idle_ns,
+        )
+    }
+
+    /// The recount also drops connections the LRU evicted from `flows`. A
+    /// connection the kernel opens or closes while it runs is only counted
+    /// correctly from the next cycle on.
+    fn expire_idle_conns_in(
+        flows: &impl MapCore,
+        conn_counts: &impl MapCore,
+        now: u64,
+        idle_ns: u64,
+    ) -> Result<usize> {
+        // A flow key starts with the (saddr, daddr) pair it counts towards
+        const PAIR_LEN: usize = 8;
+        let mut expired = 0;
+        let mut open: HashMap<Vec<u8>, u32> = HashMap::new();
+        for key in flows.keys() {
+            let last_seen = match flows.lookup(&key, MapFlags::ANY) {
+                Ok(Some(value)) => match <[u8; 8]>::try_from(value.as_slice()) {
+                    Ok(bytes) => u64::from_ne_bytes(bytes),
+                    Err(_) => continue,
+                },
+                // Closed since the key was listed
+                _ => continue,
+            };
+            if Self::is_expired(now, last_seen, idle_ns) {
+                match flows.delete(&key) {
+                    Ok(()) => expired += 1,
+                    Err(e) if e.kind() == libbpf_rs::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e).context("Failed to expire idle connection"),
+                }
+            } else {
+                *open.entry(key[..PAIR_LEN].to_vec()).or_default() += 1;
+            }
+        }
+
+        let pairs: Vec<Vec<u8>> = conn_counts.keys().collect();
+        for pair in pairs.iter().filter(|pair| !open.contains_key(*pair)) {
+            match conn_counts.delete(pair) {
+                Ok(()) => {}
+                Err(e) if e.kind() == libbpf_rs::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).context("Failed to reset connection count"),
+            }
+        }
+        for (pair, count) in &open {
+            conn_counts
+                .update(pair, &count.to_ne_bytes(), MapFlags::ANY)
+                .context("Failed to update connection count")?;
+        }
+
+        if expired > 0 {
+            debug!("Expired {} idle connections", expired);
+        }
+        Ok(expired)
+    }
+
+    /// Collects the keys of sessions in `map` idle for longer than
+    /// `timeout_ns`, or older than a non-zero `max_age_ns`, at `now`.
+    fn stale_keys_in(
+        map: &impl MapCore,
+        now: u64,
+        timeout_ns: u64,
+        max_age_ns: u64,
+    ) -> Vec<Vec<u8>> {
+        map.keys()
             .filter(|key_bytes| {
                 // Safely check value size before accessing
-                if let Ok(Some(val_bytes)) = self.skel.maps.session.lookup(key_bytes, MapFlags::ANY)
-                {
+                if let Ok(Some(val_bytes)) = map.lookup(key_bytes, MapFlags::ANY) {
                     // Validate size to prevent out-of-bounds access
                     if val_bytes.len() != std::mem::size_of::<session_val>() {
                         warn!(
@@ -303,44 +1498,239 @@ impl<'a> Bpf<'a> {
                     }
 
                     let val: &session_val = bytemuck::from_bytes(&val_bytes);
-                    now.saturating_sub(val.last_seen_ns) > timeout_ns
+                    Self::is_expired(now, val.last_seen_ns, timeout_ns)
+                        || Self::is_over_age(now, val.created_at_ns, max_age_ns)
                 } else {
                     false
                 }
             })
-            .collect();
+            .collect()
+    }
+
+    /// Removes every session not submitted in `current_epoch`, including
+    /// sessions added without an epoch, so a restarted controller can drop
+    /// whatever its previous incarnation left behind once it has
+    /// re-submitted the sessions it still wants.
+    /// Returns the number of sessions removed.
+    pub fn prune_stale_epochs(&self, current_epoch: u64) -> Result<usize> {
+        let stale_keys = Self::stale_epoch_keys_in(&self.skel.maps.session, current_epoch);
+        let removed = self.delete_keys(&stale_keys)?;
+        info!(
+            "Pruned {} sessions from epochs other than {}",
+            removed, current_epoch
+        );
+        Ok(removed)
+    }
+
+    /// Collects the keys of sessions in `map` whose epoch is not
+    /// `current_epoch`.
+    fn stale_epoch_keys_in(map: &impl MapCore, current_epoch: u64) -> Vec<Vec<u8>> {
+        map.keys()
+            .filter(|key_bytes| match map.lookup(key_bytes, MapFlags::ANY) {
+                Ok(Some(val_bytes)) if val_bytes.len() == std::mem::size_of::<session_val>() => {
+                    let val: session_val = bytemuck::pod_read_unaligned(&val_bytes);
+                    val.epoch != current_epoch
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Removes every session from the map, revoking all access at once.
+    /// Returns the number of sessions removed.
+    pub fn clear_all_rules(&self) -> Result<usize> {
+        let removed = Self::clear_all_in(&self.skel.maps.session, &self.batch_delete)?;
+        self.session_count.sub(removed);
+        warn!("Flushed all {} session rules", removed);
+        Ok(removed)
+    }
+
+    fn clear_all_in(map: &impl MapCore, batch_delete: &AtomicBool) -> Result<usize> {
+        let keys: Vec<Vec<u8>> = map.keys().collect();
+        Self::delete_keys_in(map, batch_delete, &keys)
+    }
+
+    /// Deletes `keys` from the session map in one batch, falling back to
+    /// per-key deletion when the kernel lacks batch support.
+    /// Returns the number of keys removed.
+    fn delete_keys(&self, keys: &[Vec<u8>]) -> Result<usize> {
+        let removed = Self::delete_keys_in(&self.skel.maps.session, &self.batch_delete, keys)?;
+        self.session_count.sub(removed);
+        Ok(removed)
+    }
 
-        let count = stale_keys.len();
+    /// Deletes `keys` from `map`, in one batch while `batch_delete` is set.
+    ///
+    /// The flag is only cleared when the kernel rejects batch deletion
+    /// itself (`EINVAL` or `EOPNOTSUPP`). Any other batch failure is retried
+    /// key by key for this call alone; after `ENOENT`, e.g. a key the LRU or
+    /// another caller already removed, keys that are gone count as removed.
+    fn delete_keys_in(
+        map: &impl MapCore,
+        batch_delete: &AtomicBool,
+        keys: &[Vec<u8>],
+    ) -> Result<usize> {
+        let count = keys.len();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let flat_keys: Vec<u8> = keys.concat();
+
+        // Validate that we have the right amount of data
+        if flat_keys.len() != count * std::mem::size_of::<session_key>() {
+            return Err(anyhow!("Key data size mismatch during deletion"));
+        }
 
-        if count > 0 {
-            let flat_keys: Vec<u8> = stale_keys.concat();
+        let delete_key = |key: &[u8]| map.delete(key).map_err(|e| anyhow!(e));
 
-            // Validate that we have the right amount of data
-            if flat_keys.len() != count * std::mem::size_of::<session_key>() {
-                return Err(anyhow!("Key data size mismatch during cleanup"));
+        let removed = if batch_delete.load(Ordering::Relaxed) {
+            match map.delete_batch(&flat_keys, count as u32, MapFlags::ANY, MapFlags::ANY) {
+                Ok(()) => count,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        libbpf_rs::ErrorKind::InvalidInput | libbpf_rs::ErrorKind::Unsupported
+                    ) =>
+                {
+                    // Kernels before 5.6 lack BPF_MAP_DELETE_BATCH
+                    warn!(
+                        "Kernel rejected batch delete ({}), deleting keys one at a time from now on",
+                        e
+                    );
+                    batch_delete.store(false, Ordering::Relaxed);
+                    Self::delete_each(keys, delete_key)
+                }
+                Err(e) if e.kind() == libbpf_rs::ErrorKind::NotFound => {
+                    // The batch stops at the first missing key
+                    debug!("Batch delete hit a missing key, retrying key by key");
+                    Self::delete_each(keys, |key| match map.delete(key) {
+                        Err(e) if e.kind() == libbpf_rs::ErrorKind::NotFound => Ok(()),
+                        res => res.map_err(|e| anyhow!(e)),
+                    })
+                }
+                Err(e) => {
+                    warn!("Batch delete failed ({}), retrying key by key", e);
+                    Self::delete_each(keys, delete_key)
+                }
             }
+        } else {
+            Self::delete_each(keys, delete_key)
+        };
 
-            self.skel.maps.session.delete_batch(
-                &flat_keys,
-                count as u32,
-                MapFlags::ANY,
-                MapFlags::ANY,
-            )?;
+        Ok(removed)
+    }
 
-            debug!("Reaped {} stale session rules", count);
+    /// Deletes keys one at a time, skipping failures.
+    /// Returns the number of keys removed.
+    fn delete_each(keys: &[Vec<u8>], mut delete: impl FnMut(&[u8]) -> Result<()>) -> usize {
+        keys.iter()
+            .filter(|key| match delete(key) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Failed to delete session rule: {}", e);
+                    false
+                }
+            })
+            .count()
+    }
+
+    /// Returns the program attached on the configured hook.
+    fn attached_prog(&self) -> BorrowedFd<'_> {
+        match self.attach_hook {
+            AttachHook::Xdp => self.skel.progs.xdp_drop_prog.as_fd(),
+            AttachHook::Tc => self.skel.progs.tc_drop_prog.as_fd(),
+        }
+    }
+
+    /// Reads the attached program's run statistics from the kernel.
+    ///
+    /// Counters only advance while statistics are enabled, see [`enable_stats`].
+    pub fn prog_stats(&self) -> Result<ProgStats> {
+        let mut info = libbpf_sys::bpf_prog_info::default();
+        let mut len = std::mem::size_of::<libbpf_sys::bpf_prog_info>() as u32;
+        let fd = self.attached_prog().as_raw_fd();
+
+        // SAFETY: `info` and `len` describe a valid, writable bpf_prog_info.
+        let ret = unsafe { libbpf_sys::bpf_prog_get_info_by_fd(fd, &mut info, &mut len) };
+        if ret < 0 {
+            return Err(std::io::Error::from_raw_os_error(-ret))
+                .context("Failed to read BPF program info");
         }
 
-        Ok(count)
+        Ok(ProgStats {
+            run_cnt: info.run_cnt,
+            run_time_ns: info.run_time_ns,
+        })
+    }
+
+    /// Reads the XDP program's event counters.
+    pub fn get_counters(&self) -> Result<Counters> {
+        let map = &self.skel.maps.counters;
+        Ok(Counters {
+            refresh_applied: Self::read_counter(map, COUNTER_REFRESH_APPLIED)?,
+            refresh_suppressed: Self::read_counter(map, COUNTER_REFRESH_SUPPRESSED)?,
+            conn_capped: Self::read_counter(map, COUNTER_CONN_CAPPED)?,
+            mac_mismatch: Self::read_counter(map, COUNTER_MAC_MISMATCH)?,
+        })
+    }
+
+    /// Sums one per-CPU counter across all CPUs.
+    fn read_counter(map: &impl MapCore, index: u32) -> Result<u64> {
+        let per_cpu = map
+            .lookup_percpu(&index.to_ne_bytes(), MapFlags::ANY)
+            .with_context(|| format!("Failed to read counter {}", index))?
+            .unwrap_or_default();
+        Ok(per_cpu
+            .iter()
+            .filter(|value| value.len() == std::mem::size_of::<u64>())
+            .map(|value| bytemuck::pod_read_unaligned::<u64>(value))
+            .sum())
     }
 
     /// Lists all active sessions with their remaining time.
-    /// Returns a vector of (src_ip, dest_ip, dest_port, time_left_sec).
-    pub fn list_rules(&self, timeout_ns: u64) -> Result<Vec<(u32, u32, u16, i32)>> {
-        let now = Self::get_ktime_ns();
-        let sessions = self
-            .skel
-            .maps
-            .session
+    /// Returns a vector of (session, time_left_sec); expired sessions that
+    /// have not been reaped yet report a negative time left.
+    /// Wildcard sessions are reported with `dest_port` set to [`WILDCARD_PORT`],
+    /// and any-source sessions with `src_ip` set to [`ANY_SRC_IP`].
+    pub fn list_rules(&self, timeout_ns: u64) -> Result<Vec<(SessionKey, i64)>> {
+        Self::list_rules_in(&self.skel.maps.session, self.clock.now_ns(), timeout_ns)
+    }
+
+    /// Reads every session in `map` as seen at `now`; shared with [`PinnedSessions`].
+    fn list_rules_in(
+        map: &impl MapCore,
+        now: u64,
+        timeout_ns: u64,
+    ) -> Result<Vec<(SessionKey, i64)>> {
+        let sessions = Self::entries_in(map)?
+            .into_iter()
+            .map(|(key, val)| (key, Self::time_left_sec(now, val.last_seen_ns, timeout_ns)))
+            .collect();
+        Ok(sessions)
+    }
+
+    /// Reads every session in `map` with its age at `now`, see [`SessionEntry`].
+    fn dump_rules_in(map: &impl MapCore, now: u64, timeout_ns: u64) -> Result<Vec<SessionEntry>> {
+        let entries = Self::entries_in(map)?
+            .into_iter()
+            .map(|(key, val)| Self::session_entry(key, val, now, timeout_ns))
+            .collect();
+        Ok(entries)
+    }
+
+    /// Describes a session as seen at `now`.
+    fn session_entry(key: SessionKey, val: SessionVal, now: u64, timeout_ns: u64) -> SessionEntry {
+        SessionEntry {
+            key,
+            age_sec: now.saturating_sub(val.created_at_ns) / 1_000_000_000,
+            time_left_sec: Self::time_left_sec(now, val.last_seen_ns, timeout_ns),
+        }
+    }
+
+    /// Reads every raw entry in `map`, skipping any with an unexpected layout.
+    fn entries_in(map: &impl MapCore) -> Result<Vec<(SessionKey, SessionVal)>> {
+        let entries = map
             .keys()
             .filter_map(|key_bytes| {
                 // Validate sizes before accessing to prevent out-of-bounds reads
@@ -361,9 +1751,7 @@ impl<'a> Bpf<'a> {
                     return None;
                 }
 
-                if let Ok(Some(val_bytes)) =
-                    self.skel.maps.session.lookup(&key_bytes, MapFlags::ANY)
-                {
+                if let Ok(Some(val_bytes)) = map.lookup(&key_bytes, MapFlags::ANY) {
                     // Validate value size
                     if val_bytes.len() != std::mem::size_of::<session_val>() {
                         warn!(
@@ -384,34 +1772,1756 @@ impl<'a> Bpf<'a> {
 
                     let key: &session_key = bytemuck::from_bytes(&key_bytes);
                     let val: &session_val = bytemuck::from_bytes(&val_bytes);
-                    let elapsed = now.saturating_sub(val.last_seen_ns);
-                    let time_left_ns = timeout_ns.saturating_sub(elapsed);
-                    let time_left_sec = (time_left_ns / 1_000_000_000) as i32;
 
-                    Some((key.src_ip, key.dest_ip, key.dest_port, time_left_sec))
+                    Some((SessionKey::from(*key), SessionVal::from(*val)))
                 } else {
                     None
                 }
             })
             .collect();
-        Ok(sessions)
+        Ok(entries)
     }
 
-    /// Returns the current kernel monotonic time in nanoseconds.
-    /// Uses a fallback value if the system call fails to prevent panic.
-    fn get_ktime_ns() -> u64 {
-        match clock_gettime(ClockId::CLOCK_MONOTONIC) {
-            Ok(now) => {
-                // Safely compute nanoseconds with overflow protection
-                now.tv_sec()
-                    .saturating_mul(1_000_000_000)
-                    .saturating_add(now.tv_nsec()) as u64
-            }
-            Err(e) => {
-                error!("Failed to get monotonic time: {}, using fallback", e);
-                // Return a fallback value to prevent panic
-                0
-            }
-        }
+    /// Computes the whole seconds left before a session idles out.
+    /// Negative once the session has expired but not yet been reaped, giving
+    /// how long ago it expired.
+    fn time_left_sec(now_ns: u64, last_seen_ns: u64, timeout_ns: u64) -> i64 {
+        // A timestamp slightly ahead of `now` only means the packet raced the read
+        let elapsed = now_ns.saturating_sub(last_seen_ns);
+        let time_left_ns = i128::from(timeout_ns) - i128::from(elapsed);
+        (time_left_ns / 1_000_000_000) as i64
+    }
+
+    /// Returns true once a session last seen at `last_seen_ns` has been idle
+    /// for longer than `timeout_ns` at `now_ns`.
+    fn is_expired(now_ns: u64, last_seen_ns: u64, timeout_ns: u64) -> bool {
+        now_ns.saturating_sub(last_seen_ns) > timeout_ns
+    }
+
+    /// Returns true once a session created at `created_at_ns` has existed for
+    /// longer than `max_age_ns` at `now_ns`. Never true when `max_age_ns` is 0.
+    fn is_over_age(now_ns: u64, created_at_ns: u64, max_age_ns: u64) -> bool {
+        max_age_ns != 0 && now_ns.saturating_sub(created_at_ns) > max_age_ns
+    }
+}
+
+/// Session map opened from its pin, without loading or attaching the program.
+///
+/// Used by the local inspection subcommands while the agent daemon owns the
+/// XDP program. Key fields use network byte order, as in [`Bpf`].
+pub struct PinnedSessions {
+    map: MapHandle,
+}
+
+impl PinnedSessions {
+    /// Opens the session map pinned under `pin_prefix`.
+    pub fn open(pin_prefix: &Path) -> Result<Self> {
+        let path = map_pin_path(pin_prefix);
+        let map = MapHandle::from_pinned_path(&path).with_context(|| {
+            format!(
+                "Failed to open pinned session map {} (is the agent running?)",
+                path.display()
+            )
+        })?;
+        check_session_schema(&map, &path)?;
+        Ok(Self { map })
+    }
+
+    /// Adds a session, see [`Bpf::add_rule`].
+    pub fn add_rule(&self, key: SessionKey) -> Result<bool> {
+        Bpf::add_rule_to(&self.map, key, MonotonicClock.now_ns(), NO_EPOCH)
+    }
+
+    /// Removes a session, see [`Bpf::remove_rule`].
+    pub fn remove_rule(&self, key: SessionKey) -> Result<bool> {
+        Bpf::remove_rule_from(&self.map, key)
+    }
+
+    /// Lists sessions, see [`Bpf::list_rules`].
+    pub fn list_rules(&self, timeout_ns: u64) -> Result<Vec<(SessionKey, i64)>> {
+        Bpf::list_rules_in(&self.map, MonotonicClock.now_ns(), timeout_ns)
+    }
+
+    /// Lists sessions with their age and remaining time.
+    pub fn dump_rules(&self, timeout_ns: u64) -> Result<Vec<SessionEntry>> {
+        Bpf::dump_rules_in(&self.map, MonotonicClock.now_ns(), timeout_ns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    const TIMEOUT_NS: u64 = 60_000_000_000;
+
+    /// XDP verdicts returned by `test_run`
+    const XDP_DROP: u32 = 1;
+    const XDP_PASS: u32 = 2;
+    const XDP_REDIRECT: u32 = 4;
+
+    /// tc verdicts returned by `test_run`
+    const TC_ACT_OK: u32 = 0;
+    const TC_ACT_SHOT: u32 = 2;
+    const TC_ACT_REDIRECT: u32 = 7;
+
+    /// Builds an Ethernet/IPv4/TCP (ACK) packet.
+    fn tcp_packet(src_ip: Ipv4Addr, dst_ip: Ipv4Addr, dst_port: u16) -> [u8; 64] {
+        let mut packet = [0u8; 64];
+        packet[12..14].copy_from_slice(&[0x08, 0x00]); // EtherType IPv4
+        packet[14] = 0x45; // Version 4, IHL 5
+        packet[17] = 0x32; // Total length
+        packet[22] = 0x40; // TTL
+        packet[23] = 0x06; // Protocol (TCP)
+        packet[26..30].copy_from_slice(&src_ip.octets());
+        packet[30..34].copy_from_slice(&dst_ip.octets());
+        packet[34..36].copy_from_slice(&8080u16.to_be_bytes()); // Src port
+        packet[36..38].copy_from_slice(&dst_port.to_be_bytes());
+        packet[46] = 0x50; // Data offset
+        packet[47] = 0x10; // ACK
+        packet
+    }
+
+    /// Runs one packet through the program and returns the XDP verdict.
+    fn verdict(skel: &AegisSkel, packet: &[u8]) -> u32 {
+        let mut input = libbpf_rs::ProgramInput::default();
+        input.data_in = Some(packet);
+        skel.progs
+            .xdp_drop_prog
+            .test_run(input)
+            .expect("Test run failed")
+            .return_value
+    }
+
+    /// Runs one packet through the tc program and returns the tc verdict.
+    fn tc_verdict(skel: &AegisSkel, packet: &[u8]) -> u32 {
+        let mut input = libbpf_rs::ProgramInput::default();
+        input.data_in = Some(packet);
+        skel.progs
+            .tc_drop_prog
+            .test_run(input)
+            .expect("Test run failed")
+            .return_value
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_controller_traffic_passes_with_empty_map() {
+        let controller_ip = Ipv4Addr::new(172, 21, 0, 5);
+        let controller_port = 443u16;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = controller_port.to_be();
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, controller_ip).unwrap();
+
+        assert_eq!(skel.maps.session.keys().count(), 0);
+
+        // Traffic to the controller endpoint always passes
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), controller_ip, controller_port);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+
+        // Same host on another port, and another host on the same port, are dropped
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), controller_ip, 8443);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+        let packet = tcp_packet(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(172, 21, 0, 6),
+            controller_port,
+        );
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_every_controller_port_passes() {
+        let controller_ip = Ipv4Addr::new(172, 21, 0, 5);
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = 443u16.to_be();
+        rodata.CONTROLLER_EXTRA_PORTS[0] = 8443u16.to_be();
+        rodata.CONTROLLER_EXTRA_PORT_COUNT = 1;
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, controller_ip).unwrap();
+
+        for port in [443, 8443] {
+            let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), controller_ip, port);
+            assert_eq!(verdict(&skel, &packet), XDP_PASS, "port {}", port);
+        }
+        // Other ports on the controller are still dropped
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), controller_ip, 9443);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_controller_net_reaches_grpc_port() {
+        let agent_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let grpc_port = 50001u16;
+        let net: crate::types::Ipv4Net = "10.0.0.0/28".parse().unwrap();
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_NET = ip::to_network(net.network());
+        rodata.CONTROLLER_NET_MASK = ip::to_network(net.netmask());
+        rodata.GRPC_PORT = grpc_port.to_be();
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, Ipv4Addr::new(172, 21, 0, 5)).unwrap();
+
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 14), agent_ip, grpc_port);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        let packet = tcp_packet(Ipv4Addr::new(172, 21, 0, 5), agent_ip, grpc_port);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+
+        // Just outside the prefix
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 16), agent_ip, grpc_port);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_extra_controllers_reach_grpc_port() {
+        let agent_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let grpc_port = 50001u16;
+        let standby = Ipv4Addr::new(10, 0, 1, 5);
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.GRPC_PORT = grpc_port.to_be();
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, Ipv4Addr::new(10, 0, 0, 5)).unwrap();
+        skel.maps
+            .controllers
+            .update(
+                bytemuck::bytes_of(&ip::to_network(standby)),
+                &[1u8],
+                MapFlags::ANY,
+            )
+            .unwrap();
+
+        for ip in [Ipv4Addr::new(10, 0, 0, 5), standby] {
+            let packet = tcp_packet(ip, agent_ip, grpc_port);
+            assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        }
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 2, 5), agent_ip, grpc_port);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    async fn test_controller_ip_file_change_reaches_xdp() {
+        use std::time::{Duration, Instant};
+
+        let old_ip = Ipv4Addr::new(172, 21, 0, 5);
+        let new_ip = Ipv4Addr::new(172, 21, 0, 6);
+        let controller_port = 443u16;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = controller_port.to_be();
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, old_ip).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("controller-ip");
+        std::fs::write(&path, format!("{}\n", old_ip)).unwrap();
+        let (tx, rx) = tokio::sync::watch::channel(old_ip);
+        let map = MapHandle::try_from(&skel.maps.controller_ip).unwrap();
+        let _follower =
+            crate::follow_controller_ip(rx, move |ip| Bpf::set_controller_ip_in(&map, ip));
+        let _watcher = crate::controller_ip_file::watch_controller_ip(path.clone(), tx).unwrap();
+
+        std::fs::write(&path, format!("{}\n", new_ip)).unwrap();
+
+        // The watcher and follower run asynchronously
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), new_ip, controller_port);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while verdict(&skel, &packet) != XDP_PASS {
+            assert!(
+                Instant::now() < deadline,
+                "new controller IP never reached XDP"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let packet = tcp_packet(Ipv4Addr::new(10, 0, 0, 1), old_ip, controller_port);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_monitor_ip_reaches_grpc_port() {
+        let monitor_ip = Ipv4Addr::new(10, 0, 0, 9);
+        let agent_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let grpc_port = 50001u16;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.GRPC_PORT = grpc_port.to_be();
+        rodata.MONITOR_IP = ip::to_network(monitor_ip);
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, Ipv4Addr::new(172, 21, 0, 5)).unwrap();
+
+        let packet = tcp_packet(monitor_ip, agent_ip, grpc_port);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+
+        // The monitoring client gets no access beyond the gRPC port
+        let packet = tcp_packet(monitor_ip, agent_ip, 22);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    async fn test_submitted_session_matches_packet() {
+        use crate::grpc_server::{
+            RuleChange, SessionManagerService,
+            session::{LoginEvent, session_manager_server::SessionManager},
+        };
+        use std::sync::Arc;
+        use tokio::sync::{Mutex, broadcast};
+
+        let open_object = Box::leak(Box::new(std::mem::MaybeUninit::uninit()));
+        let open_skel = AegisSkelBuilder::default()
+            .open(open_object)
+            .expect("Failed to open skel");
+        let skel = Arc::new(std::sync::Mutex::new(
+            open_skel.load().expect("Failed to load"),
+        ));
+
+        // Same conversion path the agent wires into the gRPC service
+        let skel_rules = skel.clone();
+        let modify_rules = Arc::new(Mutex::new(
+            move |is_add: bool, key: SessionKey, epoch: u64, src_mac: u64| {
+                let skel = skel_rules.lock().unwrap();
+                let previous_existed = if is_add {
+                    Bpf::add_bound_rule_to(
+                        &skel.maps.session,
+                        key,
+                        MonotonicClock.now_ns(),
+                        epoch,
+                        src_mac,
+                    )?
+                } else {
+                    Bpf::remove_rule_from(&skel.maps.session, key)?
+                };
+                Ok(RuleChange {
+                    previous_existed,
+                    active_sessions: Bpf::len_in(&skel.maps.session)?,
+                })
+            },
+        ));
+        let update_ip = Arc::new(Mutex::new(|_: u32, _: u32| -> Result<usize> { Ok(0) }));
+        let (tx, _) = broadcast::channel(4);
+        let service = SessionManagerService::new(modify_rules, update_ip, tx);
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let packet = tcp_packet(src_ip, dst_ip, 8080);
+        assert_eq!(verdict(&skel.lock().unwrap(), &packet), XDP_DROP);
+
+        let event = LoginEvent {
+            src_ip: ip::to_host(src_ip),
+            dst_ip: ip::to_host(dst_ip),
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        };
+        let ack = service
+            .submit_session(crate::grpc_server::controller_request(event))
+            .await
+            .expect("submit_session failed")
+            .into_inner();
+        assert!(ack.success);
+        assert_eq!(ack.active_sessions, Some(1));
+        assert_eq!(ack.previous_existed, Some(false));
+
+        assert_eq!(verdict(&skel.lock().unwrap(), &packet), XDP_PASS);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_permissive_mode_passes_unauthorized_traffic() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+
+        let packet = tcp_packet(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(172, 21, 0, 10),
+            443,
+        );
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+
+        Bpf::set_enforcing_in(&skel.maps.enforcement, false).unwrap();
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_OK);
+
+        Bpf::set_enforcing_in(&skel.maps.enforcement, true).unwrap();
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_SHOT);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_grace_window_closes_in_datapath() {
+        let packet = tcp_packet(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(172, 21, 0, 10),
+            443,
+        );
+        let verdict_with_deadline = |grace_until: u64| {
+            let mut open_object = std::mem::MaybeUninit::uninit();
+            let mut open_skel = AegisSkelBuilder::default()
+                .open(&mut open_object)
+                .expect("Failed to open skel");
+            open_skel
+                .maps
+                .rodata_data
+                .as_deref_mut()
+                .unwrap()
+                .GRACE_UNTIL = grace_until;
+            let skel = open_skel.load().expect("Failed to load");
+            // The agent never switches back to enforcing
+            Bpf::set_enforcing_in(&skel.maps.enforcement, false).unwrap();
+            verdict(&skel, &packet)
+        };
+
+        let now = MonotonicClock.now_ns();
+        assert_eq!(verdict_with_deadline(grace_deadline(now, 60)), XDP_PASS);
+        assert_eq!(verdict_with_deadline(now), XDP_DROP);
+    }
+
+    #[test]
+    fn test_grace_deadline() {
+        assert_eq!(grace_deadline(1_000, 30), 30_000_001_000);
+        assert_eq!(grace_deadline(u64::MAX - 1, 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_enforces_past_grace_deadline() {
+        assert!(enforces(MODE_ENFORCING, 0, 1_000));
+        assert!(!enforces(MODE_PERMISSIVE, 0, 1_000));
+        assert!(!enforces(MODE_PERMISSIVE, 2_000, 1_000));
+        assert!(enforces(MODE_PERMISSIVE, 2_000, 2_000));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_max_conns_per_src_caps_new_connections() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.MAX_CONNS_PER_SRC = 2;
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        let segment = |src_port: u16, flags: u8| {
+            let mut packet = tcp_packet(src_ip, dst_ip, 443);
+            packet[34..36].copy_from_slice(&src_port.to_be_bytes());
+            packet[47] = flags;
+            packet
+        };
+        const SYN: u8 = 0x02;
+        const ACK: u8 = 0x10;
+        const FIN_ACK: u8 = 0x11;
+
+        assert_eq!(verdict(&skel, &segment(40001, SYN)), XDP_PASS);
+        assert_eq!(verdict(&skel, &segment(40002, SYN)), XDP_PASS);
+        // Retransmitted SYNs do not take another slot
+        assert_eq!(verdict(&skel, &segment(40001, SYN)), XDP_PASS);
+
+        let read_capped = || Bpf::read_counter(&skel.maps.counters, COUNTER_CONN_CAPPED).unwrap();
+        assert_eq!(verdict(&skel, &segment(40003, SYN)), XDP_DROP);
+        assert_eq!(read_capped(), 1);
+
+        // Established connections keep flowing at the cap
+        assert_eq!(verdict(&skel, &segment(40001, ACK)), XDP_PASS);
+
+        // Closing one frees a slot
+        assert_eq!(verdict(&skel, &segment(40002, FIN_ACK)), XDP_PASS);
+        assert_eq!(verdict(&skel, &segment(40003, SYN)), XDP_PASS);
+        assert_eq!(read_capped(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_connect4_allows_dns_only_to_resolvers() {
+        use std::{io::Write, os::unix::process::CommandExt, process::Command};
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let resolver = Ipv4Addr::new(10, 0, 0, 53);
+        Bpf::set_dns_resolvers_in(&skel.maps.dns_resolvers, &[resolver]).unwrap();
+
+        let cgroup = Path::new("/sys/fs/cgroup").join(format!("aegis-test-{}", std::process::id()));
+        fs::create_dir(&cgroup).expect("Failed to create cgroup");
+        let dir = fs::File::open(&cgroup).unwrap();
+        let link = skel
+            .progs
+            .connect4_prog
+            .attach_cgroup(dir.as_raw_fd())
+            .expect("Failed to attach egress filter");
+
+        // connect() a UDP socket from inside the cgroup; nothing is sent
+        let connects = |ip: Ipv4Addr| {
+            let procs = fs::OpenOptions::new()
+                .write(true)
+                .open(cgroup.join("cgroup.procs"))
+                .unwrap();
+            let mut command = Command::new("bash");
+            command.arg("-c").arg(format!("exec 3<>/dev/udp/{}/53", ip));
+            // Joins the cgroup between fork and exec, without allocating
+            unsafe {
+                command.pre_exec(move || (&procs).write_all(b"0"));
+            }
+            command.status().expect("Failed to run bash").success()
+        };
+        let resolver_reached = connects(resolver);
+        let other_reached = connects(Ipv4Addr::new(10, 0, 0, 99));
+
+        drop(link);
+        fs::remove_dir(&cgroup).unwrap();
+        assert!(resolver_reached, "Configured resolver was refused");
+        assert!(
+            !other_reached,
+            "Port 53 reachable on a host that is not a resolver"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_idle_connections_release_their_slot() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.MAX_CONNS_PER_SRC = 1;
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        let syn = |src_port: u16| {
+            let mut packet = tcp_packet(src_ip, dst_ip, 443);
+            packet[34..36].copy_from_slice(&src_port.to_be_bytes());
+            packet[47] = 0x02;
+            packet
+        };
+        let expire = |now: u64| {
+            Bpf::expire_idle_conns_in(
+                &skel.maps.flows,
+                &skel.maps.conn_counts,
+                now,
+                60_000_000_000,
+            )
+            .expect("Failed to expire connections")
+        };
+
+        // The first connection ends without FIN or RST reaching us
+        assert_eq!(verdict(&skel, &syn(40001)), XDP_PASS);
+        assert_eq!(verdict(&skel, &syn(40002)), XDP_DROP);
+
+        // Still within the idle timeout
+        assert_eq!(expire(MonotonicClock.now_ns()), 0);
+        assert_eq!(verdict(&skel, &syn(40002)), XDP_DROP);
+
+        assert_eq!(expire(MonotonicClock.now_ns() + 120_000_000_000), 1);
+        assert_eq!(verdict(&skel, &syn(40002)), XDP_PASS);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_session_binds_first_src_mac() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = 443u16.to_be();
+        rodata.BIND_SRC_MAC = true;
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, Ipv4Addr::new(172, 21, 0, 5)).unwrap();
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        let from_mac = |mac: [u8; 6]| {
+            let mut packet = tcp_packet(src_ip, dst_ip, 8080);
+            packet[6..12].copy_from_slice(&mac);
+            packet
+        };
+        let host = [0x02, 0x42, 0xac, 0x15, 0x00, 0x0a];
+        let spoofer = [0x02, 0x42, 0xac, 0x15, 0x00, 0x63];
+
+        assert_eq!(verdict(&skel, &from_mac(host)), XDP_PASS);
+        assert_eq!(verdict(&skel, &from_mac(spoofer)), XDP_DROP);
+        assert_eq!(verdict(&skel, &from_mac(host)), XDP_PASS);
+        assert_eq!(
+            Bpf::read_counter(&skel.maps.counters, COUNTER_MAC_MISMATCH).unwrap(),
+            1
+        );
+
+        // Refreshing the session keeps the binding
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to refresh rule");
+        assert_eq!(verdict(&skel, &from_mac(spoofer)), XDP_DROP);
+        let bytes = skel
+            .maps
+            .session
+            .lookup(bytemuck::bytes_of(&session_key::from(key)), MapFlags::ANY)
+            .unwrap()
+            .unwrap();
+        let val: session_val = bytemuck::pod_read_unaligned(&bytes);
+        assert_eq!(val.src_mac, 0x02_42_ac_15_00_0a);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_submitted_src_mac_is_bound_before_first_packet() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.BIND_SRC_MAC = true;
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        let from_mac = |mac: [u8; 6]| {
+            let mut packet = tcp_packet(src_ip, dst_ip, 8080);
+            packet[6..12].copy_from_slice(&mac);
+            packet
+        };
+        let host = [0x02, 0x42, 0xac, 0x15, 0x00, 0x0a];
+        let spoofer = [0x02, 0x42, 0xac, 0x15, 0x00, 0x63];
+        Bpf::add_bound_rule_to(
+            &skel.maps.session,
+            key,
+            MonotonicClock.now_ns(),
+            NO_EPOCH,
+            0x02_42_ac_15_00_0a,
+        )
+        .expect("Failed to add rule");
+
+        // The spoofer sending first gains nothing
+        assert_eq!(verdict(&skel, &from_mac(spoofer)), XDP_DROP);
+        assert_eq!(verdict(&skel, &from_mac(host)), XDP_PASS);
+
+        // A refresh without a MAC keeps the binding
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to refresh rule");
+        assert_eq!(verdict(&skel, &from_mac(spoofer)), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_dropped_first_packet_does_not_bind_src_mac() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = 443u16.to_be();
+        rodata.STRICT_TCP = true;
+        rodata.BIND_SRC_MAC = true;
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, Ipv4Addr::new(172, 21, 0, 5)).unwrap();
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        let segment = |mac: [u8; 6], flags: u8| {
+            let mut packet = tcp_packet(src_ip, dst_ip, 8080);
+            packet[6..12].copy_from_slice(&mac);
+            packet[47] = flags;
+            packet
+        };
+        const SYN: u8 = 0x02;
+        const ACK: u8 = 0x10;
+        let host = [0x02, 0x42, 0xac, 0x15, 0x00, 0x0a];
+        let spoofer = [0x02, 0x42, 0xac, 0x15, 0x00, 0x63];
+
+        // Strict TCP drops a bare ACK as the first packet, before any binding
+        assert_eq!(verdict(&skel, &segment(spoofer, ACK)), XDP_DROP);
+        assert_eq!(verdict(&skel, &segment(host, SYN)), XDP_PASS);
+        assert_eq!(verdict(&skel, &segment(spoofer, ACK)), XDP_DROP);
+        assert_eq!(
+            Bpf::read_counter(&skel.maps.counters, COUNTER_MAC_MISMATCH).unwrap(),
+            1
+        );
+        let bytes = skel
+            .maps
+            .session
+            .lookup(bytemuck::bytes_of(&session_key::from(key)), MapFlags::ANY)
+            .unwrap()
+            .unwrap();
+        let val: session_val = bytemuck::pod_read_unaligned(&bytes);
+        assert_eq!(val.src_mac, 0x02_42_ac_15_00_0a);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_session_traffic_is_redirected() {
+        let lo = iface::index_of("lo").unwrap();
+        let controller_ip = Ipv4Addr::new(172, 21, 0, 5);
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.CONTROLLER_PORT = 443u16.to_be();
+        rodata.REDIRECT_IFINDEX = lo;
+        let skel = open_skel.load().expect("Failed to load");
+        Bpf::set_controller_ip_in(&skel.maps.controller_ip, controller_ip).unwrap();
+        skel.maps
+            .redirect_devmap
+            .update(&0u32.to_ne_bytes(), &lo.to_ne_bytes(), MapFlags::ANY)
+            .unwrap();
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+
+        let packet = tcp_packet(src_ip, dst_ip, 8080);
+        assert_eq!(verdict(&skel, &packet), XDP_REDIRECT);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_REDIRECT);
+
+        // Controller traffic stays on this host
+        let packet = tcp_packet(src_ip, controller_ip, 443);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_OK);
+
+        let packet = tcp_packet(src_ip, dst_ip, 22);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_tc_program_matches_xdp() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let packet = tcp_packet(src_ip, dst_ip, 443);
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_SHOT);
+
+        // A session added once is enforced by both hooks
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_OK);
+
+        let packet = tcp_packet(src_ip, dst_ip, 22);
+        assert_eq!(tc_verdict(&skel, &packet), TC_ACT_SHOT);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_any_source_session() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip: ANY_SRC_IP,
+            dest_ip: dst_ip,
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+
+        for src_ip in [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(198, 51, 100, 7)] {
+            assert_eq!(verdict(&skel, &tcp_packet(src_ip, dst_ip, 443)), XDP_PASS);
+            // Only the authorized port is open
+            assert_eq!(verdict(&skel, &tcp_packet(src_ip, dst_ip, 22)), XDP_DROP);
+        }
+
+        // Any source on every port would disable the firewall for the host
+        let wide_open = SessionKey {
+            dest_port: WILDCARD_PORT,
+            ..key
+        };
+        assert!(
+            Bpf::add_rule_to(
+                &skel.maps.session,
+                wide_open,
+                MonotonicClock.now_ns(),
+                NO_EPOCH
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_lazy_update_throttles_last_seen() {
+        const LAZY_NS: u64 = 10_000_000_000;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let mut open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let rodata = open_skel.maps.rodata_data.as_deref_mut().unwrap();
+        rodata.LAZY_UPDATE_TIMEOUT = LAZY_NS;
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        let raw_key = session_key::from(key);
+        let read_val = || {
+            let bytes = skel
+                .maps
+                .session
+                .lookup(bytemuck::bytes_of(&raw_key), MapFlags::ANY)
+                .unwrap()
+                .expect("Session missing");
+            SessionVal::from(*bytemuck::from_bytes::<session_val>(&bytes))
+        };
+        let packet = tcp_packet(src_ip, dst_ip, 8080);
+
+        // The first packet always stamps the session
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        let first = read_val();
+        assert_ne!(first.last_seen_ns, first.created_at_ns);
+        assert_eq!(first.flags, SESSION_SEEN);
+
+        // Further packets inside the window leave the timestamp alone
+        for _ in 0..10 {
+            assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        }
+        assert_eq!(read_val(), first);
+
+        // Pretend the last refresh was longer ago than the window
+        let stale = SessionVal {
+            created_at_ns: first.created_at_ns.saturating_sub(2 * LAZY_NS),
+            last_seen_ns: first.last_seen_ns - LAZY_NS - 1,
+            ..first
+        };
+        skel.maps
+            .session
+            .update(
+                bytemuck::bytes_of(&raw_key),
+                bytemuck::bytes_of(&session_val::from(stale)),
+                MapFlags::ANY,
+            )
+            .unwrap();
+
+        let before = MonotonicClock.now_ns();
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        let refreshed = read_val();
+        assert!(refreshed.last_seen_ns >= before);
+        assert_eq!(refreshed.created_at_ns, stale.created_at_ns);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_session_matches_tcp_and_udp() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        assert_eq!(skel.maps.session.keys().count(), 1);
+
+        // One entry covers both transports, e.g. HTTPS and QUIC on 443
+        let tcp = tcp_packet(src_ip, dst_ip, 443);
+        let mut udp = tcp;
+        udp[23] = 0x11; // Protocol (UDP), ports sit at the same offsets
+        assert_eq!(verdict(&skel, &tcp), XDP_PASS);
+        assert_eq!(verdict(&skel, &udp), XDP_PASS);
+
+        // Other protocols never reach the session lookup
+        let mut icmp = tcp;
+        icmp[23] = 0x01;
+        assert_eq!(verdict(&skel, &icmp), XDP_DROP);
+
+        // Both transports still only reach the authorized port
+        let mut other_port = udp;
+        other_port[36..38].copy_from_slice(&444u16.to_be_bytes());
+        assert_eq!(verdict(&skel, &other_port), XDP_DROP);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_len_counts_sessions() {
+        const K: u16 = 50;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+        assert_eq!(Bpf::len_in(map).unwrap(), 0);
+
+        let key = |dest_port| SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port,
+            src_port: ANY_SRC_PORT,
+        };
+        for port in 1..=K {
+            Bpf::add_rule_to(map, key(port), MonotonicClock.now_ns(), NO_EPOCH)
+                .expect("Failed to add rule");
+        }
+        assert_eq!(Bpf::len_in(map).unwrap(), usize::from(K));
+
+        // Refreshing an existing session does not add one
+        Bpf::add_rule_to(map, key(1), MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        assert_eq!(Bpf::len_in(map).unwrap(), usize::from(K));
+        assert_eq!(
+            Bpf::map_stats_in(map).unwrap(),
+            (usize::from(K), 10240),
+            "capacity should match max_entries in aegis.bpf.c"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_clear_all_empties_map() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let key = |dest_port| SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port,
+            src_port: ANY_SRC_PORT,
+        };
+        // Batched where the kernel supports it, then key by key
+        for batch in [true, false] {
+            for port in 1..=20 {
+                Bpf::add_rule_to(map, key(port), MonotonicClock.now_ns(), NO_EPOCH)
+                    .expect("Failed to add rule");
+            }
+            let batch_delete = AtomicBool::new(batch);
+            assert_eq!(Bpf::clear_all_in(map, &batch_delete).unwrap(), 20);
+            assert_eq!(Bpf::len_in(map).unwrap(), 0);
+        }
+        assert_eq!(Bpf::clear_all_in(map, &AtomicBool::new(true)).unwrap(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_missing_key_keeps_batch_delete() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let key = |dest_port| SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port,
+            src_port: ANY_SRC_PORT,
+        };
+        for port in [1, 3] {
+            Bpf::add_rule_to(map, key(port), MonotonicClock.now_ns(), NO_EPOCH)
+                .expect("Failed to add rule");
+        }
+        // Port 2 was never added, e.g. the LRU evicted it after it was listed
+        let keys: Vec<Vec<u8>> = [1, 2, 3]
+            .map(|port| bytemuck::bytes_of(&session_key::from(key(port))).to_vec())
+            .into();
+
+        let supported = probe_features().batch_delete;
+        let batch_delete = AtomicBool::new(supported);
+        let removed = Bpf::delete_keys_in(map, &batch_delete, &keys).unwrap();
+        assert_eq!(removed, if supported { 3 } else { 2 });
+        assert_eq!(Bpf::len_in(map).unwrap(), 0);
+        // A missing key says nothing about kernel support
+        assert_eq!(batch_delete.load(Ordering::Relaxed), supported);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_probe_leaves_map_unchanged() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        Bpf::probe_in(&skel.maps.probe, map).expect("Probe failed on an empty map");
+        assert_eq!(Bpf::len_in(map).unwrap(), 0);
+
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 443,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(map, key, MonotonicClock.now_ns(), NO_EPOCH).unwrap();
+        Bpf::probe_in(&skel.maps.probe, map).expect("Probe failed next to a session");
+        assert_eq!(Bpf::len_in(map).unwrap(), 1);
+        assert!(Bpf::get_rule_in(map, key, 0, u64::MAX).unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_prune_keeps_current_epoch() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let key = |dest_port| SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port,
+            src_port: ANY_SRC_PORT,
+        };
+        let now = MonotonicClock.now_ns();
+        Bpf::add_rule_to(map, key(22), now, NO_EPOCH).expect("Failed to add rule");
+        Bpf::add_rule_to(map, key(80), now, 1).expect("Failed to add rule");
+        Bpf::add_rule_to(map, key(443), now, 1).expect("Failed to add rule");
+        Bpf::add_rule_to(map, key(8080), now, 2).expect("Failed to add rule");
+        // Re-submitted by the restarted controller, so it moves to epoch 2
+        Bpf::add_rule_to(map, key(443), now, 2).expect("Failed to add rule");
+
+        let mut stale: Vec<u16> = Bpf::stale_epoch_keys_in(map, 2)
+            .iter()
+            .map(|bytes| SessionKey::from(*bytemuck::from_bytes::<session_key>(bytes)).dest_port)
+            .collect();
+        stale.sort_unstable();
+        assert_eq!(stale, vec![22, 80]);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_get_rule_present_and_absent() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let clock = MockClock::new(1_000_000_000);
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(map, key, clock.now_ns(), NO_EPOCH).expect("Failed to add rule");
+        clock.advance(15_000_000_000);
+
+        let entry = Bpf::get_rule_in(map, key, clock.now_ns(), TIMEOUT_NS).unwrap();
+        assert_eq!(
+            entry,
+            Some(SessionEntry {
+                key,
+                age_sec: 15,
+                time_left_sec: 45,
+            })
+        );
+
+        // Another port on the same host is a different session
+        let other = SessionKey {
+            dest_port: 8443,
+            ..key
+        };
+        assert_eq!(
+            Bpf::get_rule_in(map, other, clock.now_ns(), TIMEOUT_NS).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_resubmit_keeps_created_at() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let clock = MockClock::new(1_000_000_000);
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        let raw_key = session_key::from(key);
+        let read_val = || {
+            let bytes = map
+                .lookup(bytemuck::bytes_of(&raw_key), MapFlags::ANY)
+                .unwrap()
+                .expect("Session missing");
+            SessionVal::from(*bytemuck::from_bytes::<session_val>(&bytes))
+        };
+
+        let created_at_ns = clock.now_ns();
+        assert!(!Bpf::add_rule_to(map, key, created_at_ns, NO_EPOCH).unwrap());
+
+        // A session that never carried traffic keeps its creation time too,
+        // and stays unseen so strict TCP still wants a SYN first
+        clock.advance(1_000_000_000);
+        assert!(Bpf::add_rule_to(map, key, clock.now_ns(), NO_EPOCH).unwrap());
+        assert_eq!(
+            read_val(),
+            SessionVal {
+                created_at_ns,
+                last_seen_ns: clock.now_ns(),
+                epoch: NO_EPOCH,
+                src_mac: 0,
+                flags: 0,
+            }
+        );
+
+        // The session carries traffic, as the XDP program would record it
+        clock.advance(1_000_000_000);
+        let seen = SessionVal {
+            created_at_ns,
+            last_seen_ns: clock.now_ns(),
+            epoch: NO_EPOCH,
+            src_mac: 0,
+            flags: SESSION_SEEN,
+        };
+        map.update(
+            bytemuck::bytes_of(&raw_key),
+            bytemuck::bytes_of(&session_val::from(seen)),
+            MapFlags::ANY,
+        )
+        .unwrap();
+
+        // Re-submitting refreshes the TTL but not the creation time or flags
+        clock.advance(28_000_000_000);
+        assert!(Bpf::add_rule_to(map, key, clock.now_ns(), NO_EPOCH).unwrap());
+        assert_eq!(
+            read_val(),
+            SessionVal {
+                created_at_ns,
+                last_seen_ns: clock.now_ns(),
+                epoch: NO_EPOCH,
+                src_mac: 0,
+                flags: SESSION_SEEN,
+            }
+        );
+        let entry = Bpf::get_rule_in(map, key, clock.now_ns(), TIMEOUT_NS)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.age_sec, 30);
+        assert_eq!(entry.time_left_sec, 60);
+
+        clock.advance(5_000_000_000);
+        assert!(Bpf::add_rule_to(map, key, clock.now_ns(), NO_EPOCH).unwrap());
+        assert_eq!(read_val().created_at_ns, created_at_ns);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_blocklist_overrides_session() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+
+        let packet = tcp_packet(src_ip, dst_ip, 8080);
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+
+        // The session is still present, but the block wins
+        Bpf::block_ip_in(&skel.maps.blocklist, src_ip).expect("Failed to block");
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+        assert_eq!(skel.maps.session.keys().count(), 1);
+
+        Bpf::unblock_ip_in(&skel.maps.blocklist, src_ip).expect("Failed to unblock");
+        assert_eq!(verdict(&skel, &packet), XDP_PASS);
+        assert!(Bpf::unblock_ip_in(&skel.maps.blocklist, src_ip).is_err());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_blocklist_survives_reload() {
+        let prefix = PathBuf::from(format!(
+            "/sys/fs/bpf/aegis-test-blocklist-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&prefix).expect("Failed to create pin prefix");
+        let path = blocklist_pin_path(&prefix);
+        fn load<'obj>(
+            open_object: &'obj mut std::mem::MaybeUninit<libbpf_rs::OpenObject>,
+            path: &Path,
+        ) -> AegisSkel<'obj> {
+            let mut open_skel = AegisSkelBuilder::default()
+                .open(open_object)
+                .expect("Failed to open skel");
+            open_skel
+                .maps
+                .blocklist
+                .set_pin_path(path)
+                .expect("Failed to set pin path");
+            open_skel.load().expect("Failed to load")
+        }
+
+        let src_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let dst_ip = Ipv4Addr::new(172, 21, 0, 10);
+        let key = SessionKey {
+            src_ip,
+            dest_ip: dst_ip,
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        let packet = tcp_packet(src_ip, dst_ip, 8080);
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let skel = load(&mut open_object, &path);
+        Bpf::block_ip_in(&skel.maps.blocklist, src_ip).expect("Failed to block");
+        drop(skel);
+
+        // A restarted agent reuses the pinned blocklist
+        prepare_pinned_map(&path, MapLayout::blocklist(), false)
+            .expect("Pinned blocklist rejected");
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let skel = load(&mut open_object, &path);
+        Bpf::add_rule_to(&skel.maps.session, key, MonotonicClock.now_ns(), NO_EPOCH)
+            .expect("Failed to add rule");
+        assert_eq!(verdict(&skel, &packet), XDP_DROP);
+
+        drop(skel);
+        fs::remove_file(&path).unwrap();
+        fs::remove_dir(&prefix).unwrap();
+    }
+
+    #[test]
+    fn test_fresh_rule_reports_full_time_left() {
+        // A rule stamped "now" should report the full timeout.
+        let clock = MockClock::new(5_000_000_000);
+        let stamped = clock.now_ns();
+        clock.advance(1);
+        let time_left = Bpf::time_left_sec(clock.now_ns(), stamped, TIMEOUT_NS);
+        assert_eq!(time_left, 59);
+        assert_eq!(Bpf::time_left_sec(stamped, stamped, TIMEOUT_NS), 60);
+    }
+
+    #[test]
+    fn test_expiry_boundary() {
+        let clock = MockClock::new(1_000_000_000);
+        let last_seen = clock.now_ns();
+
+        // Exactly at the timeout the session is still live
+        clock.advance(TIMEOUT_NS);
+        assert!(!Bpf::is_expired(clock.now_ns(), last_seen, TIMEOUT_NS));
+        assert_eq!(Bpf::time_left_sec(clock.now_ns(), last_seen, TIMEOUT_NS), 0);
+
+        // One nanosecond later it is stale
+        clock.advance(1);
+        assert!(Bpf::is_expired(clock.now_ns(), last_seen, TIMEOUT_NS));
+
+        // A packet stamped after the read never counts as expired
+        assert!(!Bpf::is_expired(last_seen, clock.now_ns(), TIMEOUT_NS));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_cleanup_and_list_follow_clock() {
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let clock = MockClock::new(1_000_000_000);
+        let key = |dest_port| SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port,
+            src_port: ANY_SRC_PORT,
+        };
+        Bpf::add_rule_to(map, key(8080), clock.now_ns(), NO_EPOCH).expect("Failed to add rule");
+        clock.advance(10_000_000_000);
+        Bpf::add_rule_to(map, key(8443), clock.now_ns(), NO_EPOCH).expect("Failed to add rule");
+
+        let mut rules = Bpf::list_rules_in(map, clock.now_ns(), TIMEOUT_NS).unwrap();
+        rules.sort_by_key(|(key, _)| key.dest_port);
+        assert_eq!(rules, vec![(key(8080), 50), (key(8443), 60)]);
+
+        // The older session sits exactly on the boundary and is kept
+        clock.advance(TIMEOUT_NS - 10_000_000_000);
+        assert!(Bpf::stale_keys_in(map, clock.now_ns(), TIMEOUT_NS, 0).is_empty());
+
+        clock.advance(1);
+        let stale = Bpf::stale_keys_in(map, clock.now_ns(), TIMEOUT_NS, 0);
+        assert_eq!(
+            stale,
+            vec![bytemuck::bytes_of(&session_key::from(key(8080))).to_vec()]
+        );
+
+        // Unreaped sessions report how long ago they expired
+        clock.advance(5_000_000_000);
+        let mut rules = Bpf::list_rules_in(map, clock.now_ns(), TIMEOUT_NS).unwrap();
+        rules.sort_by_key(|(key, _)| key.dest_port);
+        assert_eq!(rules, vec![(key(8080), -5), (key(8443), 4)]);
+    }
+
+    #[test]
+    fn test_age_cap_boundary() {
+        const MAX_AGE_NS: u64 = 3_600_000_000_000;
+        let clock = MockClock::new(1_000_000_000);
+        let created_at = clock.now_ns();
+
+        clock.advance(MAX_AGE_NS);
+        assert!(!Bpf::is_over_age(clock.now_ns(), created_at, MAX_AGE_NS));
+
+        clock.advance(1);
+        assert!(Bpf::is_over_age(clock.now_ns(), created_at, MAX_AGE_NS));
+
+        // 0 disables the cap
+        assert!(!Bpf::is_over_age(clock.now_ns(), created_at, 0));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_age_cap_reaps_active_session() {
+        const MAX_AGE_NS: u64 = 3_600_000_000_000;
+
+        let mut open_object = std::mem::MaybeUninit::uninit();
+        let open_skel = AegisSkelBuilder::default()
+            .open(&mut open_object)
+            .expect("Failed to open skel");
+        let skel = open_skel.load().expect("Failed to load");
+        let map = &skel.maps.session;
+
+        let clock = MockClock::new(1_000_000_000);
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        let created_at_ns = clock.now_ns();
+        Bpf::add_rule_to(map, key, created_at_ns, NO_EPOCH).expect("Failed to add rule");
+
+        // Traffic an hour later keeps the session far from idling out
+        clock.advance(MAX_AGE_NS + 1);
+        let active = SessionVal {
+            created_at_ns,
+            last_seen_ns: clock.now_ns(),
+            epoch: NO_EPOCH,
+            src_mac: 0,
+            flags: SESSION_SEEN,
+        };
+        map.update(
+            bytemuck::bytes_of(&session_key::from(key)),
+            bytemuck::bytes_of(&session_val::from(active)),
+            MapFlags::ANY,
+        )
+        .unwrap();
+
+        assert!(Bpf::stale_keys_in(map, clock.now_ns(), TIMEOUT_NS, 0).is_empty());
+        assert_eq!(
+            Bpf::stale_keys_in(map, clock.now_ns(), TIMEOUT_NS, MAX_AGE_NS),
+            vec![bytemuck::bytes_of(&session_key::from(key)).to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_session_count_stays_non_negative() {
+        let count = SessionCount::new(2);
+        count.add(1);
+        count.sub(1);
+        assert_eq!(count.get(), 2);
+        // More removals than counted, e.g. after LRU evictions
+        count.sub(5);
+        assert_eq!(count.get(), 0);
+        count.set(7);
+        assert_eq!(count.get(), 7);
+    }
+
+    #[test]
+    fn test_xdp_flags_mapping() {
+        assert_eq!(Bpf::xdp_flags(XdpMode::Native), XdpFlags::DRV_MODE);
+        assert_eq!(Bpf::xdp_flags(XdpMode::Skb), XdpFlags::SKB_MODE);
+        assert_eq!(Bpf::xdp_flags(XdpMode::Offload), XdpFlags::HW_MODE);
+    }
+
+    #[test]
+    fn test_only_aegis_filters_are_replaced() {
+        assert!(check_replaceable(2, AttachHook::Xdp, None, "xdp_drop_prog").is_ok());
+        let ours = Some((41, "xdp_drop_prog".to_string()));
+        assert!(check_replaceable(2, AttachHook::Xdp, ours, "xdp_drop_prog").is_ok());
+
+        let other = Some((42, "xdp_lb".to_string()));
+        let err = check_replaceable(2, AttachHook::Xdp, other, "xdp_drop_prog").unwrap_err();
+        assert!(err.to_string().contains("\"xdp_lb\" (id 42)"));
+    }
+
+    #[test]
+    fn test_features_display() {
+        let features = BpfFeatures {
+            batch_delete: true,
+            ringbuf: false,
+            btf: true,
+            xdp: true,
+            lru_hash: true,
+            percpu_array: true,
+        };
+        assert_eq!(
+            features.to_string(),
+            "batch_delete=yes ringbuf=no btf=yes xdp=yes lru_hash=yes percpu_array=yes"
+        );
+    }
+
+    #[test]
+    fn test_check_required_features() {
+        let mut features = BpfFeatures {
+            batch_delete: false,
+            ringbuf: false,
+            btf: false,
+            xdp: true,
+            lru_hash: true,
+            percpu_array: true,
+        };
+        assert!(features.check_required().is_ok());
+
+        features.lru_hash = false;
+        let err = features.check_required().unwrap_err().to_string();
+        assert!(err.contains("BPF_MAP_TYPE_LRU_HASH"));
+        assert!(!err.contains("BPF_PROG_TYPE_XDP"));
+    }
+
+    #[test]
+    fn test_delete_each_counts_removed_keys() {
+        let keys = vec![vec![1u8; 10], vec![2u8; 10], vec![3u8; 10]];
+        let mut deleted = Vec::new();
+
+        let removed = Bpf::delete_each(&keys, |key| {
+            deleted.push(key[0]);
+            Ok(())
+        });
+
+        assert_eq!(removed, 3);
+        assert_eq!(deleted, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_delete_each_skips_failures() {
+        let keys = vec![vec![1u8; 10], vec![2u8; 10], vec![3u8; 10]];
+
+        let removed = Bpf::delete_each(&keys, |key| {
+            if key[0] == 2 {
+                Err(anyhow!("No such file or directory"))
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(removed, 2);
+    }
+
+    #[test]
+    fn test_pin_paths_follow_prefix() {
+        let prefix = Path::new("/sys/fs/bpf/aegis-test");
+        assert_eq!(
+            map_pin_path(prefix),
+            PathBuf::from("/sys/fs/bpf/aegis-test/session")
+        );
+        assert_eq!(
+            blocklist_pin_path(prefix),
+            PathBuf::from("/sys/fs/bpf/aegis-test/blocklist")
+        );
+        assert_eq!(
+            link_pin_path(prefix),
+            PathBuf::from("/sys/fs/bpf/aegis-test/xdp_link")
+        );
+    }
+
+    #[test]
+    fn test_pin_prefix_off_bpffs_fails() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let err = check_bpffs(&dir.path().join("aegis"), false).unwrap_err();
+        assert!(err.to_string().contains("mount -t bpf bpf"));
+    }
+
+    #[test]
+    fn test_mount_bpffs_only_at_standard_mount_point() {
+        // Never mount over an arbitrary directory, even when asked to
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        assert!(check_bpffs(&dir.path().join("aegis"), true).is_err());
+        assert!(!is_bpffs(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_session_layout_mismatch() {
+        let path = Path::new("/sys/fs/bpf/aegis/session");
+        let compiled = MapLayout::session();
+        check_layout(compiled, compiled, path).expect("Compiled layout rejected");
+
+        for found in [
+            MapLayout {
+                value_size: compiled.value_size - 8,
+                ..compiled
+            },
+            MapLayout {
+                key_size: compiled.key_size + 4,
+                ..compiled
+            },
+            MapLayout {
+                map_type: MapType::Hash,
+                ..compiled
+            },
+            MapLayout::blocklist(),
+        ] {
+            let err = check_layout(found, compiled, path).unwrap_err().to_string();
+            assert!(err.contains(&found.to_string()), "{}", err);
+            assert!(err.contains(&compiled.to_string()), "{}", err);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "bpf-tests"), ignore = "loads BPF, enable bpf-tests")]
+    fn test_incompatible_pinned_map() {
+        let prefix = PathBuf::from(format!("/sys/fs/bpf/aegis-test-{}", std::process::id()));
+        fs::create_dir_all(&prefix).expect("Failed to create pin prefix");
+        let path = map_pin_path(&prefix);
+
+        // A map from an older layout: 12-byte keys, 8-byte values
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: std::mem::size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+            ..Default::default()
+        };
+        let mut old = MapHandle::create(MapType::LruHash, Some("session"), 12, 8, 16, &opts)
+            .expect("Failed to create map");
+        old.pin(&path).expect("Failed to pin map");
+
+        let err = prepare_pinned_map(&path, MapLayout::session(), false).unwrap_err();
+        assert!(format!("{:#}", err).contains("--force-recreate"));
+        assert!(PinnedSessions::open(&prefix).is_err());
+        assert!(path.exists());
+
+        prepare_pinned_map(&path, MapLayout::session(), true).expect("Failed to recreate");
+        assert!(!path.exists());
+
+        // A map with the current layout is kept
+        let key_size = std::mem::size_of::<session_key>() as u32;
+        let value_size = std::mem::size_of::<session_val>() as u32;
+        let mut current = MapHandle::create(
+            MapType::LruHash,
+            Some("session"),
+            key_size,
+            value_size,
+            16,
+            &opts,
+        )
+        .expect("Failed to create map");
+        current.pin(&path).expect("Failed to pin map");
+        prepare_pinned_map(&path, MapLayout::session(), false).expect("Compatible map rejected");
+        assert!(path.exists());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_dir(&prefix).unwrap();
+    }
+
+    #[test]
+    fn test_open_missing_pin_fails() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        assert!(PinnedSessions::open(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_probe_features_is_cached() {
+        assert_eq!(probe_features(), probe_features());
+    }
+
+    #[test]
+    fn test_prog_stats_since() {
+        let earlier = ProgStats {
+            run_cnt: 100,
+            run_time_ns: 5_000,
+        };
+        let later = ProgStats {
+            run_cnt: 300,
+            run_time_ns: 15_000,
+        };
+        assert_eq!(later.since(&earlier), Some((200, 50.0)));
+
+        // No runs in between, or counters that went backwards
+        assert_eq!(earlier.since(&earlier), None);
+        assert_eq!(earlier.since(&later), None);
+    }
+
+    #[test]
+    fn test_counters_since() {
+        let earlier = Counters {
+            refresh_applied: 10,
+            refresh_suppressed: 30,
+            conn_capped: 2,
+            mac_mismatch: 0,
+        };
+        let later = Counters {
+            refresh_applied: 15,
+            refresh_suppressed: 75,
+            conn_capped: 7,
+            mac_mismatch: 3,
+        };
+        let delta = later.since(&earlier);
+        assert_eq!(
+            delta,
+            Counters {
+                refresh_applied: 5,
+                refresh_suppressed: 45,
+                conn_capped: 5,
+                mac_mismatch: 3,
+            }
+        );
+        assert_eq!(delta.suppressed_ratio(), Some(0.9));
+        assert_eq!(Counters::default().suppressed_ratio(), None);
+    }
+
+    #[test]
+    fn test_near_expiry_rule_reports_remaining_time() {
+        let last_seen = 1_000_000_000;
+        let now = last_seen + TIMEOUT_NS - 1_500_000_000;
+        assert_eq!(Bpf::time_left_sec(now, last_seen, TIMEOUT_NS), 1);
+    }
+
+    #[test]
+    fn test_expired_rule_reports_negative_time_left() {
+        let last_seen = 1_000_000_000;
+        let now = last_seen + TIMEOUT_NS + 90_000_000_000;
+        assert_eq!(Bpf::time_left_sec(now, last_seen, TIMEOUT_NS), -90);
+    }
+
+    #[test]
+    fn test_future_last_seen_does_not_underflow() {
+        let now = 1_000_000_000;
+        let time_left = Bpf::time_left_sec(now, now + 5_000_000_000, TIMEOUT_NS);
+        assert_eq!(time_left, 60);
     }
 }
@@ -8,18 +8,77 @@ char __license[] SEC("license") = "GPL";
 /* Protocol constants */
 #define ETH_P_IP 0x0800
 #define ETH_P_ARP 0x0806
+#define ETH_P_8021Q 0x8100
+#define ETH_P_8021AD 0x88A8
 #define IPPROTO_TCP 6
 #define IPPROTO_UDP 17
 
+/* IPv4 fragment field masks (host byte order) */
+#define IP_MF 0x2000     // More Fragments flag
+#define IP_OFFSET 0x1FFF // Fragment offset in 8-byte units
+
+/* FRAGMENT_POLICY values, mirrored by FragmentPolicy in config.rs */
+#define FRAGMENT_POLICY_DROP 0  // Drop every non-first fragment
+#define FRAGMENT_POLICY_TRACK 1 // Pass non-first fragments of passed datagrams
+
+/* Maximum stacked VLAN tags to skip (QinQ: outer 802.1ad + inner 802.1Q) */
+#define VLAN_MAX_DEPTH 2
+
+/* cgroup/connect4 return values: refuse with EPERM, or let connect() proceed */
+#define CONNECT_REFUSE 0
+#define CONNECT_ALLOW 1
+
+/* tc actions from linux/pkt_cls.h, which vmlinux.h does not carry */
+#define TC_ACT_OK 0
+#define TC_ACT_SHOT 2
+
+/* Bytes pulled into the linear skb area before tc parsing: Ethernet, two
+ * VLAN tags, a maximal IPv4 header and the fixed TCP header */
+#define TC_PULL_LEN (14 + 2 * 4 + 60 + 20)
+
+/**
+ * @brief Hook-independent verdict of filter_packet
+ *
+ * Mapped to XDP_* by the XDP program and to TC_ACT_* by the tc program.
+ * VERDICT_SESSION marks packets admitted by a session, which are redirected
+ * to REDIRECT_IFINDEX when it is set; infrastructure traffic such as ARP and
+ * the controller's always gets VERDICT_PASS and stays on this host.
+ */
+enum aegis_verdict {
+  VERDICT_DROP = 0,
+  VERDICT_PASS = 1,
+  VERDICT_SESSION = 2,
+};
+
+/**
+ * @brief 802.1Q / 802.1ad VLAN tag following the Ethernet addresses
+ */
+struct aegis_vlan_hdr {
+  __be16 tci;         // Priority, DEI and VLAN ID
+  __be16 encap_proto; // EtherType of the encapsulated frame
+};
+
 /**
  * @brief Configuration Constants
  *
- * Dynamic configuration of controller ip:port and lazy update timeout
+ * Dynamic configuration of controller ports and lazy update timeout
  */
-volatile const __be32 CONTROLLER_IP;   // Big Endian (Network Byte Order)
+volatile const __be32 CONTROLLER_NET;  // Extra controller prefix (Network Byte Order)
+volatile const __be32 CONTROLLER_NET_MASK; // Its netmask, 0 if unset
 volatile const __be16 CONTROLLER_PORT; // Little Endian (Network Byte Order)
+// Further controller ports, e.g. during a port migration (Network Byte Order)
+volatile const __be16 CONTROLLER_EXTRA_PORTS[MAX_EXTRA_CONTROLLER_PORTS];
+volatile const u32 CONTROLLER_EXTRA_PORT_COUNT; // Entries used in CONTROLLER_EXTRA_PORTS
+volatile const __be16 GRPC_PORT;       // Agent gRPC port (Network Byte Order)
+volatile const __be32 MONITOR_IP;      // Read-only gRPC client, 0 if unset
 volatile const u64
-    LAZY_UPDATE_TIMEOUT; // Min time (ns) between timestamp updates
+    LAZY_UPDATE_TIMEOUT; // Min time (ns) between last_seen_ns writes per session
+volatile const bool STRICT_TCP; // Drop TCP packets with illegal flag combinations
+volatile const bool BIND_SRC_MAC; // Bind sessions to the source MAC of their first packet
+volatile const u8 FRAGMENT_POLICY; // How non-first IPv4 fragments are handled
+volatile const u32 MAX_CONNS_PER_SRC; // Open TCP connections per (src, dst), 0 = no limit
+volatile const u32 REDIRECT_IFINDEX; // Interface session traffic is redirected to, 0 = pass
+volatile const u64 GRACE_UNTIL; // bpf_ktime_get_ns() after which MODE_PERMISSIVE is ignored, 0 = never
 struct session_key _session_key = {0};
 struct session_val _session_val = {0};
 
@@ -37,76 +96,556 @@ struct {
 } session SEC(".maps");
 
 /**
- * @brief XDP Drop Program
+ * @brief Always-Allowed Ports
  *
- * This function hooks into the XDP (eXpress Data Path) at the network driver
- * level. It parses incoming packets to filter traffic based on a simple
+ * Destination ports (Network Byte Order) that pass regardless of session
+ * state, e.g. SSH, so operators cannot lock themselves out.
+ * Populated by the Userspace Agent at startup.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, 64);
+  __type(key, __be16);
+  __type(value, __u8);
+} allowed_ports SEC(".maps");
+
+/**
+ * @brief Event Counters
+ *
+ * Per-CPU so the hot path never contends on a shared cache line; the agent
+ * sums the CPUs when reading. Indexed by enum aegis_counter.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+  __uint(max_entries, COUNTER_MAX);
+  __type(key, __u32);
+  __type(value, __u64);
+} counters SEC(".maps");
+
+/**
+ * @brief Blocked Source IPs
+ *
+ * Source addresses (Network Byte Order) dropped before any other rule is
+ * consulted, overriding sessions, the controller and always-allowed ports.
+ * Managed by the Userspace Agent at runtime.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, 1024);
+  __type(key, __be32);
+  __type(value, __u8);
+} blocklist SEC(".maps");
+
+/**
+ * @brief Controller IP
+ *
+ * Single entry holding the primary controller address (Network Byte Order),
+ * 0 if unset. A map rather than a constant so the Userspace Agent can follow
+ * controller.ip_file changes without reloading the program.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_ARRAY);
+  __uint(max_entries, 1);
+  __type(key, __u32);
+  __type(value, __be32);
+} controller_ip SEC(".maps");
+
+/**
+ * @brief Extra Controller IPs
+ *
+ * Controller addresses (Network Byte Order) accepted in addition to the one
+ * in controller_ip, e.g. a standby controller in another subnet. Populated by
+ * the Userspace Agent at startup. Sized by MAX_EXTRA_CONTROLLERS in config.rs.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, 16);
+  __type(key, __be32);
+  __type(value, __u8);
+} controllers SEC(".maps");
+
+/**
+ * @brief DNS Resolvers
+ *
+ * Addresses (Network Byte Order) that connect4_prog lets the cgroup reach on
+ * port 53 without a session. Populated by the Userspace Agent at startup.
+ * Sized by MAX_DNS_RESOLVERS in config.rs.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, 16);
+  __type(key, __be32);
+  __type(value, __u8);
+} dns_resolvers SEC(".maps");
+
+/**
+ * @brief Enforcement Mode
+ *
+ * Single entry holding an enum aegis_mode. Zero-initialized, so the program
+ * enforces unless the Userspace Agent switches it to permissive.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_ARRAY);
+  __uint(max_entries, 1);
+  __type(key, __u32);
+  __type(value, __u32);
+} enforcement SEC(".maps");
+
+/**
+ * @brief Watchdog Probe
+ *
+ * Single entry the Userspace Agent writes and reads back to check that its
+ * map handles still work. Never touched by the program, so probing cannot
+ * evict a session or show up in the session map.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_ARRAY);
+  __uint(max_entries, 1);
+  __type(key, __u32);
+  __type(value, __u64);
+} probe SEC(".maps");
+
+/**
+ * @brief Redirect Target
+ *
+ * Single entry at index 0 holding REDIRECT_IFINDEX, used by the XDP program
+ * with bpf_redirect_map. Populated by the Userspace Agent at startup.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_DEVMAP);
+  __uint(max_entries, 1);
+  __type(key, __u32);
+  __type(value, __u32);
+} redirect_devmap SEC(".maps");
+
+/**
+ * @brief Identifies the fragments of one IPv4 datagram (RFC 791)
+ */
+struct frag_key {
+  __be32 saddr;
+  __be32 daddr;
+  __be16 id;
+  __u8 protocol;
+  __u8 pad;
+};
+
+/**
+ * @brief Passed Fragmented Datagrams
+ *
+ * Only used with FRAGMENT_POLICY_TRACK. A first fragment that passes records
+ * its datagram and verdict here so the remaining fragments, which carry no
+ * transport header, inherit the verdict. Entries are left to LRU eviction so reordered
+ * fragments still match.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_LRU_HASH);
+  __uint(max_entries, 1024);
+  __type(key, struct frag_key);
+  __type(value, __u8);
+} fragments SEC(".maps");
+
+/**
+ * @brief Identifies one TCP connection in the inbound direction
+ */
+struct flow_key {
+  __be32 saddr;
+  __be32 daddr;
+  __be16 sport;
+  __be16 dport;
+};
+
+/**
+ * @brief Identifies the (source, destination) pair MAX_CONNS_PER_SRC applies to
+ */
+struct conn_pair {
+  __be32 saddr;
+  __be32 daddr;
+};
+
+/**
+ * @brief Open TCP Connections
+ *
+ * Only used when MAX_CONNS_PER_SRC is set. A SYN that passes records its
+ * connection here with the time its last packet was seen; the connection's
+ * FIN or RST removes it again, so retransmitted SYNs are not counted twice.
+ * Connections that end without either, e.g. reset by the destination, are
+ * removed by the agent's cleanup once idle.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_LRU_HASH);
+  __uint(max_entries, 65536);
+  __type(key, struct flow_key);
+  __type(value, __u64); // last_seen_ns
+} flows SEC(".maps");
+
+/**
+ * @brief Open Connection Counts
+ *
+ * Number of flows entries per (source, destination) pair. The agent recounts
+ * it from flows on every cleanup cycle, so connections expired or evicted
+ * from flows stop counting then.
+ */
+struct {
+  __uint(type, BPF_MAP_TYPE_LRU_HASH);
+  __uint(max_entries, 10240);
+  __type(key, struct conn_pair);
+  __type(value, __u32);
+} conn_counts SEC(".maps");
+
+/**
+ * @brief Validates TCP flag combinations
+ *
+ * Rejects packets no legitimate stack sends: no flags at all (NULL scan),
+ * SYN with FIN or RST, FIN with RST, and FIN without ACK (XMAS/FIN scans).
+ *
+ * @return 1 if the flags are legal, 0 otherwise.
+ */
+static __always_inline int tcp_flags_valid(const struct tcphdr *tcph) {
+  if (!(tcph->fin | tcph->syn | tcph->rst | tcph->psh | tcph->ack |
+        tcph->urg)) {
+    return 0;
+  }
+  if (tcph->syn && (tcph->fin || tcph->rst)) {
+    return 0;
+  }
+  if (tcph->fin && (tcph->rst || !tcph->ack)) {
+    return 0;
+  }
+  return 1;
+}
+
+/**
+ * @brief Increments one of the per-CPU event counters
+ */
+static __always_inline void count(__u32 idx) {
+  __u64 *value = bpf_map_lookup_elem(&counters, &idx);
+  if (value) {
+    *value += 1;
+  }
+}
+
+/**
+ * @brief Packs a MAC address into the low 48 bits of a u64
+ */
+static __always_inline __u64 mac_to_u64(const unsigned char *mac) {
+  return ((__u64)mac[0] << 40) | ((__u64)mac[1] << 32) | ((__u64)mac[2] << 24) |
+         ((__u64)mac[3] << 16) | ((__u64)mac[4] << 8) | (__u64)mac[5];
+}
+
+/**
+ * @brief Builds the fragment map key for the datagram carrying iph
+ */
+static __always_inline struct frag_key frag_key_of(const struct iphdr *iph) {
+  struct frag_key key = {0};
+  key.saddr = iph->saddr;
+  key.daddr = iph->daddr;
+  key.id = iph->id;
+  key.protocol = iph->protocol;
+  return key;
+}
+
+/**
+ * @brief Checks whether addr belongs to a controller
+ *
+ * Matches the controller_ip entry, any address in the controllers map, or
+ * any address inside CONTROLLER_NET when a prefix is configured, e.g. for an
+ * HA group of controllers sharing a subnet.
+ */
+static __always_inline bool is_controller(__be32 addr) {
+  __u32 idx = 0;
+  __be32 *primary = bpf_map_lookup_elem(&controller_ip, &idx);
+
+  return (primary && *primary && addr == *primary) ||
+         (CONTROLLER_NET_MASK && (addr & CONTROLLER_NET_MASK) == CONTROLLER_NET) ||
+         bpf_map_lookup_elem(&controllers, &addr);
+}
+
+/**
+ * @brief Checks whether port is one the controller listens on
+ *
+ * CONTROLLER_PORT is the common case; the extra ports are only scanned when
+ * more than one is configured.
+ */
+static __always_inline bool is_controller_port(__be16 port) {
+  if (port == CONTROLLER_PORT) {
+    return true;
+  }
+  for (u32 i = 0; i < MAX_EXTRA_CONTROLLER_PORTS; i++) {
+    if (i >= CONTROLLER_EXTRA_PORT_COUNT) {
+      break;
+    }
+    if (port == CONTROLLER_EXTRA_PORTS[i]) {
+      return true;
+    }
+  }
+  return false;
+}
+
+/**
+ * @brief Tracks open TCP connections against MAX_CONNS_PER_SRC
+ *
+ * A SYN without ACK opens a connection and is refused once its (source,
+ * destination) pair already has MAX_CONNS_PER_SRC open; FIN or RST closes
+ * it, and any other packet refreshes its last_seen_ns. Packets of
+ * connections that are already open are never refused.
+ *
+ * @return 1 if the packet may pass, 0 if it opens one connection too many.
+ */
+static __always_inline int conn_limit_allows(const struct iphdr *iph,
+                                             const struct tcphdr *tcph) {
+  struct flow_key flow = {0};
+  flow.saddr = iph->saddr;
+  flow.daddr = iph->daddr;
+  flow.sport = tcph->source;
+  flow.dport = tcph->dest;
+
+  struct conn_pair pair = {0};
+  pair.saddr = iph->saddr;
+  pair.daddr = iph->daddr;
+
+  __u64 now = bpf_ktime_get_ns();
+  if (tcph->syn && !tcph->ack) {
+    // A retransmitted SYN belongs to a connection that is already counted
+    if (bpf_map_lookup_elem(&flows, &flow)) {
+      return 1;
+    }
+    __u32 *open = bpf_map_lookup_elem(&conn_counts, &pair);
+    if (open && *open >= MAX_CONNS_PER_SRC) {
+      count(COUNTER_CONN_CAPPED);
+      return 0;
+    }
+    if (bpf_map_update_elem(&flows, &flow, &now, BPF_NOEXIST) == 0) {
+      if (open) {
+        __sync_fetch_and_add(open, 1);
+      } else {
+        __u32 first = 1;
+        bpf_map_update_elem(&conn_counts, &pair, &first, BPF_NOEXIST);
+      }
+    }
+  } else if (tcph->fin || tcph->rst) {
+    // Only the packet that removes the flow releases its slot
+    if (bpf_map_delete_elem(&flows, &flow) == 0) {
+      __u32 *open = bpf_map_lookup_elem(&conn_counts, &pair);
+      if (open && *open > 0) {
+        __sync_fetch_and_sub(open, 1);
+      }
+    }
+  } else {
+    // Keep the connection from expiring, as lazily as session refreshes
+    __u64 *last_seen = bpf_map_lookup_elem(&flows, &flow);
+    if (last_seen && now - *last_seen >= LAZY_UPDATE_TIMEOUT) {
+      *last_seen = now;
+    }
+  }
+  return 1;
+}
+
+/**
+ * @brief Accepts a packet, remembering its datagram if more fragments follow
+ *
+ * @param verdict VERDICT_PASS or VERDICT_SESSION, inherited by the fragments.
+ */
+static __always_inline int pass_packet(const struct iphdr *iph, __u8 verdict) {
+  if (FRAGMENT_POLICY == FRAGMENT_POLICY_TRACK &&
+      (iph->frag_off & bpf_htons(IP_MF))) {
+    struct frag_key key = frag_key_of(iph);
+    bpf_map_update_elem(&fragments, &key, &verdict, BPF_ANY);
+  }
+  return verdict;
+}
+
+/**
+ * @brief Applies the enforcement mode to a verdict
+ *
+ * In MODE_PERMISSIVE, packets the policy would drop pass instead. Sessions
+ * are still matched and refreshed, so switching back to MODE_ENFORCING keeps
+ * every authorized flow working.
+ *
+ * Past GRACE_UNTIL the program enforces even if the agent never switched the
+ * mode back, e.g. because it died during the startup grace period and left
+ * the program attached.
+ */
+static __always_inline int enforce(int verdict) {
+  if (verdict != VERDICT_DROP) {
+    return verdict;
+  }
+  if (GRACE_UNTIL && bpf_ktime_get_ns() >= GRACE_UNTIL) {
+    return verdict;
+  }
+  __u32 idx = 0;
+  __u32 *mode = bpf_map_lookup_elem(&enforcement, &idx);
+  if (mode && *mode == MODE_PERMISSIVE) {
+    return VERDICT_PASS;
+  }
+  return verdict;
+}
+
+/**
+ * @brief Packet Filter
+ *
+ * Shared by the XDP and tc programs, so both hooks enforce the same policy
+ * against the same maps. It parses the Ethernet frame in [data, data_end)
+ * and decides whether it may pass.
+ *
+ * Up to two VLAN tags (802.1Q, or 802.1ad + 802.1Q for QinQ) are skipped
+ * before the EtherType is evaluated.
+ *
+ * Every header is bounds-checked against data_end before it is read, and
+ * truncated or malformed headers (IPv4 version/IHL/total length, TCP data
+ * offset) are dropped explicitly.
+ *
+ * Non-first IPv4 fragments carry no transport header. FRAGMENT_POLICY_DROP
+ * drops them; FRAGMENT_POLICY_TRACK passes them only if the first fragment
+ * of the same datagram passed. First fragments are evaluated normally.
  *
  * Policy:
  * 1. Pass ARP packets (essential for L2 discovery).
- * 2. Drop non-IPv4 packets.
- * 3. Pass IPv4 TCP/UDP packets matching CONTROLLER_IP and CONTROLLER_PORT.
+ * 2. Drop non-IPv4 packets, and IPv4 packets from a blocklisted source.
+ *    With STRICT_TCP, drop TCP packets carrying illegal flag combinations.
+ * 3. Pass IPv4 TCP/UDP packets matching a controller and a controller port,
+ *    from a controller or MONITOR_IP to the agent's GRPC_PORT,
+ *    or destined to an always-allowed port.
  * 4. Pass traffic from allowed IPs to allowed services.
- * 4. Drop everything else.
+ *    Lookup order: exact 4-tuple, then any source port, then a session with
+ *    dest_port 0, which authorizes every port on the destination, then a
+ *    session with src_ip 0, which admits every source to that port.
+ *    With STRICT_TCP, a session that has not carried traffic yet only
+ *    accepts a TCP SYN without ACK as its first TCP packet.
+ *    With MAX_CONNS_PER_SRC, a TCP SYN without ACK is dropped while its
+ *    source already has that many connections open to the destination.
+ *    With BIND_SRC_MAC, the first packet of a session binds it to its
+ *    Ethernet source and packets from any other MAC are dropped.
+ * 5. Drop everything else.
  *
- * @param ctx Context containing packet data pointers.
- * @return XDP_PASS to accept the packet, XDP_DROP to discard it.
+ * @param data Start of the Ethernet frame.
+ * @param data_end End of the readable packet data.
+ * @return VERDICT_PASS or VERDICT_SESSION to accept the packet, VERDICT_DROP
+ * to discard it.
  */
-SEC("xdp") int xdp_drop_prog(struct xdp_md *ctx) {
-  // Initialize data pointers for packet parsing
-  void *data_end = (void *)(long)ctx->data_end;
-  void *data = (void *)(long)ctx->data;
-
+static __always_inline int filter_packet(void *data, void *data_end) {
   // Parse Ethernet header
   struct ethhdr *eth = data;
 
   // Verify header within packet bounds
   if ((void *)(eth + 1) > data_end) {
-    return XDP_DROP;
+    return VERDICT_DROP;
+  }
+
+  __be16 h_proto = eth->h_proto;
+  void *cursor = eth + 1;
+
+  // Skip VLAN tags so trunked traffic is parsed at the right offset
+#pragma unroll
+  for (int i = 0; i < VLAN_MAX_DEPTH; i++) {
+    if (h_proto != bpf_htons(ETH_P_8021Q) &&
+        h_proto != bpf_htons(ETH_P_8021AD)) {
+      break;
+    }
+    struct aegis_vlan_hdr *vlan = cursor;
+    if ((void *)(vlan + 1) > data_end) {
+      return VERDICT_DROP;
+    }
+    h_proto = vlan->encap_proto;
+    cursor = vlan + 1;
   }
 
   // Allow ARP for network discovery
-  if (eth->h_proto == bpf_htons(ETH_P_ARP)) {
-    return XDP_PASS;
+  if (h_proto == bpf_htons(ETH_P_ARP)) {
+    return VERDICT_PASS;
   }
 
   // Drop non-IPv4 traffic
-  if (eth->h_proto != bpf_htons(ETH_P_IP)) {
-    return XDP_DROP;
+  if (h_proto != bpf_htons(ETH_P_IP)) {
+    return VERDICT_DROP;
   }
 
   // Parse IPv4 header
-  struct iphdr *iph = (void *)(eth + 1);
+  struct iphdr *iph = cursor;
 
   // Verify header within packet bounds
   if ((void *)(iph + 1) > data_end) {
-    return XDP_DROP;
+    return VERDICT_DROP;
   }
 
+  // Reject malformed headers explicitly rather than trusting their fields:
+  // wrong version, a header shorter than 20 bytes, or a total length that
+  // cannot even hold the header
+  if (iph->version != 4 || iph->ihl < 5 ||
+      bpf_ntohs(iph->tot_len) < iph->ihl * 4) {
+    return VERDICT_DROP;
+  }
+
+  // Blocklisted sources are dropped whatever else would let them in
+  if (bpf_map_lookup_elem(&blocklist, &iph->saddr)) {
+    return VERDICT_DROP;
+  }
+
+  // Later fragments have no transport header to match against
+  if (iph->frag_off & bpf_htons(IP_OFFSET)) {
+    if (FRAGMENT_POLICY == FRAGMENT_POLICY_TRACK) {
+      struct frag_key key = frag_key_of(iph);
+      __u8 *verdict = bpf_map_lookup_elem(&fragments, &key);
+      if (verdict) {
+        return *verdict;
+      }
+    }
+    return VERDICT_DROP;
+  }
+
+  // The transport header follows any IP options (IHL is in 32-bit words)
+  void *l4 = (void *)iph + iph->ihl * 4;
+
+  __be16 src_port = 0;
   __be16 dst_port = 0;
+  struct tcphdr *tcph = NULL;
+  bool is_tcp = false;
+  bool tcp_opens = false; // SYN without ACK, i.e. a new connection attempt
 
   // Parse transport layer (TCP/UDP)
   if (iph->protocol == IPPROTO_TCP) {
-    struct tcphdr *tcph = (void *)(iph + 1);
+    tcph = l4;
     if ((void *)(tcph + 1) > data_end) {
-      return XDP_DROP;
+      return VERDICT_DROP;
     }
+    // A data offset below 5 words cannot hold the fixed TCP header
+    if (tcph->doff < 5) {
+      return VERDICT_DROP;
+    }
+    if (STRICT_TCP && !tcp_flags_valid(tcph)) {
+      return VERDICT_DROP;
+    }
+    src_port = tcph->source;
     dst_port = tcph->dest;
+    is_tcp = true;
+    tcp_opens = tcph->syn && !tcph->ack;
   } else if (iph->protocol == IPPROTO_UDP) {
-    struct udphdr *udph = (void *)(iph + 1);
+    struct udphdr *udph = l4;
     if ((void *)(udph + 1) > data_end) {
-      return XDP_DROP;
+      return VERDICT_DROP;
     }
+    src_port = udph->source;
     dst_port = udph->dest;
   } else {
     // Drop ICMP and other protocols
-    return XDP_DROP;
+    return VERDICT_DROP;
   }
 
   // Allow traffic to controller or DNS
   if (dst_port == 53 ||
-      (dst_port == CONTROLLER_PORT && iph->daddr == CONTROLLER_IP)) {
-    return XDP_PASS;
+      (is_controller_port(dst_port) && is_controller(iph->daddr))) {
+    return pass_packet(iph, VERDICT_PASS);
+  }
+
+  // Allow the controller and monitoring client to reach the agent's gRPC server
+  if (dst_port == GRPC_PORT &&
+      (is_controller(iph->saddr) ||
+       (MONITOR_IP && iph->saddr == MONITOR_IP))) {
+    return pass_packet(iph, VERDICT_PASS);
+  }
+
+  // Allow infrastructure ports that bypass sessions
+  if (bpf_map_lookup_elem(&allowed_ports, &dst_port)) {
+    return pass_packet(iph, VERDICT_PASS);
   }
 
   // Check if session is authorized
@@ -114,17 +653,198 @@ SEC("xdp") int xdp_drop_prog(struct xdp_md *ctx) {
   key.src_ip = iph->saddr;
   key.dest_ip = iph->daddr;
   key.dest_port = dst_port;
+  key.src_port = src_port;
 
   struct session_val *val = bpf_map_lookup_elem(&session, &key);
+  if (!val) {
+    // Fall back to a session that accepts any source port
+    key.src_port = ANY_SRC_PORT;
+    val = bpf_map_lookup_elem(&session, &key);
+  }
+  if (!val) {
+    // Fall back to a wildcard session covering every port on this host
+    key.dest_port = WILDCARD_PORT;
+    val = bpf_map_lookup_elem(&session, &key);
+  }
+  if (!val) {
+    // Fall back to a session open to every source for this port
+    key.src_ip = ANY_SRC_IP;
+    key.dest_port = dst_port;
+    val = bpf_map_lookup_elem(&session, &key);
+  }
   if (val) {
-    // Update activity timestamp (with lazy update to reduce overhead)
+    // Another host on the segment may spoof an authorized source IP. Sessions
+    // open to every source have no single host to bind to.
+    bool bind_mac = BIND_SRC_MAC && key.src_ip != ANY_SRC_IP;
+    __u64 src_mac = bind_mac ? mac_to_u64(eth->h_source) : 0;
+    if (bind_mac && val->src_mac && val->src_mac != src_mac) {
+      count(COUNTER_MAC_MISMATCH);
+      return VERDICT_DROP;
+    }
+    // Refused SYNs must not keep the session alive, so check before refreshing
+    if (MAX_CONNS_PER_SRC && tcph && !conn_limit_allows(iph, tcph)) {
+      return VERDICT_DROP;
+    }
+    // Update activity timestamp lazily: after the first packet, last_seen_ns
+    // is only rewritten once LAZY_UPDATE_TIMEOUT has passed, so a busy flow
+    // does not dirty the map entry on every packet. Idle detection is
+    // therefore only accurate to within LAZY_UPDATE_TIMEOUT.
+    // bpf_ktime_get_ns() is CLOCK_MONOTONIC, the same clock the agent uses.
     u64 now = bpf_ktime_get_ns();
-    if (now - val->last_seen_ns >= LAZY_UPDATE_TIMEOUT) {
+    if (!(val->flags & SESSION_SEEN)) {
+      // First packet of the session. With STRICT_TCP it must be a bare SYN,
+      // so stray ACK/FIN/RST probes cannot use a freshly authorized flow.
+      if (STRICT_TCP && is_tcp && !tcp_opens) {
+        return VERDICT_DROP;
+      }
       val->last_seen_ns = now;
+      val->flags |= SESSION_SEEN;
+      count(COUNTER_REFRESH_APPLIED);
+    } else if (now - val->last_seen_ns >= LAZY_UPDATE_TIMEOUT) {
+      val->last_seen_ns = now;
+      count(COUNTER_REFRESH_APPLIED);
+    } else {
+      count(COUNTER_REFRESH_SUPPRESSED);
     }
-    return XDP_PASS;
+    // Bind only once the packet passes, so a dropped first packet from a
+    // spoofer cannot claim the session.
+    if (bind_mac && !val->src_mac) {
+      val->src_mac = src_mac;
+    }
+    return pass_packet(iph, VERDICT_SESSION);
   }
 
   // Default: drop unauthorized traffic
-  return XDP_DROP;
+  return VERDICT_DROP;
+}
+
+/**
+ * @brief XDP Drop Program
+ *
+ * This function hooks into the XDP (eXpress Data Path) at the network driver
+ * level and applies filter_packet to every received frame.
+ *
+ * @param ctx Context containing packet data pointers.
+ * @return XDP_PASS to accept the packet, XDP_REDIRECT to hand session traffic
+ * to REDIRECT_IFINDEX, XDP_DROP to discard it.
+ */
+SEC("xdp") int xdp_drop_prog(struct xdp_md *ctx) {
+  void *data_end = (void *)(long)ctx->data_end;
+  void *data = (void *)(long)ctx->data;
+
+  switch (enforce(filter_packet(data, data_end))) {
+  case VERDICT_DROP:
+    return XDP_DROP;
+  case VERDICT_SESSION:
+    if (REDIRECT_IFINDEX) {
+      // Falls back to XDP_PASS if the devmap entry is missing
+      return bpf_redirect_map(&redirect_devmap, 0, XDP_PASS);
+    }
+    return XDP_PASS;
+  default:
+    return XDP_PASS;
+  }
+}
+
+/**
+ * @brief tc Drop Program
+ *
+ * Attached to the clsact ingress hook instead of XDP on interfaces where XDP
+ * is unavailable, e.g. some veth and nested container setups. Applies the
+ * same filter_packet policy and shares every map with the XDP program.
+ *
+ * Headers may sit outside the linear skb area, so they are pulled in first;
+ * a packet shorter than TC_PULL_LEN fails the pull and is parsed as is.
+ *
+ * @param skb Socket buffer of the received packet.
+ * @return TC_ACT_OK to accept the packet, TC_ACT_REDIRECT to hand session
+ * traffic to REDIRECT_IFINDEX, TC_ACT_SHOT to discard it.
+ */
+SEC("tc") int tc_drop_prog(struct __sk_buff *skb) {
+  if ((void *)(long)skb->data + TC_PULL_LEN > (void *)(long)skb->data_end) {
+    bpf_skb_pull_data(skb, TC_PULL_LEN);
+  }
+
+  // Pulling invalidates earlier packet pointers, so load them afterwards
+  void *data_end = (void *)(long)skb->data_end;
+  void *data = (void *)(long)skb->data;
+
+  switch (enforce(filter_packet(data, data_end))) {
+  case VERDICT_DROP:
+    return TC_ACT_SHOT;
+  case VERDICT_SESSION:
+    if (REDIRECT_IFINDEX) {
+      return bpf_redirect(REDIRECT_IFINDEX, 0);
+    }
+    return TC_ACT_OK;
+  default:
+    return TC_ACT_OK;
+  }
+}
+
+/**
+ * @brief Egress connect() Filter
+ *
+ * Attached to the cgroup configured as network.cgroup, it decides whether a
+ * process in that cgroup may connect() a TCP or UDP socket to an IPv4
+ * address. A session (src, dst, port) is read as permission for src to
+ * reach dst:port, so the controller authorizes outbound flows through the
+ * same session map as inbound ones.
+ *
+ * Policy:
+ * 1. Allow loopback, the controllers on a controller port, and TCP or UDP
+ *    port 53 on a configured DNS resolver.
+ * 2. Refuse blocklisted destinations.
+ * 3. Allow if a session matches. A socket bound to a source address tries
+ *    (src, dst, port), then (src, dst, WILDCARD_PORT); every socket then
+ *    tries (ANY_SRC_IP, dst, port).
+ * 4. Refuse everything else, unless the enforcement mode is permissive.
+ *
+ * Unconnected UDP sendmsg() does not pass through this hook.
+ *
+ * @param ctx Address the socket is being connected to.
+ * @return CONNECT_ALLOW to let connect() proceed, CONNECT_REFUSE to fail it
+ * with EPERM.
+ */
+SEC("cgroup/connect4") int connect4_prog(struct bpf_sock_addr *ctx) {
+  __be32 daddr = ctx->user_ip4;
+  __be16 dport = (__be16)ctx->user_port;
+
+  if ((bpf_ntohl(daddr) >> 24) == 127 ||
+      (is_controller_port(dport) && is_controller(daddr))) {
+    return CONNECT_ALLOW;
+  }
+  // Any other host on port 53 would be an open channel out of the cgroup
+  if (dport == bpf_htons(53) &&
+      (ctx->protocol == IPPROTO_TCP || ctx->protocol == IPPROTO_UDP) &&
+      bpf_map_lookup_elem(&dns_resolvers, &daddr)) {
+    return CONNECT_ALLOW;
+  }
+
+  if (bpf_map_lookup_elem(&blocklist, &daddr)) {
+    return CONNECT_REFUSE;
+  }
+
+  struct session_key key = {0};
+  key.src_ip = ctx->sk ? ctx->sk->src_ip4 : ANY_SRC_IP;
+  key.dest_ip = daddr;
+  key.dest_port = dport;
+  key.src_port = ANY_SRC_PORT;
+
+  if (key.src_ip != ANY_SRC_IP) {
+    if (bpf_map_lookup_elem(&session, &key)) {
+      return CONNECT_ALLOW;
+    }
+    key.dest_port = WILDCARD_PORT;
+    if (bpf_map_lookup_elem(&session, &key)) {
+      return CONNECT_ALLOW;
+    }
+    key.src_ip = ANY_SRC_IP;
+    key.dest_port = dport;
+  }
+  if (bpf_map_lookup_elem(&session, &key)) {
+    return CONNECT_ALLOW;
+  }
+
+  return enforce(VERDICT_DROP) == VERDICT_DROP ? CONNECT_REFUSE : CONNECT_ALLOW;
 }
@@ -1,14 +1,78 @@
 #ifndef AEGIS_H
 #define AEGIS_H
 
+/**
+ * @brief Reserved destination port meaning "any port"
+ *
+ * A session stored with this port authorizes the source to reach every port
+ * on the destination. Exact (src, dst, port) entries are checked first.
+ */
+#define WILDCARD_PORT 0
+
+/**
+ * @brief Reserved source port meaning "any source port"
+ *
+ * Sessions with a specific source port are checked before ANY_SRC_PORT ones.
+ */
+#define ANY_SRC_PORT 0
+
+/**
+ * @brief Reserved source IP meaning "any source"
+ *
+ * A session stored with this IP lets every source reach dest_ip:dest_port.
+ * It is only tried after all lookups for the packet's real source missed,
+ * and is always stored with ANY_SRC_PORT and a specific dest_port.
+ */
+#define ANY_SRC_IP 0
+
+/**
+ * @brief Capacity of CONTROLLER_EXTRA_PORTS
+ *
+ * Mirrored by MAX_EXTRA_CONTROLLER_PORTS in config.rs.
+ */
+#define MAX_EXTRA_CONTROLLER_PORTS 8
+
+/**
+ * @brief session_val flag set once the program has passed a packet for it
+ *
+ * Strict TCP checks the first packet of a session that lacks it. Mirrored by
+ * SESSION_SEEN in bpf.rs.
+ */
+#define SESSION_SEEN 1
+
+/**
+ * @brief Indices into the per-CPU counters map
+ *
+ * Mirrored by the COUNTER_* constants in bpf.rs.
+ */
+enum aegis_counter {
+  COUNTER_REFRESH_APPLIED = 0,    // last_seen_ns rewritten for a session hit
+  COUNTER_REFRESH_SUPPRESSED = 1, // Rewrite skipped inside LAZY_UPDATE_TIMEOUT
+  COUNTER_CONN_CAPPED = 2,        // SYN dropped by MAX_CONNS_PER_SRC
+  COUNTER_MAC_MISMATCH = 3,       // Dropped by BIND_SRC_MAC for a foreign source MAC
+  COUNTER_MAX,
+};
+
+/**
+ * @brief Values of the enforcement map
+ *
+ * Mirrored by the MODE_* constants in bpf.rs.
+ */
+enum aegis_mode {
+  MODE_ENFORCING = 0, // Drop whatever the policy rejects
+  MODE_PERMISSIVE = 1, // Pass everything, e.g. during the startup grace period
+};
+
 /**
  * @brief Session Lookup Key
  * * Used to identify unique flows in the BPF hash map.
+ * * There is no protocol field: one session admits both TCP and UDP.
  */
 typedef struct session_key {
   __be32 src_ip;    // Source IP Address (Network Byte Order)
   __be32 dest_ip;   // Destination IP Address (Network Byte Order)
   __be16 dest_port; // Destination Port (Network Byte Order)
+  __be16 src_port;  // Source Port (Network Byte Order), or ANY_SRC_PORT
 } __attribute__((packed)) session_key;
 
 /**
@@ -16,8 +80,11 @@ typedef struct session_key {
  * * Stores the state and telemetry data for an active session.
  */
 typedef struct session_val {
-  __u64 last_seen_ns;  // Timestamp of the last valid packet (System uptime)
-  __u64 created_at_ns; // Timestamp when the session was authorized
+  __u64 last_seen_ns;  // Timestamp of the last valid packet (CLOCK_MONOTONIC)
+  __u64 created_at_ns; // Timestamp when the session was authorized (CLOCK_MONOTONIC)
+  __u64 epoch;         // Controller epoch that last submitted it, 0 if added locally
+  __u64 src_mac;       // Source MAC bound by BIND_SRC_MAC (low 48 bits), 0 if unbound
+  __u64 flags;         // SESSION_* bits
 } session_val;
 
 #endif // AEGIS_H
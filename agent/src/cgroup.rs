@@ -0,0 +1,134 @@
+use crate::config::MAX_DNS_RESOLVERS;
+use anyhow::{Context, Result, bail};
+use libbpf_rs::ProgramMut;
+use nix::sys::statfs::{CGROUP2_SUPER_MAGIC, statfs};
+use std::{
+    fs::{self, File},
+    net::Ipv4Addr,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+const LINK_PIN_NAME: &str = "cgroup_connect4_link";
+
+/// Read for nameservers when `network.dns_resolvers` is empty.
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Path the egress filter's link is pinned at under `pin_prefix`.
+fn link_pin_path(pin_prefix: &Path) -> PathBuf {
+    pin_prefix.join(LINK_PIN_NAME)
+}
+
+/// Opens `path` after checking it is a cgroup v2 directory.
+fn open_cgroup(path: &Path) -> Result<File> {
+    let stat = statfs(path).with_context(|| format!("Failed to statfs {}", path.display()))?;
+    if stat.filesystem_type() != CGROUP2_SUPER_MAGIC {
+        bail!("{} is not a cgroup v2 directory", path.display());
+    }
+    File::open(path).with_context(|| format!("Failed to open cgroup {}", path.display()))
+}
+
+/// Attaches `prog` to the `connect4` hook of the cgroup at `cgroup`, so
+/// processes in it can only connect to destinations a session allows.
+///
+/// Like the XDP program, the filter stays attached after the agent exits:
+/// its link is pinned under `pin_prefix`. A filter left by an earlier run is
+/// replaced only once the new one is attached, so the cgroup is never
+/// unfiltered in between.
+pub fn attach(prog: &ProgramMut<'_>, cgroup: &Path, pin_prefix: &Path) -> Result<()> {
+    let dir = open_cgroup(cgroup)?;
+    let mut link = prog
+        .attach_cgroup(dir.as_raw_fd())
+        .with_context(|| format!("Failed to attach egress filter to {}", cgroup.display()))?;
+    detach(pin_prefix)?;
+    let pin_path = link_pin_path(pin_prefix);
+    link.pin(&pin_path)
+        .with_context(|| format!("Failed to pin egress filter at {}", pin_path.display()))?;
+    info!("Egress filter attached to cgroup {}", cgroup.display());
+    Ok(())
+}
+
+/// Returns the DNS servers processes in the cgroup may reach without a
+/// session: `configured`, or the IPv4 nameservers in /etc/resolv.conf if it
+/// is empty.
+pub fn dns_resolvers(configured: &[Ipv4Addr]) -> Result<Vec<Ipv4Addr>> {
+    if !configured.is_empty() {
+        return Ok(configured.to_vec());
+    }
+    let contents = fs::read_to_string(RESOLV_CONF)
+        .with_context(|| format!("Failed to read {}", RESOLV_CONF))?;
+    Ok(parse_nameservers(&contents))
+}
+
+/// Collects the IPv4 `nameserver` entries of resolv.conf `contents`, up to
+/// the capacity of the filter's resolver map.
+fn parse_nameservers(contents: &str) -> Vec<Ipv4Addr> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("nameserver") => fields.next()?.parse().ok(),
+                _ => None,
+            }
+        })
+        .take(MAX_DNS_RESOLVERS)
+        .collect()
+}
+
+/// Detaches the egress filter pinned under `pin_prefix`, if any, by dropping
+/// its pin. Returns true if one was attached.
+pub fn detach(pin_prefix: &Path) -> Result<bool> {
+    let pin_path = link_pin_path(pin_prefix);
+    match fs::remove_file(&pin_path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => {
+            Err(e).with_context(|| format!("Failed to unpin egress filter {}", pin_path.display()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_non_cgroup_dir() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let err = open_cgroup(dir.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("not a cgroup v2 directory"),
+            "{}",
+            err
+        );
+        assert!(open_cgroup(Path::new("/nonexistent/cgroup")).is_err());
+    }
+
+    #[test]
+    fn test_parse_nameservers() {
+        let contents = "# Generated\n\
+                        search example.com\n\
+                        nameserver 10.0.0.2\n\
+                        nameserver fe80::1\n\
+                        nameserver\t192.168.1.1 # secondary\n\
+                        options edns0\n";
+        assert_eq!(
+            parse_nameservers(contents),
+            vec![Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(192, 168, 1, 1)]
+        );
+        assert!(parse_nameservers("").is_empty());
+    }
+
+    #[test]
+    fn test_detach_without_pin() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        assert!(!detach(dir.path()).unwrap());
+
+        // Any file at the pin path counts as a filter to release
+        fs::write(link_pin_path(dir.path()), b"").unwrap();
+        assert!(detach(dir.path()).unwrap());
+        assert!(!link_pin_path(dir.path()).exists());
+    }
+}
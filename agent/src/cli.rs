@@ -0,0 +1,1450 @@
+use anyhow::{Context, Result, anyhow};
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{Level, warn};
+
+use crate::{
+    bpf::{ANY_SRC_IP, ANY_SRC_PORT, WILDCARD_PORT},
+    config::{
+        AttachHook, Config, FragmentPolicy, MAX_EXTRA_CONTROLLER_PORTS, MAX_KEEPALIVE_INTERVAL_SEC,
+        XdpMode,
+    },
+    hostname_to_ip,
+    types::{Ipv4Net, SessionKey},
+};
+
+/// Local subcommands that operate on the pinned session map instead of
+/// starting the agent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// `list`: print every session and its remaining time
+    List,
+    /// `dump`: print every session as a table with its age and remaining time
+    Dump,
+    /// `add <src>[:<src_port>] <dst> <port>`: authorize a session
+    Add(SessionKey),
+    /// `remove <src>[:<src_port>] <dst> <port>`: revoke a session
+    Remove(SessionKey),
+}
+
+/// Command-line options that override or extend `config.toml`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    /// Serve gRPC on this Unix domain socket instead of TCP
+    pub grpc_uds: Option<PathBuf>,
+    /// Override `network.attach_hook`
+    pub attach_hook: Option<AttachHook>,
+    /// Override `network.xdp_mode`
+    pub xdp_mode: Option<XdpMode>,
+    /// Force `network.strict_tcp` on
+    pub strict_tcp: bool,
+    /// Force `network.bind_src_mac` on
+    pub bind_src_mac: bool,
+    /// Force `network.require_link_up` on
+    pub require_link_up: bool,
+    /// Override `network.fragment_policy`
+    pub fragment_policy: Option<FragmentPolicy>,
+    /// Extra ports appended to `network.always_allow_ports`
+    pub always_allow_ports: Vec<u16>,
+    /// Override `network.attach_retries`
+    pub attach_retries: Option<u32>,
+    /// Override `network.attach_retry_delay_ms`
+    pub attach_retry_delay_ms: Option<u64>,
+    /// Override `network.enforcement_grace_sec`
+    pub enforcement_grace_sec: Option<u64>,
+    /// Override `network.max_conns_per_src`
+    pub max_conns_per_src: Option<u32>,
+    /// Override `network.redirect_to`
+    pub redirect_to: Option<String>,
+    /// Override `network.cgroup`
+    pub cgroup: Option<PathBuf>,
+    /// Override `session.max_session_age_ns`, given in seconds
+    pub max_session_age_sec: Option<u64>,
+    /// Override `session.broadcast_channel_size`
+    pub channel_size: Option<usize>,
+    /// Override `session.monitor_buffer_size`
+    pub monitor_buffer: Option<usize>,
+    /// Override `session.max_monitors`
+    pub max_monitors: Option<usize>,
+    /// Override `controller.ip` with the first `-c`/`--controller-ip`
+    pub controller_ip: Option<Ipv4Addr>,
+    /// Override `controller.host`; wins over `controller_ip` when both are set
+    pub controller_host: Option<String>,
+    /// Override `controller.ip_file`
+    pub controller_ip_file: Option<PathBuf>,
+    /// Override `controller.net`
+    pub controller_net: Option<Ipv4Net>,
+    /// Further `-c`/`--controller-ip` values, appended to
+    /// `controller.extra_ips`
+    pub extra_controllers: Vec<Ipv4Addr>,
+    /// Override `controller.port` with the first and `controller.extra_ports`
+    /// with the rest
+    pub controller_ports: Vec<u16>,
+    /// Override `grpc.monitor_ip`
+    pub monitor_ip: Option<Ipv4Addr>,
+    /// Take the controller IP and port from the CA certificate's SAN
+    pub pair_from_cert: bool,
+    /// Take the controller IP and port from this DNS SRV record
+    pub controller_srv: Option<String>,
+    /// Resolve hostnames through this DNS server instead of the system resolver
+    pub dns_server: Option<SocketAddr>,
+    /// Detach into the background before loading BPF
+    pub daemonize: bool,
+    /// Write the process ID here and remove it on shutdown
+    pub pidfile: Option<PathBuf>,
+    /// Enter this network namespace before resolving the interface
+    pub netns: Option<PathBuf>,
+    /// CSV file of sessions to insert right after attaching
+    pub seed_sessions: Option<PathBuf>,
+    /// Write logs to this file, rotated daily, instead of stderr
+    pub log_file: Option<PathBuf>,
+    /// Keep logging to stderr when `log_file` is set
+    pub log_stderr: bool,
+    /// Most verbose level to log unless `RUST_LOG` is set
+    pub log_level: Option<Level>,
+    /// Print version information and exit
+    pub version: bool,
+    /// Override `bpf.pin_prefix`
+    pub pin_prefix: Option<PathBuf>,
+    /// Force `bpf.mount_bpffs` on
+    pub mount_bpffs: bool,
+    /// Force `bpf.force_recreate` on
+    pub force_recreate: bool,
+    /// Force `bpf.stats` on
+    pub bpf_stats: bool,
+    /// Override `bpf.watchdog_interval_sec`
+    pub watchdog_interval_sec: Option<u64>,
+    /// Override `bpf.watchdog_failures`
+    pub watchdog_failures: Option<u32>,
+    /// Override `grpc.workers`
+    pub grpc_workers: Option<usize>,
+    /// Override `grpc.shutdown_grace_sec`
+    pub shutdown_grace_sec: Option<u64>,
+    /// Override `grpc.keepalive_interval_sec`
+    pub grpc_keepalive_interval_sec: Option<u64>,
+    /// Override `grpc.audit_file`
+    pub audit_file: Option<PathBuf>,
+    /// Enable `grpc.proxy_protocol`
+    pub proxy_protocol: bool,
+    /// Enable `grpc.read_only`
+    pub read_only: bool,
+    /// Enable `grpc.uds_allow_gid`
+    pub grpc_uds_allow_gid: bool,
+    /// Override `grpc.submit_rate_limit`
+    pub submit_rate_limit: Option<u32>,
+    /// Subcommand given as the first positional argument
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Parses arguments from the process command line.
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses arguments from an iterator (excluding the program name).
+    pub fn parse_from<I, S>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().map(Into::into).peekable();
+
+        // A leading positional argument selects a subcommand
+        if let Some(name) = args.next_if(|arg: &String| !arg.starts_with('-')) {
+            parsed.command = Some(Self::parse_command(&name, &mut args)?);
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--grpc-uds" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--grpc-uds requires a path"))?;
+                    parsed.grpc_uds = Some(PathBuf::from(path));
+                }
+                "--attach-hook" => {
+                    let hook = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--attach-hook requires a value"))?;
+                    parsed.attach_hook = Some(hook.parse().context("Invalid --attach-hook")?);
+                }
+                "--xdp-mode" => {
+                    let mode = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--xdp-mode requires a value"))?;
+                    parsed.xdp_mode = Some(mode.parse().context("Invalid --xdp-mode")?);
+                }
+                "--strict-tcp" => parsed.strict_tcp = true,
+                "--bind-src-mac" => parsed.bind_src_mac = true,
+                "--require-link-up" => parsed.require_link_up = true,
+                "--fragment-policy" => {
+                    let policy = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--fragment-policy requires a value"))?;
+                    parsed.fragment_policy =
+                        Some(policy.parse().context("Invalid --fragment-policy")?);
+                }
+                "--pair-from-cert" => parsed.pair_from_cert = true,
+                "--daemonize" => parsed.daemonize = true,
+                "--version" => parsed.version = true,
+                "--mount-bpffs" => parsed.mount_bpffs = true,
+                "--force-recreate" => parsed.force_recreate = true,
+                "--bpf-stats" => parsed.bpf_stats = true,
+                "--watchdog-interval" => {
+                    let duration = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} requires a duration", arg))?;
+                    parsed.watchdog_interval_sec = Some(parse_duration_secs(&arg, &duration)?);
+                }
+                "--watchdog-failures" => {
+                    let count = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--watchdog-failures requires a count"))?;
+                    parsed.watchdog_failures = Some(match count.parse() {
+                        Ok(0) => return Err(anyhow!("--watchdog-failures must be at least 1")),
+                        Ok(count) => count,
+                        Err(e) => {
+                            return Err(e).with_context(|| {
+                                format!("Invalid --watchdog-failures: {}", count)
+                            });
+                        }
+                    });
+                }
+                "--pin-prefix" | "--pin-dir" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} requires a path", arg))?;
+                    parsed.pin_prefix = Some(PathBuf::from(path));
+                }
+                "--pidfile" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--pidfile requires a path"))?;
+                    parsed.pidfile = Some(PathBuf::from(path));
+                }
+                "--netns" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--netns requires a path"))?;
+                    parsed.netns = Some(PathBuf::from(path));
+                }
+                "--seed-sessions" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--seed-sessions requires a path"))?;
+                    parsed.seed_sessions = Some(PathBuf::from(path));
+                }
+                "--log-file" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--log-file requires a path"))?;
+                    parsed.log_file = Some(PathBuf::from(path));
+                }
+                "--log-stderr" => parsed.log_stderr = true,
+                "--log-level" => {
+                    let level = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--log-level requires a level"))?;
+                    parsed.log_level = Some(parse_log_level(&level)?);
+                }
+                "--audit-file" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--audit-file requires a path"))?;
+                    parsed.audit_file = Some(PathBuf::from(path));
+                }
+                "--proxy-protocol" => parsed.proxy_protocol = true,
+                "--read-only" => parsed.read_only = true,
+                "--grpc-uds-allow-gid" => parsed.grpc_uds_allow_gid = true,
+                "--submit-rate-limit" => {
+                    let rate = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--submit-rate-limit requires a rate"))?;
+                    parsed.submit_rate_limit = Some(
+                        rate.parse()
+                            .with_context(|| format!("Invalid --submit-rate-limit: {}", rate))?,
+                    );
+                }
+                "--always-allow-port" => {
+                    let port = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--always-allow-port requires a port"))?;
+                    parsed.always_allow_ports.push(
+                        port.parse()
+                            .with_context(|| format!("Invalid --always-allow-port: {}", port))?,
+                    );
+                }
+                "--attach-retries" => {
+                    let retries = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--attach-retries requires a count"))?;
+                    parsed.attach_retries = Some(
+                        retries
+                            .parse()
+                            .with_context(|| format!("Invalid --attach-retries: {}", retries))?,
+                    );
+                }
+                "--attach-retry-delay" => {
+                    let delay = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--attach-retry-delay requires milliseconds"))?;
+                    parsed.attach_retry_delay_ms = Some(
+                        delay
+                            .parse()
+                            .with_context(|| format!("Invalid --attach-retry-delay: {}", delay))?,
+                    );
+                }
+                "--enforcement-grace" | "--startup-grace" => {
+                    let duration = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} requires a duration", arg))?;
+                    parsed.enforcement_grace_sec = Some(parse_duration_secs(&arg, &duration)?);
+                }
+                "--max-conns-per-src" => {
+                    let max = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--max-conns-per-src requires a count"))?;
+                    parsed.max_conns_per_src = Some(
+                        max.parse()
+                            .with_context(|| format!("Invalid --max-conns-per-src: {}", max))?,
+                    );
+                }
+                "--redirect-to" => {
+                    let iface = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--redirect-to requires an interface"))?;
+                    parsed.redirect_to = Some(iface);
+                }
+                "--cgroup" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--cgroup requires a path"))?;
+                    parsed.cgroup = Some(PathBuf::from(path));
+                }
+                "--shutdown-grace" => {
+                    let secs = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--shutdown-grace requires seconds"))?;
+                    parsed.shutdown_grace_sec = Some(parse_secs("--shutdown-grace", &secs)?);
+                }
+                "--grpc-keepalive-interval" => {
+                    let duration = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} requires a duration", arg))?;
+                    let secs = parse_duration_secs(&arg, &duration)?;
+                    if secs > MAX_KEEPALIVE_INTERVAL_SEC {
+                        return Err(anyhow!(
+                            "{} must be at most {}s",
+                            arg,
+                            MAX_KEEPALIVE_INTERVAL_SEC
+                        ));
+                    }
+                    parsed.grpc_keepalive_interval_sec = Some(secs);
+                }
+                "--max-session-age" => {
+                    let secs = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--max-session-age requires seconds"))?;
+                    parsed.max_session_age_sec =
+                        Some(secs.parse().context("Invalid --max-session-age")?);
+                }
+                "--channel-size" => {
+                    let size = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--channel-size requires a size"))?;
+                    parsed.channel_size = Some(parse_buffer_size("--channel-size", &size)?);
+                }
+                "--monitor-buffer" => {
+                    let size = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--monitor-buffer requires a size"))?;
+                    parsed.monitor_buffer = Some(parse_buffer_size("--monitor-buffer", &size)?);
+                }
+                "--max-monitors" => {
+                    let max = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--max-monitors requires a count"))?;
+                    parsed.max_monitors = Some(
+                        max.parse()
+                            .with_context(|| format!("Invalid --max-monitors: {}", max))?,
+                    );
+                }
+                "-c" | "--controller-ip" => {
+                    let ip = args
+                        .next()
+                        .ok_or_else(|| anyhow!("{} requires an address", arg))?;
+                    let ip = hostname_to_ip::parse_controller_ip(&ip)
+                        .with_context(|| format!("Invalid {}: {}", arg, ip))?;
+                    // The first is the primary controller, the rest extra ones
+                    if parsed.controller_ip.is_none() {
+                        parsed.controller_ip = Some(ip);
+                    } else {
+                        parsed.extra_controllers.push(ip);
+                    }
+                }
+                "--controller-host" => {
+                    let host = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--controller-host requires a hostname"))?;
+                    parsed.controller_host = Some(host);
+                }
+                "--controller-ip-file" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--controller-ip-file requires a path"))?;
+                    parsed.controller_ip_file = Some(PathBuf::from(path));
+                }
+                "--controller-net" => {
+                    let net = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--controller-net requires a prefix"))?;
+                    parsed.controller_net = Some(
+                        net.parse()
+                            .with_context(|| format!("Invalid --controller-net: {}", net))?,
+                    );
+                }
+                "--controller-port" => {
+                    let ports = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--controller-port requires a port"))?;
+                    parsed.controller_ports = parse_controller_ports(&ports)?;
+                }
+                "--monitor-ip" => {
+                    let ip = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--monitor-ip requires an address"))?;
+                    parsed.monitor_ip = Some(
+                        ip.parse()
+                            .with_context(|| format!("Invalid --monitor-ip: {}", ip))?,
+                    );
+                }
+                "--controller-srv" => {
+                    let service = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--controller-srv requires a record name"))?;
+                    parsed.controller_srv = Some(service);
+                }
+                "--dns-server" => {
+                    let server = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--dns-server requires an address"))?;
+                    parsed.dns_server = Some(
+                        server
+                            .parse()
+                            .with_context(|| format!("Invalid --dns-server: {}", server))?,
+                    );
+                }
+                "--grpc-workers" => {
+                    let workers = args
+                        .next()
+                        .ok_or_else(|| anyhow!("--grpc-workers requires a count"))?;
+                    let workers: usize = workers
+                        .parse()
+                        .with_context(|| format!("Invalid --grpc-workers: {}", workers))?;
+                    if workers == 0 {
+                        return Err(anyhow!("--grpc-workers must be at least 1"));
+                    }
+                    parsed.grpc_workers = Some(workers);
+                }
+                other => return Err(anyhow!("Unknown argument: {}", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Parses a subcommand and its positional arguments.
+    fn parse_command(name: &str, args: &mut impl Iterator<Item = String>) -> Result<Command> {
+        match name {
+            "list" => Ok(Command::List),
+            "dump" => Ok(Command::Dump),
+            "add" => Ok(Command::Add(Self::parse_session_key(name, args)?)),
+            "remove" => Ok(Command::Remove(Self::parse_session_key(name, args)?)),
+            other => Err(anyhow!("Unknown command: {}", other)),
+        }
+    }
+
+    /// Parses `<src>[:<src_port>] <dst> <port>`, where `*` or `0` as the port
+    /// means every port, `*` as the source means any source, and an omitted
+    /// source port means any source port.
+    pub(crate) fn parse_session_key(
+        command: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<SessionKey> {
+        let mut next = |what: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!("{} requires <src> <dst> <port>, missing {}", command, what))
+        };
+
+        let src = next("src")?;
+        let dst = next("dst")?;
+        let port = next("port")?;
+
+        let (src_ip, src_port) = if src == "*" {
+            (ANY_SRC_IP, ANY_SRC_PORT)
+        } else if src.contains(':') {
+            let addr = src
+                .parse::<SocketAddrV4>()
+                .with_context(|| format!("Invalid source address: {}", src))?;
+            (*addr.ip(), addr.port())
+        } else {
+            let ip = src
+                .parse::<Ipv4Addr>()
+                .with_context(|| format!("Invalid source IP: {}", src))?;
+            (ip, ANY_SRC_PORT)
+        };
+
+        Ok(SessionKey {
+            src_ip,
+            dest_ip: dst
+                .parse::<Ipv4Addr>()
+                .with_context(|| format!("Invalid destination IP: {}", dst))?,
+            dest_port: match port.as_str() {
+                "*" => WILDCARD_PORT,
+                port => port
+                    .parse()
+                    .with_context(|| format!("Invalid port: {}", port))?,
+            },
+            src_port,
+        })
+    }
+
+    /// Joins every relative path option to `dir`, so the paths keep their
+    /// meaning after `--daemonize` has moved the working directory to `/`.
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        for path in [
+            &mut self.grpc_uds,
+            &mut self.cgroup,
+            &mut self.controller_ip_file,
+            &mut self.pidfile,
+            &mut self.netns,
+            &mut self.seed_sessions,
+            &mut self.log_file,
+            &mut self.pin_prefix,
+            &mut self.audit_file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = dir.join(&*path);
+        }
+    }
+
+    /// Applies options that must take effect before `config.toml` is loaded,
+    /// since loading it may resolve `controller.host`.
+    pub fn apply_early(&self) {
+        if let Some(server) = self.dns_server {
+            hostname_to_ip::use_dns_server(server);
+        }
+    }
+
+    /// Applies `--controller-host` and `--controller-ip`, resolving the host.
+    ///
+    /// Follows the same precedence as `config.toml`, independent of argument
+    /// order: the host wins over the IP, with a warning when both are given.
+    /// Either one replaces whatever `controller.host` or `controller.ip`
+    /// selected, but cannot be combined with `controller.ip_file`.
+    pub fn apply_controller(&self, config: &mut Config) -> Result<()> {
+        config.controller_ip = match (&self.controller_host, self.controller_ip) {
+            (None, None) => return Ok(()),
+            _ if config.controller_ip_file.is_some() => {
+                return Err(anyhow!(
+                    "--controller-host and --controller-ip cannot be combined with controller.ip_file"
+                ));
+            }
+            (Some(host), ip) => {
+                if let Some(ip) = ip {
+                    warn!(
+                        "Both --controller-host and --controller-ip given, using {} over {}",
+                        host, ip
+                    );
+                }
+                hostname_to_ip::cached_hostname_to_ip(
+                    host,
+                    Duration::from_secs(config.controller_resolve_ttl_sec),
+                )
+                .with_context(|| format!("Failed to resolve --controller-host: {}", host))?
+            }
+            (None, Some(ip)) => ip,
+        };
+        config.controller_host = self.controller_host.clone();
+        Ok(())
+    }
+
+    /// Applies command-line overrides on top of the loaded configuration.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(hook) = self.attach_hook {
+            config.attach_hook = hook;
+        }
+        if let Some(mode) = self.xdp_mode {
+            config.xdp_mode = mode;
+        }
+        if self.strict_tcp {
+            config.strict_tcp = true;
+        }
+        if self.bind_src_mac {
+            config.bind_src_mac = true;
+        }
+        if self.require_link_up {
+            config.require_link_up = true;
+        }
+        if let Some(policy) = self.fragment_policy {
+            config.fragment_policy = policy;
+        }
+        for port in &self.always_allow_ports {
+            if !config.always_allow_ports.contains(port) {
+                config.always_allow_ports.push(*port);
+            }
+        }
+        if let Some(retries) = self.attach_retries {
+            config.attach_retries = retries;
+        }
+        if let Some(delay) = self.attach_retry_delay_ms {
+            config.attach_retry_delay_ms = delay;
+        }
+        if let Some(secs) = self.enforcement_grace_sec {
+            config.enforcement_grace_sec = secs;
+        }
+        if let Some(max) = self.max_conns_per_src {
+            config.max_conns_per_src = max;
+        }
+        if let Some(iface) = &self.redirect_to {
+            config.redirect_to = Some(iface.clone());
+        }
+        if let Some(path) = &self.cgroup {
+            config.cgroup = Some(path.clone());
+        }
+        if let Some(secs) = self.max_session_age_sec {
+            config.max_session_age_ns = secs.saturating_mul(1_000_000_000);
+        }
+        if let Some(size) = self.channel_size {
+            config.broadcast_channel_size = size;
+        }
+        if let Some(size) = self.monitor_buffer {
+            config.monitor_buffer_size = size;
+        }
+        if let Some(max) = self.max_monitors {
+            config.max_monitors = max;
+        }
+        if let Some(path) = &self.controller_ip_file {
+            config.controller_ip_file = Some(path.clone());
+        }
+        if let Some(net) = self.controller_net {
+            config.controller_net = Some(net);
+        }
+        for ip in &self.extra_controllers {
+            if !config.controller_extra_ips.contains(ip) {
+                config.controller_extra_ips.push(*ip);
+            }
+        }
+        if let Some((&port, extra)) = self.controller_ports.split_first() {
+            config.controller_port = port;
+            config.controller_extra_ports = extra.to_vec();
+        }
+        if let Some(ip) = self.monitor_ip {
+            config.grpc_monitor_ip = Some(ip);
+        }
+        if let Some(path) = &self.pin_prefix {
+            config.pin_prefix = path.clone();
+        }
+        if self.mount_bpffs {
+            config.mount_bpffs = true;
+        }
+        if self.force_recreate {
+            config.force_recreate = true;
+        }
+        if self.bpf_stats {
+            config.bpf_stats = true;
+        }
+        if let Some(secs) = self.watchdog_interval_sec {
+            config.bpf_watchdog_interval_sec = secs;
+        }
+        if let Some(count) = self.watchdog_failures {
+            config.bpf_watchdog_failures = count;
+        }
+        if let Some(workers) = self.grpc_workers {
+            config.grpc_workers = workers;
+        }
+        if let Some(secs) = self.shutdown_grace_sec {
+            config.grpc_shutdown_grace_sec = secs;
+        }
+        if let Some(secs) = self.grpc_keepalive_interval_sec {
+            config.grpc_keepalive_interval_sec = secs;
+        }
+        if let Some(path) = &self.audit_file {
+            config.grpc_audit_file = Some(path.clone());
+        }
+        if self.proxy_protocol {
+            config.grpc_proxy_protocol = true;
+        }
+        if self.read_only {
+            config.grpc_read_only = true;
+        }
+        if self.grpc_uds_allow_gid {
+            config.grpc_uds_allow_gid = true;
+        }
+        if let Some(rate) = self.submit_rate_limit {
+            config.grpc_submit_rate_limit = rate;
+        }
+    }
+}
+
+/// Parses a duration in seconds, given as `30` or `30s`.
+fn parse_secs(flag: &str, secs: &str) -> Result<u64> {
+    secs.strip_suffix('s')
+        .unwrap_or(secs)
+        .parse()
+        .with_context(|| format!("Invalid {}: {}", flag, secs))
+}
+
+/// Parses a duration in whole seconds, given as `90`, `90s`, `5m` or `1h`.
+fn parse_duration_secs(flag: &str, duration: &str) -> Result<u64> {
+    let (value, scale) = if let Some(minutes) = duration.strip_suffix('m') {
+        (minutes, 60)
+    } else if let Some(hours) = duration.strip_suffix('h') {
+        (hours, 3600)
+    } else {
+        (duration.strip_suffix('s').unwrap_or(duration), 1)
+    };
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|value| value.checked_mul(scale))
+        .ok_or_else(|| anyhow!("Invalid {}: {}", flag, duration))
+}
+
+/// Parses a comma-separated list of controller ports and inclusive ranges,
+/// such as `443,8443` or `8440-8443`.
+fn parse_controller_ports(ports: &str) -> Result<Vec<u16>> {
+    let parse_port = |port: &str| -> Result<u16> {
+        match port.trim().parse() {
+            Ok(0) => Err(anyhow!("Invalid --controller-port: port 0")),
+            Ok(port) => Ok(port),
+            Err(e) => Err(e).with_context(|| format!("Invalid --controller-port: {}", port)),
+        }
+    };
+
+    let max = MAX_EXTRA_CONTROLLER_PORTS + 1;
+    let mut parsed = Vec::new();
+    for item in ports.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (parse_port(first)?, parse_port(last)?),
+            None => {
+                let port = parse_port(item)?;
+                (port, port)
+            }
+        };
+        if first > last {
+            return Err(anyhow!("Invalid --controller-port range: {}", item));
+        }
+        // Checked per item so a wide range fails before it is expanded
+        if parsed.len() + usize::from(last - first) >= max {
+            return Err(anyhow!("--controller-port takes at most {} ports", max));
+        }
+        for port in first..=last {
+            if !parsed.contains(&port) {
+                parsed.push(port);
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses a log level name such as `debug`, case-insensitively.
+fn parse_log_level(level: &str) -> Result<Level> {
+    match level.to_ascii_lowercase().as_str() {
+        "trace" => Ok(Level::TRACE),
+        "debug" => Ok(Level::DEBUG),
+        "info" => Ok(Level::INFO),
+        "warn" => Ok(Level::WARN),
+        "error" => Ok(Level::ERROR),
+        _ => Err(anyhow!(
+            "Invalid --log-level: {} (expected trace, debug, info, warn or error)",
+            level
+        )),
+    }
+}
+
+/// Parses a channel size, which tokio requires to be non-zero.
+fn parse_buffer_size(flag: &str, size: &str) -> Result<usize> {
+    match size.parse() {
+        Ok(0) => Err(anyhow!("{} must be at least 1", flag)),
+        Ok(size) => Ok(size),
+        Err(e) => Err(e).with_context(|| format!("Invalid {}: {}", flag, size)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_args() {
+        let args = Args::parse_from(Vec::<String>::new()).unwrap();
+        assert_eq!(args, Args::default());
+    }
+
+    #[test]
+    fn test_grpc_uds() {
+        let args = Args::parse_from(["--grpc-uds", "/run/aegis/agent.sock"]).unwrap();
+        assert_eq!(args.grpc_uds, Some(PathBuf::from("/run/aegis/agent.sock")));
+    }
+
+    #[test]
+    fn test_grpc_uds_missing_path() {
+        assert!(Args::parse_from(["--grpc-uds"]).is_err());
+    }
+
+    #[test]
+    fn test_attach_hook_override() {
+        let args = Args::parse_from(["--attach-hook", "tc"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.attach_hook, AttachHook::Tc);
+        assert!(Args::parse_from(["--attach-hook", "ebpf"]).is_err());
+    }
+
+    #[test]
+    fn test_xdp_mode_override() {
+        let args = Args::parse_from(["--xdp-mode", "skb"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.xdp_mode, XdpMode::Skb);
+    }
+
+    #[test]
+    fn test_xdp_mode_invalid() {
+        assert!(Args::parse_from(["--xdp-mode", "turbo"]).is_err());
+    }
+
+    #[test]
+    fn test_require_link_up_flag() {
+        let args = Args::parse_from(["--require-link-up"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.require_link_up);
+    }
+
+    #[test]
+    fn test_strict_tcp_flag() {
+        let args = Args::parse_from(["--strict-tcp"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.strict_tcp);
+    }
+
+    #[test]
+    fn test_bind_src_mac_flag() {
+        let args = Args::parse_from(["--bind-src-mac"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.bind_src_mac);
+    }
+
+    #[test]
+    fn test_fragment_policy_override() {
+        let args = Args::parse_from(["--fragment-policy", "track"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.fragment_policy, FragmentPolicy::Track);
+    }
+
+    #[test]
+    fn test_fragment_policy_invalid() {
+        assert!(Args::parse_from(["--fragment-policy", "pass"]).is_err());
+    }
+
+    #[test]
+    fn test_always_allow_port_repeatable() {
+        let args = Args::parse_from(["--always-allow-port", "22", "--always-allow-port", "50001"])
+            .unwrap();
+        let mut config = Config {
+            always_allow_ports: vec![22],
+            ..Default::default()
+        };
+        args.apply(&mut config);
+        assert_eq!(config.always_allow_ports, vec![22, 50001]);
+    }
+
+    #[test]
+    fn test_always_allow_port_invalid() {
+        assert!(Args::parse_from(["--always-allow-port", "70000"]).is_err());
+        assert!(Args::parse_from(["--always-allow-port"]).is_err());
+    }
+
+    #[test]
+    fn test_controller_ip_override() {
+        let args = Args::parse_from(["--controller-ip", "10.0.0.7"]).unwrap();
+        let mut config = Config::default();
+        args.apply_controller(&mut config).unwrap();
+        assert_eq!(config.controller_ip, Ipv4Addr::new(10, 0, 0, 7));
+
+        assert!(Args::parse_from(["--controller-ip", "controller"]).is_err());
+        assert!(Args::parse_from(["--controller-ip", "::1"]).is_err());
+        assert!(Args::parse_from(["--controller-ip"]).is_err());
+        assert!(Args::parse_from(["--controller-host"]).is_err());
+    }
+
+    #[test]
+    fn test_controller_host_wins_in_either_order() {
+        for argv in [
+            [
+                "--controller-ip",
+                "1.1.1.1",
+                "--controller-host",
+                "localhost",
+            ],
+            [
+                "--controller-host",
+                "localhost",
+                "--controller-ip",
+                "1.1.1.1",
+            ],
+        ] {
+            let args = Args::parse_from(argv).unwrap();
+            let mut config = Config::default();
+            args.apply_controller(&mut config)
+                .expect("Failed to resolve localhost");
+            assert_eq!(config.controller_ip, Ipv4Addr::LOCALHOST, "{:?}", argv);
+            assert_eq!(config.controller_host.as_deref(), Some("localhost"));
+        }
+    }
+
+    #[test]
+    fn test_controller_override_rejects_ip_file() {
+        let args = Args::parse_from(["--controller-ip", "10.0.0.7"]).unwrap();
+        let mut config = Config {
+            controller_ip_file: Some(PathBuf::from("/etc/aegis/controller-ip")),
+            ..Config::default()
+        };
+        assert!(args.apply_controller(&mut config).is_err());
+    }
+
+    #[test]
+    fn test_controller_ip_file_override() {
+        let args = Args::parse_from(["--controller-ip-file", "/etc/aegis/controller-ip"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(
+            config.controller_ip_file,
+            Some(PathBuf::from("/etc/aegis/controller-ip"))
+        );
+        assert!(Args::parse_from(["--controller-ip-file"]).is_err());
+    }
+
+    #[test]
+    fn test_controller_net() {
+        let args = Args::parse_from(["--controller-net", "10.0.0.0/28"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.controller_net, Some("10.0.0.0/28".parse().unwrap()));
+
+        assert!(Args::parse_from(["--controller-net", "10.0.0.0"]).is_err());
+        assert!(Args::parse_from(["--controller-net"]).is_err());
+    }
+
+    #[test]
+    fn test_controller_ports() {
+        let args = Args::parse_from(["--controller-port", "443,8443"]).unwrap();
+        let mut config = Config {
+            controller_extra_ports: vec![9443],
+            ..Config::default()
+        };
+        args.apply(&mut config);
+        assert_eq!(config.controller_port, 443);
+        assert_eq!(config.controller_extra_ports, vec![8443]);
+
+        // A single port is the common case and clears the extra ones
+        let args = Args::parse_from(["--controller-port", "8443"]).unwrap();
+        args.apply(&mut config);
+        assert_eq!(config.controller_port, 8443);
+        assert!(config.controller_extra_ports.is_empty());
+
+        let args = Args::parse_from(["--controller-port", "443,8440-8442"]).unwrap();
+        assert_eq!(args.controller_ports, vec![443, 8440, 8441, 8442]);
+
+        assert!(Args::parse_from(["--controller-port", "443,0"]).is_err());
+        assert!(Args::parse_from(["--controller-port", "8443-8440"]).is_err());
+        assert!(Args::parse_from(["--controller-port", "1-65535"]).is_err());
+        assert!(Args::parse_from(["--controller-port", "443,https"]).is_err());
+        assert!(Args::parse_from(["--controller-port", "1,2,3,4,5,6,7,8,9,10"]).is_err());
+        assert!(Args::parse_from(["--controller-port"]).is_err());
+    }
+
+    #[test]
+    fn test_repeated_controller_ip() {
+        let args = Args::parse_from([
+            "-c",
+            "10.0.0.5",
+            "-c",
+            "10.0.1.5",
+            "--controller-ip",
+            "10.0.2.5",
+        ])
+        .unwrap();
+        let mut config = Config {
+            controller_extra_ips: vec![Ipv4Addr::new(10, 0, 2, 5)],
+            ..Config::default()
+        };
+        args.apply(&mut config);
+        args.apply_controller(&mut config).unwrap();
+        assert_eq!(config.controller_ip, Ipv4Addr::new(10, 0, 0, 5));
+        assert_eq!(
+            config.controller_extra_ips,
+            vec![Ipv4Addr::new(10, 0, 2, 5), Ipv4Addr::new(10, 0, 1, 5)]
+        );
+
+        assert!(Args::parse_from(["-c", "10.0.0.5", "-c", "controller"]).is_err());
+        assert!(Args::parse_from(["-c"]).is_err());
+    }
+
+    #[test]
+    fn test_controller_srv() {
+        let args = Args::parse_from(["--controller-srv", "_aegis._tcp.example.com"]).unwrap();
+        assert_eq!(
+            args.controller_srv.as_deref(),
+            Some("_aegis._tcp.example.com")
+        );
+        assert!(Args::parse_from(["--controller-srv"]).is_err());
+    }
+
+    #[test]
+    fn test_attach_retry_overrides() {
+        let args =
+            Args::parse_from(["--attach-retries", "8", "--attach-retry-delay", "500"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.attach_retries, 8);
+        assert_eq!(config.attach_retry_delay_ms, 500);
+        assert!(Args::parse_from(["--attach-retries", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_enforcement_grace() {
+        let args = Args::parse_from(["--enforcement-grace", "30s"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.enforcement_grace_sec, 30);
+
+        let args = Args::parse_from(["--enforcement-grace", "45"]).unwrap();
+        assert_eq!(args.enforcement_grace_sec, Some(45));
+
+        let args = Args::parse_from(["--startup-grace", "2m"]).unwrap();
+        assert_eq!(args.enforcement_grace_sec, Some(120));
+        let args = Args::parse_from(["--startup-grace", "1h"]).unwrap();
+        assert_eq!(args.enforcement_grace_sec, Some(3600));
+
+        assert!(Args::parse_from(["--enforcement-grace", "1d"]).is_err());
+        assert!(Args::parse_from(["--startup-grace", "m"]).is_err());
+        assert!(Args::parse_from(["--enforcement-grace"]).is_err());
+    }
+
+    #[test]
+    fn test_max_conns_per_src() {
+        let args = Args::parse_from(["--max-conns-per-src", "32"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.max_conns_per_src, 32);
+
+        assert!(Args::parse_from(["--max-conns-per-src", "-1"]).is_err());
+        assert!(Args::parse_from(["--max-conns-per-src"]).is_err());
+    }
+
+    #[test]
+    fn test_redirect_to() {
+        let args = Args::parse_from(["--redirect-to", "ids0"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.redirect_to.as_deref(), Some("ids0"));
+
+        assert!(Args::parse_from(["--redirect-to"]).is_err());
+    }
+
+    #[test]
+    fn test_cgroup() {
+        let args = Args::parse_from(["--cgroup", "/sys/fs/cgroup/aegis"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.cgroup, Some(PathBuf::from("/sys/fs/cgroup/aegis")));
+
+        assert!(Args::parse_from(["--cgroup"]).is_err());
+    }
+
+    #[test]
+    fn test_audit_file() {
+        let args = Args::parse_from(["--audit-file", "/var/log/aegis/audit.jsonl"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(
+            config.grpc_audit_file,
+            Some(PathBuf::from("/var/log/aegis/audit.jsonl"))
+        );
+
+        assert!(Args::parse_from(["--audit-file"]).is_err());
+    }
+
+    #[test]
+    fn test_proxy_protocol() {
+        let args = Args::parse_from(["--proxy-protocol"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.grpc_proxy_protocol);
+
+        let mut config = Config::default();
+        Args::parse_from(Vec::<String>::new())
+            .unwrap()
+            .apply(&mut config);
+        assert!(!config.grpc_proxy_protocol);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let args = Args::parse_from(["--read-only"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.grpc_read_only);
+    }
+
+    #[test]
+    fn test_grpc_uds_allow_gid() {
+        let args = Args::parse_from(["--grpc-uds-allow-gid"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.grpc_uds_allow_gid);
+    }
+
+    #[test]
+    fn test_submit_rate_limit() {
+        let args = Args::parse_from(["--submit-rate-limit", "50"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_submit_rate_limit, 50);
+
+        assert!(Args::parse_from(["--submit-rate-limit"]).is_err());
+        assert!(Args::parse_from(["--submit-rate-limit", "fast"]).is_err());
+    }
+
+    #[test]
+    fn test_shutdown_grace() {
+        let args = Args::parse_from(["--shutdown-grace", "10s"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_shutdown_grace_sec, 10);
+        assert!(Args::parse_from(["--shutdown-grace", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_grpc_keepalive_interval() {
+        let args = Args::parse_from(["--grpc-keepalive-interval", "2m"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_keepalive_interval_sec, 120);
+
+        let args = Args::parse_from(["--grpc-keepalive-interval", "0"]).unwrap();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_keepalive_interval_sec, 0);
+        assert!(Args::parse_from(["--grpc-keepalive-interval"]).is_err());
+        assert!(Args::parse_from(["--grpc-keepalive-interval", "10h"]).is_err());
+    }
+
+    #[test]
+    fn test_max_session_age() {
+        let args = Args::parse_from(["--max-session-age", "3600"]).unwrap();
+        assert_eq!(args.max_session_age_sec, Some(3600));
+
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.max_session_age_ns, 3_600_000_000_000);
+
+        assert!(Args::parse_from(["--max-session-age", "1h"]).is_err());
+        assert!(Args::parse_from(["--max-session-age"]).is_err());
+    }
+
+    #[test]
+    fn test_channel_sizes() {
+        let args = Args::parse_from(["--channel-size", "64", "--monitor-buffer", "32"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.broadcast_channel_size, 64);
+        assert_eq!(config.monitor_buffer_size, 32);
+
+        assert!(Args::parse_from(["--channel-size", "0"]).is_err());
+        assert!(Args::parse_from(["--monitor-buffer", "many"]).is_err());
+        assert!(Args::parse_from(["--monitor-buffer"]).is_err());
+    }
+
+    #[test]
+    fn test_max_monitors() {
+        let args = Args::parse_from(["--max-monitors", "8"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.max_monitors, 8);
+
+        let args = Args::parse_from(["--max-monitors", "0"]).unwrap();
+        args.apply(&mut config);
+        assert_eq!(config.max_monitors, 0);
+        assert!(Args::parse_from(["--max-monitors", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_dns_server() {
+        let args = Args::parse_from(["--dns-server", "10.0.0.53:53"]).unwrap();
+        assert_eq!(
+            args.dns_server,
+            Some(SocketAddr::from(([10, 0, 0, 53], 53)))
+        );
+        assert!(Args::parse_from(["--dns-server", "10.0.0.53"]).is_err());
+    }
+
+    #[test]
+    fn test_monitor_ip_override() {
+        let args = Args::parse_from(["--monitor-ip", "10.0.0.9"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_monitor_ip, Some(Ipv4Addr::new(10, 0, 0, 9)));
+        assert!(Args::parse_from(["--monitor-ip", "10.0.0"]).is_err());
+    }
+
+    #[test]
+    fn test_pair_from_cert_flag() {
+        let args = Args::parse_from(["--pair-from-cert"]).unwrap();
+        assert!(args.pair_from_cert);
+    }
+
+    #[test]
+    fn test_daemonize_with_pidfile() {
+        let args = Args::parse_from(["--daemonize", "--pidfile", "/run/aegis-agent.pid"]).unwrap();
+        assert!(args.daemonize);
+        assert_eq!(args.pidfile, Some(PathBuf::from("/run/aegis-agent.pid")));
+        assert!(Args::parse_from(["--pidfile"]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let mut args = Args::parse_from([
+            "--pidfile",
+            "/run/aegis-agent.pid",
+            "--log-file",
+            "logs/agent.log",
+            "--seed-sessions",
+            "sessions.csv",
+        ])
+        .unwrap();
+        args.resolve_paths(Path::new("/opt/aegis"));
+        assert_eq!(args.pidfile, Some(PathBuf::from("/run/aegis-agent.pid")));
+        assert_eq!(
+            args.log_file,
+            Some(PathBuf::from("/opt/aegis/logs/agent.log"))
+        );
+        assert_eq!(
+            args.seed_sessions,
+            Some(PathBuf::from("/opt/aegis/sessions.csv"))
+        );
+        assert_eq!(args.grpc_uds, None);
+    }
+
+    #[test]
+    fn test_netns() {
+        let args = Args::parse_from(["--netns", "/var/run/netns/tenant1"]).unwrap();
+        assert_eq!(args.netns, Some(PathBuf::from("/var/run/netns/tenant1")));
+        assert!(Args::parse_from(["--netns"]).is_err());
+    }
+
+    #[test]
+    fn test_seed_sessions() {
+        let args = Args::parse_from(["--seed-sessions", "/etc/aegis/seed.csv"]).unwrap();
+        assert_eq!(
+            args.seed_sessions,
+            Some(PathBuf::from("/etc/aegis/seed.csv"))
+        );
+        assert!(Args::parse_from(["--seed-sessions"]).is_err());
+    }
+
+    #[test]
+    fn test_log_file() {
+        let args = Args::parse_from(["--log-file", "/var/log/aegis/agent.log"]).unwrap();
+        assert_eq!(
+            args.log_file,
+            Some(PathBuf::from("/var/log/aegis/agent.log"))
+        );
+        assert!(!args.log_stderr);
+
+        let args =
+            Args::parse_from(["--log-file", "/var/log/aegis/agent.log", "--log-stderr"]).unwrap();
+        assert!(args.log_stderr);
+
+        assert!(Args::parse_from(["--log-file"]).is_err());
+    }
+
+    #[test]
+    fn test_log_level() {
+        let args = Args::parse_from(["--log-level", "debug"]).unwrap();
+        assert_eq!(args.log_level, Some(Level::DEBUG));
+        let args = Args::parse_from(["--log-level", "WARN"]).unwrap();
+        assert_eq!(args.log_level, Some(Level::WARN));
+
+        assert!(Args::parse_from(["--log-level", "verbose"]).is_err());
+        assert!(Args::parse_from(["--log-level", "3"]).is_err());
+        assert!(Args::parse_from(["--log-level"]).is_err());
+    }
+
+    #[test]
+    fn test_grpc_workers_override() {
+        let args = Args::parse_from(["--grpc-workers", "4"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.grpc_workers, 4);
+
+        assert!(Args::parse_from(["--grpc-workers"]).is_err());
+        assert!(Args::parse_from(["--grpc-workers", "0"]).is_err());
+        assert!(Args::parse_from(["--grpc-workers", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_pin_prefix_override() {
+        let args = Args::parse_from(["--pin-prefix", "/sys/fs/bpf/aegis-b"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.pin_prefix, PathBuf::from("/sys/fs/bpf/aegis-b"));
+        assert!(Args::parse_from(["--pin-prefix"]).is_err());
+
+        let args = Args::parse_from(["--pin-dir", "/sys/fs/bpf/aegis-c"]).unwrap();
+        assert_eq!(args.pin_prefix, Some(PathBuf::from("/sys/fs/bpf/aegis-c")));
+    }
+
+    #[test]
+    fn test_mount_bpffs_flag() {
+        let args = Args::parse_from(["--mount-bpffs"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.mount_bpffs);
+    }
+
+    #[test]
+    fn test_force_recreate_flag() {
+        let args = Args::parse_from(["--force-recreate"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.force_recreate);
+    }
+
+    #[test]
+    fn test_bpf_stats_flag() {
+        let args = Args::parse_from(["--bpf-stats"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert!(config.bpf_stats);
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let args =
+            Args::parse_from(["--watchdog-interval", "2m", "--watchdog-failures", "5"]).unwrap();
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.bpf_watchdog_interval_sec, 120);
+        assert_eq!(config.bpf_watchdog_failures, 5);
+
+        assert!(Args::parse_from(["--watchdog-failures", "0"]).is_err());
+        assert!(Args::parse_from(["--watchdog-failures", "many"]).is_err());
+        assert!(Args::parse_from(["--watchdog-interval"]).is_err());
+    }
+
+    #[test]
+    fn test_version_flag() {
+        let args = Args::parse_from(["--version"]).unwrap();
+        assert!(args.version);
+    }
+
+    #[test]
+    fn test_list_command() {
+        let args = Args::parse_from(["list"]).unwrap();
+        assert_eq!(args.command, Some(Command::List));
+    }
+
+    #[test]
+    fn test_dump_command() {
+        let args = Args::parse_from(["dump", "--pin-prefix", "/sys/fs/bpf/aegis-b"]).unwrap();
+        assert_eq!(args.command, Some(Command::Dump));
+        assert_eq!(args.pin_prefix, Some(PathBuf::from("/sys/fs/bpf/aegis-b")));
+    }
+
+    #[test]
+    fn test_add_command() {
+        let args = Args::parse_from(["add", "10.0.0.1", "172.21.0.10", "8080"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Add(SessionKey {
+                src_ip: Ipv4Addr::new(10, 0, 0, 1),
+                dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                dest_port: 8080,
+                src_port: ANY_SRC_PORT,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_add_command_with_src_port() {
+        let args = Args::parse_from(["add", "10.0.0.1:1021", "172.21.0.10", "2049"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Add(SessionKey {
+                src_ip: Ipv4Addr::new(10, 0, 0, 1),
+                dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                dest_port: 2049,
+                src_port: 1021,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_add_command_any_source() {
+        let args = Args::parse_from(["add", "*", "172.21.0.10", "443"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Add(SessionKey {
+                src_ip: ANY_SRC_IP,
+                dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                dest_port: 443,
+                src_port: ANY_SRC_PORT,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_remove_wildcard_command_with_flags() {
+        let args = Args::parse_from([
+            "remove",
+            "10.0.0.1",
+            "172.21.0.10",
+            "*",
+            "--pin-prefix",
+            "/sys/fs/bpf/aegis-b",
+        ])
+        .unwrap();
+        match args.command {
+            Some(Command::Remove(key)) => assert!(key.is_wildcard()),
+            other => panic!("Expected remove command, got {:?}", other),
+        }
+        assert_eq!(args.pin_prefix, Some(PathBuf::from("/sys/fs/bpf/aegis-b")));
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        assert!(Args::parse_from(["flush"]).is_err());
+        assert!(Args::parse_from(["add", "10.0.0.1", "172.21.0.10"]).is_err());
+        assert!(Args::parse_from(["add", "10.0.0.1", "not-an-ip", "80"]).is_err());
+        assert!(Args::parse_from(["remove", "10.0.0.1", "172.21.0.10", "70000"]).is_err());
+        assert!(Args::parse_from(["add", "10.0.0.1:70000", "172.21.0.10", "80"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_argument() {
+        assert!(Args::parse_from(["--bogus"]).is_err());
+    }
+}
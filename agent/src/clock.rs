@@ -0,0 +1,96 @@
+use nix::time::{ClockId, clock_gettime};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::error;
+
+/// Source of the timestamps stored in and compared against session values.
+///
+/// Session expiry only depends on the difference between `now_ns()` and the
+/// timestamps in the map, so a controllable implementation makes cleanup and
+/// time-left logic testable without sleeping.
+pub trait Clock: Send + Sync {
+    /// Returns the current time in nanoseconds.
+    fn now_ns(&self) -> u64;
+}
+
+/// Kernel monotonic clock, the one the XDP program reads.
+///
+/// The XDP program refreshes `last_seen_ns` with `bpf_ktime_get_ns()`, which
+/// reads `CLOCK_MONOTONIC`, so user space has to use the same source or the
+/// cleanup and time-left math will drift.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    /// Uses a fallback value if the system call fails to prevent panic.
+    fn now_ns(&self) -> u64 {
+        match clock_gettime(ClockId::CLOCK_MONOTONIC) {
+            Ok(now) => {
+                // Safely compute nanoseconds with overflow protection
+                now.tv_sec()
+                    .saturating_mul(1_000_000_000)
+                    .saturating_add(now.tv_nsec()) as u64
+            }
+            Err(e) => {
+                error!("Failed to get monotonic time: {}, using fallback", e);
+                // Return a fallback value to prevent panic
+                0
+            }
+        }
+    }
+}
+
+/// Clock that only moves when told to, for deterministic tests.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_ns: AtomicU64,
+}
+
+impl MockClock {
+    /// Creates a clock reading `now_ns`.
+    pub fn new(now_ns: u64) -> Self {
+        Self {
+            now_ns: AtomicU64::new(now_ns),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now_ns: u64) {
+        self.now_ns.store(now_ns, Ordering::Relaxed);
+    }
+
+    /// Moves the clock forward by `delta_ns`.
+    pub fn advance(&self, delta_ns: u64) {
+        self.now_ns.fetch_add(delta_ns, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ns(&self) -> u64 {
+        self.now_ns.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_clock_is_monotonic() {
+        let first = MonotonicClock.now_ns();
+        let second = MonotonicClock.now_ns();
+        assert!(first > 0);
+        assert!(second >= first);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1_000);
+        assert_eq!(clock.now_ns(), 1_000);
+
+        clock.advance(500);
+        assert_eq!(clock.now_ns(), 1_500);
+
+        clock.set(42);
+        assert_eq!(clock.now_ns(), 42);
+    }
+}
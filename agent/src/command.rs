@@ -0,0 +1,144 @@
+use anyhow::{Result, bail};
+use std::fmt::Write;
+
+use crate::{
+    bpf::{PinnedSessions, SessionEntry},
+    cli::Command,
+    config::Config,
+};
+
+/// Runs a local subcommand against the pinned session map and prints the result.
+pub fn run(command: &Command, config: &Config) -> Result<()> {
+    let sessions = PinnedSessions::open(&config.pin_prefix)?;
+
+    match command {
+        Command::List => {
+            let rules = sessions.list_rules(config.rule_timeout_ns)?;
+            if rules.is_empty() {
+                println!("No active sessions");
+            }
+            for (key, time_left) in rules {
+                if time_left < 0 {
+                    println!("{}  expired {}s ago", key, -time_left);
+                } else {
+                    println!("{}  {}s left", key, time_left);
+                }
+            }
+        }
+        Command::Dump => {
+            let mut entries = sessions.dump_rules(config.rule_timeout_ns)?;
+            if entries.is_empty() {
+                println!("No active sessions");
+            } else {
+                entries.sort_by_key(|entry| {
+                    (entry.key.src_ip, entry.key.dest_ip, entry.key.dest_port)
+                });
+                print!("{}", format_table(&entries));
+            }
+        }
+        Command::Add(key) => {
+            if sessions.add_rule(*key)? {
+                println!("Refreshed {}", key);
+            } else {
+                println!("Added {}", key);
+            }
+        }
+        Command::Remove(key) => {
+            if !sessions.remove_rule(*key)? {
+                bail!("No session {}", key);
+            }
+            println!("Removed {}", key);
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats sessions as an aligned table with a header row.
+fn format_table(entries: &[SessionEntry]) -> String {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            let key = entry.key;
+            let src = if key.is_any_source() {
+                "*".to_string()
+            } else if key.has_src_port() {
+                format!("{}:{}", key.src_ip, key.src_port)
+            } else {
+                key.src_ip.to_string()
+            };
+            let port = if key.is_wildcard() {
+                "*".to_string()
+            } else {
+                key.dest_port.to_string()
+            };
+            [
+                src,
+                key.dest_ip.to_string(),
+                port,
+                format!("{}s", entry.age_sec),
+                format!("{}s", entry.time_left_sec),
+            ]
+        })
+        .collect();
+
+    let header = ["SRC", "DST", "PORT", "AGE", "TTL"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(header.map(String::from)).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let _ = writeln!(table, "{}", line.trim_end());
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SessionKey;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_format_table() {
+        let entries = [
+            SessionEntry {
+                key: SessionKey {
+                    src_ip: Ipv4Addr::new(10, 0, 0, 1),
+                    dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                    dest_port: 8080,
+                    src_port: 0,
+                },
+                age_sec: 125,
+                time_left_sec: 42,
+            },
+            SessionEntry {
+                key: SessionKey {
+                    src_ip: Ipv4Addr::new(10, 0, 0, 2),
+                    dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                    dest_port: 0,
+                    src_port: 1021,
+                },
+                age_sec: 3,
+                time_left_sec: -5,
+            },
+        ];
+
+        assert_eq!(
+            format_table(&entries),
+            "SRC            DST          PORT  AGE   TTL\n\
+             10.0.0.1       172.21.0.10  8080  125s  42s\n\
+             10.0.0.2:1021  172.21.0.10  *     3s    -5s\n"
+        );
+    }
+}
@@ -1,19 +1,171 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use serde::Deserialize;
+use std::fmt;
+use std::fs::File;
 use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use tracing::{debug, warn};
 
-use crate::hostname_to_ip::hostname_to_ip;
+use crate::controller_ip_file::read_controller_ip;
+use crate::hostname_to_ip::{cached_hostname_to_ip, parse_controller_ip};
+use crate::types::Ipv4Net;
 
 /// Default path for the TOML configuration file.
 pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
 
+/// Capacity of the XDP program's `controllers` map.
+pub const MAX_EXTRA_CONTROLLERS: usize = 16;
+
+/// Capacity of the egress filter's `dns_resolvers` map.
+pub const MAX_DNS_RESOLVERS: usize = 16;
+
+/// Largest `grpc.keepalive_interval_sec`; the kernel rejects a longer
+/// `TCP_KEEPIDLE` or `TCP_KEEPINTVL` with `EINVAL`.
+pub const MAX_KEEPALIVE_INTERVAL_SEC: u64 = 32_767;
+
+/// Capacity of the XDP program's `CONTROLLER_EXTRA_PORTS` (mirrors
+/// `MAX_EXTRA_CONTROLLER_PORTS` in `aegis.h`).
+pub const MAX_EXTRA_CONTROLLER_PORTS: usize = 8;
+
+/// How the XDP program is attached to the interface.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XdpMode {
+    /// Try native mode, fall back to SKB mode if the driver rejects it
+    #[default]
+    Auto,
+    /// Driver (native) mode
+    Native,
+    /// Generic SKB mode, works on any interface
+    Skb,
+    /// Hardware offload to the NIC
+    Offload,
+}
+
+impl FromStr for XdpMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "native" => Ok(Self::Native),
+            "skb" => Ok(Self::Skb),
+            "offload" => Ok(Self::Offload),
+            other => Err(anyhow!(
+                "Invalid XDP mode '{}', expected native, skb, offload or auto",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for XdpMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Auto => "auto",
+            Self::Native => "native",
+            Self::Skb => "skb",
+            Self::Offload => "offload",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Kernel hook the packet filter is attached to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachHook {
+    /// XDP, see [`XdpMode`]
+    #[default]
+    Xdp,
+    /// tc clsact ingress, for interfaces where XDP is unavailable
+    Tc,
+}
+
+impl FromStr for AttachHook {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "xdp" => Ok(Self::Xdp),
+            "tc" => Ok(Self::Tc),
+            other => Err(anyhow!(
+                "Invalid attach hook '{}', expected xdp or tc",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AttachHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Xdp => "xdp",
+            Self::Tc => "tc",
+        })
+    }
+}
+
+/// How the XDP program treats IPv4 fragments after the first.
+///
+/// The discriminants are the `FRAGMENT_POLICY` values in `aegis.bpf.c`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum FragmentPolicy {
+    /// Drop every non-first fragment
+    #[default]
+    Drop = 0,
+    /// Pass non-first fragments whose first fragment was passed
+    Track = 1,
+}
+
+impl FromStr for FragmentPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "track" => Ok(Self::Track),
+            other => Err(anyhow!(
+                "Invalid fragment policy '{}', expected drop or track",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FragmentPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Drop => "drop",
+            Self::Track => "track",
+        };
+        f.write_str(name)
+    }
+}
+
 // TOML file structure
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 struct TomlNetwork {
     iface: String,
+    attach_hook: AttachHook,
+    xdp_mode: XdpMode,
+    strict_tcp: bool,
+    bind_src_mac: bool,
+    fragment_policy: FragmentPolicy,
+    always_allow_ports: Vec<u16>,
+    attach_retries: u32,
+    attach_retry_delay_ms: u64,
+    enforcement_grace_sec: u64,
+    require_link_up: bool,
+    max_conns_per_src: u32,
+    redirect_to: String,
+    cgroup: String,
+    dns_resolvers: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,7 +173,12 @@ struct TomlNetwork {
 struct TomlController {
     ip: String,
     host: String,
+    ip_file: String,
+    net: String,
+    extra_ips: Vec<String>,
     port: u16,
+    extra_ports: Vec<u16>,
+    resolve_ttl_sec: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,14 +194,43 @@ struct TomlCerts {
 struct TomlSession {
     lazy_update_timeout_ns: u64,
     rule_timeout_ns: u64,
+    max_session_age_ns: u64,
     cleanup_interval_sec: u64,
     broadcast_channel_size: usize,
+    monitor_buffer_size: usize,
+    max_monitors: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct TomlBpf {
+    pin_prefix: String,
+    batch_delete: bool,
+    mount_bpffs: bool,
+    force_recreate: bool,
+    stats: bool,
+    stats_interval_sec: u64,
+    watchdog_interval_sec: u64,
+    watchdog_failures: u32,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 struct TomlGrpc {
     port: u16,
+    workers: usize,
+    bind_attempts: u32,
+    shutdown_grace_sec: u64,
+    keepalive_interval_sec: u64,
+    keepalive_timeout_sec: u64,
+    audit_file: String,
+    proxy_protocol: bool,
+    trusted_proxies: Vec<String>,
+    read_only: bool,
+    uds_allow_gid: bool,
+    submit_rate_limit: u32,
+    monitor_ip: String,
+    monitor_cn: String,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -54,13 +240,47 @@ struct TomlFile {
     controller: TomlController,
     certs: TomlCerts,
     session: TomlSession,
+    bpf: TomlBpf,
     grpc: TomlGrpc,
 }
 
+impl TomlFile {
+    /// Joins every relative file path to `dir`; empty paths stay unset.
+    fn resolve_paths(&mut self, dir: &Path) {
+        for path in [
+            &mut self.certs.cert_file,
+            &mut self.certs.key_file,
+            &mut self.certs.ca_file,
+            &mut self.controller.ip_file,
+            &mut self.grpc.audit_file,
+            &mut self.network.cgroup,
+            &mut self.bpf.pin_prefix,
+        ] {
+            if !path.is_empty() {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
 impl Default for TomlNetwork {
     fn default() -> Self {
         Self {
             iface: "eth0".to_string(),
+            attach_hook: AttachHook::Xdp,
+            xdp_mode: XdpMode::Auto,
+            strict_tcp: false,
+            bind_src_mac: false,
+            fragment_policy: FragmentPolicy::Drop,
+            always_allow_ports: Vec::new(),
+            attach_retries: 0,
+            attach_retry_delay_ms: 1000,
+            enforcement_grace_sec: 0,
+            require_link_up: false,
+            max_conns_per_src: 0,
+            redirect_to: String::new(),
+            cgroup: String::new(),
+            dns_resolvers: Vec::new(),
         }
     }
 }
@@ -70,7 +290,12 @@ impl Default for TomlController {
         Self {
             ip: "172.21.0.5".to_string(),
             host: String::new(),
+            ip_file: String::new(),
+            net: String::new(),
+            extra_ips: Vec::new(),
+            extra_ports: Vec::new(),
             port: 443,
+            resolve_ttl_sec: 300,
         }
     }
 }
@@ -90,15 +315,48 @@ impl Default for TomlSession {
         Self {
             lazy_update_timeout_ns: 1_000_000_000,
             rule_timeout_ns: 60_000_000_000,
+            max_session_age_ns: 0,
             cleanup_interval_sec: 30,
             broadcast_channel_size: 16,
+            monitor_buffer_size: 4,
+            max_monitors: 64,
+        }
+    }
+}
+
+impl Default for TomlBpf {
+    fn default() -> Self {
+        Self {
+            pin_prefix: "/sys/fs/bpf/aegis".to_string(),
+            batch_delete: true,
+            mount_bpffs: false,
+            force_recreate: false,
+            stats: false,
+            stats_interval_sec: 60,
+            watchdog_interval_sec: 30,
+            watchdog_failures: 3,
         }
     }
 }
 
 impl Default for TomlGrpc {
     fn default() -> Self {
-        Self { port: 50001 }
+        Self {
+            port: 50001,
+            workers: 1,
+            bind_attempts: 5,
+            shutdown_grace_sec: 5,
+            keepalive_interval_sec: 60,
+            keepalive_timeout_sec: 20,
+            audit_file: String::new(),
+            proxy_protocol: false,
+            trusted_proxies: Vec::new(),
+            read_only: false,
+            uds_allow_gid: false,
+            submit_rate_limit: 0,
+            monitor_ip: String::new(),
+            monitor_cn: String::new(),
+        }
     }
 }
 
@@ -119,10 +377,55 @@ impl Default for TomlGrpc {
 pub struct Config {
     /// Network interface to attach XDP program to
     pub iface_name: String,
+    /// Hook the filter is attached to
+    pub attach_hook: AttachHook,
+    /// XDP attach mode, only used with [`AttachHook::Xdp`]
+    pub xdp_mode: XdpMode,
+    /// Drop TCP packets with illegal flag combinations
+    pub strict_tcp: bool,
+    /// Bind each session to the source MAC of its first packet
+    pub bind_src_mac: bool,
+    /// Handling of non-first IPv4 fragments
+    pub fragment_policy: FragmentPolicy,
+    /// Destination ports reachable regardless of session state
+    pub always_allow_ports: Vec<u16>,
+    /// Extra attempts to find the interface and attach XDP before giving up
+    pub attach_retries: u32,
+    /// Delay before the first attach retry in milliseconds, doubled each time
+    pub attach_retry_delay_ms: u64,
+    /// Seconds after attach during which rejected traffic still passes, 0 to
+    /// enforce immediately
+    pub enforcement_grace_sec: u64,
+    /// Refuse to attach while the interface has no link, instead of warning
+    pub require_link_up: bool,
+    /// Concurrent TCP connections one source may open to one destination,
+    /// 0 for no limit
+    pub max_conns_per_src: u32,
+    /// Interface that session traffic is redirected to instead of passed up
+    /// the stack
+    pub redirect_to: Option<String>,
+    /// cgroup v2 directory whose outbound connections are filtered
+    pub cgroup: Option<PathBuf>,
+    /// DNS servers the cgroup may reach without a session, empty to use the
+    /// nameservers in /etc/resolv.conf
+    pub dns_resolvers: Vec<Ipv4Addr>,
     /// Controller IP address
     pub controller_ip: Ipv4Addr,
+    /// File holding the controller IP, watched for changes at runtime
+    pub controller_ip_file: Option<PathBuf>,
+    /// Hostname `controller_ip` was resolved from, re-resolved at runtime
+    pub controller_host: Option<String>,
+    /// Prefix whose every address is accepted as a controller, in addition
+    /// to `controller_ip`
+    pub controller_net: Option<Ipv4Net>,
+    /// Further controller IPs accepted alongside `controller_ip`
+    pub controller_extra_ips: Vec<Ipv4Addr>,
     /// Controller port number
     pub controller_port: u16,
+    /// Further controller ports passed alongside `controller_port`
+    pub controller_extra_ports: Vec<u16>,
+    /// How long a resolved controller hostname is reused before DNS is queried again
+    pub controller_resolve_ttl_sec: u64,
     /// Delay before updating session timestamp (nanoseconds)
     pub lazy_update_timeout: u64,
     /// TLS certificate paths
@@ -131,12 +434,62 @@ pub struct Config {
     pub ca_file: String,
     /// Rule timeout in nanoseconds before cleanup
     pub rule_timeout_ns: u64,
+    /// Lifetime in nanoseconds after which even an active session is
+    /// reaped, or 0 for no limit
+    pub max_session_age_ns: u64,
     /// Cleanup interval in seconds
     pub cleanup_interval_sec: u64,
     /// Broadcast channel size for monitoring
     pub broadcast_channel_size: usize,
+    /// Per-subscriber buffer of `MonitorSessions` streams
+    pub monitor_buffer_size: usize,
+    /// Concurrent `MonitorSessions` streams allowed, or 0 for no limit
+    pub max_monitors: usize,
+    /// bpffs directory holding this instance's pinned objects
+    pub pin_prefix: PathBuf,
+    /// Use batch deletion for stale rules when the kernel supports it
+    pub batch_delete: bool,
+    /// Mount bpffs at `/sys/fs/bpf` if it is missing
+    pub mount_bpffs: bool,
+    /// Replace a pinned session map whose layout does not match this build
+    pub force_recreate: bool,
+    /// Periodically log the XDP program's kernel run statistics
+    pub bpf_stats: bool,
+    /// Interval between XDP statistics reports in seconds
+    pub bpf_stats_interval_sec: u64,
+    /// Seconds between session map health probes, or 0 to disable them
+    pub bpf_watchdog_interval_sec: u64,
+    /// Consecutive failed probes after which the agent exits
+    pub bpf_watchdog_failures: u32,
     /// gRPC server port
     pub grpc_server_port: u16,
+    /// Number of gRPC listeners sharing the port through `SO_REUSEPORT`
+    pub grpc_workers: usize,
+    /// Attempts to bind the gRPC port while it is still held, e.g. in TIME_WAIT
+    pub grpc_bind_attempts: u32,
+    /// Seconds to wait on shutdown for gRPC streams to close
+    pub grpc_shutdown_grace_sec: u64,
+    /// Seconds between HTTP/2 and TCP keepalive probes on idle gRPC
+    /// connections, or 0 to disable them
+    pub grpc_keepalive_interval_sec: u64,
+    /// Seconds to wait for an HTTP/2 keepalive ping to be acknowledged
+    pub grpc_keepalive_timeout_sec: u64,
+    /// File receiving one JSON audit record per mutating RPC
+    pub grpc_audit_file: Option<PathBuf>,
+    /// Expect a PROXY protocol v2 header on every TCP gRPC connection
+    pub grpc_proxy_protocol: bool,
+    /// Peers allowed to send a PROXY protocol header
+    pub grpc_trusted_proxies: Vec<Ipv4Net>,
+    /// Reject every mutating RPC, leaving only monitoring and lookups
+    pub grpc_read_only: bool,
+    /// Accept `--grpc-uds` peers in the agent's primary group, not only its uid
+    pub grpc_uds_allow_gid: bool,
+    /// `SubmitSession` calls allowed per second per caller, 0 for no limit
+    pub grpc_submit_rate_limit: u32,
+    /// IP allowed to call read-only RPCs such as `MonitorSessions`
+    pub grpc_monitor_ip: Option<Ipv4Addr>,
+    /// Client certificate CN allowed to call read-only RPCs
+    pub grpc_monitor_cn: Option<String>,
 }
 
 impl Default for Config {
@@ -145,16 +498,60 @@ impl Default for Config {
         let controller_ip = Ipv4Addr::from_str(&tf.controller.ip).unwrap();
         Self {
             iface_name: tf.network.iface,
+            attach_hook: tf.network.attach_hook,
+            xdp_mode: tf.network.xdp_mode,
+            strict_tcp: tf.network.strict_tcp,
+            bind_src_mac: tf.network.bind_src_mac,
+            fragment_policy: tf.network.fragment_policy,
+            always_allow_ports: tf.network.always_allow_ports,
+            attach_retries: tf.network.attach_retries,
+            attach_retry_delay_ms: tf.network.attach_retry_delay_ms,
+            enforcement_grace_sec: tf.network.enforcement_grace_sec,
+            require_link_up: tf.network.require_link_up,
+            max_conns_per_src: tf.network.max_conns_per_src,
+            redirect_to: None,
+            cgroup: None,
+            dns_resolvers: Vec::new(),
             controller_ip,
+            controller_ip_file: None,
+            controller_host: None,
+            controller_net: None,
+            controller_extra_ips: Vec::new(),
             controller_port: tf.controller.port,
+            controller_extra_ports: Vec::new(),
+            controller_resolve_ttl_sec: tf.controller.resolve_ttl_sec,
             lazy_update_timeout: tf.session.lazy_update_timeout_ns,
             cert_file: tf.certs.cert_file,
             key_file: tf.certs.key_file,
             ca_file: tf.certs.ca_file,
             rule_timeout_ns: tf.session.rule_timeout_ns,
+            max_session_age_ns: tf.session.max_session_age_ns,
             cleanup_interval_sec: tf.session.cleanup_interval_sec,
             broadcast_channel_size: tf.session.broadcast_channel_size,
+            monitor_buffer_size: tf.session.monitor_buffer_size,
+            max_monitors: tf.session.max_monitors,
+            pin_prefix: PathBuf::from(tf.bpf.pin_prefix),
+            batch_delete: tf.bpf.batch_delete,
+            mount_bpffs: tf.bpf.mount_bpffs,
+            force_recreate: tf.bpf.force_recreate,
+            bpf_stats: tf.bpf.stats,
+            bpf_stats_interval_sec: tf.bpf.stats_interval_sec,
+            bpf_watchdog_interval_sec: tf.bpf.watchdog_interval_sec,
+            bpf_watchdog_failures: tf.bpf.watchdog_failures,
             grpc_server_port: tf.grpc.port,
+            grpc_workers: tf.grpc.workers,
+            grpc_bind_attempts: tf.grpc.bind_attempts,
+            grpc_shutdown_grace_sec: tf.grpc.shutdown_grace_sec,
+            grpc_keepalive_interval_sec: tf.grpc.keepalive_interval_sec,
+            grpc_keepalive_timeout_sec: tf.grpc.keepalive_timeout_sec,
+            grpc_audit_file: None,
+            grpc_proxy_protocol: false,
+            grpc_trusted_proxies: Vec::new(),
+            grpc_read_only: false,
+            grpc_uds_allow_gid: false,
+            grpc_submit_rate_limit: tf.grpc.submit_rate_limit,
+            grpc_monitor_ip: None,
+            grpc_monitor_cn: None,
         }
     }
 }
@@ -166,9 +563,24 @@ impl Config {
         Self::load_from_file(DEFAULT_CONFIG_PATH)
     }
 
+    /// Loads `config.toml` from `dir` and resolves the relative paths it
+    /// holds against `dir`, so they keep working after `--daemonize` has
+    /// moved the working directory to `/`.
+    pub fn load_in(dir: &Path) -> Result<Self> {
+        let mut tf = Self::read_toml(&dir.join(DEFAULT_CONFIG_PATH).to_string_lossy())?;
+        tf.resolve_paths(dir);
+        Self::from_toml(tf)
+    }
+
     /// Loads configuration from an explicit TOML file path.
     /// If the file does not exist, defaults are used.
     pub fn load_from_file(path: &str) -> Result<Self> {
+        Self::from_toml(Self::read_toml(path)?)
+    }
+
+    /// Parses the TOML file at `path`, or returns the defaults if it does
+    /// not exist.
+    fn read_toml(path: &str) -> Result<TomlFile> {
         let tf: TomlFile = match std::fs::read_to_string(path) {
             Ok(contents) => toml::from_str(&contents)
                 .with_context(|| format!("Failed to parse config file: {}", path))?,
@@ -180,30 +592,214 @@ impl Config {
                 return Err(e).with_context(|| format!("Failed to read config file: {}", path));
             }
         };
-        Self::from_toml(tf)
+        Ok(tf)
+    }
+
+    /// Checks that the TLS certificate, key and CA files are readable files.
+    ///
+    /// The gRPC server only reads them once XDP is already enforcing, so a
+    /// bad path found there leaves the host locked down with no control
+    /// plane. Checking up front keeps the agent from attaching at all.
+    pub fn check_cert_files(&self) -> Result<()> {
+        for (option, path) in [
+            ("certs.cert_file", &self.cert_file),
+            ("certs.key_file", &self.key_file),
+            ("certs.ca_file", &self.ca_file),
+        ] {
+            let file =
+                File::open(path).with_context(|| format!("Cannot read {}: {}", option, path))?;
+            let metadata = file
+                .metadata()
+                .with_context(|| format!("Cannot read {}: {}", option, path))?;
+            if !metadata.is_file() {
+                return Err(anyhow!("{} is not a regular file: {}", option, path));
+            }
+        }
+        Ok(())
     }
 
     fn from_toml(tf: TomlFile) -> Result<Self> {
-        let controller_ip = if !tf.controller.host.is_empty() {
-            hostname_to_ip(tf.controller.host.clone())
-                .with_context(|| format!("Failed to resolve host: {}", tf.controller.host))?
+        let controller_ip_file =
+            (!tf.controller.ip_file.is_empty()).then(|| PathBuf::from(&tf.controller.ip_file));
+
+        let controller_ip = if let Some(path) = &controller_ip_file {
+            read_controller_ip(path)?
+        } else if !tf.controller.host.is_empty() {
+            cached_hostname_to_ip(
+                &tf.controller.host,
+                Duration::from_secs(tf.controller.resolve_ttl_sec),
+            )
+            .with_context(|| format!("Failed to resolve host: {}", tf.controller.host))?
         } else {
-            Ipv4Addr::from_str(&tf.controller.ip)
+            parse_controller_ip(&tf.controller.ip)
                 .with_context(|| format!("Invalid controller.ip: {}", tf.controller.ip))?
         };
 
+        let controller_host = (controller_ip_file.is_none() && !tf.controller.host.is_empty())
+            .then(|| tf.controller.host.clone());
+
+        if tf.network.attach_hook == AttachHook::Tc && tf.network.xdp_mode == XdpMode::Offload {
+            return Err(anyhow!(
+                "network.xdp_mode = \"offload\" cannot be used with attach_hook = \"tc\""
+            ));
+        }
+
+        if !tf.network.redirect_to.is_empty() && tf.network.xdp_mode == XdpMode::Offload {
+            return Err(anyhow!(
+                "network.redirect_to cannot be used with xdp_mode = \"offload\""
+            ));
+        }
+        let redirect_to = (!tf.network.redirect_to.is_empty()).then_some(tf.network.redirect_to);
+        let cgroup = (!tf.network.cgroup.is_empty()).then(|| PathBuf::from(tf.network.cgroup));
+        let dns_resolvers = tf
+            .network
+            .dns_resolvers
+            .iter()
+            .map(|ip| {
+                ip.parse::<Ipv4Addr>()
+                    .with_context(|| format!("Invalid network.dns_resolvers entry: {}", ip))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if dns_resolvers.len() > MAX_DNS_RESOLVERS {
+            return Err(anyhow!(
+                "network.dns_resolvers holds at most {} addresses",
+                MAX_DNS_RESOLVERS
+            ));
+        }
+
+        if tf.grpc.workers == 0 {
+            return Err(anyhow!("grpc.workers must be at least 1"));
+        }
+        if tf.session.broadcast_channel_size == 0 {
+            return Err(anyhow!("session.broadcast_channel_size must be at least 1"));
+        }
+        if tf.session.monitor_buffer_size == 0 {
+            return Err(anyhow!("session.monitor_buffer_size must be at least 1"));
+        }
+        if tf.grpc.bind_attempts == 0 {
+            return Err(anyhow!("grpc.bind_attempts must be at least 1"));
+        }
+        if tf.bpf.watchdog_failures == 0 {
+            return Err(anyhow!("bpf.watchdog_failures must be at least 1"));
+        }
+        if tf.grpc.keepalive_interval_sec > MAX_KEEPALIVE_INTERVAL_SEC {
+            return Err(anyhow!(
+                "grpc.keepalive_interval_sec must be at most {}",
+                MAX_KEEPALIVE_INTERVAL_SEC
+            ));
+        }
+
+        let grpc_monitor_ip = if tf.grpc.monitor_ip.is_empty() {
+            None
+        } else {
+            Some(
+                Ipv4Addr::from_str(&tf.grpc.monitor_ip)
+                    .with_context(|| format!("Invalid grpc.monitor_ip: {}", tf.grpc.monitor_ip))?,
+            )
+        };
+        let grpc_monitor_cn = (!tf.grpc.monitor_cn.is_empty()).then_some(tf.grpc.monitor_cn);
+        let grpc_audit_file =
+            (!tf.grpc.audit_file.is_empty()).then(|| PathBuf::from(&tf.grpc.audit_file));
+        let grpc_trusted_proxies = tf
+            .grpc
+            .trusted_proxies
+            .iter()
+            .map(|net| {
+                net.parse()
+                    .with_context(|| format!("Invalid grpc.trusted_proxies entry: {}", net))
+            })
+            .collect::<Result<Vec<Ipv4Net>>>()?;
+        let controller_net = if tf.controller.net.is_empty() {
+            None
+        } else {
+            Some(
+                tf.controller
+                    .net
+                    .parse()
+                    .with_context(|| format!("Invalid controller.net: {}", tf.controller.net))?,
+            )
+        };
+        let controller_extra_ips = tf
+            .controller
+            .extra_ips
+            .iter()
+            .map(|ip| {
+                parse_controller_ip(ip)
+                    .with_context(|| format!("Invalid controller.extra_ips entry: {}", ip))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if controller_extra_ips.len() > MAX_EXTRA_CONTROLLERS {
+            return Err(anyhow!(
+                "controller.extra_ips holds at most {} addresses",
+                MAX_EXTRA_CONTROLLERS
+            ));
+        }
+        if tf.controller.extra_ports.len() > MAX_EXTRA_CONTROLLER_PORTS {
+            return Err(anyhow!(
+                "controller.extra_ports holds at most {} ports",
+                MAX_EXTRA_CONTROLLER_PORTS
+            ));
+        }
+        if tf.controller.extra_ports.contains(&0) {
+            return Err(anyhow!("controller.extra_ports cannot contain port 0"));
+        }
+
         let config = Self {
             iface_name: tf.network.iface,
+            attach_hook: tf.network.attach_hook,
+            xdp_mode: tf.network.xdp_mode,
+            strict_tcp: tf.network.strict_tcp,
+            bind_src_mac: tf.network.bind_src_mac,
+            fragment_policy: tf.network.fragment_policy,
+            always_allow_ports: tf.network.always_allow_ports,
+            attach_retries: tf.network.attach_retries,
+            attach_retry_delay_ms: tf.network.attach_retry_delay_ms,
+            enforcement_grace_sec: tf.network.enforcement_grace_sec,
+            require_link_up: tf.network.require_link_up,
+            max_conns_per_src: tf.network.max_conns_per_src,
+            redirect_to,
+            cgroup,
+            dns_resolvers,
             controller_ip,
+            controller_ip_file,
+            controller_host,
+            controller_net,
+            controller_extra_ips,
             controller_port: tf.controller.port,
+            controller_extra_ports: tf.controller.extra_ports,
+            controller_resolve_ttl_sec: tf.controller.resolve_ttl_sec,
             lazy_update_timeout: tf.session.lazy_update_timeout_ns,
             cert_file: tf.certs.cert_file,
             key_file: tf.certs.key_file,
             ca_file: tf.certs.ca_file,
             rule_timeout_ns: tf.session.rule_timeout_ns,
+            max_session_age_ns: tf.session.max_session_age_ns,
             cleanup_interval_sec: tf.session.cleanup_interval_sec,
             broadcast_channel_size: tf.session.broadcast_channel_size,
+            monitor_buffer_size: tf.session.monitor_buffer_size,
+            max_monitors: tf.session.max_monitors,
+            pin_prefix: PathBuf::from(tf.bpf.pin_prefix),
+            batch_delete: tf.bpf.batch_delete,
+            mount_bpffs: tf.bpf.mount_bpffs,
+            force_recreate: tf.bpf.force_recreate,
+            bpf_stats: tf.bpf.stats,
+            bpf_stats_interval_sec: tf.bpf.stats_interval_sec,
+            bpf_watchdog_interval_sec: tf.bpf.watchdog_interval_sec,
+            bpf_watchdog_failures: tf.bpf.watchdog_failures,
             grpc_server_port: tf.grpc.port,
+            grpc_workers: tf.grpc.workers,
+            grpc_bind_attempts: tf.grpc.bind_attempts,
+            grpc_shutdown_grace_sec: tf.grpc.shutdown_grace_sec,
+            grpc_keepalive_interval_sec: tf.grpc.keepalive_interval_sec,
+            grpc_keepalive_timeout_sec: tf.grpc.keepalive_timeout_sec,
+            grpc_audit_file,
+            grpc_proxy_protocol: tf.grpc.proxy_protocol,
+            grpc_trusted_proxies,
+            grpc_read_only: tf.grpc.read_only,
+            grpc_uds_allow_gid: tf.grpc.uds_allow_gid,
+            grpc_submit_rate_limit: tf.grpc.submit_rate_limit,
+            grpc_monitor_ip,
+            grpc_monitor_cn,
         };
 
         debug!("Configuration loaded: {:?}", config);
@@ -228,10 +824,52 @@ mod tests {
     fn test_load_defaults() {
         let cfg = Config::default();
         assert_eq!(cfg.iface_name, "eth0");
+        assert_eq!(cfg.attach_hook, AttachHook::Xdp);
+        assert_eq!(cfg.xdp_mode, XdpMode::Auto);
+        assert!(!cfg.strict_tcp);
+        assert!(!cfg.bind_src_mac);
+        assert_eq!(cfg.fragment_policy, FragmentPolicy::Drop);
+        assert!(cfg.always_allow_ports.is_empty());
+        assert_eq!(cfg.attach_retries, 0);
+        assert_eq!(cfg.attach_retry_delay_ms, 1000);
+        assert_eq!(cfg.enforcement_grace_sec, 0);
+        assert!(!cfg.require_link_up);
+        assert_eq!(cfg.max_conns_per_src, 0);
+        assert_eq!(cfg.redirect_to, None);
+        assert_eq!(cfg.cgroup, None);
+        assert!(cfg.dns_resolvers.is_empty());
         assert_eq!(cfg.controller_ip, Ipv4Addr::new(172, 21, 0, 5));
         assert_eq!(cfg.controller_port, 443);
+        assert!(cfg.controller_extra_ports.is_empty());
+        assert_eq!(cfg.controller_net, None);
+        assert!(cfg.controller_extra_ips.is_empty());
+        assert_eq!(cfg.controller_resolve_ttl_sec, 300);
         assert_eq!(cfg.lazy_update_timeout, 1_000_000_000);
+        assert_eq!(cfg.broadcast_channel_size, 16);
+        assert_eq!(cfg.monitor_buffer_size, 4);
+        assert_eq!(cfg.max_monitors, 64);
+        assert_eq!(cfg.pin_prefix, PathBuf::from("/sys/fs/bpf/aegis"));
+        assert!(cfg.batch_delete);
+        assert!(!cfg.mount_bpffs);
+        assert!(!cfg.force_recreate);
+        assert!(!cfg.bpf_stats);
+        assert_eq!(cfg.bpf_stats_interval_sec, 60);
+        assert_eq!(cfg.bpf_watchdog_interval_sec, 30);
+        assert_eq!(cfg.bpf_watchdog_failures, 3);
         assert_eq!(cfg.grpc_server_port, 50001);
+        assert_eq!(cfg.grpc_workers, 1);
+        assert_eq!(cfg.grpc_bind_attempts, 5);
+        assert_eq!(cfg.grpc_shutdown_grace_sec, 5);
+        assert_eq!(cfg.grpc_keepalive_interval_sec, 60);
+        assert_eq!(cfg.grpc_keepalive_timeout_sec, 20);
+        assert_eq!(cfg.grpc_audit_file, None);
+        assert!(!cfg.grpc_proxy_protocol);
+        assert!(cfg.grpc_trusted_proxies.is_empty());
+        assert!(!cfg.grpc_read_only);
+        assert!(!cfg.grpc_uds_allow_gid);
+        assert_eq!(cfg.grpc_submit_rate_limit, 0);
+        assert_eq!(cfg.grpc_monitor_ip, None);
+        assert_eq!(cfg.grpc_monitor_cn, None);
     }
 
     #[test]
@@ -254,10 +892,28 @@ mod tests {
             r#"
 [network]
 iface = "docker0"
+attach_hook = "tc"
+xdp_mode = "skb"
+strict_tcp = true
+bind_src_mac = true
+fragment_policy = "track"
+always_allow_ports = [22, 8443]
+attach_retries = 6
+attach_retry_delay_ms = 250
+enforcement_grace_sec = 30
+require_link_up = true
+max_conns_per_src = 64
+redirect_to = "ids0"
+cgroup = "/sys/fs/cgroup/aegis"
+dns_resolvers = ["10.0.0.2"]
 
 [controller]
 ip   = "10.0.0.1"
+net  = "10.0.0.0/28"
+extra_ips = ["10.0.1.5", "10.0.2.5"]
 port = 8080
+extra_ports = [8443]
+resolve_ttl_sec = 60
 
 [certs]
 cert_file = "/custom/cert.pem"
@@ -267,27 +923,100 @@ ca_file   = "/custom/ca.pem"
 [session]
 lazy_update_timeout_ns  = 5_000_000_000
 rule_timeout_ns         = 120_000_000_000
+max_session_age_ns      = 3_600_000_000_000
 cleanup_interval_sec    = 60
 broadcast_channel_size  = 32
+monitor_buffer_size     = 8
+max_monitors            = 2
+
+[bpf]
+pin_prefix = "/sys/fs/bpf/aegis-test"
+batch_delete = false
+stats = true
+stats_interval_sec = 10
+watchdog_interval_sec = 5
+watchdog_failures = 2
 
 [grpc]
 port = 50002
+workers = 4
+bind_attempts = 10
+shutdown_grace_sec = 2
+keepalive_interval_sec = 15
+keepalive_timeout_sec = 5
+audit_file = "/var/log/aegis/audit.jsonl"
+proxy_protocol = true
+trusted_proxies = ["172.21.0.0/24"]
+read_only = true
+uds_allow_gid = true
+submit_rate_limit = 20
+monitor_ip = "10.0.0.9"
+monitor_cn = "grafana"
 "#,
         );
         let cfg = Config::load_from_file(f.path().to_str().unwrap())
             .expect("Failed to load custom config");
 
         assert_eq!(cfg.iface_name, "docker0");
+        assert_eq!(cfg.attach_hook, AttachHook::Tc);
+        assert_eq!(cfg.xdp_mode, XdpMode::Skb);
+        assert!(cfg.strict_tcp);
+        assert!(cfg.bind_src_mac);
+        assert_eq!(cfg.fragment_policy, FragmentPolicy::Track);
+        assert_eq!(cfg.always_allow_ports, vec![22, 8443]);
+        assert_eq!(cfg.attach_retries, 6);
+        assert_eq!(cfg.attach_retry_delay_ms, 250);
+        assert_eq!(cfg.enforcement_grace_sec, 30);
+        assert!(cfg.require_link_up);
+        assert_eq!(cfg.max_conns_per_src, 64);
+        assert_eq!(cfg.redirect_to.as_deref(), Some("ids0"));
+        assert_eq!(cfg.cgroup, Some(PathBuf::from("/sys/fs/cgroup/aegis")));
+        assert_eq!(cfg.dns_resolvers, vec![Ipv4Addr::new(10, 0, 0, 2)]);
         assert_eq!(cfg.controller_ip, Ipv4Addr::new(10, 0, 0, 1));
         assert_eq!(cfg.controller_port, 8080);
+        assert_eq!(cfg.controller_extra_ports, vec![8443]);
+        assert_eq!(cfg.controller_net, Some("10.0.0.0/28".parse().unwrap()));
+        assert_eq!(
+            cfg.controller_extra_ips,
+            vec![Ipv4Addr::new(10, 0, 1, 5), Ipv4Addr::new(10, 0, 2, 5)]
+        );
+        assert_eq!(cfg.controller_resolve_ttl_sec, 60);
         assert_eq!(cfg.cert_file, "/custom/cert.pem");
         assert_eq!(cfg.key_file, "/custom/key.pem");
         assert_eq!(cfg.ca_file, "/custom/ca.pem");
         assert_eq!(cfg.lazy_update_timeout, 5_000_000_000);
         assert_eq!(cfg.rule_timeout_ns, 120_000_000_000);
+        assert_eq!(cfg.max_session_age_ns, 3_600_000_000_000);
         assert_eq!(cfg.cleanup_interval_sec, 60);
         assert_eq!(cfg.broadcast_channel_size, 32);
+        assert_eq!(cfg.monitor_buffer_size, 8);
+        assert_eq!(cfg.max_monitors, 2);
+        assert_eq!(cfg.pin_prefix, PathBuf::from("/sys/fs/bpf/aegis-test"));
+        assert!(!cfg.batch_delete);
+        assert!(cfg.bpf_stats);
+        assert_eq!(cfg.bpf_stats_interval_sec, 10);
+        assert_eq!(cfg.bpf_watchdog_interval_sec, 5);
+        assert_eq!(cfg.bpf_watchdog_failures, 2);
         assert_eq!(cfg.grpc_server_port, 50002);
+        assert_eq!(cfg.grpc_workers, 4);
+        assert_eq!(cfg.grpc_bind_attempts, 10);
+        assert_eq!(cfg.grpc_shutdown_grace_sec, 2);
+        assert_eq!(cfg.grpc_keepalive_interval_sec, 15);
+        assert_eq!(cfg.grpc_keepalive_timeout_sec, 5);
+        assert_eq!(
+            cfg.grpc_audit_file,
+            Some(PathBuf::from("/var/log/aegis/audit.jsonl"))
+        );
+        assert!(cfg.grpc_proxy_protocol);
+        assert_eq!(
+            cfg.grpc_trusted_proxies,
+            vec!["172.21.0.0/24".parse().unwrap()]
+        );
+        assert!(cfg.grpc_read_only);
+        assert!(cfg.grpc_uds_allow_gid);
+        assert_eq!(cfg.grpc_submit_rate_limit, 20);
+        assert_eq!(cfg.grpc_monitor_ip, Some(Ipv4Addr::new(10, 0, 0, 9)));
+        assert_eq!(cfg.grpc_monitor_cn.as_deref(), Some("grafana"));
     }
 
     #[test]
@@ -298,6 +1027,71 @@ port = 50002
         assert_eq!(cfg.controller_port, 443);
     }
 
+    #[test]
+    fn test_load_in_resolves_relative_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(DEFAULT_CONFIG_PATH),
+            r#"
+[controller]
+ip_file = "controller-ip"
+
+[grpc]
+audit_file = "/var/log/aegis/audit.jsonl"
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("controller-ip"), "10.0.0.1\n").unwrap();
+
+        let cfg = Config::load_in(dir.path()).unwrap();
+        assert_eq!(
+            cfg.cert_file,
+            dir.path().join("certs/agent.pem").to_string_lossy()
+        );
+        assert_eq!(
+            cfg.ca_file,
+            dir.path().join("certs/ca.pem").to_string_lossy()
+        );
+        assert_eq!(
+            cfg.controller_ip_file,
+            Some(dir.path().join("controller-ip"))
+        );
+        // Absolute paths are kept
+        assert_eq!(
+            cfg.grpc_audit_file,
+            Some(PathBuf::from("/var/log/aegis/audit.jsonl"))
+        );
+        assert_eq!(cfg.pin_prefix, PathBuf::from("/sys/fs/bpf/aegis"));
+    }
+
+    #[test]
+    fn test_invalid_extra_controller_fails() {
+        let f = write_toml(
+            r#"
+[controller]
+extra_ips = ["10.0.1.5", "fd00::5"]
+"#,
+        );
+        assert!(Config::load_from_file(f.path().to_str().unwrap()).is_err());
+
+        let ips: Vec<String> = (1..=17).map(|i| format!("\"10.0.1.{}\"", i)).collect();
+        let f = write_toml(&format!("[controller]\nextra_ips = [{}]\n", ips.join(", ")));
+        assert!(Config::load_from_file(f.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_extra_ports_fail() {
+        let f = write_toml("[controller]\nextra_ports = [8443, 0]\n");
+        assert!(Config::load_from_file(f.path().to_str().unwrap()).is_err());
+
+        let ports: Vec<String> = (8001..=8009).map(|port| port.to_string()).collect();
+        let f = write_toml(&format!(
+            "[controller]\nextra_ports = [{}]\n",
+            ports.join(", ")
+        ));
+        assert!(Config::load_from_file(f.path().to_str().unwrap()).is_err());
+    }
+
     #[test]
     fn test_invalid_ip_fails() {
         let f = write_toml(
@@ -310,6 +1104,153 @@ ip = "999.999.999.999"
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_ipv6_controller_ip_fails() {
+        let f = write_toml(
+            r#"
+[controller]
+ip = "fd00::5"
+"#,
+        );
+        let err = Config::load_from_file(f.path().to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("IPv6 controllers are not supported yet"));
+    }
+
+    #[test]
+    fn test_invalid_monitor_ip_fails() {
+        let f = write_toml(
+            r#"
+[grpc]
+monitor_ip = "monitoring.local"
+"#,
+        );
+        assert!(Config::load_from_file(f.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_trusted_proxy_fails() {
+        let f = write_toml(
+            r#"
+[grpc]
+trusted_proxies = ["172.21.0.10"]
+"#,
+        );
+        let err = Config::load_from_file(f.path().to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid grpc.trusted_proxies entry"));
+    }
+
+    #[test]
+    fn test_invalid_dns_resolver_fails() {
+        let f = write_toml(
+            r#"
+[network]
+dns_resolvers = ["resolver.local"]
+"#,
+        );
+        let err = Config::load_from_file(f.path().to_str().unwrap()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid network.dns_resolvers entry"));
+    }
+
+    #[test]
+    fn test_xdp_mode_parsing() {
+        assert_eq!("auto".parse::<XdpMode>().unwrap(), XdpMode::Auto);
+        assert_eq!("native".parse::<XdpMode>().unwrap(), XdpMode::Native);
+        assert_eq!("skb".parse::<XdpMode>().unwrap(), XdpMode::Skb);
+        assert_eq!("offload".parse::<XdpMode>().unwrap(), XdpMode::Offload);
+        assert!("generic".parse::<XdpMode>().is_err());
+    }
+
+    #[test]
+    fn test_attach_hook_parsing() {
+        assert_eq!("xdp".parse::<AttachHook>().unwrap(), AttachHook::Xdp);
+        assert_eq!("tc".parse::<AttachHook>().unwrap(), AttachHook::Tc);
+        assert!("clsact".parse::<AttachHook>().is_err());
+    }
+
+    #[test]
+    fn test_tc_hook_rejects_offload() {
+        let f = write_toml(
+            r#"
+[network]
+attach_hook = "tc"
+xdp_mode = "offload"
+"#,
+        );
+        let result = Config::load_from_file(f.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_fragment_policy_parsing() {
+        assert_eq!(
+            "drop".parse::<FragmentPolicy>().unwrap(),
+            FragmentPolicy::Drop
+        );
+        assert_eq!(
+            "track".parse::<FragmentPolicy>().unwrap(),
+            FragmentPolicy::Track
+        );
+        assert!("pass".parse::<FragmentPolicy>().is_err());
+        assert_eq!(FragmentPolicy::Track as u8, 1);
+    }
+
+    #[test]
+    fn test_invalid_xdp_mode_fails() {
+        let f = write_toml(
+            r#"
+[network]
+xdp_mode = "turbo"
+"#,
+        );
+        let result = Config::load_from_file(f.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_bind_attempts_fails() {
+        let f = write_toml(
+            r#"
+[grpc]
+bind_attempts = 0
+"#,
+        );
+        let result = Config::load_from_file(f.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_watchdog_failures_fails() {
+        let f = write_toml(
+            r#"
+[bpf]
+watchdog_failures = 0
+"#,
+        );
+        let result = Config::load_from_file(f.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_keepalive_interval_too_long_fails() {
+        let f = write_toml("[grpc]\nkeepalive_interval_sec = 32767\n");
+        let cfg =
+            Config::load_from_file(f.path().to_str().unwrap()).expect("Failed to load config");
+        assert_eq!(cfg.grpc_keepalive_interval_sec, 32_767);
+
+        let f = write_toml("[grpc]\nkeepalive_interval_sec = 32768\n");
+        let err = Config::load_from_file(f.path().to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("at most 32767"), "{}", err);
+    }
+
+    #[test]
+    fn test_zero_channel_sizes_fail() {
+        for session in ["broadcast_channel_size = 0", "monitor_buffer_size = 0"] {
+            let f = write_toml(&format!("[session]\n{}\n", session));
+            let result = Config::load_from_file(f.path().to_str().unwrap());
+            assert!(result.is_err(), "{}", session);
+        }
+    }
+
     #[test]
     fn test_invalid_toml_fails() {
         let f = write_toml("{ this is not valid toml");
@@ -343,5 +1284,60 @@ host = "localhost"
             Config::load_from_file(f.path().to_str().unwrap()).expect("Failed to load config");
         // host should win
         assert_eq!(cfg.controller_ip, Ipv4Addr::new(127, 0, 0, 1));
+        assert_eq!(cfg.controller_host.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn test_ip_file_takes_priority_over_host() {
+        let ip_file = write_toml("10.0.0.9\n");
+        let f = write_toml(&format!(
+            r#"
+[controller]
+host    = "localhost"
+ip_file = "{}"
+"#,
+            ip_file.path().display()
+        ));
+        let cfg =
+            Config::load_from_file(f.path().to_str().unwrap()).expect("Failed to load config");
+        assert_eq!(cfg.controller_ip, Ipv4Addr::new(10, 0, 0, 9));
+        assert_eq!(cfg.controller_ip_file.as_deref(), Some(ip_file.path()));
+        assert_eq!(cfg.controller_host, None);
+    }
+
+    #[test]
+    fn test_check_cert_files() {
+        let cert = write_toml("cert");
+        let key = write_toml("key");
+        let ca = write_toml("ca");
+        let path = |f: &NamedTempFile| f.path().to_str().unwrap().to_string();
+        let config = |cert_file: String, key_file: String, ca_file: String| Config {
+            cert_file,
+            key_file,
+            ca_file,
+            ..Config::default()
+        };
+        config(path(&cert), path(&key), path(&ca))
+            .check_cert_files()
+            .expect("All files exist");
+
+        let missing = || "/nonexistent/aegis.pem".to_string();
+        for (cfg, option) in [
+            (config(missing(), path(&key), path(&ca)), "certs.cert_file"),
+            (config(path(&cert), missing(), path(&ca)), "certs.key_file"),
+            (config(path(&cert), path(&key), missing()), "certs.ca_file"),
+        ] {
+            let err = cfg.check_cert_files().unwrap_err().to_string();
+            assert!(err.contains(option), "{}", err);
+        }
+
+        // A directory opens fine but is not a certificate
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let dir_path = dir.path().to_str().unwrap().to_string();
+        assert!(
+            config(path(&cert), path(&key), dir_path)
+                .check_cert_files()
+                .is_err()
+        );
     }
 }
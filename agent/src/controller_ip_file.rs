@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, anyhow};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::hostname_to_ip::parse_controller_ip;
+
+/// Reads the controller IPv4 address from a file written by an external process.
+pub fn read_controller_ip(path: &Path) -> Result<Ipv4Addr> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read controller IP file: {}", path.display()))?;
+    let ip = contents.trim();
+    parse_controller_ip(ip)
+        .with_context(|| format!("Invalid controller IP '{}' in file: {}", ip, path.display()))
+}
+
+/// Watches the controller IP file and publishes every valid change to `tx`.
+///
+/// The parent directory is watched so files replaced by rename are picked up.
+/// Unreadable or unparseable contents are ignored and the last good IP stays
+/// in effect. The returned watcher must be kept alive for updates to continue.
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+pub fn watch_controller_ip(
+    path: PathBuf,
+    tx: watch::Sender<Ipv4Addr>,
+) -> Result<RecommendedWatcher> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Controller IP file has no file name: {}", path.display()))?
+        .to_os_string();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            if event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == Some(file_name.as_os_str()))
+            {
+                reload_controller_ip(&path, &tx);
+            }
+        }
+        Err(e) => warn!("Controller IP file watch error: {}", e),
+    })
+    .context("Failed to create controller IP file watcher")?;
+
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch directory: {}", dir.display()))?;
+    debug!("Watching {} for controller IP changes", dir.display());
+
+    Ok(watcher)
+}
+
+/// Re-reads the file and publishes the IP if it is valid and has changed.
+/// Returns `true` if a new IP was published.
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+fn reload_controller_ip(path: &Path, tx: &watch::Sender<Ipv4Addr>) -> bool {
+    match read_controller_ip(path) {
+        Ok(ip) => tx.send_if_modified(|current| {
+            if *current == ip {
+                return false;
+            }
+            info!("Controller IP changed: {} -> {}", current, ip);
+            *current = ip;
+            true
+        }),
+        Err(e) => {
+            warn!("Ignoring controller IP file update: {:#}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn write_ip_file(content: &str) -> NamedTempFile {
+        let mut f = NamedTempFile::new().expect("Failed to create temp file");
+        f.write_all(content.as_bytes())
+            .expect("Failed to write temp file");
+        f
+    }
+
+    #[test]
+    fn test_read_valid_ip() {
+        let f = write_ip_file("10.0.0.7\n");
+        let ip = read_controller_ip(f.path()).expect("Failed to read IP");
+        assert_eq!(ip, Ipv4Addr::new(10, 0, 0, 7));
+    }
+
+    #[test]
+    fn test_read_invalid_ip_fails() {
+        let f = write_ip_file("10.0.0");
+        assert!(read_controller_ip(f.path()).is_err());
+    }
+
+    #[test]
+    fn test_read_missing_file_fails() {
+        assert!(read_controller_ip(Path::new("/nonexistent/controller-ip")).is_err());
+    }
+
+    #[test]
+    fn test_reload_ignores_bad_write() {
+        let f = write_ip_file("");
+        let (tx, rx) = watch::channel(Ipv4Addr::new(10, 0, 0, 1));
+
+        assert!(!reload_controller_ip(f.path(), &tx));
+        assert_eq!(*rx.borrow(), Ipv4Addr::new(10, 0, 0, 1));
+    }
+
+    #[test]
+    fn test_reload_publishes_new_ip() {
+        let f = write_ip_file("10.0.0.2");
+        let (tx, rx) = watch::channel(Ipv4Addr::new(10, 0, 0, 1));
+
+        assert!(reload_controller_ip(f.path(), &tx));
+        assert_eq!(*rx.borrow(), Ipv4Addr::new(10, 0, 0, 2));
+
+        // Rewriting the same IP is not a change
+        assert!(!reload_controller_ip(f.path(), &tx));
+    }
+}
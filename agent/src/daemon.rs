@@ -0,0 +1,185 @@
+use anyhow::{Context, Result, bail};
+use nix::{
+    errno::Errno,
+    fcntl::{Flock, FlockArg},
+    unistd::{ForkResult, chdir, dup2_stderr, dup2_stdin, dup2_stdout, fork, setsid},
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process,
+};
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{debug, info, warn};
+
+/// Detaches the process from the terminal with the classic double fork.
+///
+/// Must be called before the Tokio runtime starts and before any BPF objects
+/// are loaded, since only the calling thread survives a fork. Capabilities
+/// are inherited by the child. The daemon moves to `/` so it keeps no mount
+/// busy; relative paths must be resolved beforehand. Standard input, output
+/// and error point at `/dev/null` afterwards, so logs need `--log-file`.
+pub fn daemonize() -> Result<()> {
+    // First fork: let the shell return and drop process group leadership
+    // SAFETY: called while the process is still single-threaded.
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("First fork failed")? {
+        process::exit(0);
+    }
+
+    // New session without a controlling terminal
+    setsid().context("setsid failed")?;
+
+    // Second fork: a non-leader can never reacquire a controlling terminal
+    // SAFETY: still single-threaded in the intermediate child.
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Second fork failed")? {
+        process::exit(0);
+    }
+
+    chdir("/").context("Failed to change directory to /")?;
+    // Writes to a terminal that has gone away would fail or raise SIGHUP
+    let null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+        .context("Failed to open /dev/null")?;
+    dup2_stdin(&null).context("Failed to redirect stdin")?;
+    dup2_stdout(&null).context("Failed to redirect stdout")?;
+    dup2_stderr(&null).context("Failed to redirect stderr")?;
+
+    Ok(())
+}
+
+/// PID file holding an exclusive `flock` for as long as it lives, so two
+/// agents never fight over the same interface. Removed when dropped.
+///
+/// The lock belongs to the open file, not the process, so it is kept across
+/// [`daemonize`] and released by the kernel however the agent exits. A stale
+/// file left by a crash is therefore never mistaken for a running agent.
+pub struct PidFile {
+    path: PathBuf,
+    file: Flock<File>,
+}
+
+impl PidFile {
+    /// Opens or creates `path` and locks it, failing if another agent
+    /// holds the lock. Call [`PidFile::write_pid`] once the final PID is
+    /// known, i.e. after daemonizing.
+    pub fn lock(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o644)
+            .open(path)
+            .with_context(|| format!("Failed to open PID file: {}", path.display()))?;
+        let file = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(file) => file,
+            Err((_, Errno::EWOULDBLOCK)) => {
+                let pid = fs::read_to_string(path).unwrap_or_default();
+                bail!(
+                    "Another agent (pid {}) is already running, see {}",
+                    pid.trim(),
+                    path.display()
+                );
+            }
+            Err((_, e)) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to lock PID file: {}", path.display()));
+            }
+        };
+        debug!("Locked PID file {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Replaces the file's contents with the current process ID.
+    pub fn write_pid(&self) -> Result<()> {
+        let mut file: &File = &self.file;
+        file.set_len(0)
+            .and_then(|()| file.write_all(format!("{}\n", process::id()).as_bytes()))
+            .with_context(|| format!("Failed to write PID file: {}", self.path.display()))?;
+        debug!("Wrote PID file {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Resolves when the process receives SIGTERM or SIGINT.
+pub async fn shutdown_signal() -> Result<()> {
+    let mut sigterm =
+        signal(SignalKind::terminate()).context("Failed to install SIGTERM handler")?;
+    let mut sigint = signal(SignalKind::interrupt()).context("Failed to install SIGINT handler")?;
+
+    tokio::select! {
+        _ = sigterm.recv() => info!("Received SIGTERM, shutting down"),
+        _ = sigint.recv() => info!("Received SIGINT, shutting down"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_lifecycle() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("aegis-agent.pid");
+
+        let pid_file = PidFile::lock(&path).expect("Failed to lock PID file");
+        pid_file.write_pid().expect("Failed to write PID file");
+        let contents = fs::read_to_string(&path).expect("Failed to read PID file");
+        assert_eq!(contents.trim(), process::id().to_string());
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_locked_pid_file_blocks_second_agent() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("aegis-agent.pid");
+
+        let pid_file = PidFile::lock(&path).expect("Failed to lock PID file");
+        pid_file.write_pid().unwrap();
+        // A second open file description cannot take the lock, even in the
+        // same process
+        let err = PidFile::lock(&path).err().expect("Second lock should fail");
+        assert!(err.to_string().contains(&process::id().to_string()));
+
+        drop(pid_file);
+        let _pid_file = PidFile::lock(&path).expect("Lock should be free again");
+    }
+
+    #[test]
+    fn test_pid_file_replaces_stale_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("aegis-agent.pid");
+        fs::write(&path, "4194303\nnot a pid\n").unwrap();
+
+        let pid_file = PidFile::lock(&path).expect("Stale PID file should not block startup");
+        pid_file
+            .write_pid()
+            .expect("Failed to replace stale PID file");
+        let contents = fs::read_to_string(&path).expect("Failed to read PID file");
+        assert_eq!(contents.trim(), process::id().to_string());
+    }
+
+    #[test]
+    fn test_pid_file_bad_path_fails() {
+        assert!(PidFile::lock(Path::new("/nonexistent/dir/aegis-agent.pid")).is_err());
+    }
+}
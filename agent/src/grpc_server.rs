@@ -3,45 +3,202 @@
 //! Implements the SessionManager service for the controller to:
 //! - Submit session authentication events
 //! - Monitor active sessions
+//! - Look up a single session
+//! - Block and unblock source IPs
+//!
+//! The server listens on TCP with mTLS by default, or on a Unix domain socket
+//! when the controller is colocated and shares a volume with the agent.
 
 // Include the generated protobuf code
 pub mod session {
     tonic::include_proto!("session");
 }
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
+use nix::sys::socket::{setsockopt, sockopt};
 use session::{
-    Ack, Empty, IpChangeList, LoginEvent, SessionList,
+    Ack, AgentConfig, BlockRequest, Empty, IpChangeList, IpChangeResult, LoginEvent, MapStats,
+    PruneRequest, RejectCount, Session, SessionList, VersionInfo,
     session_manager_server::{SessionManager, SessionManagerServer},
 };
 use std::{
-    fs,
+    collections::{HashMap, HashSet},
+    fs, io,
     net::{Ipv4Addr, SocketAddr},
-    sync::Arc,
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::{TcpListener, TcpSocket, UnixListener},
+    sync::{Mutex, broadcast, watch},
+    task::JoinSet,
 };
-use tokio::sync::{Mutex, broadcast};
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
 use tonic::{
     Request, Response, Status,
-    transport::{Certificate, Identity, Server, ServerTlsConfig},
+    transport::{
+        Certificate, Identity, Server, ServerTlsConfig,
+        server::{Router, UdsConnectInfo},
+    },
 };
 use tracing::{debug, error, info, warn};
 
-use crate::config::Config;
+use crate::{
+    audit::{AuditEvent, AuditSink, FileSink, LogSink},
+    certs,
+    config::Config,
+    ip, proxy_protocol,
+    types::{Ipv4Net, SessionKey},
+    version,
+};
 
-/// Callback function type for adding/removing firewall rules
-type ModifyRulesFn = Arc<Mutex<dyn Fn(bool, u32, u32, u16) -> Result<()> + Send + Sync>>;
+/// Callback function type for adding/removing firewall rules, tagged with the
+/// submitting controller's epoch and bound to the client's source MAC if known
+type ModifyRulesFn =
+    Arc<Mutex<dyn Fn(bool, SessionKey, u64, u64) -> Result<RuleChange> + Send + Sync>>;
 
 /// Callback function type for updating destination IPs
 type UpdateIpFn = Arc<Mutex<dyn Fn(u32, u32) -> Result<usize> + Send + Sync>>;
 
+/// Callback function type for removing every session
+type FlushAllFn = Arc<Mutex<dyn Fn() -> Result<usize> + Send + Sync>>;
+
+/// Callback function type for adding/removing blocklisted source IPs
+type ModifyBlocklistFn = Arc<Mutex<dyn Fn(bool, Ipv4Addr) -> Result<()> + Send + Sync>>;
+
+/// Callback function type for looking up one session, returning its time left
+type GetSessionFn = Arc<Mutex<dyn Fn(SessionKey) -> Result<Option<i64>> + Send + Sync>>;
+
+/// Callback function type for reading the session map's entries used and capacity
+type MapStatsFn = Arc<Mutex<dyn Fn() -> Result<(usize, usize)> + Send + Sync>>;
+
+/// Callback function type for removing sessions outside the current epoch
+type PruneEpochsFn = Arc<Mutex<dyn Fn(u64) -> Result<usize> + Send + Sync>>;
+
+/// Callback function type for reading whether the XDP program enforces its policy
+type IsEnforcingFn = Arc<Mutex<dyn Fn() -> Result<bool> + Send + Sync>>;
+
+/// Callback function type for listing every session with its time left
+type ListSessionsFn = Arc<Mutex<dyn Fn() -> Result<Vec<(SessionKey, i64)>> + Send + Sync>>;
+
+/// Effect of adding or removing a session, reported back in the `Ack`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RuleChange {
+    /// Whether the session was in the map before the change
+    pub previous_existed: bool,
+    /// Sessions in the map after the change
+    pub active_sessions: usize,
+}
+
+/// Identity an authenticated request acts as, attached to its extensions by
+/// the interceptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Full access to every RPC
+    Controller,
+    /// Read-only access: `MonitorSessions`, `GetSession`, `GetVersion` and
+    /// `GetConfig`
+    Monitor,
+}
+
+/// Returns the handler set for an RPC, or `UNIMPLEMENTED` if the service was
+/// built without one.
+fn handler<'a, F>(handler: &'a Option<F>, operation: &str) -> Result<&'a F, Status> {
+    handler.as_ref().ok_or_else(|| {
+        warn!("{} requested but not served", operation);
+        Status::unimplemented(format!("{} is not available", operation))
+    })
+}
+
+/// Names the caller of a request for the audit log.
+///
+/// Prefers the CN of the client certificate, then the peer IP, then the
+/// uid of a Unix socket peer. In-process calls have none of these.
+fn peer_identity<T>(request: &Request<T>) -> String {
+    let cn = request.peer_certs().and_then(|chain| {
+        chain
+            .first()
+            .and_then(|cert| certs::common_name(cert.as_ref()))
+    });
+    if let Some(cn) = cn {
+        return format!("cn={}", cn);
+    }
+    if let Some(addr) = request.remote_addr() {
+        return format!("ip={}", addr.ip());
+    }
+    match request
+        .extensions()
+        .get::<UdsConnectInfo>()
+        .and_then(|info| info.peer_cred)
+    {
+        Some(cred) => format!("uid={}", cred.uid()),
+        None => "local".to_string(),
+    }
+}
+
+/// Accepts requests from the controller's current IP or any other configured
+/// controller, and read-only requests from the monitoring client.
+///
+/// The primary IP is read through a watch channel so it can follow changes to
+/// the controller IP file without restarting the server. The monitoring client is
+/// recognized by its IP or by the CN of its client certificate; a matching CN
+/// downgrades even a request from the controller IP to [`Role::Monitor`].
 #[derive(Clone)]
 pub struct AuthInterceptor {
-    pub controller_ip: Ipv4Addr,
+    pub controller_ip: watch::Receiver<Ipv4Addr>,
+    /// Any address in this prefix is also accepted as a controller
+    pub controller_net: Option<Ipv4Net>,
+    /// Further controllers accepted alongside `controller_ip`, e.g. a standby
+    /// in another subnet
+    pub extra_controllers: HashSet<Ipv4Addr>,
+    pub monitor_ip: Option<Ipv4Addr>,
+    pub monitor_cn: Option<String>,
+}
+
+impl AuthInterceptor {
+    /// Returns `true` if the client certificate carries the monitoring CN.
+    fn has_monitor_cn<T>(&self, request: &tonic::Request<T>) -> bool {
+        let Some(monitor_cn) = &self.monitor_cn else {
+            return false;
+        };
+        request
+            .peer_certs()
+            .and_then(|chain| {
+                chain
+                    .first()
+                    .and_then(|cert| certs::common_name(cert.as_ref()))
+            })
+            .is_some_and(|cn| cn == *monitor_cn)
+    }
+
+    /// Returns `true` if `ip` is `controller_ip` or any other configured controller.
+    fn is_controller(&self, ip: Ipv4Addr, controller_ip: Ipv4Addr) -> bool {
+        ip == controller_ip
+            || self.extra_controllers.contains(&ip)
+            || self.controller_net.is_some_and(|net| net.contains(ip))
+    }
+
+    /// Lists the accepted controllers for log messages.
+    fn describe_controllers(&self, controller_ip: Ipv4Addr) -> String {
+        let mut extra: Vec<_> = self.extra_controllers.iter().collect();
+        extra.sort();
+        std::iter::once(controller_ip.to_string())
+            .chain(extra.into_iter().map(Ipv4Addr::to_string))
+            .chain(self.controller_net.map(|net| net.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 impl tonic::service::Interceptor for AuthInterceptor {
-    /// Verifies the request originates from the authorized controller.
-    fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+    /// Verifies the request originates from the controller or monitoring
+    /// client and tags it with the matching [`Role`].
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
         let remote_addr = request.remote_addr();
 
         match remote_addr {
@@ -56,17 +213,23 @@ impl tonic::service::Interceptor for AuthInterceptor {
                     }
                 };
 
-                if ip == self.controller_ip {
-                    Ok(request)
+                let controller_ip = *self.controller_ip.borrow();
+                let role = if self.has_monitor_cn(&request) || self.monitor_ip == Some(ip) {
+                    Role::Monitor
+                } else if self.is_controller(ip, controller_ip) {
+                    Role::Controller
                 } else {
                     warn!(
-                        "Rejected unauthorized IP: {} (expected {})",
-                        ip, self.controller_ip
+                        "Rejected unauthorized IP: {} (expected one of {})",
+                        ip,
+                        self.describe_controllers(controller_ip)
                     );
-                    Err(Status::permission_denied(
+                    return Err(Status::permission_denied(
                         "Only controller requests are accepted",
-                    ))
-                }
+                    ));
+                };
+                request.extensions_mut().insert(role);
+                Ok(request)
             }
             None => {
                 warn!("Rejected request - no remote address");
@@ -76,11 +239,346 @@ impl tonic::service::Interceptor for AuthInterceptor {
     }
 }
 
+/// Verifies Unix socket peers via `SO_PEERCRED` instead of their IP address.
+#[derive(Clone)]
+pub struct PeerCredInterceptor {
+    pub uid: u32,
+    /// Also accept peers in this primary group, whatever their uid
+    pub gid: Option<u32>,
+}
+
+impl PeerCredInterceptor {
+    /// Creates an interceptor that accepts peers running as the agent's own
+    /// uid, and with `allow_gid` also those in its primary group.
+    pub fn current_user(allow_gid: bool) -> Self {
+        Self {
+            uid: nix::unistd::getuid().as_raw(),
+            gid: allow_gid.then(|| nix::unistd::getgid().as_raw()),
+        }
+    }
+
+    /// Returns `true` if a peer with these credentials may issue requests.
+    fn is_authorized(&self, uid: u32, gid: u32) -> bool {
+        uid == self.uid || self.gid == Some(gid)
+    }
+}
+
+impl tonic::service::Interceptor for PeerCredInterceptor {
+    /// Verifies the request originates from a local process with matching credentials.
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let cred = request
+            .extensions()
+            .get::<UdsConnectInfo>()
+            .and_then(|info| info.peer_cred);
+
+        match cred {
+            Some(cred) if self.is_authorized(cred.uid(), cred.gid()) => {
+                request.extensions_mut().insert(Role::Controller);
+                Ok(request)
+            }
+            Some(cred) => {
+                warn!(
+                    "Rejected unauthorized peer uid={} gid={} (expected uid={}{})",
+                    cred.uid(),
+                    cred.gid(),
+                    self.uid,
+                    self.gid
+                        .map_or(String::new(), |gid| format!(" or gid={}", gid))
+                );
+                Err(Status::permission_denied(
+                    "Only controller requests are accepted",
+                ))
+            }
+            None => {
+                warn!("Rejected request - no peer credentials");
+                Err(Status::permission_denied(
+                    "Cannot determine peer credentials",
+                ))
+            }
+        }
+    }
+}
+
+/// Why `SubmitSession` rejected a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// `dst_port` does not fit in 16 bits
+    InvalidDstPort,
+    /// `src_port` does not fit in 16 bits
+    InvalidSrcPort,
+    /// Destination IP is `0.0.0.0`
+    UnspecifiedDstIp,
+    /// The caller exceeded `grpc.submit_rate_limit`
+    RateLimited,
+    /// `src_mac` is wider than 48 bits or set on an any-source session
+    InvalidSrcMac,
+}
+
+impl RejectReason {
+    const ALL: [RejectReason; 5] = [
+        RejectReason::InvalidDstPort,
+        RejectReason::InvalidSrcPort,
+        RejectReason::UnspecifiedDstIp,
+        RejectReason::RateLimited,
+        RejectReason::InvalidSrcMac,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RejectReason::InvalidDstPort => "invalid_dst_port",
+            RejectReason::InvalidSrcPort => "invalid_src_port",
+            RejectReason::UnspecifiedDstIp => "unspecified_dst_ip",
+            RejectReason::RateLimited => "rate_limited",
+            RejectReason::InvalidSrcMac => "invalid_src_mac",
+        }
+    }
+}
+
+/// Minimum time between two reject warnings; rejects in between are counted
+/// and summarized in the next warning.
+const REJECT_WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Counts rejected `SubmitSession` requests by reason.
+///
+/// Warnings are rate limited so a controller flooding bad requests cannot
+/// drown the log.
+#[derive(Debug, Default)]
+pub struct RejectCounters {
+    counts: [AtomicU64; RejectReason::ALL.len()],
+    suppressed: AtomicU64,
+    last_warn: std::sync::Mutex<Option<Instant>>,
+}
+
+impl RejectCounters {
+    /// Returns how many requests were rejected for `reason` so far.
+    pub fn get(&self, reason: RejectReason) -> u64 {
+        self.counts[reason as usize].load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of every reason, as reported by `GetMapStats`.
+    fn all(&self) -> Vec<RejectCount> {
+        RejectReason::ALL
+            .iter()
+            .map(|r| RejectCount {
+                reason: r.as_str().to_string(),
+                count: self.get(*r),
+            })
+            .collect()
+    }
+
+    /// Counts a reject and logs it unless a warning went out recently.
+    fn record(&self, reason: RejectReason, detail: &str) {
+        self.record_at(reason, detail, Instant::now());
+    }
+
+    /// Like [`Self::record`] at a given time. Returns `true` if it logged.
+    fn record_at(&self, reason: RejectReason, detail: &str, now: Instant) -> bool {
+        self.counts[reason as usize].fetch_add(1, Ordering::Relaxed);
+
+        let mut last_warn = self.last_warn.lock().unwrap_or_else(|e| e.into_inner());
+        if last_warn.is_some_and(|last| now.duration_since(last) < REJECT_WARN_INTERVAL) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        *last_warn = Some(now);
+
+        let totals = RejectReason::ALL
+            .iter()
+            .map(|r| format!("{}={}", r.as_str(), self.get(*r)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        warn!(
+            "Rejected session request ({}): {} [{} similar suppressed, totals: {}]",
+            reason.as_str(),
+            detail,
+            self.suppressed.swap(0, Ordering::Relaxed),
+            totals
+        );
+        true
+    }
+}
+
+/// Default buffer between the monitor broadcast and each subscriber's stream.
+const DEFAULT_MONITOR_BUFFER: usize = 4;
+
+/// One of the `MonitorSessions` streams allowed at a time, given back when
+/// dropped.
+struct MonitorSlot(Arc<AtomicUsize>);
+
+impl MonitorSlot {
+    /// Takes a slot from the `active` count, or returns `None` if `max` are
+    /// already taken. A `max` of 0 means no limit.
+    fn acquire(active: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (max == 0 || count < max).then_some(count + 1)
+            })
+            .ok()?;
+        Some(Self(active.clone()))
+    }
+}
+
+impl Drop for MonitorSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Callers a [`SubmitLimiter`] tracks; new callers are refused beyond it.
+const SUBMIT_LIMITER_CALLERS: usize = 1024;
+
+/// Token bucket of one caller of a [`SubmitLimiter`].
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Tokens available at `now`, refilled at `rate` per second up to `rate`.
+    fn tokens_at(&self, now: Instant, rate: f64) -> f64 {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        (self.tokens + elapsed.as_secs_f64() * rate).min(rate)
+    }
+}
+
+/// Limits `SubmitSession` calls per caller, as named by [`peer_identity`].
+///
+/// Each caller may make `rate` calls per second on average and as many in a
+/// burst, so one controller hammering the RPC cannot keep the session map
+/// lock away from everything else.
+///
+/// Once [`SUBMIT_LIMITER_CALLERS`] callers are tracked, only those whose
+/// bucket refilled completely are forgotten. Any other new caller is refused
+/// rather than evicting an active one, so cycling through source IPs cannot
+/// reset a caller's budget.
+#[derive(Debug)]
+struct SubmitLimiter {
+    rate: f64,
+    buckets: std::sync::Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl SubmitLimiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            buckets: std::sync::Mutex::default(),
+        }
+    }
+
+    /// Takes a token from `caller`'s bucket. Returns `false` if it is empty.
+    fn allow(&self, caller: &str) -> bool {
+        self.allow_at(caller, Instant::now())
+    }
+
+    /// Like [`Self::allow`] at a given time.
+    fn allow_at(&self, caller: &str, now: Instant) -> bool {
+        let rate = self.rate;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= SUBMIT_LIMITER_CALLERS && !buckets.contains_key(caller) {
+            // A full bucket behaves like a new one, so it can go
+            buckets.retain(|_, bucket| bucket.tokens_at(now, rate) < rate);
+            if buckets.len() >= SUBMIT_LIMITER_CALLERS {
+                return false;
+            }
+        }
+        let bucket = buckets.entry(caller.to_string()).or_insert(TokenBucket {
+            tokens: rate,
+            refilled_at: now,
+        });
+        bucket.tokens = bucket.tokens_at(now, rate);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+/// Builds the `MonitorSessions` snapshot of `rules`, as returned by
+/// `Bpf::list_rules`.
+pub fn session_list(rules: Vec<(SessionKey, i64)>) -> SessionList {
+    SessionList {
+        sessions: rules
+            .into_iter()
+            .map(|(key, time_left)| Session {
+                src_ip: ip::to_host(key.src_ip),
+                dst_ip: ip::to_host(key.dest_ip),
+                dst_port: key.dest_port.into(),
+                time_left,
+                src_port: key.src_port.into(),
+            })
+            .collect(),
+        resync: false,
+        closing: false,
+    }
+}
+
+/// Receives the newest message still queued in `rx`, discarding older ones.
+/// Returns `None` if nothing is queued.
+fn latest<T: Clone>(rx: &mut broadcast::Receiver<T>) -> Option<T> {
+    let mut latest = None;
+    loop {
+        match rx.try_recv() {
+            Ok(msg) => latest = Some(msg),
+            // Overwritten while draining; newer messages are still queued
+            Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+            Err(_) => return latest,
+        }
+    }
+}
+
+/// Resolves once `shutdown` is set. Never resolves without a shutdown
+/// signal, or if its sender is dropped without setting it.
+async fn shutdown_requested(shutdown: Option<watch::Receiver<bool>>) {
+    if let Some(mut rx) = shutdown
+        && rx.wait_for(|closing| *closing).await.is_ok()
+    {
+        return;
+    }
+    std::future::pending().await
+}
+
 /// SessionManager service implementation that handles session add/remove and IP-change events.
+#[derive(Clone)]
 pub struct SessionManagerService {
     modify_rules: ModifyRulesFn,
     update_ip: UpdateIpFn,
+    /// Handlers of the RPCs below answer `UNIMPLEMENTED` while unset
+    flush_all: Option<FlushAllFn>,
+    modify_blocklist: Option<ModifyBlocklistFn>,
+    get_session: Option<GetSessionFn>,
+    map_stats: Option<MapStatsFn>,
+    prune_epochs: Option<PruneEpochsFn>,
     monitor_tx: broadcast::Sender<Result<SessionList, Status>>,
+    /// Capacity of each subscriber's stream buffer
+    monitor_buffer: usize,
+    /// Session lists skipped across all lagging subscribers
+    monitor_lagged: Arc<AtomicU64>,
+    /// Concurrent `MonitorSessions` streams allowed, 0 for no limit
+    max_monitors: usize,
+    /// `MonitorSessions` streams currently open
+    monitors: Arc<AtomicUsize>,
+    /// Set to `true` when the agent starts shutting down
+    shutdown: Option<watch::Receiver<bool>>,
+    rejects: Arc<RejectCounters>,
+    /// Receivers of an [`AuditEvent`] for every mutating RPC
+    audit_sinks: Vec<Arc<dyn AuditSink>>,
+    /// Settings reported by `GetConfig`
+    agent_config: AgentConfig,
+    /// Fills in `AgentConfig.enforcing`, left unset without it
+    is_enforcing: Option<IsEnforcingFn>,
+    /// Current controller IP for `AgentConfig.controller_ip`, which
+    /// otherwise reports the one configured at startup
+    controller_ip: Option<watch::Receiver<Ipv4Addr>>,
+    /// Reads the current sessions for a lagging subscriber's resync; without
+    /// it the newest queued snapshot is sent instead
+    list_sessions: Option<ListSessionsFn>,
+    /// Reject every mutating RPC, whoever calls it
+    read_only: bool,
+    /// Per-caller `SubmitSession` limit, unset for no limit
+    submit_limiter: Option<Arc<SubmitLimiter>>,
 }
 
 impl SessionManagerService {
@@ -92,46 +590,338 @@ impl SessionManagerService {
         Self {
             modify_rules,
             update_ip,
+            flush_all: None,
+            modify_blocklist: None,
+            get_session: None,
+            map_stats: None,
+            prune_epochs: None,
             monitor_tx,
+            monitor_buffer: DEFAULT_MONITOR_BUFFER,
+            monitor_lagged: Arc::default(),
+            max_monitors: 0,
+            monitors: Arc::default(),
+            shutdown: None,
+            rejects: Arc::default(),
+            audit_sinks: vec![Arc::new(LogSink)],
+            agent_config: AgentConfig::default(),
+            is_enforcing: None,
+            controller_ip: None,
+            list_sessions: None,
+            read_only: false,
+            submit_limiter: None,
+        }
+    }
+
+    /// Serves `FlushAll`, removing every session through `flush_all`.
+    pub fn with_flush_all(mut self, flush_all: FlushAllFn) -> Self {
+        self.flush_all = Some(flush_all);
+        self
+    }
+
+    /// Serves `BlockIp` and `UnblockIp`, changing the blocklist through
+    /// `modify_blocklist`.
+    pub fn with_blocklist(mut self, modify_blocklist: ModifyBlocklistFn) -> Self {
+        self.modify_blocklist = Some(modify_blocklist);
+        self
+    }
+
+    /// Serves `GetSession`, looking sessions up through `get_session`.
+    pub fn with_get_session(mut self, get_session: GetSessionFn) -> Self {
+        self.get_session = Some(get_session);
+        self
+    }
+
+    /// Serves `GetMapStats` with the figures `map_stats` reads.
+    pub fn with_map_stats(mut self, map_stats: MapStatsFn) -> Self {
+        self.map_stats = Some(map_stats);
+        self
+    }
+
+    /// Serves `PruneStaleEpochs`, removing sessions through `prune_epochs`.
+    pub fn with_prune_epochs(mut self, prune_epochs: PruneEpochsFn) -> Self {
+        self.prune_epochs = Some(prune_epochs);
+        self
+    }
+
+    /// Sets the capacity of each `MonitorSessions` subscriber's buffer.
+    pub fn with_monitor_buffer(mut self, size: usize) -> Self {
+        self.monitor_buffer = size.max(1);
+        self
+    }
+
+    /// Rejects `MonitorSessions` calls with `RESOURCE_EXHAUSTED` while `max`
+    /// streams are open. 0 means no limit.
+    pub fn with_max_monitors(mut self, max: usize) -> Self {
+        self.max_monitors = max;
+        self
+    }
+
+    /// Ends every `MonitorSessions` stream with a `closing` marker once
+    /// `shutdown` is set to `true`.
+    pub fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Also records audit events to `sink`, in addition to the log.
+    pub fn with_audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sinks.push(sink);
+        self
+    }
+
+    /// Rejects every mutating RPC with `PERMISSION_DENIED` when `read_only`
+    /// is set, leaving monitoring and lookups working.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Rejects `SubmitSession` calls with `RESOURCE_EXHAUSTED` once a caller
+    /// exceeds `rate` per second. 0 means no limit.
+    pub fn with_submit_rate_limit(mut self, rate: u32) -> Self {
+        self.submit_limiter = (rate > 0).then(|| Arc::new(SubmitLimiter::new(rate)));
+        self
+    }
+
+    /// Resyncs lagging `MonitorSessions` subscribers with the sessions
+    /// `list_sessions` reads at that moment.
+    pub fn with_list_sessions(mut self, list_sessions: ListSessionsFn) -> Self {
+        self.list_sessions = Some(list_sessions);
+        self
+    }
+
+    /// Reports `config` from `GetConfig`, with the enforcement mode read
+    /// through `is_enforcing` on every call.
+    pub fn with_config(mut self, config: &Config, is_enforcing: IsEnforcingFn) -> Self {
+        self.agent_config = agent_config(config);
+        self.is_enforcing = Some(is_enforcing);
+        self
+    }
+
+    /// Reports the controller IP last published on `controller_ip` from
+    /// `GetConfig`, so changes after startup show up there.
+    pub fn with_controller_ip(mut self, controller_ip: watch::Receiver<Ipv4Addr>) -> Self {
+        self.controller_ip = Some(controller_ip);
+        self
+    }
+
+    /// Returns how many session lists lagging `MonitorSessions` subscribers
+    /// have skipped so far.
+    pub fn monitor_lagged(&self) -> u64 {
+        self.monitor_lagged.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many `MonitorSessions` streams are open.
+    pub fn active_monitors(&self) -> usize {
+        self.monitors.load(Ordering::Acquire)
+    }
+
+    /// Returns the counters of rejected `SubmitSession` requests.
+    pub fn rejects(&self) -> &RejectCounters {
+        &self.rejects
+    }
+}
+
+impl SessionManagerService {
+    /// Accepts only requests an interceptor tagged with [`Role::Controller`],
+    /// and none in read-only mode.
+    ///
+    /// Requests without a role never passed through an interceptor, so they
+    /// are rejected rather than trusted. Rejections are audited under
+    /// `operation`.
+    fn require_controller<T>(&self, request: &Request<T>, operation: &str) -> Result<(), Status> {
+        if self.read_only {
+            warn!("Rejected mutating request in read-only mode");
+            self.audit(&peer_identity(request), operation, "-", "denied");
+            return Err(Status::permission_denied("Agent is in read-only mode"));
+        }
+        match request.extensions().get::<Role>() {
+            Some(Role::Controller) => Ok(()),
+            Some(Role::Monitor) => {
+                warn!("Rejected mutating request from monitoring client");
+                self.audit(&peer_identity(request), operation, "-", "denied");
+                Err(Status::permission_denied(
+                    "Monitoring clients may only call read-only methods",
+                ))
+            }
+            None => {
+                warn!("Rejected mutating request without a role");
+                self.audit(&peer_identity(request), operation, "-", "denied");
+                Err(Status::permission_denied(
+                    "Only controller requests are accepted",
+                ))
+            }
+        }
+    }
+
+    /// Hands one audit event for a mutating RPC to every sink.
+    ///
+    /// A sink that fails is logged but does not fail the RPC, which has
+    /// already been applied.
+    fn audit(&self, actor: &str, op: &str, tuple: &str, result: &str) {
+        let event = AuditEvent {
+            actor,
+            op,
+            tuple,
+            result,
+        };
+        for sink in &self.audit_sinks {
+            if let Err(e) = sink.record(&event) {
+                error!("Failed to record audit event: {:#}", e);
+            }
+        }
+    }
+
+    /// Adds (`block`) or removes a source IP from the blocklist.
+    async fn modify_blocklist(
+        &self,
+        block: bool,
+        request: Request<BlockRequest>,
+    ) -> Result<Response<Ack>, Status> {
+        let operation = if block { "block_ip" } else { "unblock_ip" };
+        self.require_controller(&request, operation)?;
+        let peer = peer_identity(&request);
+        let request = request.into_inner();
+        if request.ip == 0 {
+            warn!("Unspecified IP in blocklist request");
+            return Err(Status::invalid_argument("IP is required"));
         }
+        let ip = ip::from_host(request.ip);
+
+        let modify = handler(&self.modify_blocklist, operation)?.lock().await;
+        let success = match modify(block, ip) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to update blocklist for {}: {:#}", ip, e);
+                false
+            }
+        };
+        self.audit(
+            &peer,
+            operation,
+            &ip.to_string(),
+            if success { "ok" } else { "failed" },
+        );
+
+        Ok(Response::new(Ack {
+            success,
+            active_sessions: None,
+            previous_existed: None,
+            pruned: None,
+            ip_changes: Vec::new(),
+            flushed: None,
+        }))
     }
 }
 
 #[tonic::async_trait]
 impl SessionManager for SessionManagerService {
     async fn submit_session(&self, request: Request<LoginEvent>) -> Result<Response<Ack>, Status> {
+        self.require_controller(&request, "submit_session")?;
+        let peer = peer_identity(&request);
+
+        // Turn a flood away before it queues up on the map lock
+        if let Some(limiter) = &self.submit_limiter
+            && !limiter.allow(&peer)
+        {
+            self.rejects.record(RejectReason::RateLimited, &peer);
+            return Err(Status::resource_exhausted(
+                "Session submission rate limit exceeded",
+            ));
+        }
         let event = request.into_inner();
 
         // Validate port range to prevent overflow
         if event.dst_port > u16::MAX as u32 {
-            warn!("Invalid destination port: {}", event.dst_port);
+            self.rejects.record(
+                RejectReason::InvalidDstPort,
+                &format!("destination port {}", event.dst_port),
+            );
             return Err(Status::invalid_argument("Destination port out of range"));
         }
+        if event.src_port > u16::MAX as u32 {
+            self.rejects.record(
+                RejectReason::InvalidSrcPort,
+                &format!("source port {}", event.src_port),
+            );
+            return Err(Status::invalid_argument("Source port out of range"));
+        }
+
+        // 0.0.0.0 is never a real destination and usually means the field was
+        // not set. As a source it is ANY_SRC_IP and admits every source.
+        if event.dst_ip == 0 {
+            self.rejects.record(
+                RejectReason::UnspecifiedDstIp,
+                &format!("{} → {}", event.src_ip, event.dst_ip),
+            );
+            return Err(Status::invalid_argument("Destination IP is required"));
+        }
 
-        let dst_port = event.dst_port as u16;
+        // IPs arrive as host-order integers; SessionKey handles the conversion
+        // to the network byte order the XDP program compares against
+        let key = SessionKey {
+            src_ip: ip::from_host(event.src_ip),
+            dest_ip: ip::from_host(event.dst_ip),
+            dest_port: event.dst_port as u16,
+            src_port: event.src_port as u16,
+        };
+        if event.activate
+            && let Err(e) = key.check_src_mac(event.src_mac)
+        {
+            self.rejects
+                .record(RejectReason::InvalidSrcMac, &format!("{}: {:#}", key, e));
+            return Err(Status::invalid_argument(format!("{:#}", e)));
+        }
 
         debug!(
-            "Session request (activate={}): {} → {}:{}",
-            event.activate, event.src_ip, event.dst_ip, dst_port
+            "Session request (activate={}, epoch={}): {}",
+            event.activate, event.epoch, key
         );
+        let operation = if event.activate {
+            "activate_session"
+        } else {
+            "deactivate_session"
+        };
 
         // Add or remove session rule
         let add_rule = self.modify_rules.lock().await;
-        let success = match add_rule(event.activate, event.dst_ip, event.src_ip, dst_port) {
-            Ok(_) => {
+        let reply = match add_rule(event.activate, key, event.epoch, event.src_mac) {
+            Ok(change) => {
                 debug!(
-                    "Session modified (is_active: {}): {} → {}:{}",
-                    event.activate, event.src_ip, event.dst_ip, dst_port
+                    "Session modified (is_active: {}, existed: {}): {}",
+                    event.activate, change.previous_existed, key
                 );
-                true
+                let result = match (event.activate, change.previous_existed) {
+                    (true, false) => "added",
+                    (true, true) => "refreshed",
+                    (false, true) => "removed",
+                    (false, false) => "absent",
+                };
+                self.audit(&peer, operation, &key.to_string(), result);
+                Ack {
+                    success: true,
+                    active_sessions: Some(
+                        u32::try_from(change.active_sessions).unwrap_or(u32::MAX),
+                    ),
+                    previous_existed: Some(change.previous_existed),
+                    pruned: None,
+                    ip_changes: Vec::new(),
+                    flushed: None,
+                }
             }
             Err(e) => {
                 error!("Failed to modify session: {}", e);
-                false
+                self.audit(&peer, operation, &key.to_string(), "failed");
+                Ack {
+                    success: false,
+                    active_sessions: None,
+                    previous_existed: None,
+                    pruned: None,
+                    ip_changes: Vec::new(),
+                    flushed: None,
+                }
             }
         };
-
-        let reply = Ack { success };
         Ok(Response::new(reply))
     }
 
@@ -142,24 +932,86 @@ impl SessionManager for SessionManagerService {
         &self,
         _: Request<Empty>,
     ) -> Result<Response<Self::MonitorSessionsStream>, Status> {
+        let Some(slot) = MonitorSlot::acquire(&self.monitors, self.max_monitors) else {
+            warn!(
+                "Rejected session monitoring stream: {} already open",
+                self.max_monitors
+            );
+            return Err(Status::resource_exhausted(
+                "Too many session monitoring streams",
+            ));
+        };
         debug!("Starting session monitoring stream");
 
         let mut broadcast_rx = self.monitor_tx.subscribe();
-        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let (tx, rx) = tokio::sync::mpsc::channel(self.monitor_buffer);
+        let monitor_lagged = self.monitor_lagged.clone();
+        let list_sessions = self.list_sessions.clone();
+        let closing = shutdown_requested(self.shutdown.clone());
         tokio::spawn(async move {
+            // Held until the stream ends, whichever way
+            let _slot = slot;
+            tokio::pin!(closing);
+            let mut subscriber_lagged = 0u64;
             loop {
-                match broadcast_rx.recv().await {
-                    Ok(msg) => {
-                        if tx.send(msg).await.is_err() {
-                            break;
-                        }
+                let received = tokio::select! {
+                    received = broadcast_rx.recv() => received,
+                    // The client went away; free its slot now rather than
+                    // at the next session list
+                    () = tx.closed() => break,
+                    _ = &mut closing => {
+                        // Tell the client this is a planned close, not a failure
+                        debug!("Closing session monitoring stream for shutdown");
+                        let marker = SessionList {
+                            closing: true,
+                            ..Default::default()
+                        };
+                        let _ = tx.send(Ok(marker)).await;
+                        break;
                     }
+                };
+                let msg = match received {
+                    Ok(msg) => msg,
                     Err(broadcast::error::RecvError::Lagged(skipped)) => {
-                        warn!("Monitor stream lagged, skipped {} messages", skipped);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        break;
+                        subscriber_lagged += skipped;
+                        monitor_lagged.fetch_add(skipped, Ordering::Relaxed);
+                        warn!(
+                            "Monitor stream lagged, skipped {} messages ({} for this subscriber), resyncing",
+                            skipped, subscriber_lagged
+                        );
+                        // Every list is a full snapshot, so the queued ones
+                        // are stale: send the current sessions instead, flagged
+                        // so the client replaces its view
+                        let queued = latest(&mut broadcast_rx);
+                        let snapshot = match &list_sessions {
+                            Some(list_sessions) => {
+                                let list_sessions = list_sessions.lock().await;
+                                match list_sessions() {
+                                    Ok(rules) => Some(Ok(session_list(rules))),
+                                    Err(e) => {
+                                        warn!(
+                                            "Failed to list sessions for resync, sending the last snapshot: {:#}",
+                                            e
+                                        );
+                                        queued
+                                    }
+                                }
+                            }
+                            None => queued,
+                        };
+                        match snapshot {
+                            Some(Ok(mut list)) => {
+                                list.resync = true;
+                                Ok(list)
+                            }
+                            Some(Err(status)) => Err(status),
+                            None => continue,
+                        }
                     }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if tx.send(msg).await.is_err() {
+                    break;
                 }
             }
         });
@@ -169,13 +1021,49 @@ impl SessionManager for SessionManagerService {
         )))
     }
 
+    async fn get_session(&self, request: Request<LoginEvent>) -> Result<Response<Session>, Status> {
+        let event = request.into_inner();
+        if event.dst_port > u16::MAX as u32 || event.src_port > u16::MAX as u32 {
+            return Err(Status::invalid_argument("Port out of range"));
+        }
+        if event.dst_ip == 0 {
+            return Err(Status::invalid_argument("Destination IP is required"));
+        }
+
+        let key = SessionKey {
+            src_ip: ip::from_host(event.src_ip),
+            dest_ip: ip::from_host(event.dst_ip),
+            dest_port: event.dst_port as u16,
+            src_port: event.src_port as u16,
+        };
+        debug!("Session lookup: {}", key);
+
+        let get_session = handler(&self.get_session, "get_session")?.lock().await;
+        match get_session(key) {
+            Ok(Some(time_left)) => Ok(Response::new(Session {
+                src_ip: event.src_ip,
+                dst_ip: event.dst_ip,
+                dst_port: event.dst_port,
+                time_left,
+                src_port: event.src_port,
+            })),
+            Ok(None) => Err(Status::not_found(format!("No session for {}", key))),
+            Err(e) => {
+                error!("Failed to look up session {}: {:#}", key, e);
+                Err(Status::internal("BPF error"))
+            }
+        }
+    }
+
     async fn ip_change(&self, request: Request<IpChangeList>) -> Result<Response<Ack>, Status> {
+        self.require_controller(&request, "ip_change")?;
+        let peer = peer_identity(&request);
         let ip_changes = request.into_inner();
 
         debug!("Received {} IP change events", ip_changes.ip_changes.len());
 
         let mut total_updated = 0;
-        let mut has_errors = false;
+        let mut results = Vec::with_capacity(ip_changes.ip_changes.len());
 
         // Process each IP change event
         for change in ip_changes.ip_changes {
@@ -185,8 +1073,14 @@ impl SessionManager for SessionManagerService {
             );
 
             // Update all sessions using the old IP to use the new IP
+            let resource = format!(
+                "{} → {}",
+                ip::from_host(change.old_ip),
+                ip::from_host(change.new_ip)
+            );
             match self.update_ip.lock().await(change.old_ip, change.new_ip) {
                 Ok(count) => {
+                    self.audit(&peer, "ip_change", &resource, &format!("updated {}", count));
                     if count > 0 {
                         info!(
                             "Updated {} sessions: old IP {} → new IP {}",
@@ -196,13 +1090,27 @@ impl SessionManager for SessionManagerService {
                     } else {
                         debug!("No sessions found for old IP {}", change.old_ip);
                     }
+                    results.push(IpChangeResult {
+                        old_ip: change.old_ip,
+                        new_ip: change.new_ip,
+                        success: true,
+                        updated: count as u64,
+                        error: String::new(),
+                    });
                 }
                 Err(e) => {
                     error!(
                         "Failed to update IP {} → {}: {}",
                         change.old_ip, change.new_ip, e
                     );
-                    has_errors = true;
+                    self.audit(&peer, "ip_change", &resource, "failed");
+                    results.push(IpChangeResult {
+                        old_ip: change.old_ip,
+                        new_ip: change.new_ip,
+                        success: false,
+                        updated: 0,
+                        error: "BPF error".to_string(),
+                    });
                 }
             }
         }
@@ -212,61 +1120,742 @@ impl SessionManager for SessionManagerService {
         }
 
         let reply = Ack {
-            success: !has_errors,
+            success: results.iter().all(|result| result.success),
+            active_sessions: None,
+            previous_existed: None,
+            pruned: None,
+            ip_changes: results,
+            flushed: None,
         };
         Ok(Response::new(reply))
     }
-}
 
-/// Starts the gRPC server with mTLS authentication.
-pub async fn start_grpc_server(
-    config: &Config,
-    addr: SocketAddr,
-    modify_rules: ModifyRulesFn,
-    update_ip: UpdateIpFn,
-    monitor_tx: broadcast::Sender<Result<SessionList, Status>>,
-) -> Result<()> {
-    let service = SessionManagerService::new(modify_rules, update_ip, monitor_tx);
+    async fn flush_all(&self, request: Request<Empty>) -> Result<Response<Ack>, Status> {
+        self.require_controller(&request, "flush_all")?;
+        let peer = peer_identity(&request);
+        warn!("Flush of all sessions requested");
+
+        let flush = handler(&self.flush_all, "flush_all")?.lock().await;
+        let flushed = match flush() {
+            Ok(count) => {
+                warn!("Flushed {} sessions, all access revoked", count);
+                self.audit(&peer, "flush_all", "*", &format!("flushed {}", count));
+                Some(count as u64)
+            }
+            Err(e) => {
+                error!("Failed to flush sessions: {}", e);
+                self.audit(&peer, "flush_all", "*", "failed");
+                None
+            }
+        };
 
-    let interceptor = AuthInterceptor {
-        controller_ip: config.controller_ip,
-    };
+        Ok(Response::new(Ack {
+            success: flushed.is_some(),
+            active_sessions: None,
+            previous_existed: None,
+            pruned: None,
+            ip_changes: Vec::new(),
+            flushed,
+        }))
+    }
 
-    debug!("Loading TLS certificates...");
-    let cert = fs::read_to_string(&config.cert_file).context("Failed to read certificate")?;
-    let key = fs::read_to_string(&config.key_file).context("Failed to read private key")?;
-    let server_identity = Identity::from_pem(cert, key);
+    async fn block_ip(&self, request: Request<BlockRequest>) -> Result<Response<Ack>, Status> {
+        self.modify_blocklist(true, request).await
+    }
 
-    let ca_pem = fs::read_to_string(&config.ca_file).context("Failed to read CA certificate")?;
-    let client_ca_cert = Certificate::from_pem(ca_pem);
+    async fn unblock_ip(&self, request: Request<BlockRequest>) -> Result<Response<Ack>, Status> {
+        self.modify_blocklist(false, request).await
+    }
 
-    let tls_config = ServerTlsConfig::new()
-        .identity(server_identity)
-        .client_ca_root(client_ca_cert);
+    async fn prune_stale_epochs(
+        &self,
+        request: Request<PruneRequest>,
+    ) -> Result<Response<Ack>, Status> {
+        self.require_controller(&request, "prune_stale_epochs")?;
+        let peer = peer_identity(&request);
+        let epoch = request.into_inner().epoch;
+        let tuple = format!("epoch {}", epoch);
+
+        let prune = handler(&self.prune_epochs, "prune_stale_epochs")?
+            .lock()
+            .await;
+        let reply = match prune(epoch) {
+            Ok(count) => {
+                self.audit(
+                    &peer,
+                    "prune_stale_epochs",
+                    &tuple,
+                    &format!("pruned {}", count),
+                );
+                Ack {
+                    success: true,
+                    active_sessions: None,
+                    previous_existed: None,
+                    pruned: Some(count as u64),
+                    ip_changes: Vec::new(),
+                    flushed: None,
+                }
+            }
+            Err(e) => {
+                error!("Failed to prune sessions outside epoch {}: {:#}", epoch, e);
+                self.audit(&peer, "prune_stale_epochs", &tuple, "failed");
+                Ack {
+                    success: false,
+                    active_sessions: None,
+                    previous_existed: None,
+                    pruned: None,
+                    ip_changes: Vec::new(),
+                    flushed: None,
+                }
+            }
+        };
+        Ok(Response::new(reply))
+    }
 
-    info!("gRPC server starting with mTLS on {}", addr);
-    debug!("Only accepting requests from: {}", config.controller_ip);
+    async fn get_map_stats(&self, _: Request<Empty>) -> Result<Response<MapStats>, Status> {
+        let map_stats = handler(&self.map_stats, "get_map_stats")?.lock().await;
+        match map_stats() {
+            Ok((used, capacity)) => {
+                debug!("Map stats requested: {}/{}", used, capacity);
+                Ok(Response::new(MapStats {
+                    used: used as u64,
+                    capacity: capacity as u64,
+                    rejected_sessions: self.rejects.all(),
+                    monitor_lagged: self.monitor_lagged(),
+                }))
+            }
+            Err(e) => {
+                error!("Failed to read session map stats: {:#}", e);
+                Err(Status::internal("BPF error"))
+            }
+        }
+    }
 
-    Server::builder()
-        .tls_config(tls_config)?
-        .add_service(SessionManagerServer::with_interceptor(service, interceptor))
-        .serve(addr)
-        .await
-        .map_err(|e| anyhow!("gRPC server error: {}", e))?;
+    async fn get_version(&self, _: Request<Empty>) -> Result<Response<VersionInfo>, Status> {
+        debug!("Version requested");
+        Ok(Response::new(version::version_info()))
+    }
 
-    Ok(())
+    async fn get_config(&self, _: Request<Empty>) -> Result<Response<AgentConfig>, Status> {
+        debug!("Configuration requested");
+        let mut config = self.agent_config.clone();
+        if let Some(controller_ip) = &self.controller_ip {
+            config.controller_ip = ip::to_host(*controller_ip.borrow());
+        }
+        if let Some(is_enforcing) = &self.is_enforcing {
+            let is_enforcing = is_enforcing.lock().await;
+            match is_enforcing() {
+                Ok(enforcing) => config.enforcing = Some(enforcing),
+                Err(e) => warn!("Failed to read enforcement mode: {:#}", e),
+            }
+        }
+        Ok(Response::new(config))
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-    use tonic::service::Interceptor;
+/// Converts the settings worth comparing against the controller's view into
+/// an [`AgentConfig`]. `enforcing` is left unset; it changes at runtime.
+fn agent_config(config: &Config) -> AgentConfig {
+    AgentConfig {
+        iface: config.iface_name.clone(),
+        attach_hook: config.attach_hook.to_string(),
+        controller_ip: ip::to_host(config.controller_ip),
+        controller_port: config.controller_port.into(),
+        controller_extra_ports: config
+            .controller_extra_ports
+            .iter()
+            .map(|&port| port.into())
+            .collect(),
+        controller_extra_ips: config
+            .controller_extra_ips
+            .iter()
+            .map(|&addr| ip::to_host(addr))
+            .collect(),
+        rule_timeout_ns: config.rule_timeout_ns,
+        lazy_update_timeout_ns: config.lazy_update_timeout,
+        max_session_age_ns: config.max_session_age_ns,
+        cleanup_interval_sec: config.cleanup_interval_sec,
+        enforcing: None,
+        enforcement_grace_sec: config.enforcement_grace_sec,
+        strict_tcp: config.strict_tcp,
+        bind_src_mac: config.bind_src_mac,
+        fragment_policy: config.fragment_policy.to_string(),
+        always_allow_ports: config
+            .always_allow_ports
+            .iter()
+            .map(|&port| port.into())
+            .collect(),
+        max_conns_per_src: config.max_conns_per_src,
+        grpc_port: config.grpc_server_port.into(),
+        read_only: config.grpc_read_only,
+        max_monitors: u32::try_from(config.max_monitors).unwrap_or(u32::MAX),
+        submit_rate_limit: config.grpc_submit_rate_limit,
+    }
+}
+
+/// TLS settings and audit file of the gRPC server.
+///
+/// Loaded along with the listener before the XDP program is attached, so an
+/// expired or mismatched certificate or an audit file that cannot be opened
+/// fails startup instead of leaving the host enforcing without a control
+/// plane.
+pub struct ServerSetup {
+    /// mTLS settings; `None` on the Unix socket, which does not use TLS
+    tls: Option<ServerTlsConfig>,
+    /// `grpc.audit_file` sink, if one is configured
+    audit_file: Option<Arc<dyn AuditSink>>,
+}
+
+impl ServerSetup {
+    /// Loads and validates the TLS material unless serving on a Unix socket,
+    /// and opens `grpc.audit_file`.
+    pub fn load(config: &Config, uds: bool) -> Result<Self> {
+        if !uds && config.grpc_proxy_protocol && config.grpc_trusted_proxies.is_empty() {
+            bail!("grpc.proxy_protocol requires grpc.trusted_proxies");
+        }
+        let tls = if uds { None } else { Some(load_tls(config)?) };
+        let audit_file = match &config.grpc_audit_file {
+            Some(path) => {
+                let sink: Arc<dyn AuditSink> = Arc::new(FileSink::open(path)?);
+                info!("Writing audit records to {}", path.display());
+                Some(sink)
+            }
+            None => None,
+        };
+        Ok(Self { tls, audit_file })
+    }
+
+    /// Adds the audit file sink, if any, to `service`.
+    fn apply(&self, service: SessionManagerService) -> SessionManagerService {
+        match &self.audit_file {
+            Some(sink) => service.with_audit_sink(sink.clone()),
+            None => service,
+        }
+    }
+}
+
+/// Reads the server certificate, key and client CA, and checks they belong
+/// together before any client connects.
+fn load_tls(config: &Config) -> Result<ServerTlsConfig> {
+    debug!("Loading TLS certificates...");
+    let cert = fs::read_to_string(&config.cert_file).context("Failed to read certificate")?;
+    let key = fs::read_to_string(&config.key_file).context("Failed to read private key")?;
+    let ca_pem = fs::read_to_string(&config.ca_file).context("Failed to read CA certificate")?;
+
+    // Catch a bad cert/key/CA combination now rather than at the first handshake
+    certs::validate_server_certs(cert.as_bytes(), key.as_bytes(), ca_pem.as_bytes())
+        .context("Invalid TLS certificates")?;
+
+    Ok(ServerTlsConfig::new()
+        .identity(Identity::from_pem(cert, key))
+        .client_ca_root(Certificate::from_pem(ca_pem)))
+}
+
+/// Sockets the gRPC server will serve on, bound before the XDP program is
+/// attached so the agent never enforces without a reachable control plane.
+pub enum GrpcListener {
+    /// One listener per `grpc.workers`, for [`start_grpc_server`]
+    Tcp(Vec<TcpListener>),
+    /// Unix domain socket and its path, for [`start_grpc_server_uds`]
+    Unix(UnixListener, PathBuf),
+}
+
+impl GrpcListener {
+    /// Binds the Unix socket at `uds` if given, else `grpc.server_port` on
+    /// every interface, retrying a busy port up to `grpc.bind_attempts` times.
+    pub async fn bind(config: &Config, uds: Option<&Path>) -> Result<Self> {
+        if let Some(path) = uds {
+            return Ok(Self::Unix(bind_uds(path)?, path.to_path_buf()));
+        }
+
+        let addr = SocketAddr::from(([0, 0, 0, 0], config.grpc_server_port));
+        let attempts = config.grpc_bind_attempts;
+        let reuseport = config.grpc_workers > 1;
+        let keepalive = keepalive_interval(config);
+        let mut listeners = Vec::with_capacity(config.grpc_workers.max(1));
+        for _ in 0..config.grpc_workers.max(1) {
+            listeners.push(
+                bind_with_retry(addr, attempts, || bind_listener(addr, reuseport, keepalive))
+                    .await?,
+            );
+        }
+        Ok(Self::Tcp(listeners))
+    }
+}
+
+/// Starts the gRPC server with mTLS authentication on `listeners`, one
+/// worker each, using the TLS settings loaded into `setup`.
+/// Only requests from the IP currently held in `controller_ip` are accepted,
+/// plus read-only requests from the configured monitoring client.
+pub async fn start_grpc_server(
+    config: &Config,
+    setup: ServerSetup,
+    listeners: Vec<TcpListener>,
+    controller_ip: watch::Receiver<Ipv4Addr>,
+    modify_rules: ModifyRulesFn,
+    update_ip: UpdateIpFn,
+    flush_all: FlushAllFn,
+    modify_blocklist: ModifyBlocklistFn,
+    get_session: GetSessionFn,
+    map_stats: MapStatsFn,
+    prune_epochs: PruneEpochsFn,
+    is_enforcing: IsEnforcingFn,
+    list_sessions: ListSessionsFn,
+    monitor_tx: broadcast::Sender<Result<SessionList, Status>>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let service = SessionManagerService::new(modify_rules, update_ip, monitor_tx)
+        .with_flush_all(flush_all)
+        .with_blocklist(modify_blocklist)
+        .with_get_session(get_session)
+        .with_map_stats(map_stats)
+        .with_prune_epochs(prune_epochs)
+        .with_monitor_buffer(config.monitor_buffer_size)
+        .with_max_monitors(config.max_monitors)
+        .with_submit_rate_limit(config.grpc_submit_rate_limit)
+        .with_shutdown(shutdown.clone())
+        .with_config(config, is_enforcing)
+        .with_controller_ip(controller_ip.clone())
+        .with_list_sessions(list_sessions)
+        .with_read_only(config.grpc_read_only);
+    let service = setup.apply(service);
+    let tls_config = setup
+        .tls
+        .ok_or_else(|| anyhow!("TLS settings not loaded for the TCP gRPC server"))?;
+
+    let interceptor = AuthInterceptor {
+        controller_ip,
+        controller_net: config.controller_net,
+        extra_controllers: config.controller_extra_ips.iter().copied().collect(),
+        monitor_ip: config.grpc_monitor_ip,
+        monitor_cn: config.grpc_monitor_cn.clone(),
+    };
+
+    let addr = listeners
+        .first()
+        .ok_or_else(|| anyhow!("No gRPC listener to serve on"))?
+        .local_addr()
+        .context("Failed to read gRPC listener address")?;
+    info!("gRPC server starting with mTLS on {}", addr);
+    debug!(
+        "Only accepting requests from: {}",
+        interceptor.describe_controllers(*interceptor.controller_ip.borrow())
+    );
+    if let Some(ip) = interceptor.monitor_ip {
+        info!("Accepting read-only monitoring requests from {}", ip);
+    }
+    if let Some(cn) = &interceptor.monitor_cn {
+        info!(
+            "Accepting read-only monitoring requests with certificate CN {}",
+            cn
+        );
+    }
+    let proxy_protocol = config.grpc_proxy_protocol;
+    let trusted_proxies = config.grpc_trusted_proxies.clone();
+    if proxy_protocol {
+        info!(
+            "Taking client addresses from PROXY protocol headers sent by {}",
+            trusted_proxies
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    if listeners.len() == 1 {
+        let listener = listeners.into_iter().next().expect("One listener");
+        let router = server_builder(config)
+            .tls_config(tls_config)?
+            .add_service(SessionManagerServer::with_interceptor(service, interceptor));
+        serve_tcp(router, listener, proxy_protocol, trusted_proxies, shutdown)
+            .await
+            .map_err(|e| anyhow!("gRPC server error: {}", e))?;
+        return Ok(());
+    }
+
+    // One listener per worker on the same port; the kernel spreads incoming
+    // connections across them
+    info!("Using {} gRPC acceptors with SO_REUSEPORT", listeners.len());
+    let mut workers = JoinSet::new();
+    for listener in listeners {
+        let router = server_builder(config)
+            .tls_config(tls_config.clone())?
+            .add_service(SessionManagerServer::with_interceptor(
+                service.clone(),
+                interceptor.clone(),
+            ));
+        workers.spawn(serve_tcp(
+            router,
+            listener,
+            proxy_protocol,
+            trusted_proxies.clone(),
+            shutdown.clone(),
+        ));
+    }
+
+    // Workers return together on shutdown; one failing takes the whole
+    // server down
+    while let Some(res) = workers.join_next().await {
+        res.context("gRPC worker panicked")?
+            .map_err(|e| anyhow!("gRPC server error: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Failed TCP keepalive probes after which the kernel drops a connection.
+const TCP_KEEPALIVE_PROBES: u32 = 3;
+
+/// `grpc.keepalive_interval_sec` as a duration, or `None` when disabled.
+fn keepalive_interval(config: &Config) -> Option<Duration> {
+    (config.grpc_keepalive_interval_sec > 0)
+        .then(|| Duration::from_secs(config.grpc_keepalive_interval_sec))
+}
+
+/// Server builder with HTTP/2 keepalive pings configured, so connections
+/// whose peer vanished are closed along with their `MonitorSessions`
+/// streams.
+fn server_builder(config: &Config) -> Server {
+    Server::builder()
+        .http2_keepalive_interval(keepalive_interval(config))
+        .http2_keepalive_timeout(Some(Duration::from_secs(config.grpc_keepalive_timeout_sec)))
+}
+
+/// Serves `router` on `listener` until shutdown. With `proxy_protocol`, every
+/// connection must come from one of `trusted_proxies` and start with a PROXY
+/// protocol v2 header, whose source address replaces the peer address seen
+/// by the interceptor.
+async fn serve_tcp(
+    router: Router,
+    listener: TcpListener,
+    proxy_protocol: bool,
+    trusted_proxies: Vec<Ipv4Net>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<(), tonic::transport::Error> {
+    let shutdown = shutdown_requested(Some(shutdown));
+    if proxy_protocol {
+        router
+            .serve_with_incoming_shutdown(
+                proxy_protocol::incoming(listener, trusted_proxies),
+                shutdown,
+            )
+            .await
+    } else {
+        router
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+            .await
+    }
+}
+
+/// Delay before the first bind retry; doubled after every failure.
+const BIND_RETRY_INITIAL: Duration = Duration::from_millis(500);
+
+/// Upper bound for the delay between bind retries.
+const BIND_RETRY_MAX: Duration = Duration::from_secs(10);
+
+/// Calls `bind` up to `attempts` times, backing off exponentially between
+/// transient failures. Permanent failures are returned immediately.
+async fn bind_with_retry<T>(
+    addr: SocketAddr,
+    attempts: u32,
+    mut bind: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut delay = BIND_RETRY_INITIAL;
+    let mut attempt = 1;
+    loop {
+        match bind() {
+            Ok(listener) => return Ok(listener),
+            Err(e) if attempt < attempts && is_transient_bind_error(&e) => {
+                warn!(
+                    "{:#}, retrying in {:?} (attempt {}/{})",
+                    e, delay, attempt, attempts
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(BIND_RETRY_MAX);
+                attempt += 1;
+            }
+            Err(e) if is_transient_bind_error(&e) => {
+                return Err(e.context(format!(
+                    "gRPC port {} still unavailable after {} attempts",
+                    addr, attempts
+                )));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns `true` for bind failures that usually clear up on their own, such
+/// as a previous instance's socket lingering in TIME_WAIT.
+fn is_transient_bind_error(e: &anyhow::Error) -> bool {
+    matches!(
+        e.root_cause()
+            .downcast_ref::<io::Error>()
+            .map(io::Error::kind),
+        Some(io::ErrorKind::AddrInUse | io::ErrorKind::AddrNotAvailable)
+    )
+}
+
+/// Binds a TCP listener on `addr`. With `reuseport`, `SO_REUSEPORT` is set
+/// so several listeners can share the address.
+///
+/// With `keepalive`, TCP keepalive is enabled with that idle time and probe
+/// interval. The listener is served through our own stream, so tonic's
+/// `tcp_keepalive` does not apply; accepted sockets inherit the options
+/// from the listener instead.
+fn bind_listener(
+    addr: SocketAddr,
+    reuseport: bool,
+    keepalive: Option<Duration>,
+) -> Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()
+    } else {
+        TcpSocket::new_v6()
+    }
+    .context("Failed to create gRPC socket")?;
+    socket.set_reuseaddr(true)?;
+    if reuseport {
+        socket
+            .set_reuseport(true)
+            .context("Failed to enable SO_REUSEPORT")?;
+    }
+    if let Some(interval) = keepalive {
+        set_tcp_keepalive(&socket, interval).context("Failed to enable TCP keepalive")?;
+    }
+    socket
+        .bind(addr)
+        .with_context(|| format!("Failed to bind gRPC listener on {}", addr))?;
+    socket
+        .listen(1024)
+        .context("Failed to listen on gRPC socket")
+}
+
+/// Enables TCP keepalive on `socket`, probing after `interval` of silence and
+/// every `interval` after that.
+fn set_tcp_keepalive(socket: &impl AsFd, interval: Duration) -> nix::Result<()> {
+    let secs = u32::try_from(interval.as_secs()).unwrap_or(u32::MAX);
+    setsockopt(socket, sockopt::KeepAlive, &true)?;
+    setsockopt(socket, sockopt::TcpKeepIdle, &secs)?;
+    setsockopt(socket, sockopt::TcpKeepInterval, &secs)?;
+    setsockopt(socket, sockopt::TcpKeepCount, &TCP_KEEPALIVE_PROBES)
+}
+
+/// Binds a Unix domain socket at `path`, replacing a stale one left behind
+/// by a previous run.
+fn bind_uds(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind Unix socket {}", path.display()))
+}
+
+/// Starts the gRPC server on a Unix domain socket bound at `path`.
+///
+/// TLS is not used here; access is restricted by the socket's filesystem
+/// permissions and by checking the peer's credentials on every request.
+pub async fn start_grpc_server_uds(
+    config: &Config,
+    setup: ServerSetup,
+    listener: UnixListener,
+    path: &Path,
+    modify_rules: ModifyRulesFn,
+    update_ip: UpdateIpFn,
+    flush_all: FlushAllFn,
+    modify_blocklist: ModifyBlocklistFn,
+    get_session: GetSessionFn,
+    map_stats: MapStatsFn,
+    prune_epochs: PruneEpochsFn,
+    is_enforcing: IsEnforcingFn,
+    list_sessions: ListSessionsFn,
+    monitor_tx: broadcast::Sender<Result<SessionList, Status>>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let service = SessionManagerService::new(modify_rules, update_ip, monitor_tx)
+        .with_flush_all(flush_all)
+        .with_blocklist(modify_blocklist)
+        .with_get_session(get_session)
+        .with_map_stats(map_stats)
+        .with_prune_epochs(prune_epochs)
+        .with_monitor_buffer(config.monitor_buffer_size)
+        .with_max_monitors(config.max_monitors)
+        .with_submit_rate_limit(config.grpc_submit_rate_limit)
+        .with_shutdown(shutdown.clone())
+        .with_config(config, is_enforcing)
+        .with_list_sessions(list_sessions)
+        .with_read_only(config.grpc_read_only);
+    let service = setup.apply(service);
+    let interceptor = PeerCredInterceptor::current_user(config.grpc_uds_allow_gid);
+
+    info!("gRPC server starting on unix:{}", path.display());
+    match interceptor.gid {
+        Some(gid) => debug!(
+            "Only accepting requests from uid={} or gid={}",
+            interceptor.uid, gid
+        ),
+        None => debug!("Only accepting requests from uid={}", interceptor.uid),
+    }
+
+    server_builder(config)
+        .add_service(SessionManagerServer::with_interceptor(service, interceptor))
+        .serve_with_incoming_shutdown(
+            UnixListenerStream::new(listener),
+            shutdown_requested(Some(shutdown)),
+        )
+        .await
+        .map_err(|e| anyhow!("gRPC server error: {}", e))?;
+
+    Ok(())
+}
+
+/// Request as [`AuthInterceptor`] passes it on for the controller, for
+/// calling the service in-process.
+#[cfg(test)]
+pub(crate) fn controller_request<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.extensions_mut().insert(Role::Controller);
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::socket::getsockopt;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use tonic::{service::Interceptor, transport::server::TcpConnectInfo};
+
+    /// A service whose handlers all succeed without doing anything, and the
+    /// sender of its `MonitorSessions` broadcasts.
+    fn test_service() -> (
+        SessionManagerService,
+        broadcast::Sender<Result<SessionList, Status>>,
+    ) {
+        let (tx, _) = broadcast::channel(4);
+        let service = SessionManagerService::new(
+            Arc::new(Mutex::new(|_, _, _, _| Ok(RuleChange::default()))),
+            Arc::new(Mutex::new(|_, _| Ok(0))),
+            tx.clone(),
+        )
+        .with_flush_all(Arc::new(Mutex::new(|| Ok(0))))
+        .with_blocklist(Arc::new(Mutex::new(|_, _| Ok(()))))
+        .with_get_session(Arc::new(Mutex::new(|_| Ok(None))))
+        .with_map_stats(Arc::new(Mutex::new(|| Ok((0, 10240)))))
+        .with_prune_epochs(Arc::new(Mutex::new(|_| Ok(0))));
+        (service, tx)
+    }
+
+    #[tokio::test]
+    async fn test_reuseport_listeners_share_port() {
+        let first = bind_listener(SocketAddr::from(([127, 0, 0, 1], 0)), true, None).unwrap();
+        let addr = first.local_addr().unwrap();
+
+        let second = bind_listener(addr, true, None).unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+    }
+
+    #[tokio::test]
+    async fn test_accepted_sockets_inherit_keepalive() {
+        let listener = bind_listener(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            false,
+            Some(Duration::from_secs(15)),
+        )
+        .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        assert!(getsockopt(&accepted, sockopt::KeepAlive).unwrap());
+        assert_eq!(getsockopt(&accepted, sockopt::TcpKeepIdle).unwrap(), 15);
+        assert_eq!(getsockopt(&accepted, sockopt::TcpKeepInterval).unwrap(), 15);
+        assert_eq!(
+            getsockopt(&accepted, sockopt::TcpKeepCount).unwrap(),
+            TCP_KEEPALIVE_PROBES
+        );
+
+        let plain = bind_listener(SocketAddr::from(([127, 0, 0, 1], 0)), false, None).unwrap();
+        assert!(!getsockopt(&plain, sockopt::KeepAlive).unwrap());
+    }
+
+    #[test]
+    fn test_keepalive_interval_from_config() {
+        let config = Config {
+            grpc_keepalive_interval_sec: 30,
+            ..Config::default()
+        };
+        assert_eq!(keepalive_interval(&config), Some(Duration::from_secs(30)));
+        let config = Config {
+            grpc_keepalive_interval_sec: 0,
+            ..config
+        };
+        assert_eq!(keepalive_interval(&config), None);
+    }
+
+    #[tokio::test]
+    async fn test_bind_retries_until_port_is_free() {
+        let held = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = held.local_addr().unwrap();
+        assert!(is_transient_bind_error(
+            &bind_listener(addr, false, None).unwrap_err()
+        ));
+
+        // Release the port while the first retry is waiting
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(held);
+        });
+
+        let listener = bind_with_retry(addr, 3, || bind_listener(addr, false, None))
+            .await
+            .expect("Bind should succeed once the port is released");
+        assert_eq!(listener.local_addr().unwrap(), addr);
+    }
+
+    #[tokio::test]
+    async fn test_bind_permanent_error_fails_fast() {
+        let mut calls = 0;
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let result: Result<()> = bind_with_retry(addr, 5, || {
+            calls += 1;
+            Err(
+                anyhow::Error::from(io::Error::from(io::ErrorKind::PermissionDenied))
+                    .context("Failed to bind gRPC listener"),
+            )
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_bind_gives_up_after_attempts() {
+        let mut calls = 0;
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let result: Result<()> = bind_with_retry(addr, 2, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::AddrInUse).into())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+    }
 
     #[test]
     fn test_interceptor_rejects_unauthorized_ip() {
         let controller_ip = Ipv4Addr::new(10, 0, 0, 1);
-        let mut interceptor = AuthInterceptor { controller_ip };
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(controller_ip).1,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
 
         let mut request = Request::new(());
         let unauthorized_ip = Ipv4Addr::new(10, 0, 0, 99);
@@ -279,10 +1868,126 @@ mod tests {
         assert_eq!(result.unwrap_err().code(), tonic::Code::PermissionDenied);
     }
 
+    #[test]
+    fn test_interceptor_accepts_controller_net() {
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(Ipv4Addr::new(10, 0, 0, 1)).1,
+            controller_net: Some("10.0.0.0/28".parse().unwrap()),
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
+        let call = |interceptor: &mut AuthInterceptor, ip: Ipv4Addr| {
+            let mut request = Request::new(());
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::new(IpAddr::V4(ip), 1234)),
+            });
+            interceptor.call(request)
+        };
+
+        // Any member of the HA group is a controller
+        let request = call(&mut interceptor, Ipv4Addr::new(10, 0, 0, 14)).unwrap();
+        assert_eq!(request.extensions().get::<Role>(), Some(&Role::Controller));
+
+        let err = call(&mut interceptor, Ipv4Addr::new(10, 0, 0, 16)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_interceptor_accepts_extra_controllers() {
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(Ipv4Addr::new(10, 0, 0, 5)).1,
+            controller_net: None,
+            extra_controllers: HashSet::from([Ipv4Addr::new(10, 0, 1, 5)]),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
+        let call = |interceptor: &mut AuthInterceptor, ip: Ipv4Addr| {
+            let mut request = Request::new(());
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::new(IpAddr::V4(ip), 1234)),
+            });
+            interceptor.call(request)
+        };
+
+        for ip in [Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 1, 5)] {
+            let request = call(&mut interceptor, ip).unwrap();
+            assert_eq!(request.extensions().get::<Role>(), Some(&Role::Controller));
+        }
+
+        let err = call(&mut interceptor, Ipv4Addr::new(10, 0, 2, 5)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        assert_eq!(
+            interceptor.describe_controllers(Ipv4Addr::new(10, 0, 0, 5)),
+            "10.0.0.5, 10.0.1.5"
+        );
+    }
+
+    #[test]
+    fn test_proxy_protocol_requires_trusted_proxies() {
+        let config = Config {
+            grpc_proxy_protocol: true,
+            ..Config::default()
+        };
+        let err = ServerSetup::load(&config, false).err().unwrap();
+        assert!(format!("{:#}", err).contains("grpc.trusted_proxies"));
+    }
+
+    #[tokio::test]
+    async fn test_interceptor_uses_proxied_source() {
+        use std::net::SocketAddrV4;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_stream::StreamExt;
+        use tonic::transport::server::Connected;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut incoming =
+            proxy_protocol::incoming(listener, vec!["127.0.0.1/32".parse().unwrap()]);
+
+        // The load balancer connects from 127.0.0.1 on behalf of 10.0.0.1
+        let controller = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 40000);
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut header = proxy_protocol::encode_tcp4(
+            controller,
+            SocketAddrV4::new(Ipv4Addr::new(172, 21, 0, 10), 50001),
+        );
+        header.extend(b"hello");
+        client.write_all(&header).await.unwrap();
+
+        let mut stream = incoming.next().await.unwrap().unwrap();
+        let info = stream.connect_info();
+        assert_eq!(info.remote_addr, Some(SocketAddr::V4(controller)));
+        // Bytes after the header reach the server unchanged
+        let mut payload = [0u8; 5];
+        stream.read_exact(&mut payload).await.unwrap();
+        assert_eq!(&payload, b"hello");
+
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(*controller.ip()).1,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
+        let mut request = Request::new(());
+        request.extensions_mut().insert(info);
+        let request = interceptor.call(request).unwrap();
+        assert_eq!(request.extensions().get::<Role>(), Some(&Role::Controller));
+    }
+
     #[test]
     fn test_interceptor_rejects_ipv6() {
         let controller_ip = Ipv4Addr::new(10, 0, 0, 1);
-        let mut interceptor = AuthInterceptor { controller_ip };
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(controller_ip).1,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
 
         let mut request = Request::new(());
         let remote_addr = SocketAddr::new(IpAddr::V6("::1".parse().unwrap()), 1234);
@@ -296,7 +2001,13 @@ mod tests {
     #[test]
     fn test_interceptor_rejects_no_address() {
         let controller_ip = Ipv4Addr::new(10, 0, 0, 1);
-        let mut interceptor = AuthInterceptor { controller_ip };
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(controller_ip).1,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
 
         let request = Request::new(());
 
@@ -306,20 +2017,950 @@ mod tests {
     }
 
     #[test]
-    fn test_service_creation() {
-        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(|_, _, _, _| Ok(())));
-        let update_ip: UpdateIpFn = Arc::new(Mutex::new(|_, _| Ok(0)));
+    fn test_interceptor_follows_controller_ip_change() {
+        let (tx, rx) = watch::channel(Ipv4Addr::new(10, 0, 0, 1));
+        let mut interceptor = AuthInterceptor {
+            controller_ip: rx,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: None,
+            monitor_cn: None,
+        };
+        let new_ip = Ipv4Addr::new(10, 0, 0, 2);
+
+        let mut request = Request::new(());
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(SocketAddr::new(IpAddr::V4(new_ip), 1234)),
+        });
+        assert!(interceptor.call(request).is_err());
+
+        tx.send(new_ip).unwrap();
+
+        let mut request = Request::new(());
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(SocketAddr::new(IpAddr::V4(new_ip), 1234)),
+        });
+        assert!(interceptor.call(request).is_ok());
+    }
+
+    #[test]
+    fn test_interceptor_assigns_roles() {
+        let controller_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let monitor_ip = Ipv4Addr::new(10, 0, 0, 9);
+        let mut interceptor = AuthInterceptor {
+            controller_ip: watch::channel(controller_ip).1,
+            controller_net: None,
+            extra_controllers: HashSet::new(),
+            monitor_ip: Some(monitor_ip),
+            monitor_cn: None,
+        };
+
+        for (ip, role) in [
+            (controller_ip, Role::Controller),
+            (monitor_ip, Role::Monitor),
+        ] {
+            let mut request = Request::new(());
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::new(IpAddr::V4(ip), 1234)),
+            });
+            let request = interceptor.call(request).expect("Request should pass");
+            assert_eq!(request.extensions().get::<Role>(), Some(&role));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lagging_monitor_resyncs() {
+        let (service, tx) = test_service();
+        let list = |time_left| SessionList {
+            sessions: vec![Session {
+                src_ip: 0x0A000001,
+                dst_ip: 0x0A000002,
+                dst_port: 8080,
+                time_left,
+                src_port: 0,
+            }],
+            resync: false,
+            closing: false,
+        };
+
+        let mut stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+
+        // The forwarder has not run yet, so all but the last four are overwritten
+        for time_left in 0..10 {
+            tx.send(Ok(list(time_left))).unwrap();
+        }
+
+        let first = stream.recv().await.unwrap().unwrap();
+        assert!(first.resync);
+        assert_eq!(first.sessions[0].time_left, 9);
+        assert_eq!(service.monitor_lagged(), 6);
+        let stats = service
+            .get_map_stats(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stats.monitor_lagged, 6);
+
+        tx.send(Ok(list(10))).unwrap();
+        let next = stream.recv().await.unwrap().unwrap();
+        assert!(!next.resync);
+        assert_eq!(next.sessions[0].time_left, 10);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_monitor_resyncs_from_map() {
+        let (service, tx) = test_service();
+        let service = service.with_list_sessions(Arc::new(Mutex::new(|| {
+            Ok(vec![(
+                SessionKey {
+                    src_ip: Ipv4Addr::new(10, 0, 0, 1),
+                    dest_ip: Ipv4Addr::new(10, 0, 0, 2),
+                    dest_port: 8080,
+                    src_port: 0,
+                },
+                42,
+            )])
+        })));
+
+        let mut stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+
+        // Queued snapshots are all older than what the map holds now
+        for _ in 0..10 {
+            tx.send(Ok(SessionList::default())).unwrap();
+        }
+
+        let first = stream.recv().await.unwrap().unwrap();
+        assert!(first.resync);
+        assert_eq!(first.sessions.len(), 1);
+        assert_eq!(first.sessions[0].src_ip, 0x0A000001);
+        assert_eq!(first.sessions[0].dst_port, 8080);
+        assert_eq!(first.sessions[0].time_left, 42);
+        assert_eq!(service.monitor_lagged(), 6);
+
+        // The snapshot that survived the lag was dropped, not sent after it
+        tx.send(Ok(SessionList::default())).unwrap();
+        let next = stream.recv().await.unwrap().unwrap();
+        assert!(!next.resync);
+        assert!(next.sessions.is_empty());
+        assert!(stream.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_stream_closes_on_shutdown() {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (service, _) = test_service();
+        let service = service.with_shutdown(shutdown_rx);
+
+        let mut stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+
+        shutdown_tx.send(true).unwrap();
+        let last = stream.recv().await.unwrap().unwrap();
+        assert!(last.closing);
+        assert!(last.sessions.is_empty());
+        assert!(stream.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_subscribers_are_limited() {
+        let (service, _) = test_service();
+        let service = service.with_max_monitors(2);
+
+        let first = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap();
+        let _second = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap();
+        assert_eq!(service.active_monitors(), 2);
+
+        // Every surplus call is rejected, and none takes a slot
+        for _ in 0..5 {
+            let status = service
+                .monitor_sessions(Request::new(Empty {}))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        }
+        assert_eq!(service.active_monitors(), 2);
+
+        // A client going away frees its slot without any list being sent
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while service.active_monitors() > 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("Slot not released");
+        assert!(
+            service
+                .monitor_sessions(Request::new(Empty {}))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_monitor_task_ends_when_client_drops() {
+        let (service, tx) = test_service();
+        let service = service.with_max_monitors(0);
+
+        let stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+        // The forwarding task holds the only broadcast subscription
+        assert_eq!(tx.receiver_count(), 1);
+
+        // Nothing is ever broadcast, so only the drop can wake the task
+        drop(stream);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while tx.receiver_count() > 0 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("Forwarding task outlived its client");
+        assert_eq!(service.active_monitors(), 0);
+    }
+
+    #[test]
+    fn test_monitor_slots_unlimited_when_zero() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<_> = (0..100)
+            .map(|_| MonitorSlot::acquire(&active, 0).unwrap())
+            .collect();
+        assert_eq!(active.load(Ordering::Acquire), 100);
+        drop(slots);
+        assert_eq!(active.load(Ordering::Acquire), 0);
+    }
+
+    #[tokio::test]
+    async fn test_monitor_uses_configured_buffer() {
+        let (service, _) = test_service();
+
+        let stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+        assert_eq!(stream.max_capacity(), DEFAULT_MONITOR_BUFFER);
+
+        let config = Config {
+            monitor_buffer_size: 32,
+            ..Config::default()
+        };
+        let service = service.with_monitor_buffer(config.monitor_buffer_size);
+        let stream = service
+            .monitor_sessions(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+        assert_eq!(stream.max_capacity(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_monitor_role_is_read_only() {
+        let flushed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flushed_clone = flushed.clone();
+        let flush_all: FlushAllFn = Arc::new(Mutex::new(move || {
+            flushed_clone.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(0)
+        }));
+        let (service, _) = test_service();
+        let service = service.with_flush_all(flush_all);
+
+        let mut request = Request::new(Empty {});
+        request.extensions_mut().insert(Role::Monitor);
+        let status = service.flush_all(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(!flushed.load(std::sync::atomic::Ordering::SeqCst));
+
+        let mut request = Request::new(BlockRequest { ip: 0x0A000063 });
+        request.extensions_mut().insert(Role::Monitor);
+        let status = service.block_ip(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let mut request = Request::new(Empty {});
+        request.extensions_mut().insert(Role::Monitor);
+        assert!(service.monitor_sessions(request).await.is_ok());
+
+        let mut request = Request::new(Empty {});
+        request.extensions_mut().insert(Role::Controller);
+        assert!(
+            service
+                .flush_all(request)
+                .await
+                .unwrap()
+                .into_inner()
+                .success
+        );
+        assert!(flushed.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_request_without_role_is_rejected() {
+        let flushed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flushed_clone = flushed.clone();
+        let flush_all: FlushAllFn = Arc::new(Mutex::new(move || {
+            flushed_clone.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(0)
+        }));
+        let (service, _) = test_service();
+        let service = service.with_flush_all(flush_all);
+
+        // A request that skipped the interceptors must not default to full access
+        let status = service.flush_all(Request::new(Empty {})).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert!(!flushed.load(std::sync::atomic::Ordering::SeqCst));
+
+        let status = service
+            .block_ip(Request::new(BlockRequest { ip: 0x0A000063 }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // Read-only methods need no role
+        assert!(service.get_map_stats(Request::new(Empty {})).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_read_only_rejects_mutations() {
+        let mutated = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (rules_flag, flush_flag) = (mutated.clone(), mutated.clone());
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(move |_, _, _, _| {
+            rules_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(RuleChange::default())
+        }));
+        let flush_all: FlushAllFn = Arc::new(Mutex::new(move || {
+            flush_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(0)
+        }));
+        let get_session: GetSessionFn = Arc::new(Mutex::new(|_| Ok(Some(42))));
+        let map_stats: MapStatsFn = Arc::new(Mutex::new(|| Ok((1, 10240))));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        }
+        .with_flush_all(flush_all)
+        .with_get_session(get_session)
+        .with_map_stats(map_stats)
+        .with_read_only(true);
+
+        let event = || LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        };
+
+        let denied = [
+            service
+                .submit_session(controller_request(event()))
+                .await
+                .err(),
+            service
+                .ip_change(controller_request(IpChangeList {
+                    ip_changes: vec![session::IpChangeEvent {
+                        old_ip: 0x0A000001,
+                        new_ip: 0x0A000003,
+                    }],
+                }))
+                .await
+                .err(),
+            service.flush_all(controller_request(Empty {})).await.err(),
+            service
+                .block_ip(controller_request(BlockRequest { ip: 0x0A000063 }))
+                .await
+                .err(),
+            service
+                .unblock_ip(controller_request(BlockRequest { ip: 0x0A000063 }))
+                .await
+                .err(),
+            service
+                .prune_stale_epochs(controller_request(PruneRequest { epoch: 2 }))
+                .await
+                .err(),
+        ];
+        for status in denied {
+            assert_eq!(
+                status.expect("mutation accepted").code(),
+                tonic::Code::PermissionDenied
+            );
+        }
+        assert!(!mutated.load(std::sync::atomic::Ordering::SeqCst));
+
+        // Lookups and monitoring still work
+        let session = service
+            .get_session(controller_request(event()))
+            .await
+            .unwrap();
+        assert_eq!(session.into_inner().time_left, 42);
+        let stats = service
+            .get_map_stats(controller_request(Empty {}))
+            .await
+            .unwrap();
+        assert_eq!(stats.into_inner().used, 1);
+        assert!(
+            service
+                .monitor_sessions(controller_request(Empty {}))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_session_present_and_absent() {
+        let present = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: 0,
+        };
+        let get_session: GetSessionFn = Arc::new(Mutex::new(move |key: SessionKey| {
+            Ok((key == present).then_some(42))
+        }));
+        let (service, _) = test_service();
+        let service = service.with_get_session(get_session);
+
+        let event = LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0xAC15000A,
+            dst_port: 8080,
+            activate: false,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        };
+        // Read-only clients may look sessions up
+        let mut request = Request::new(event);
+        request.extensions_mut().insert(Role::Monitor);
+        let session = service.get_session(request).await.unwrap().into_inner();
+        assert_eq!(
+            session,
+            Session {
+                src_ip: 0x0A000001,
+                dst_ip: 0xAC15000A,
+                dst_port: 8080,
+                time_left: 42,
+                src_port: 0,
+            }
+        );
+
+        let absent = LoginEvent {
+            dst_port: 8443,
+            ..event
+        };
+        let status = service.get_session(Request::new(absent)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let invalid = LoginEvent { dst_ip: 0, ..event };
+        let status = service
+            .get_session(Request::new(invalid))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_peer_cred_interceptor_rejects_no_credentials() {
+        let mut interceptor = PeerCredInterceptor {
+            uid: 1000,
+            gid: Some(1000),
+        };
+
+        let result = interceptor.call(Request::new(()));
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_peer_cred_authorization() {
+        let interceptor = PeerCredInterceptor {
+            uid: 1000,
+            gid: None,
+        };
+
+        assert!(interceptor.is_authorized(1000, 1000));
+        assert!(!interceptor.is_authorized(2000, 100));
+        assert!(!interceptor.is_authorized(2000, 2000));
+
+        // Group members are only accepted when opted in
+        let interceptor = PeerCredInterceptor {
+            uid: 1000,
+            gid: Some(100),
+        };
+
+        assert!(interceptor.is_authorized(1000, 1000));
+        assert!(interceptor.is_authorized(2000, 100));
+        assert!(!interceptor.is_authorized(2000, 2000));
+    }
+
+    #[tokio::test]
+    async fn test_unset_handler_is_unimplemented() {
         let (tx, _) = broadcast::channel(4);
+        let service = SessionManagerService::new(
+            Arc::new(Mutex::new(|_, _, _, _| Ok(RuleChange::default()))),
+            Arc::new(Mutex::new(|_, _| Ok(0))),
+            tx,
+        );
+
+        let status = service
+            .flush_all(controller_request(Empty {}))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+        let status = service
+            .get_map_stats(Request::new(Empty {}))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_routes_activate_flag() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let calls_clone = calls.clone();
+
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(
+            move |is_add: bool, key: SessionKey, _epoch: u64, _src_mac: u64| {
+                calls_clone.lock().unwrap().push((is_add, key));
+                Ok(RuleChange::default())
+            },
+        ));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        };
+
+        for activate in [true, false] {
+            let request = controller_request(LoginEvent {
+                src_ip: 0x0A000001,
+                dst_ip: 0x0A000002,
+                dst_port: 8080,
+                activate,
+                src_port: 1021,
+                epoch: 0,
+                src_mac: 0,
+            });
+            let response = service.submit_session(request).await.unwrap();
+            assert!(response.into_inner().success);
+        }
+
+        // Host-order integers map to the dotted-quad they spell
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(10, 0, 0, 2),
+            dest_port: 8080,
+            src_port: 1021,
+        };
+        assert_eq!(*calls.lock().unwrap(), vec![(true, key), (false, key)]);
+    }
+
+    /// Collects formatted log output for assertions.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
 
-        let _service = SessionManagerService::new(modify_rules, update_ip, tx);
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_is_audited() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let (service, _) = test_service();
+
+        let mut request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)),
+                1234,
+            )),
+        });
+        assert!(
+            service
+                .submit_session(request)
+                .await
+                .unwrap()
+                .into_inner()
+                .success
+        );
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = output
+            .lines()
+            .find(|line| line.contains("audit=true"))
+            .expect("No audit record logged");
+        assert!(line.contains(" INFO "), "{}", line);
+        assert!(line.contains("peer=ip=10.0.0.5"), "{}", line);
+        assert!(line.contains("operation=activate_session"), "{}", line);
+        assert!(
+            line.contains("resource=10.0.0.1 -> 10.0.0.2:8080"),
+            "{}",
+            line
+        );
+        assert!(line.contains("result=added"), "{}", line);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_rejects_unspecified_ip() {
+        let (service, _) = test_service();
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        let result = service.submit_session(request).await;
+
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert_eq!(service.rejects().get(RejectReason::UnspecifiedDstIp), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_any_source() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let calls_clone = calls.clone();
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(
+            move |is_add: bool, key: SessionKey, _epoch: u64, _src_mac: u64| {
+                calls_clone.lock().unwrap().push((is_add, key));
+                Ok(RuleChange::default())
+            },
+        ));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        };
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0,
+            dst_ip: 0x0A000002,
+            dst_port: 443,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        let response = service.submit_session(request).await.unwrap();
+        assert!(response.into_inner().success);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].1.is_any_source());
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_reports_failure() {
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules: Arc::new(Mutex::new(|_, _, _, _| Err(anyhow!("BPF update failed")))),
+            ..service
+        };
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        let ack = service.submit_session(request).await.unwrap().into_inner();
+
+        assert!(!ack.success);
+        assert_eq!(ack.active_sessions, None);
+        assert_eq!(ack.previous_existed, None);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_reports_changes() {
+        let sessions = Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+        let sessions_clone = sessions.clone();
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(
+            move |is_add: bool, key: SessionKey, _epoch: u64, _src_mac: u64| {
+                let mut sessions = sessions_clone.lock().unwrap();
+                let previous_existed = if is_add {
+                    !sessions.insert(key)
+                } else {
+                    sessions.remove(&key)
+                };
+                Ok(RuleChange {
+                    previous_existed,
+                    active_sessions: sessions.len(),
+                })
+            },
+        ));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        };
+
+        // (dst_port, activate, existed before, sessions afterwards)
+        for (dst_port, activate, existed, expected) in [
+            (8080, true, false, 1),
+            (8443, true, false, 2),
+            (8080, true, true, 2),
+            (8080, false, true, 1),
+            (8080, false, false, 1),
+        ] {
+            let request = controller_request(LoginEvent {
+                src_ip: 0x0A000001,
+                dst_ip: 0x0A000002,
+                dst_port,
+                activate,
+                src_port: 0,
+                epoch: 0,
+                src_mac: 0,
+            });
+            let ack = service.submit_session(request).await.unwrap().into_inner();
+            assert!(ack.success);
+            assert_eq!(ack.active_sessions, Some(expected));
+            assert_eq!(ack.previous_existed, Some(existed));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_rejects_invalid_port() {
+        let (service, _) = test_service();
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 70000,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        let result = service.submit_session(request).await;
+
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert_eq!(service.rejects().get(RejectReason::InvalidDstPort), 1);
+        assert_eq!(service.rejects().get(RejectReason::InvalidSrcPort), 0);
+    }
+
+    #[test]
+    fn test_reject_warnings_are_rate_limited() {
+        let rejects = RejectCounters::default();
+        let start = Instant::now();
+
+        assert!(rejects.record_at(RejectReason::InvalidDstPort, "port 70000", start));
+        for i in 1..100 {
+            let now = start + Duration::from_millis(i);
+            assert!(!rejects.record_at(RejectReason::UnspecifiedDstIp, "0 → 1", now));
+        }
+        assert_eq!(rejects.suppressed.load(Ordering::Relaxed), 99);
+
+        // The next warning after the interval summarizes the suppressed ones
+        let later = start + REJECT_WARN_INTERVAL;
+        assert!(rejects.record_at(RejectReason::InvalidDstPort, "port 70001", later));
+        assert_eq!(rejects.suppressed.load(Ordering::Relaxed), 0);
+        assert_eq!(rejects.get(RejectReason::InvalidDstPort), 2);
+        assert_eq!(rejects.get(RejectReason::UnspecifiedDstIp), 99);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rate_limit_per_caller() {
+        let (service, _) = test_service();
+        let service = service.with_submit_rate_limit(3);
+
+        let submit = |controller: Ipv4Addr| {
+            let mut request = controller_request(LoginEvent {
+                src_ip: 0x0A000001,
+                dst_ip: 0x0A000002,
+                dst_port: 8080,
+                activate: true,
+                src_port: 0,
+                epoch: 0,
+                src_mac: 0,
+            });
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::new(IpAddr::V4(controller), 40000)),
+            });
+            service.submit_session(request)
+        };
+
+        let busy = Ipv4Addr::new(10, 0, 0, 5);
+        for _ in 0..3 {
+            assert!(submit(busy).await.unwrap().into_inner().success);
+        }
+        let status = submit(busy).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(service.rejects().get(RejectReason::RateLimited), 1);
+
+        // Other controllers have their own budget
+        assert!(submit(Ipv4Addr::new(10, 0, 0, 6)).await.is_ok());
+    }
+
+    #[test]
+    fn test_submit_limiter_refills() {
+        let limiter = SubmitLimiter::new(2);
+        let start = Instant::now();
+
+        assert!(limiter.allow_at("cn=controller", start));
+        assert!(limiter.allow_at("cn=controller", start));
+        assert!(!limiter.allow_at("cn=controller", start));
+
+        // One token back after half a second, never more than the burst
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.allow_at("cn=controller", later));
+        assert!(!limiter.allow_at("cn=controller", later));
+        let idle = later + Duration::from_secs(60);
+        assert!(limiter.allow_at("cn=controller", idle));
+        assert!(limiter.allow_at("cn=controller", idle));
+        assert!(!limiter.allow_at("cn=controller", idle));
+    }
+
+    #[test]
+    fn test_submit_limiter_stays_bounded() {
+        let limiter = SubmitLimiter::new(1);
+        let start = Instant::now();
+
+        // Every caller has just spent its only token, so none can be dropped as full
+        for i in 0..SUBMIT_LIMITER_CALLERS {
+            let at = start + Duration::from_micros(i as u64);
+            assert!(limiter.allow_at(&format!("caller-{}", i), at));
+        }
+        let now = start + Duration::from_millis(10);
+        assert!(!limiter.allow_at("cn=new", now));
+
+        // A new caller cannot push out a tracked one to reset its budget
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), SUBMIT_LIMITER_CALLERS);
+        assert!(buckets.contains_key("caller-0"));
+        assert!(!buckets.contains_key("cn=new"));
+        drop(buckets);
+        assert!(!limiter.allow_at("caller-0", now));
+
+        // Once a bucket refilled, its caller is forgotten and makes room
+        let refilled = start + Duration::from_secs(2);
+        assert!(limiter.allow_at("cn=new", refilled));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_rejects_invalid_src_port() {
+        let (service, _) = test_service();
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 2049,
+            activate: true,
+            src_port: 70000,
+            epoch: 0,
+            src_mac: 0,
+        });
+        let result = service.submit_session(request).await;
+
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_submit_session_passes_src_mac() {
+        let macs = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = macs.clone();
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(move |_, _, _, src_mac: u64| {
+            recorded.lock().unwrap().push(src_mac);
+            Ok(RuleChange::default())
+        }));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        };
+        let event = |src_ip: u32, src_mac: u64| LoginEvent {
+            src_ip,
+            dst_ip: 0x0A000002,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac,
+        };
+
+        let ack = service
+            .submit_session(controller_request(event(0x0A000001, 0x02_42_ac_15_00_0a)))
+            .await
+            .unwrap();
+        assert!(ack.into_inner().success);
+
+        // Wider than a MAC, or on a session with no single source to bind
+        for invalid in [event(0x0A000001, 1 << 48), event(0, 0x02_42_ac_15_00_0a)] {
+            let status = service
+                .submit_session(controller_request(invalid))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+        assert_eq!(service.rejects().get(RejectReason::InvalidSrcMac), 2);
+        assert_eq!(*macs.lock().unwrap(), vec![0x02_42_ac_15_00_0a]);
     }
 
     #[tokio::test]
     async fn test_ip_change_success() {
         use std::sync::atomic::{AtomicBool, Ordering};
 
-        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(|_, _, _, _| Ok(())));
-
         let called = Arc::new(AtomicBool::new(false));
         let called_clone = called.clone();
 
@@ -329,12 +2970,14 @@ mod tests {
             called_clone.store(true, Ordering::SeqCst);
             Ok(3)
         }));
-
-        let (tx, _) = broadcast::channel(4);
-        let service = SessionManagerService::new(modify_rules, update_ip, tx);
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            update_ip,
+            ..service
+        };
 
         // Create a fake request
-        let mut request = Request::new(IpChangeList {
+        let mut request = controller_request(IpChangeList {
             ip_changes: vec![session::IpChangeEvent {
                 old_ip: 0x0A000001,
                 new_ip: 0x0A000002,
@@ -347,15 +2990,23 @@ mod tests {
         let result = service.ip_change(request).await;
 
         assert!(result.is_ok());
-        let response = result.unwrap();
-        assert!(response.into_inner().success);
+        let response = result.unwrap().into_inner();
+        assert!(response.success);
+        assert_eq!(
+            response.ip_changes,
+            vec![IpChangeResult {
+                old_ip: 0x0A000001,
+                new_ip: 0x0A000002,
+                success: true,
+                updated: 3,
+                error: String::new(),
+            }]
+        );
         assert!(called.load(Ordering::SeqCst));
     }
 
     #[tokio::test]
     async fn test_ip_change_multiple_events() {
-        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(|_, _, _, _| Ok(())));
-
         let call_count = Arc::new(std::sync::Mutex::new(0));
         let call_count_clone = call_count.clone();
 
@@ -363,11 +3014,13 @@ mod tests {
             *call_count_clone.lock().unwrap() += 1;
             Ok(1)
         }));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            update_ip,
+            ..service
+        };
 
-        let (tx, _) = broadcast::channel(4);
-        let service = SessionManagerService::new(modify_rules, update_ip, tx);
-
-        let mut request = Request::new(IpChangeList {
+        let mut request = controller_request(IpChangeList {
             ip_changes: vec![
                 session::IpChangeEvent {
                     old_ip: 0x0A000001,
@@ -397,19 +3050,34 @@ mod tests {
 
     #[tokio::test]
     async fn test_ip_change_with_errors() {
-        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(|_, _, _, _| Ok(())));
-        let update_ip: UpdateIpFn = Arc::new(Mutex::new(|_old_ip: u32, _new_ip: u32| {
-            Err(anyhow!("BPF update failed"))
+        let update_ip: UpdateIpFn = Arc::new(Mutex::new(|old_ip: u32, _new_ip: u32| {
+            if old_ip == 0x0A000003 {
+                Err(anyhow!("BPF update failed"))
+            } else {
+                Ok(2)
+            }
         }));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            update_ip,
+            ..service
+        };
 
-        let (tx, _) = broadcast::channel(4);
-        let service = SessionManagerService::new(modify_rules, update_ip, tx);
-
-        let mut request = Request::new(IpChangeList {
-            ip_changes: vec![session::IpChangeEvent {
-                old_ip: 0x0A000001,
-                new_ip: 0x0A000002,
-            }],
+        let mut request = controller_request(IpChangeList {
+            ip_changes: vec![
+                session::IpChangeEvent {
+                    old_ip: 0x0A000001,
+                    new_ip: 0x0A000002,
+                },
+                session::IpChangeEvent {
+                    old_ip: 0x0A000003,
+                    new_ip: 0x0A000004,
+                },
+                session::IpChangeEvent {
+                    old_ip: 0x0A000005,
+                    new_ip: 0x0A000006,
+                },
+            ],
         });
 
         let remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1234);
@@ -418,19 +3086,31 @@ mod tests {
         let result = service.ip_change(request).await;
 
         assert!(result.is_ok());
-        let response = result.unwrap();
-        assert!(!response.into_inner().success);
+        let response = result.unwrap().into_inner();
+        assert!(!response.success);
+        // The failure is reported on its own and does not stop later changes
+        let outcomes: Vec<_> = response
+            .ip_changes
+            .iter()
+            .map(|result| (result.old_ip, result.success, result.updated))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (0x0A000001, true, 2),
+                (0x0A000003, false, 0),
+                (0x0A000005, true, 2)
+            ]
+        );
+        assert_eq!(response.ip_changes[1].error, "BPF error");
+        assert!(response.ip_changes[0].error.is_empty());
     }
 
     #[tokio::test]
     async fn test_ip_change_empty_list() {
-        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(|_, _, _, _| Ok(())));
-        let update_ip: UpdateIpFn = Arc::new(Mutex::new(|_, _| Ok(0)));
-
-        let (tx, _) = broadcast::channel(4);
-        let service = SessionManagerService::new(modify_rules, update_ip, tx);
+        let (service, _) = test_service();
 
-        let mut request = Request::new(IpChangeList { ip_changes: vec![] });
+        let mut request = controller_request(IpChangeList { ip_changes: vec![] });
 
         let remote_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 1234);
         request.extensions_mut().insert(remote_addr);
@@ -441,4 +3121,275 @@ mod tests {
         let response = result.unwrap();
         assert!(response.into_inner().success);
     }
+
+    #[tokio::test]
+    async fn test_flush_all() {
+        let flush_all: FlushAllFn = Arc::new(Mutex::new(|| Ok(42)));
+        let (service, _) = test_service();
+        let service = service.with_flush_all(flush_all);
+
+        let response = service
+            .flush_all(controller_request(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(response.success);
+        assert_eq!(response.flushed, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_flush_all_reports_failure() {
+        let flush_all: FlushAllFn = Arc::new(Mutex::new(|| Err(anyhow!("BPF delete failed"))));
+        let (service, _) = test_service();
+        let service = service.with_flush_all(flush_all);
+
+        let response = service
+            .flush_all(controller_request(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(!response.success);
+        assert_eq!(response.flushed, None);
+    }
+
+    #[tokio::test]
+    async fn test_block_and_unblock_ip() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let calls_clone = calls.clone();
+
+        let (service, _) = test_service();
+        let service =
+            service.with_blocklist(Arc::new(Mutex::new(move |block: bool, ip: Ipv4Addr| {
+                calls_clone.lock().unwrap().push((block, ip));
+                Ok(())
+            })));
+
+        let response = service
+            .block_ip(controller_request(BlockRequest { ip: 0x0A000001 }))
+            .await
+            .unwrap();
+        assert!(response.into_inner().success);
+        let response = service
+            .unblock_ip(controller_request(BlockRequest { ip: 0x0A000001 }))
+            .await
+            .unwrap();
+        assert!(response.into_inner().success);
+
+        let ip = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(*calls.lock().unwrap(), vec![(true, ip), (false, ip)]);
+    }
+
+    #[tokio::test]
+    async fn test_block_ip_rejects_unspecified_ip() {
+        let (service, _) = test_service();
+
+        let result = service
+            .block_ip(controller_request(BlockRequest { ip: 0 }))
+            .await;
+
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_get_map_stats() {
+        let service = |map_stats: MapStatsFn| test_service().0.with_map_stats(map_stats);
+
+        let stats = service(Arc::new(Mutex::new(|| Ok((9_500, 10_240)))))
+            .get_map_stats(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stats.used, 9_500);
+        assert_eq!(stats.capacity, 10_240);
+
+        let err = service(Arc::new(Mutex::new(|| Err(anyhow!("map gone")))))
+            .get_map_stats(Request::new(Empty {}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Internal);
+    }
+
+    #[tokio::test]
+    async fn test_get_map_stats_reports_rejects() {
+        let (service, _) = test_service();
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 0,
+            src_mac: 0,
+        });
+        assert!(service.submit_session(request).await.is_err());
+
+        let stats = service
+            .get_map_stats(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stats.rejected_sessions.len(), RejectReason::ALL.len());
+        for reject in &stats.rejected_sessions {
+            let expected = if reject.reason == "unspecified_dst_ip" {
+                1
+            } else {
+                0
+            };
+            assert_eq!(reject.count, expected, "{}", reject.reason);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_config() {
+        use crate::config::{AttachHook, FragmentPolicy};
+
+        let config = Config {
+            iface_name: "docker0".to_string(),
+            attach_hook: AttachHook::Tc,
+            controller_ip: Ipv4Addr::new(10, 0, 0, 1),
+            controller_port: 8080,
+            controller_extra_ports: vec![8443],
+            controller_extra_ips: vec![Ipv4Addr::new(10, 0, 1, 5)],
+            rule_timeout_ns: 120_000_000_000,
+            lazy_update_timeout: 5_000_000_000,
+            max_session_age_ns: 3_600_000_000_000,
+            cleanup_interval_sec: 60,
+            enforcement_grace_sec: 30,
+            strict_tcp: true,
+            bind_src_mac: true,
+            fragment_policy: FragmentPolicy::Track,
+            always_allow_ports: vec![22],
+            max_conns_per_src: 64,
+            grpc_server_port: 50002,
+            max_monitors: 8,
+            ..Config::default()
+        };
+        let reported = test_service()
+            .0
+            .with_config(&config, Arc::new(Mutex::new(|| Ok(false))))
+            .get_config(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            reported,
+            AgentConfig {
+                iface: "docker0".to_string(),
+                attach_hook: "tc".to_string(),
+                controller_ip: 0x0A00_0001,
+                controller_port: 8080,
+                controller_extra_ports: vec![8443],
+                controller_extra_ips: vec![0x0A00_0105],
+                rule_timeout_ns: 120_000_000_000,
+                lazy_update_timeout_ns: 5_000_000_000,
+                max_session_age_ns: 3_600_000_000_000,
+                cleanup_interval_sec: 60,
+                enforcing: Some(false),
+                enforcement_grace_sec: 30,
+                strict_tcp: true,
+                bind_src_mac: true,
+                fragment_policy: "track".to_string(),
+                always_allow_ports: vec![22],
+                max_conns_per_src: 64,
+                grpc_port: 50002,
+                read_only: false,
+                max_monitors: 8,
+                submit_rate_limit: 0,
+            }
+        );
+
+        // An unreadable mode leaves `enforcing` unset rather than failing
+        let reported = test_service()
+            .0
+            .with_config(&config, Arc::new(Mutex::new(|| Err(anyhow!("map gone")))))
+            .get_config(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(reported.enforcing, None);
+        assert_eq!(reported.iface, "docker0");
+
+        // A controller IP change after startup is reported
+        let (controller_ip_tx, controller_ip_rx) = watch::channel(config.controller_ip);
+        let service = test_service()
+            .0
+            .with_config(&config, Arc::new(Mutex::new(|| Ok(true))))
+            .with_controller_ip(controller_ip_rx);
+        controller_ip_tx.send(Ipv4Addr::new(10, 0, 0, 9)).unwrap();
+        let reported = service
+            .get_config(Request::new(Empty {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(reported.controller_ip, 0x0A00_0009);
+    }
+
+    #[tokio::test]
+    async fn test_prune_stale_epochs() {
+        let epochs = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let submitted = epochs.clone();
+        let pruned = epochs.clone();
+
+        let modify_rules: ModifyRulesFn = Arc::new(Mutex::new(move |_, _, epoch: u64, _| {
+            submitted.lock().unwrap().push(epoch);
+            Ok(RuleChange::default())
+        }));
+        let prune_epochs: PruneEpochsFn = Arc::new(Mutex::new(move |epoch: u64| {
+            pruned.lock().unwrap().push(epoch);
+            Ok(3)
+        }));
+        let (service, _) = test_service();
+        let service = SessionManagerService {
+            modify_rules,
+            ..service
+        }
+        .with_prune_epochs(prune_epochs);
+
+        let request = controller_request(LoginEvent {
+            src_ip: 0x0A000001,
+            dst_ip: 0x0A000002,
+            dst_port: 8080,
+            activate: true,
+            src_port: 0,
+            epoch: 7,
+            src_mac: 0,
+        });
+        assert!(
+            service
+                .submit_session(request)
+                .await
+                .unwrap()
+                .into_inner()
+                .success
+        );
+
+        let ack = service
+            .prune_stale_epochs(controller_request(PruneRequest { epoch: 7 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(ack.success);
+        assert_eq!(ack.pruned, Some(3));
+        assert_eq!(*epochs.lock().unwrap(), vec![7, 7]);
+
+        // Pruning is mutating, so monitoring clients may not call it
+        let mut request = Request::new(PruneRequest { epoch: 8 });
+        request.extensions_mut().insert(Role::Monitor);
+        let status = service.prune_stale_epochs(request).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert_eq!(epochs.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_version() {
+        let (service, _) = test_service();
+
+        let result = service.get_version(Request::new(Empty {})).await;
+
+        let info = result.unwrap().into_inner();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+    }
 }
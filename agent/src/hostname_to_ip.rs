@@ -1,27 +1,318 @@
-use anyhow::{Context, Result};
-use std::net::ToSocketAddrs;
-use tracing::info;
+use anyhow::{Context, Result, anyhow};
+use hickory_resolver::{
+    TokioResolver,
+    config::{NameServerConfigGroup, ResolverConfig},
+    name_server::TokioConnectionProvider,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+/// DNS server set with [`use_dns_server`], replacing the system resolver
+static DNS_SERVER: OnceLock<SocketAddr> = OnceLock::new();
+
+/// Sends every later hostname and SRV lookup to `server` instead of the
+/// system resolver. Only the first call takes effect.
+pub fn use_dns_server(server: SocketAddr) {
+    if DNS_SERVER.set(server).is_ok() {
+        info!("Using DNS server {}", server);
+    }
+}
 
 /// Resolves a hostname to an `Ipv4Addr`.
+///
+/// Goes through the libc resolver unless a DNS server was chosen with
+/// [`use_dns_server`].
 pub fn hostname_to_ip(hostname: String) -> Result<std::net::Ipv4Addr> {
+    if let Ok(ip) = hostname.parse::<IpAddr>() {
+        return first_ipv4(&hostname, [ip]);
+    }
+    if let Some(server) = DNS_SERVER.get() {
+        return lookup_via(*server, &hostname);
+    }
+
     let socket_str = format!("{}:0", hostname);
     info!("Resolving address: {}", socket_str);
 
     // Resolve hostname to IPv4
-    let ipv4 = socket_str
+    let addrs = socket_str
         .to_socket_addrs()
-        .with_context(|| format!("Failed to resolve host: {}", hostname))?
-        .find(|addr| addr.is_ipv4())
-        .map(|addr| match addr.ip() {
-            std::net::IpAddr::V4(ip) => ip,
-            _ => unreachable!(),
-        })
-        .ok_or_else(|| anyhow::anyhow!("hostname did not resolve to an IPv4 address"))?;
+        .with_context(|| format!("Failed to resolve host: {}", hostname))?;
+    let ipv4 = first_ipv4(&hostname, addrs.map(|addr| addr.ip()))?;
 
     info!("Resolved {} to {}", hostname, ipv4);
     Ok(ipv4)
 }
 
+/// Parses a controller address given as an IP literal.
+///
+/// Only IPv4 is supported for now, so an IPv6 literal is rejected with an
+/// error saying so instead of a generic parse failure.
+pub fn parse_controller_ip(addr: &str) -> Result<Ipv4Addr> {
+    let ip = addr
+        .parse::<IpAddr>()
+        .with_context(|| format!("Invalid IPv4 address: {}", addr))?;
+    first_ipv4(addr, [ip])
+}
+
+/// Picks the first IPv4 address `host` resolved to.
+///
+/// Fails with an explicit message when only IPv6 addresses came back, since
+/// the agent would otherwise start and then reject every controller request.
+fn first_ipv4(host: &str, addrs: impl IntoIterator<Item = IpAddr>) -> Result<Ipv4Addr> {
+    let mut ipv6 = None;
+    for addr in addrs {
+        match addr {
+            IpAddr::V4(ip) => return Ok(ip),
+            IpAddr::V6(ip) => {
+                ipv6.get_or_insert(ip);
+            }
+        }
+    }
+    match ipv6 {
+        Some(ip) => Err(anyhow!(
+            "{} only resolves to IPv6 ({}); IPv6 controllers are not supported yet, \
+             use an IPv4 address or a hostname with an A record",
+            host,
+            ip
+        )),
+        None => Err(anyhow!("{} did not resolve to an IPv4 address", host)),
+    }
+}
+
+/// Resolves `hostname` by querying `server` directly.
+fn lookup_via(server: SocketAddr, hostname: &str) -> Result<Ipv4Addr> {
+    if let Ok(ip) = hostname.parse::<IpAddr>() {
+        return first_ipv4(hostname, [ip]);
+    }
+    info!("Resolving {} via {}", hostname, server);
+
+    // hickory is async-only; query from a separate thread and runtime so this
+    // works both inside and outside the agent's Tokio runtime. Both families
+    // are looked up so an IPv6-only name gets a clear error.
+    let host = hostname.to_string();
+    let addrs = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start DNS runtime")?;
+        let lookup = runtime
+            .block_on(resolver_for(server).lookup_ip(host.as_str()))
+            .with_context(|| format!("Failed to resolve host: {}", host))?;
+        Ok::<_, anyhow::Error>(lookup.iter().collect::<Vec<_>>())
+    })
+    .join()
+    .map_err(|_| anyhow!("DNS lookup thread panicked"))??;
+    let ip = first_ipv4(hostname, addrs)?;
+
+    info!("Resolved to {}", ip);
+    Ok(ip)
+}
+
+/// Builds a resolver that only queries `server`.
+fn resolver_for(server: SocketAddr) -> TokioResolver {
+    let name_servers = NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true);
+    TokioResolver::builder_with_config(
+        ResolverConfig::from_parts(None, Vec::new(), name_servers),
+        TokioConnectionProvider::default(),
+    )
+    .build()
+}
+
+/// Resolves a hostname through the process-wide [`ResolverCache`].
+///
+/// The cache is created on first use with `ttl`; later calls share it.
+pub fn cached_hostname_to_ip(hostname: &str, ttl: Duration) -> Result<std::net::Ipv4Addr> {
+    static CACHE: OnceLock<ResolverCache> = OnceLock::new();
+    CACHE
+        .get_or_init(|| ResolverCache::new(ttl))
+        .resolve(hostname)
+}
+
+/// Re-resolves `host` every `ttl` and publishes its address to `tx` when it
+/// changes, so the agent follows a controller that moved to a new IP.
+///
+/// Lookups share the cache of [`cached_hostname_to_ip`]. A failed lookup is
+/// logged and the last good address stays in effect. Stops once every
+/// receiver of `tx` is gone.
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+pub fn refresh_controller_ip(
+    host: String,
+    ttl: Duration,
+    tx: watch::Sender<Ipv4Addr>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ttl.max(Duration::from_secs(1)));
+        // The first tick fires at once, right after the startup lookup
+        interval.tick().await;
+        while !tx.is_closed() {
+            interval.tick().await;
+            let lookup_host = host.clone();
+            let resolved =
+                tokio::task::spawn_blocking(move || cached_hostname_to_ip(&lookup_host, ttl))
+                    .await
+                    .map_err(|e| anyhow!("DNS lookup task failed: {}", e))
+                    .and_then(|resolved| resolved);
+            publish_controller_ip(&host, resolved, &tx);
+        }
+    })
+}
+
+/// Publishes a re-resolved controller address if it is valid and has
+/// changed. Returns `true` if a new IP was published.
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+fn publish_controller_ip(
+    host: &str,
+    resolved: Result<Ipv4Addr>,
+    tx: &watch::Sender<Ipv4Addr>,
+) -> bool {
+    match resolved {
+        Ok(ip) => tx.send_if_modified(|current| {
+            if *current == ip {
+                return false;
+            }
+            info!("Controller {} moved: {} -> {}", host, current, ip);
+            *current = ip;
+            true
+        }),
+        Err(e) => {
+            warn!(
+                "Keeping controller IP, re-resolving {} failed: {:#}",
+                host, e
+            );
+            false
+        }
+    }
+}
+
+/// Remembers resolved addresses so repeated lookups within `ttl` skip DNS.
+///
+/// Safe to share between threads. Failed lookups are not cached.
+pub struct ResolverCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (std::net::Ipv4Addr, Instant)>>,
+}
+
+impl ResolverCache {
+    /// Creates an empty cache whose entries stay fresh for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached address for `hostname`, resolving it if missing or stale.
+    pub fn resolve(&self, hostname: &str) -> Result<std::net::Ipv4Addr> {
+        self.resolve_with(hostname, |host| hostname_to_ip(host.to_string()))
+    }
+
+    fn resolve_with(
+        &self,
+        hostname: &str,
+        resolver: impl FnOnce(&str) -> Result<std::net::Ipv4Addr>,
+    ) -> Result<std::net::Ipv4Addr> {
+        // A poisoned lock only means another thread panicked mid-update;
+        // the map itself is still usable
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some((ip, resolved_at)) = entries.get(hostname)
+            && resolved_at.elapsed() < self.ttl
+        {
+            debug!("Using cached address {} for {}", ip, hostname);
+            return Ok(*ip);
+        }
+
+        let ip = resolver(hostname)?;
+        entries.insert(hostname.to_string(), (ip, Instant::now()));
+        Ok(ip)
+    }
+}
+
+/// One target of an SRV record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SrvTarget {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    host: String,
+}
+
+/// DNS queries needed to follow an SRV record to an address.
+trait SrvLookup {
+    /// Returns every target of `service`, or none if it has no SRV record.
+    async fn srv(&self, service: &str) -> Result<Vec<SrvTarget>>;
+    /// Returns the first IPv4 address of `host`.
+    async fn ipv4(&self, host: &str) -> Result<Ipv4Addr>;
+}
+
+impl SrvLookup for TokioResolver {
+    async fn srv(&self, service: &str) -> Result<Vec<SrvTarget>> {
+        match self.srv_lookup(service).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|srv| SrvTarget {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    host: srv.target().to_utf8(),
+                })
+                .collect()),
+            Err(e) if e.is_no_records_found() => Ok(Vec::new()),
+            Err(e) => Err(e).with_context(|| format!("SRV lookup failed for {}", service)),
+        }
+    }
+
+    async fn ipv4(&self, host: &str) -> Result<Ipv4Addr> {
+        let lookup = self
+            .ipv4_lookup(host)
+            .await
+            .with_context(|| format!("Failed to resolve host: {}", host))?;
+        lookup
+            .iter()
+            .next()
+            .map(|a| a.0)
+            .ok_or_else(|| anyhow!("{} did not resolve to an IPv4 address", host))
+    }
+}
+
+/// Resolves a DNS SRV record such as `_aegis._tcp.example.com` to the
+/// controller's IPv4 address and port.
+///
+/// Uses the system resolver configuration, or the server chosen with
+/// [`use_dns_server`]. When there are several targets, the one with the
+/// lowest priority and then the highest weight is used.
+pub async fn resolve_srv(service: &str) -> Result<(Ipv4Addr, u16)> {
+    let resolver = match DNS_SERVER.get() {
+        Some(server) => resolver_for(*server),
+        None => TokioResolver::builder_tokio()
+            .context("Failed to read system DNS configuration")?
+            .build(),
+    };
+    resolve_srv_with(service, &resolver).await
+}
+
+async fn resolve_srv_with(service: &str, resolver: &impl SrvLookup) -> Result<(Ipv4Addr, u16)> {
+    info!("Resolving SRV record: {}", service);
+    let targets = resolver.srv(service).await?;
+    let target = targets
+        .iter()
+        .min_by_key(|t| (t.priority, std::cmp::Reverse(t.weight)))
+        .ok_or_else(|| anyhow!("No SRV record found for {}", service))?;
+
+    let ip = resolver.ipv4(&target.host).await?;
+    info!(
+        "Resolved {} to {}:{} via {}",
+        service, ip, target.port, target.host
+    );
+    Ok((ip, target.port))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,10 +332,232 @@ mod tests {
         assert_eq!(ip, Ipv4Addr::new(192, 168, 1, 1));
     }
 
+    #[test]
+    fn test_ipv6_literal_is_rejected() {
+        let err = hostname_to_ip("fd00::5".to_string()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("IPv6 controllers are not supported yet"),
+            "{}",
+            err
+        );
+        assert!(parse_controller_ip("fd00::5").is_err());
+        assert_eq!(
+            parse_controller_ip("10.0.0.5").unwrap(),
+            Ipv4Addr::new(10, 0, 0, 5)
+        );
+    }
+
+    #[test]
+    fn test_ipv6_only_host_is_rejected() {
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let err = first_ipv4("controller.example.com", [v6]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "controller.example.com only resolves to IPv6 (2001:db8::1); IPv6 controllers \
+             are not supported yet, use an IPv4 address or a hostname with an A record"
+        );
+
+        let v4 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(
+            first_ipv4("controller.example.com", [v6, v4]).unwrap(),
+            Ipv4Addr::new(10, 0, 0, 1)
+        );
+        assert!(first_ipv4("controller.example.com", []).is_err());
+    }
+
     #[test]
     fn test_resolve_invalid_host() {
         // Using a invalid TLD should fail
         let result = hostname_to_ip("invalid.host.arglebargle".to_string());
         assert!(result.is_err());
     }
+
+    /// Answers SRV and A queries from fixed tables.
+    struct MockResolver {
+        targets: Vec<SrvTarget>,
+        hosts: HashMap<&'static str, Ipv4Addr>,
+    }
+
+    impl SrvLookup for MockResolver {
+        async fn srv(&self, _: &str) -> Result<Vec<SrvTarget>> {
+            Ok(self.targets.clone())
+        }
+
+        async fn ipv4(&self, host: &str) -> Result<Ipv4Addr> {
+            self.hosts
+                .get(host)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("NXDOMAIN"))
+        }
+    }
+
+    fn srv_target(priority: u16, weight: u16, port: u16, host: &str) -> SrvTarget {
+        SrvTarget {
+            priority,
+            weight,
+            port,
+            host: host.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_srv_picks_preferred_target() {
+        let resolver = MockResolver {
+            targets: vec![
+                srv_target(20, 100, 9443, "backup.example.com."),
+                srv_target(10, 10, 8443, "light.example.com."),
+                srv_target(10, 60, 443, "primary.example.com."),
+            ],
+            hosts: HashMap::from([
+                ("backup.example.com.", Ipv4Addr::new(10, 0, 0, 3)),
+                ("light.example.com.", Ipv4Addr::new(10, 0, 0, 2)),
+                ("primary.example.com.", Ipv4Addr::new(10, 0, 0, 1)),
+            ]),
+        };
+
+        let resolved = resolve_srv_with("_aegis._tcp.example.com", &resolver)
+            .await
+            .unwrap();
+        assert_eq!(resolved, (Ipv4Addr::new(10, 0, 0, 1), 443));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_srv_without_record_fails() {
+        let resolver = MockResolver {
+            targets: Vec::new(),
+            hosts: HashMap::new(),
+        };
+
+        let err = resolve_srv_with("_aegis._tcp.example.com", &resolver)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No SRV record found for _aegis._tcp.example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_srv_unresolvable_target_fails() {
+        let resolver = MockResolver {
+            targets: vec![srv_target(10, 0, 443, "gone.example.com.")],
+            hosts: HashMap::new(),
+        };
+
+        assert!(
+            resolve_srv_with("_aegis._tcp.example.com", &resolver)
+                .await
+                .is_err()
+        );
+    }
+
+    /// Answers one A query for any name with `ip`, then exits.
+    fn mock_dns_server(ip: Ipv4Addr) -> SocketAddr {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            let query = &buf[..len];
+
+            // Question section: labels up to the root, then QTYPE and QCLASS
+            let mut end = 12;
+            while query[end] != 0 {
+                end += query[end] as usize + 1;
+            }
+            end += 5;
+
+            let mut reply = Vec::new();
+            reply.extend_from_slice(&query[..2]); // ID
+            reply.extend_from_slice(&[0x81, 0x80]); // Response, RD, RA, NOERROR
+            reply.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 0]); // 1 question, 1 answer
+            reply.extend_from_slice(&query[12..end]);
+            reply.extend_from_slice(&[0xC0, 0x0C]); // Name: pointer to the question
+            reply.extend_from_slice(&[0, 1, 0, 1]); // Type A, class IN
+            reply.extend_from_slice(&60u32.to_be_bytes()); // TTL
+            reply.extend_from_slice(&[0, 4]);
+            reply.extend_from_slice(&ip.octets());
+            socket.send_to(&reply, peer).unwrap();
+        });
+        addr
+    }
+
+    #[test]
+    fn test_lookup_via_dns_server() {
+        let server = mock_dns_server(Ipv4Addr::new(10, 0, 0, 42));
+        let ip = lookup_via(server, "controller.aegis.test").expect("Lookup should succeed");
+        assert_eq!(ip, Ipv4Addr::new(10, 0, 0, 42));
+    }
+
+    #[test]
+    fn test_lookup_via_skips_ip_literal() {
+        let server = SocketAddr::from(([127, 0, 0, 1], 9));
+        let ip = lookup_via(server, "192.168.1.1").unwrap();
+        assert_eq!(ip, Ipv4Addr::new(192, 168, 1, 1));
+    }
+
+    #[test]
+    fn test_cache_reuses_fresh_entry() {
+        let cache = ResolverCache::new(Duration::from_secs(300));
+        let first = cache
+            .resolve_with("controller", |_| Ok(Ipv4Addr::new(10, 0, 0, 1)))
+            .unwrap();
+        let second = cache
+            .resolve_with("controller", |_| {
+                panic!("fresh entry should not be resolved")
+            })
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_cache_refreshes_stale_entry() {
+        let cache = ResolverCache::new(Duration::ZERO);
+        cache
+            .resolve_with("controller", |_| Ok(Ipv4Addr::new(10, 0, 0, 1)))
+            .unwrap();
+        let ip = cache
+            .resolve_with("controller", |_| Ok(Ipv4Addr::new(10, 0, 0, 2)))
+            .unwrap();
+        assert_eq!(ip, Ipv4Addr::new(10, 0, 0, 2));
+    }
+
+    #[test]
+    fn test_cache_skips_failures() {
+        let cache = ResolverCache::new(Duration::from_secs(300));
+        assert!(
+            cache
+                .resolve_with("controller", |_| Err(anyhow::anyhow!("SERVFAIL")))
+                .is_err()
+        );
+        let ip = cache
+            .resolve_with("controller", |_| Ok(Ipv4Addr::new(10, 0, 0, 1)))
+            .unwrap();
+        assert_eq!(ip, Ipv4Addr::new(10, 0, 0, 1));
+    }
+
+    #[test]
+    fn test_publish_controller_ip() {
+        let (tx, rx) = watch::channel(Ipv4Addr::new(10, 0, 0, 1));
+
+        assert!(!publish_controller_ip(
+            "controller",
+            Ok(Ipv4Addr::new(10, 0, 0, 1)),
+            &tx
+        ));
+        assert!(!publish_controller_ip(
+            "controller",
+            Err(anyhow!("SERVFAIL")),
+            &tx
+        ));
+        assert_eq!(*rx.borrow(), Ipv4Addr::new(10, 0, 0, 1));
+
+        assert!(publish_controller_ip(
+            "controller",
+            Ok(Ipv4Addr::new(10, 0, 0, 2)),
+            &tx
+        ));
+        assert_eq!(*rx.borrow(), Ipv4Addr::new(10, 0, 0, 2));
+    }
 }
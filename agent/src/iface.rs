@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, anyhow};
+use nix::{ifaddrs::getifaddrs, net::if_::InterfaceFlags, net::if_::if_nametoindex};
+use std::fs;
+use tracing::{debug, warn};
+
+/// Drivers known to implement native (driver-mode) XDP.
+const NATIVE_XDP_DRIVERS: [&str; 16] = [
+    "bnxt_en",
+    "ena",
+    "i40e",
+    "ice",
+    "igb",
+    "igc",
+    "ixgbe",
+    "mlx4_en",
+    "mlx5_core",
+    "nfp",
+    "qede",
+    "sfc",
+    "stmmac",
+    "tun",
+    "veth",
+    "virtio_net",
+];
+
+/// Result of the interface pre-flight check.
+#[derive(Debug)]
+pub struct InterfaceInfo {
+    /// Kernel interface index
+    pub index: i32,
+    /// Kernel driver bound to the interface, if it could be determined
+    pub driver: Option<String>,
+    /// Whether the driver is known to support native XDP
+    pub native_xdp: bool,
+    /// Whether the link is operationally up, e.g. has carrier
+    pub link_up: bool,
+}
+
+/// Verifies the interface exists, is up, and reports whether its driver supports native XDP.
+///
+/// Returns an error if the interface is missing or administratively down.
+/// An interface that is enabled but has no link, e.g. no carrier, only logs
+/// a warning unless `require_link_up` is set, in which case it is an error.
+pub fn check_interface(name: &str, require_link_up: bool) -> Result<InterfaceInfo> {
+    let index = index_of(name)? as i32;
+
+    if !is_up(name)? {
+        return Err(anyhow!(
+            "Interface {} is down. Bring it up with `ip link set {} up`.",
+            name,
+            name
+        ));
+    }
+
+    let state = operstate(name);
+    let link_up = state.as_deref().is_none_or(is_oper_up);
+    if !link_up {
+        let state = state.as_deref().unwrap_or_default();
+        if require_link_up {
+            return Err(anyhow!(
+                "Interface {} has no link (operstate {}), refusing to attach",
+                name,
+                state
+            ));
+        }
+        warn!(
+            "Interface {} has no link (operstate {}): the filter is attached but sees \
+             no traffic until the link comes up. Use --require-link-up to fail instead.",
+            name, state
+        );
+    }
+
+    let driver = driver_name(name);
+    let native_xdp = driver.as_deref().is_some_and(driver_supports_native_xdp);
+
+    match driver.as_deref() {
+        Some(driver) if native_xdp => {
+            debug!("Interface {} uses driver {} (native XDP)", name, driver);
+        }
+        Some(driver) => {
+            warn!(
+                "Driver {} on {} does not support native XDP, will use SKB mode",
+                driver, name
+            );
+        }
+        None => {
+            warn!(
+                "Could not determine driver for {}, native XDP support unknown",
+                name
+            );
+        }
+    }
+
+    Ok(InterfaceInfo {
+        index,
+        driver,
+        native_xdp,
+        link_up,
+    })
+}
+
+/// Returns the kernel index of the interface called `name`.
+pub fn index_of(name: &str) -> Result<u32> {
+    if_nametoindex(name).with_context(|| format!("Interface '{}' not found", name))
+}
+
+/// Returns `true` if the interface has `IFF_UP` set.
+fn is_up(name: &str) -> Result<bool> {
+    let addrs = getifaddrs().context("Failed to list network interfaces")?;
+
+    addrs
+        .find(|addr| addr.interface_name == name)
+        .map(|addr| addr.flags.contains(InterfaceFlags::IFF_UP))
+        .ok_or_else(|| anyhow!("Interface '{}' not found", name))
+}
+
+/// Reads the RFC 2863 operational state (`up`, `down`, `lowerlayerdown`, ...)
+/// from sysfs, or `None` if it is unavailable.
+fn operstate(name: &str) -> Option<String> {
+    fs::read_to_string(format!("/sys/class/net/{}/operstate", name))
+        .ok()
+        .map(|state| state.trim().to_string())
+}
+
+/// Returns `true` if an operational state can carry traffic. Loopback and
+/// many virtual devices never report carrier and stay `unknown`.
+fn is_oper_up(state: &str) -> bool {
+    matches!(state, "up" | "unknown")
+}
+
+/// Reads the kernel driver name from sysfs.
+///
+/// Virtual devices (e.g. bridges) have no `device` link and yield `None`.
+fn driver_name(name: &str) -> Option<String> {
+    fs::read_link(format!("/sys/class/net/{}/device/driver", name))
+        .ok()?
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+}
+
+/// Returns `true` if the driver is known to support native XDP.
+fn driver_supports_native_xdp(driver: &str) -> bool {
+    NATIVE_XDP_DRIVERS.contains(&driver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_native_drivers() {
+        assert!(driver_supports_native_xdp("mlx5_core"));
+        assert!(driver_supports_native_xdp("virtio_net"));
+        assert!(driver_supports_native_xdp("veth"));
+    }
+
+    #[test]
+    fn test_unknown_driver() {
+        assert!(!driver_supports_native_xdp("e1000"));
+        assert!(!driver_supports_native_xdp(""));
+    }
+
+    #[test]
+    fn test_missing_interface_fails() {
+        let result = check_interface("aegis-none0", false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_loopback_is_up() {
+        let info = check_interface("lo", true).expect("Loopback should be up");
+        assert!(info.index > 0);
+        assert!(info.link_up);
+    }
+
+    #[test]
+    fn test_oper_state() {
+        assert!(is_oper_up("up"));
+        assert!(is_oper_up("unknown"));
+        assert!(!is_oper_up("down"));
+        assert!(!is_oper_up("lowerlayerdown"));
+        assert!(!is_oper_up("dormant"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Conversions between `Ipv4Addr` and its two `u32` representations.
+//!
+//! Host order is the address read as a big-endian number, so 10.0.0.1 is
+//! `0x0A000001`. The gRPC API carries addresses this way
+//! (`u32::from(Ipv4Addr)` in Rust, `binary.BigEndian.Uint32` in Go).
+//!
+//! Network order keeps the address bytes in memory order, as in a packet
+//! header. The BPF maps and rodata hold addresses this way, since the
+//! program compares them with `iph->saddr` directly.
+
+use std::net::Ipv4Addr;
+
+/// `ip` as the host-order value used by the gRPC API.
+pub fn to_host(ip: Ipv4Addr) -> u32 {
+    u32::from(ip)
+}
+
+/// Address of a host-order value from the gRPC API.
+pub fn from_host(raw: u32) -> Ipv4Addr {
+    Ipv4Addr::from(raw)
+}
+
+/// `ip` as the network-order value stored in BPF maps.
+pub fn to_network(ip: Ipv4Addr) -> u32 {
+    u32::from_ne_bytes(ip.octets())
+}
+
+/// Address of a network-order value read from a BPF map.
+pub fn from_network(raw: u32) -> Ipv4Addr {
+    Ipv4Addr::from(raw.to_ne_bytes())
+}
+
+/// Converts a host-order value to network order.
+pub fn host_to_network(raw: u32) -> u32 {
+    to_network(from_host(raw))
+}
+
+/// Converts a network-order value to host order.
+pub fn network_to_host(raw: u32) -> u32 {
+    to_host(from_network(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+    #[test]
+    fn test_host_order() {
+        assert_eq!(to_host(ADDR), 0x0A000001);
+        assert_eq!(from_host(0x0A000001), ADDR);
+        assert_eq!(to_host(Ipv4Addr::new(192, 168, 1, 254)), 0xC0A801FE);
+    }
+
+    #[test]
+    fn test_network_order() {
+        // Same bytes as on the wire, whatever the CPU's endianness
+        assert_eq!(to_network(ADDR).to_ne_bytes(), [10, 0, 0, 1]);
+        assert_eq!(to_network(ADDR), 0x0A000001u32.to_be());
+        assert_eq!(from_network(u32::from_ne_bytes([10, 0, 0, 1])), ADDR);
+    }
+
+    #[test]
+    fn test_conversions_round_trip() {
+        for ip in [
+            Ipv4Addr::UNSPECIFIED,
+            ADDR,
+            Ipv4Addr::new(172, 21, 0, 10),
+            Ipv4Addr::BROADCAST,
+        ] {
+            assert_eq!(from_host(to_host(ip)), ip);
+            assert_eq!(from_network(to_network(ip)), ip);
+            assert_eq!(host_to_network(to_host(ip)), to_network(ip));
+            assert_eq!(network_to_host(to_network(ip)), to_host(ip));
+        }
+    }
+}
@@ -0,0 +1,978 @@
+//! # Aegis Agent Library
+//!
+//! Reusable pieces of the Aegis agent, shared by the `aegis-agent` binary and
+//! downstream crates that talk to it.
+//!
+//! Stable API:
+//! - [`session`]: generated protobuf messages and gRPC service definitions
+//!   (`grpc` feature)
+//! - [`Config`]: agent configuration loaded from `config.toml`
+//! - [`SessionKey`] / [`SessionVal`]: typed views of the eBPF session map entries
+
+pub mod audit;
+mod benchmark;
+pub mod bpf;
+mod cap;
+#[cfg(feature = "grpc")]
+mod certs;
+mod cgroup;
+pub mod cli;
+pub mod clock;
+pub mod command;
+pub mod config;
+mod controller_ip_file;
+pub mod daemon;
+#[cfg(feature = "grpc")]
+pub mod grpc_server;
+mod hostname_to_ip;
+mod iface;
+pub mod ip;
+mod link_monitor;
+pub mod logging;
+pub mod netns;
+mod pairing;
+#[cfg(feature = "grpc")]
+mod proxy_protocol;
+mod seed;
+pub mod types;
+pub mod version;
+
+pub use config::Config;
+#[cfg(feature = "grpc")]
+pub use grpc_server::session;
+pub use types::{SessionKey, SessionVal};
+
+#[cfg(feature = "grpc")]
+use crate::grpc_server::{
+    GrpcListener, RuleChange, ServerSetup, session::SessionList, session_list, start_grpc_server,
+    start_grpc_server_uds,
+};
+use crate::{
+    bpf::{Bpf, Counters, ProgStats},
+    cli::Args,
+};
+use anyhow::{Context, Result};
+#[cfg(feature = "grpc")]
+use std::net::Ipv4Addr;
+use std::{sync::Arc, time::Duration};
+#[cfg(feature = "grpc")]
+use tokio::sync::{Mutex, broadcast, watch};
+use tracing::{debug, error, info, warn};
+
+/// Runs the agent: attaches the XDP firewall and serves gRPC until shutdown.
+///
+/// Built without the `grpc` feature, it attaches the firewall and keeps it
+/// maintained until SIGTERM/SIGINT; sessions come from `--seed-sessions` and
+/// the local subcommands. `config.toml` and relative paths in it are read
+/// from `workdir`, the directory the agent was started in.
+pub async fn run(args: Args, workdir: &std::path::Path) -> Result<()> {
+    // Verify we have necessary privileges
+    debug!("Checking capabilities...");
+    cap::check_capabilities().with_context(|| "Missing required capabilities")?;
+    info!("Capabilities verified");
+
+    // Load configuration
+    args.apply_early();
+    let mut config = Config::load_in(workdir)?;
+    args.apply(&mut config);
+    args.apply_controller(&mut config)?;
+    if let Some(path) = &config.controller_ip_file {
+        config.controller_ip = controller_ip_file::read_controller_ip(path)?;
+        config.controller_host = None;
+    }
+    if args.pair_from_cert {
+        pairing::pair_from_cert(&mut config)?;
+    }
+    if let Some(service) = &args.controller_srv {
+        if config.controller_ip_file.is_some() {
+            return Err(anyhow::anyhow!(
+                "--controller-srv cannot be combined with controller.ip_file"
+            ));
+        }
+        if args.controller_host.is_some() || args.controller_ip.is_some() {
+            return Err(anyhow::anyhow!(
+                "--controller-srv cannot be combined with --controller-host or --controller-ip"
+            ));
+        }
+        (config.controller_ip, config.controller_port) =
+            hostname_to_ip::resolve_srv(service).await?;
+        config.controller_host = None;
+    }
+    debug!("Configuration: {:?}", config);
+
+    // Enforcing without a control plane would lock the controller out, so a
+    // bad certificate path must fail before anything is attached. The Unix
+    // socket server does not use TLS.
+    #[cfg(feature = "grpc")]
+    if args.grpc_uds.is_none() {
+        config.check_cert_files()?;
+    }
+
+    // Report what the kernel supports before loading anything, and refuse
+    // to start rather than fail later inside an operation
+    let features = bpf::probe_features();
+    info!("BPF features: {}", features);
+    features.check_required()?;
+    if !features.batch_delete {
+        warn!("Batch deletion unsupported, stale rules will be removed one by one");
+    }
+
+    // Read seed sessions up front so a bad path fails before anything is attached
+    let seed_sessions = args
+        .seed_sessions
+        .as_deref()
+        .map(seed::read_seed_file)
+        .transpose()?;
+
+    // Resolve the interface and attach, waiting for it if it is created late,
+    // but only once the gRPC server is ready and its port is bound
+    let attach = retry_with_backoff(
+        "Attach",
+        config.attach_retries,
+        Duration::from_millis(config.attach_retry_delay_ms),
+        || {
+            let interface_index =
+                iface::check_interface(&config.iface_name, config.require_link_up)?.index;
+            info!(
+                "Interface: {} (index: {})",
+                config.iface_name, interface_index
+            );
+            debug!("Loading BPF program...");
+            Bpf::new(interface_index, &config)
+        },
+    );
+    #[cfg(feature = "grpc")]
+    let ((setup, listener), bpf) =
+        bind_then_attach(prepare_grpc(&config, args.grpc_uds.as_deref()), attach).await?;
+    #[cfg(not(feature = "grpc"))]
+    let bpf = attach.await?;
+    info!("BPF program attached on the {} hook", config.attach_hook);
+
+    // Restore known sessions before the gRPC server accepts connections
+    if let Some(keys) = seed_sessions {
+        let restored = keys
+            .iter()
+            .filter(|key| match bpf.add_rule(**key) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("Failed to seed session {}: {:#}", key, e);
+                    false
+                }
+            })
+            .count();
+        info!("Seeded {} of {} sessions", restored, keys.len());
+    }
+    let bpf = Arc::new(std::sync::Mutex::new(bpf));
+
+    // Leave the startup grace period once the controller had time to push
+    // sessions, and exit nonzero if that keeps failing rather than stay
+    // permissive until GRACE_UNTIL alone ends it
+    let bpf_grace = bpf.clone();
+    let grace_sec = config.enforcement_grace_sec;
+    let grace = async move {
+        if grace_sec == 0 {
+            return std::future::pending().await;
+        }
+        let result = close_grace_window(Duration::from_secs(grace_sec), GRACE_RETRY_DELAY, || {
+            bpf_grace
+                .lock()
+                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?
+                .set_enforcing(true)
+        })
+        .await;
+        match result {
+            Ok(()) => {
+                info!(
+                    "Grace window of {}s closed, now dropping unauthorized traffic",
+                    grace_sec
+                );
+                std::future::pending().await
+            }
+            Err(e) => {
+                error!("Traffic is NOT filtered, exiting: {:#}", e);
+                e
+            }
+        }
+    };
+
+    // A driver reset or link flap can detach XDP; attach again once it is back
+    let bpf_link = bpf.clone();
+    let iface_name = config.iface_name.clone();
+    tokio::spawn(async move {
+        let reattach = |interface_index| {
+            let bpf = bpf_link
+                .lock()
+                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+            bpf.reattach(interface_index)
+        };
+        if let Err(e) = link_monitor::watch_link(iface_name, reattach).await {
+            warn!("Link monitor stopped, XDP will not be re-attached: {:#}", e);
+        }
+    });
+
+    // Show active policy
+    warn!("Zero-trust policy active on {}", config.iface_name);
+    warn!(
+        "Allowing only controller traffic ({}:{}) and authorized sessions",
+        config.controller_ip, config.controller_port
+    );
+
+    #[cfg(feature = "grpc")]
+    let (monitor_tx, _) = broadcast::channel(config.broadcast_channel_size);
+    #[cfg(feature = "grpc")]
+    let monitor_tx_loop = monitor_tx.clone();
+    let bpf_cleanup = bpf.clone();
+    let rule_timeout_ns = config.rule_timeout_ns;
+    let max_session_age_ns = config.max_session_age_ns;
+    let cleanup_interval_sec = config.cleanup_interval_sec;
+    let max_conns_per_src = config.max_conns_per_src;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(cleanup_interval_sec));
+        loop {
+            interval.tick().await;
+            debug!("Running periodic eBPF rule cleanup...");
+            match bpf_cleanup.lock() {
+                Ok(bpf) => {
+                    match bpf.cleanup_ebpf_rules(rule_timeout_ns, max_session_age_ns) {
+                        Ok(count) => {
+                            if count > 0 {
+                                debug!("Cleaned up {} stale rules", count);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to cleanup stale rules: {}", e);
+                        }
+                    }
+                    if max_conns_per_src > 0
+                        && let Err(e) = bpf.expire_idle_conns(rule_timeout_ns)
+                    {
+                        error!("Failed to expire idle connections: {:#}", e);
+                    }
+                    match bpf.map_stats() {
+                        Ok((used, capacity)) => log_map_utilization(used, capacity),
+                        Err(e) => error!("Failed to read session map stats: {:#}", e),
+                    }
+                    #[cfg(feature = "grpc")]
+                    publish_sessions(&monitor_tx_loop, bpf.list_rules(rule_timeout_ns));
+                }
+                Err(e) => {
+                    error!("Failed to acquire BPF lock for cleanup: {}", e);
+                }
+            }
+        }
+    });
+
+    if config.bpf_stats {
+        let bpf_stats = bpf.clone();
+        let stats_interval_sec = config.bpf_stats_interval_sec.max(1);
+
+        tokio::spawn(async move {
+            // With BPF_ENABLE_STATS, collection stops when this fd is closed
+            let _stats_fd = match bpf::enable_stats() {
+                Ok(fd) => fd,
+                Err(e) => {
+                    warn!(
+                        "{:#}; XDP statistics stay at zero unless enabled elsewhere",
+                        e
+                    );
+                    None
+                }
+            };
+
+            let mut interval = tokio::time::interval(Duration::from_secs(stats_interval_sec));
+            let mut last = ProgStats::default();
+            let mut last_counters = Counters::default();
+            loop {
+                interval.tick().await;
+                let (stats, counters) = match bpf_stats.lock() {
+                    Ok(bpf) => (bpf.prog_stats(), bpf.get_counters()),
+                    Err(e) => {
+                        error!("Failed to acquire BPF lock for statistics: {}", e);
+                        continue;
+                    }
+                };
+                match stats {
+                    Ok(stats) => {
+                        match stats.since(&last) {
+                            Some((runs, avg_ns)) => info!(
+                                "XDP program: {} runs, {:.1} ns/run over the last {}s",
+                                runs, avg_ns, stats_interval_sec
+                            ),
+                            None => debug!(
+                                "XDP program: no runs recorded in the last {}s",
+                                stats_interval_sec
+                            ),
+                        }
+                        last = stats;
+                    }
+                    Err(e) => warn!("Failed to read XDP program statistics: {:#}", e),
+                }
+                match counters {
+                    Ok(counters) => {
+                        // Helps tune lazy_update_timeout_ns: a high share means
+                        // few map writes, a low one means the window is too short
+                        let delta = counters.since(&last_counters);
+                        if let Some(ratio) = delta.suppressed_ratio() {
+                            info!(
+                                "Session refreshes: {} applied, {} suppressed ({:.1}% suppressed)",
+                                delta.refresh_applied,
+                                delta.refresh_suppressed,
+                                ratio * 100.0
+                            );
+                        }
+                        if delta.mac_mismatch > 0 {
+                            warn!(
+                                "Dropped {} session packets from a foreign source MAC in the last {}s",
+                                delta.mac_mismatch, stats_interval_sec
+                            );
+                        }
+                        if delta.conn_capped > 0 {
+                            warn!(
+                                "Dropped {} new connections over max_conns_per_src in the last {}s",
+                                delta.conn_capped, stats_interval_sec
+                            );
+                        }
+                        last_counters = counters;
+                    }
+                    Err(e) => warn!("Failed to read XDP counters: {:#}", e),
+                }
+            }
+        });
+    }
+
+    // Exit nonzero once the map stops answering, so the supervisor restarts us
+    let bpf_watchdog = bpf.clone();
+    let watchdog_interval_sec = config.bpf_watchdog_interval_sec;
+    let watchdog_failures = config.bpf_watchdog_failures;
+    let watchdog = async move {
+        if watchdog_interval_sec == 0 {
+            return std::future::pending().await;
+        }
+        watch_datapath(
+            Duration::from_secs(watchdog_interval_sec),
+            watchdog_failures,
+            || {
+                bpf_watchdog
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?
+                    .probe_session_map()
+            },
+        )
+        .await
+    };
+
+    #[cfg(feature = "grpc")]
+    let serve = serve_grpc(&config, setup, listener, &bpf, monitor_tx);
+
+    // Without the control plane the agent only keeps the datapath up
+    #[cfg(not(feature = "grpc"))]
+    let serve = async {
+        info!("Built without gRPC, manage sessions with the local subcommands");
+        daemon::shutdown_signal().await
+    };
+
+    tokio::select! {
+        result = serve => result?,
+        e = watchdog => return Err(e),
+        e = grace => return Err(e),
+    }
+
+    Ok(())
+}
+
+/// Calls `apply` with every controller IP published on `rx` until its sender
+/// is dropped, so the XDP program lets the new controller through.
+#[cfg(feature = "grpc")]
+fn follow_controller_ip(
+    mut rx: watch::Receiver<Ipv4Addr>,
+    apply: impl Fn(Ipv4Addr) -> Result<()> + Send + 'static,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while rx.changed().await.is_ok() {
+            let ip = *rx.borrow_and_update();
+            match apply(ip) {
+                Ok(()) => info!("XDP program now allows controller {}", ip),
+                Err(e) => error!("Failed to allow controller {} in XDP: {:#}", ip, e),
+            }
+        }
+    })
+}
+
+/// Builds the gRPC handlers on top of `bpf` and serves `listener` until
+/// SIGTERM/SIGINT, then drains open streams.
+#[cfg(feature = "grpc")]
+async fn serve_grpc(
+    config: &Config,
+    setup: ServerSetup,
+    listener: GrpcListener,
+    bpf: &Arc<std::sync::Mutex<Bpf<'static>>>,
+    monitor_tx: broadcast::Sender<Result<SessionList, tonic::Status>>,
+) -> Result<()> {
+    let rule_timeout_ns = config.rule_timeout_ns;
+
+    let bpf_grpc = bpf.clone();
+    let modify_rule_handler = Arc::new(Mutex::new(
+        move |is_add: bool, key: SessionKey, epoch: u64, src_mac: u64| -> Result<RuleChange> {
+            let bpf = bpf_grpc
+                .lock()
+                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+
+            let previous_existed = if is_add {
+                bpf.add_rule_in_epoch(key, epoch, src_mac)?
+            } else {
+                bpf.remove_rule(key)?
+            };
+            Ok(RuleChange {
+                previous_existed,
+                active_sessions: bpf.approx_len(),
+            })
+        },
+    ));
+
+    let bpf_ip_update = bpf.clone();
+    let update_ip_handler = Arc::new(Mutex::new(
+        move |old_dest_ip: u32, new_dest_ip: u32| -> Result<usize> {
+            let bpf = bpf_ip_update
+                .lock()
+                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+            bpf.update_dest_ip(ip::from_host(old_dest_ip), ip::from_host(new_dest_ip))
+        },
+    ));
+
+    let bpf_flush = bpf.clone();
+    let flush_all_handler = Arc::new(Mutex::new(move || -> Result<usize> {
+        let bpf = bpf_flush
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        bpf.clear_all_rules()
+    }));
+
+    let bpf_blocklist = bpf.clone();
+    let modify_blocklist_handler =
+        Arc::new(Mutex::new(move |block: bool, ip: Ipv4Addr| -> Result<()> {
+            let bpf = bpf_blocklist
+                .lock()
+                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+
+            if block {
+                bpf.block_ip(ip)
+            } else {
+                bpf.unblock_ip(ip)
+            }
+        }));
+
+    let bpf_lookup = bpf.clone();
+    let get_session_handler = Arc::new(Mutex::new(move |key: SessionKey| -> Result<Option<i64>> {
+        let bpf = bpf_lookup
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        Ok(bpf
+            .get_rule(key, rule_timeout_ns)?
+            .map(|entry| entry.time_left_sec))
+    }));
+
+    let bpf_map_stats = bpf.clone();
+    let map_stats_handler = Arc::new(Mutex::new(move || -> Result<(usize, usize)> {
+        let bpf = bpf_map_stats
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        bpf.map_stats()
+    }));
+
+    let bpf_prune = bpf.clone();
+    let prune_epochs_handler = Arc::new(Mutex::new(move |epoch: u64| -> Result<usize> {
+        let bpf = bpf_prune
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        bpf.prune_stale_epochs(epoch)
+    }));
+
+    let bpf_mode = bpf.clone();
+    let is_enforcing_handler = Arc::new(Mutex::new(move || -> Result<bool> {
+        let bpf = bpf_mode
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        bpf.is_enforcing()
+    }));
+
+    let bpf_list = bpf.clone();
+    let list_sessions_handler = Arc::new(Mutex::new(move || -> Result<Vec<(SessionKey, i64)>> {
+        let bpf = bpf_list
+            .lock()
+            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
+        bpf.list_rules(rule_timeout_ns)
+    }));
+
+    if config.grpc_read_only {
+        warn!("Read-only mode: mutating RPCs will be rejected");
+    }
+
+    // Start gRPC server
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let serve = async {
+        match listener {
+            GrpcListener::Unix(listener, path) => {
+                info!("Starting gRPC server on unix:{}", path.display());
+                start_grpc_server_uds(
+                    config,
+                    setup,
+                    listener,
+                    &path,
+                    modify_rule_handler,
+                    update_ip_handler,
+                    flush_all_handler,
+                    modify_blocklist_handler,
+                    get_session_handler,
+                    map_stats_handler,
+                    prune_epochs_handler,
+                    is_enforcing_handler,
+                    list_sessions_handler,
+                    monitor_tx,
+                    shutdown_rx,
+                )
+                .await?;
+            }
+            GrpcListener::Tcp(listeners) => {
+                let (controller_ip_tx, controller_ip_rx) = watch::channel(config.controller_ip);
+
+                // Follow controller IP changes written by an external discovery
+                // process or found by re-resolving controller.host, in both
+                // gRPC authorization and the XDP program
+                if config.controller_ip_file.is_some() || config.controller_host.is_some() {
+                    let bpf_controller = bpf.clone();
+                    follow_controller_ip(controller_ip_tx.subscribe(), move |ip| {
+                        bpf_controller
+                            .lock()
+                            .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?
+                            .set_controller_ip(ip)
+                    });
+                }
+                let _controller_ip_watcher =
+                    match (&config.controller_ip_file, &config.controller_host) {
+                        (Some(path), _) => {
+                            info!("Watching controller IP file {}", path.display());
+                            Some(controller_ip_file::watch_controller_ip(
+                                path.clone(),
+                                controller_ip_tx,
+                            )?)
+                        }
+                        (None, Some(host)) => {
+                            info!(
+                                "Re-resolving controller host {} every {}s",
+                                host, config.controller_resolve_ttl_sec
+                            );
+                            hostname_to_ip::refresh_controller_ip(
+                                host.clone(),
+                                Duration::from_secs(config.controller_resolve_ttl_sec),
+                                controller_ip_tx,
+                            );
+                            None
+                        }
+                        (None, None) => None,
+                    };
+
+                info!("Starting gRPC server on port {}", config.grpc_server_port);
+                start_grpc_server(
+                    config,
+                    setup,
+                    listeners,
+                    controller_ip_rx,
+                    modify_rule_handler,
+                    update_ip_handler,
+                    flush_all_handler,
+                    modify_blocklist_handler,
+                    get_session_handler,
+                    map_stats_handler,
+                    prune_epochs_handler,
+                    is_enforcing_handler,
+                    list_sessions_handler,
+                    monitor_tx,
+                    shutdown_rx,
+                )
+                .await?;
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+
+    // Serve until SIGTERM/SIGINT. The XDP program stays attached after exit.
+    tokio::pin!(serve);
+    tokio::select! {
+        res = &mut serve => res?,
+        res = daemon::shutdown_signal() => {
+            res?;
+            // Stop accepting RPCs and let monitor streams end with a closing
+            // marker, so clients do not reconnect straight into a dead agent
+            let grace = Duration::from_secs(config.grpc_shutdown_grace_sec);
+            info!("Shutting down, draining gRPC streams for up to {:?}", grace);
+            let _ = shutdown_tx.send(true);
+            match tokio::time::timeout(grace, &mut serve).await {
+                Ok(res) => res?,
+                Err(_) => warn!("gRPC streams still open after {:?}, closing them", grace),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the sessions left after a cleanup cycle to `MonitorSessions` streams.
+#[cfg(feature = "grpc")]
+fn publish_sessions(
+    tx: &broadcast::Sender<Result<SessionList, tonic::Status>>,
+    rules: Result<Vec<(SessionKey, i64)>>,
+) {
+    match rules {
+        Ok(rules) => {
+            let _ = tx.send(Ok(session_list(rules)));
+        }
+        Err(e) => {
+            error!("Failed to list active rules: {}", e);
+            let _ = tx.send(Err(tonic::Status::internal("BPF error")));
+        }
+    }
+}
+
+/// Loads the gRPC server's TLS material and audit file, then binds its
+/// listener: everything the control plane needs before XDP is attached.
+#[cfg(feature = "grpc")]
+async fn prepare_grpc(
+    config: &Config,
+    uds: Option<&std::path::Path>,
+) -> Result<(ServerSetup, GrpcListener)> {
+    let setup = ServerSetup::load(config, uds.is_some())?;
+    let listener = GrpcListener::bind(config, uds)
+        .await
+        .context("Failed to bind gRPC listener")?;
+    Ok((setup, listener))
+}
+
+/// Awaits `bind`, then `attach`.
+///
+/// The gRPC server is set up and its listener bound first so the XDP program
+/// is never attached while the control plane is unusable: a failed `bind`
+/// returns before `attach` is polled, and a failed attach drops the bound
+/// listener.
+#[cfg(feature = "grpc")]
+async fn bind_then_attach<L, B>(
+    bind: impl Future<Output = Result<L>>,
+    attach: impl Future<Output = Result<B>>,
+) -> Result<(L, B)> {
+    let listener = bind.await.context("Failed to set up gRPC server")?;
+    let attached = attach.await?;
+    Ok((listener, attached))
+}
+
+/// Session map utilization, in percent, above which each cleanup cycle warns.
+const MAP_FULL_WARN_PERCENT: usize = 90;
+
+/// Logs how full the session map is after a cleanup cycle, warning once it
+/// nears capacity and new sessions are about to evict older ones.
+fn log_map_utilization(used: usize, capacity: usize) {
+    let percent = (used * 100).checked_div(capacity).unwrap_or(0);
+    if percent >= MAP_FULL_WARN_PERCENT {
+        warn!(
+            "Session map {}% full ({}/{}), new sessions will evict the least recently used",
+            percent, used, capacity
+        );
+    } else {
+        debug!("Session map {}% full ({}/{})", percent, used, capacity);
+    }
+}
+
+/// Runs `probe` every `interval` and returns once it has failed
+/// `max_failures` times in a row. A success resets the count.
+async fn watch_datapath(
+    interval: Duration,
+    max_failures: u32,
+    mut probe: impl FnMut() -> Result<()>,
+) -> anyhow::Error {
+    let max_failures = max_failures.max(1);
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick fires at once; the map was just checked at attach
+    ticker.tick().await;
+    let mut failures = 0;
+    loop {
+        ticker.tick().await;
+        match probe() {
+            Ok(()) => {
+                if failures > 0 {
+                    info!(
+                        "Session map responding again after {} failed probes",
+                        failures
+                    );
+                }
+                failures = 0;
+            }
+            Err(e) => {
+                failures += 1;
+                if failures >= max_failures {
+                    error!("Session map unresponsive, exiting: {:#}", e);
+                    return e.context(format!(
+                        "Session map unresponsive after {} consecutive probe failures",
+                        failures
+                    ));
+                }
+                warn!(
+                    "Session map probe {}/{} failed: {:#}",
+                    failures, max_failures, e
+                );
+            }
+        }
+    }
+}
+
+/// Retries after a failed switch out of permissive mode, and the first delay.
+const GRACE_RETRIES: u32 = 5;
+const GRACE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Waits out the startup grace window, then switches to enforcing through
+/// `set_enforcing`, retrying with backoff. Returns the last error if every
+/// attempt failed.
+async fn close_grace_window(
+    grace: Duration,
+    retry_delay: Duration,
+    set_enforcing: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    tokio::time::sleep(grace).await;
+    retry_with_backoff(
+        "Leaving permissive mode",
+        GRACE_RETRIES,
+        retry_delay,
+        set_enforcing,
+    )
+    .await
+    .context("Failed to leave permissive mode")
+}
+
+/// Upper bound for the delay between attach retries.
+const ATTACH_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// Runs `op`, retrying up to `retries` more times after a failure. The delay
+/// starts at `delay` and doubles after every attempt, up to [`ATTACH_RETRY_MAX`].
+/// Failures are logged as attempts at `operation`.
+async fn retry_with_backoff<T>(
+    operation: &str,
+    retries: u32,
+    mut delay: Duration,
+    mut op: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                warn!(
+                    "{} attempt {}/{} failed: {:#}, retrying in {:?}",
+                    operation,
+                    attempt,
+                    retries + 1,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(ATTACH_RETRY_MAX);
+            }
+            Err(e) if retries > 0 => {
+                return Err(e.context(format!("Giving up after {} attempts", retries + 1)));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_bind_failure_skips_attach() {
+        let held = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let config = Config {
+            grpc_server_port: held.local_addr().unwrap().port(),
+            grpc_bind_attempts: 1,
+            ..Config::default()
+        };
+
+        let mut attached = false;
+        let result = bind_then_attach(GrpcListener::bind(&config, None), async {
+            attached = true;
+            Ok(())
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!attached, "attached without a control plane");
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_bad_certs_skip_attach() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "-----BEGIN CERTIFICATE-----\nbm90IGEgY2VydA==\n").unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let config = Config {
+            grpc_server_port: 0,
+            cert_file: path("agent.pem"),
+            key_file: path("agent.key"),
+            ca_file: path("ca.pem"),
+            ..Config::default()
+        };
+
+        let mut attached = false;
+        let result = bind_then_attach(prepare_grpc(&config, None), async {
+            attached = true;
+            Ok(())
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!attached, "attached with unusable TLS certificates");
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_unopenable_audit_file_skips_attach() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let config = Config {
+            grpc_audit_file: Some(dir.path().join("missing").join("audit.jsonl")),
+            ..Config::default()
+        };
+
+        let mut attached = false;
+        let result = bind_then_attach(
+            prepare_grpc(&config, Some(&dir.path().join("aegis.sock"))),
+            async {
+                attached = true;
+                Ok(())
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!attached, "attached without an audit file");
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_attach_failure_releases_listener() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("aegis.sock");
+        let config = Config::default();
+
+        let result: Result<(GrpcListener, ())> =
+            bind_then_attach(GrpcListener::bind(&config, Some(&path)), async {
+                Err(anyhow::anyhow!("Interface 'eth9' not found"))
+            })
+            .await;
+        assert!(result.is_err());
+
+        // Nothing accepts on the socket once startup gave up
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_gives_up_after_consecutive_failures() {
+        let mut calls = 0;
+        let e = watch_datapath(Duration::from_millis(1), 3, || {
+            calls += 1;
+            Err(anyhow::anyhow!("Bad file descriptor"))
+        })
+        .await;
+        assert_eq!(calls, 3);
+        assert_eq!(
+            e.to_string(),
+            "Session map unresponsive after 3 consecutive probe failures"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_success_resets_failures() {
+        let results = [false, false, true, false, false, false];
+        let mut calls = 0;
+        watch_datapath(Duration::from_millis(1), 3, || {
+            calls += 1;
+            if results[calls - 1] {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("Bad file descriptor"))
+            }
+        })
+        .await;
+        assert_eq!(calls, 6);
+    }
+
+    #[tokio::test]
+    async fn test_retry_succeeds_after_failures() {
+        let mut calls = 0;
+        let result = retry_with_backoff("Attach", 3, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                Err(anyhow::anyhow!("Interface 'docker0' not found"))
+            } else {
+                Ok(calls)
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mut calls = 0;
+        let result: Result<()> = retry_with_backoff("Attach", 2, Duration::from_millis(1), || {
+            calls += 1;
+            Err(anyhow::anyhow!("Interface 'docker0' not found"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_retries_fails_immediately() {
+        let mut calls = 0;
+        let result: Result<()> = retry_with_backoff("Attach", 0, Duration::from_secs(60), || {
+            calls += 1;
+            Err(anyhow::anyhow!("Interface 'docker0' not found"))
+        })
+        .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Interface 'docker0' not found"
+        );
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_grace_window_retries_switch_to_enforcing() {
+        let mut calls = 0;
+        close_grace_window(Duration::ZERO, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                Err(anyhow::anyhow!("Bad file descriptor"))
+            } else {
+                Ok(())
+            }
+        })
+        .await
+        .expect("Switch succeeded on the third attempt");
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let err = close_grace_window(Duration::ZERO, Duration::from_millis(1), || {
+            calls += 1;
+            Err(anyhow::anyhow!("Bad file descriptor"))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(calls, GRACE_RETRIES + 1);
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to leave permissive mode: Bad file descriptor"
+        );
+    }
+}
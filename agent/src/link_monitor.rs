@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use rtnetlink::{
+    constants::RTMGRP_LINK,
+    packet_core::NetlinkPayload,
+    packet_route::{
+        RouteNetlinkMessage,
+        link::{LinkAttribute, LinkFlag, LinkMessage},
+    },
+    sys::{AsyncSocket, SocketAddr},
+};
+use tracing::{debug, info, warn};
+
+/// State of one interface taken from a netlink link notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkEvent {
+    pub index: i32,
+    pub name: String,
+    /// `IFF_UP` and `IFF_RUNNING` are both set; `false` for removed links
+    pub up: bool,
+}
+
+/// Follows link notifications for one interface and decides when the XDP
+/// program has to be attached again.
+#[derive(Debug)]
+pub struct LinkTracker {
+    name: String,
+    up: bool,
+}
+
+impl LinkTracker {
+    /// Tracks `name`, which is up with the program attached at startup.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            up: true,
+        }
+    }
+
+    /// Records `event` and returns the interface index to re-attach to when
+    /// the interface just came back up.
+    pub fn on_event(&mut self, event: &LinkEvent) -> Option<i32> {
+        if event.name != self.name || event.up == self.up {
+            return None;
+        }
+
+        self.up = event.up;
+        if event.up {
+            info!(
+                "Interface {} is up again (index {})",
+                self.name, event.index
+            );
+            Some(event.index)
+        } else {
+            warn!(
+                "Interface {} went down, XDP may be detached until it returns",
+                self.name
+            );
+            None
+        }
+    }
+}
+
+/// Converts a `RTM_NEWLINK` / `RTM_DELLINK` message into a [`LinkEvent`].
+fn link_event(message: &LinkMessage, removed: bool) -> Option<LinkEvent> {
+    let name = message.attributes.iter().find_map(|attr| match attr {
+        LinkAttribute::IfName(name) => Some(name.clone()),
+        _ => None,
+    })?;
+    let flags = &message.header.flags;
+    let up = !removed && flags.contains(&LinkFlag::Up) && flags.contains(&LinkFlag::Running);
+
+    Some(LinkEvent {
+        index: message.header.index as i32,
+        name,
+        up,
+    })
+}
+
+/// Watches link state of `iface` over netlink and calls `reattach` with the
+/// interface index whenever it comes back up after going down or being
+/// recreated. Runs until the netlink socket closes.
+pub async fn watch_link(iface: String, reattach: impl Fn(i32) -> Result<()>) -> Result<()> {
+    let (mut connection, _handle, mut messages) =
+        rtnetlink::new_connection().context("Failed to open netlink socket")?;
+    connection
+        .socket_mut()
+        .socket_mut()
+        .bind(&SocketAddr::new(0, RTMGRP_LINK))
+        .context("Failed to subscribe to link notifications")?;
+    tokio::spawn(connection);
+    debug!("Watching link state of {}", iface);
+
+    let mut tracker = LinkTracker::new(&iface);
+    while let Some((message, _)) = messages.next().await {
+        let event = match &message.payload {
+            NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewLink(link)) => {
+                link_event(link, false)
+            }
+            NetlinkPayload::InnerMessage(RouteNetlinkMessage::DelLink(link)) => {
+                link_event(link, true)
+            }
+            _ => None,
+        };
+
+        if let Some(index) = event.and_then(|event| tracker.on_event(&event))
+            && let Err(e) = reattach(index)
+        {
+            warn!("Failed to re-attach XDP program to {}: {:#}", iface, e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, index: i32, up: bool) -> LinkEvent {
+        LinkEvent {
+            index,
+            name: name.to_string(),
+            up,
+        }
+    }
+
+    #[test]
+    fn test_reattach_after_flap() {
+        let mut tracker = LinkTracker::new("eth1");
+
+        // Repeated notifications while the link stays up change nothing
+        assert_eq!(tracker.on_event(&event("eth1", 3, true)), None);
+
+        assert_eq!(tracker.on_event(&event("eth1", 3, false)), None);
+        assert_eq!(tracker.on_event(&event("eth1", 3, false)), None);
+        assert_eq!(tracker.on_event(&event("eth1", 3, true)), Some(3));
+        assert_eq!(tracker.on_event(&event("eth1", 3, true)), None);
+    }
+
+    #[test]
+    fn test_reattach_uses_new_index() {
+        let mut tracker = LinkTracker::new("docker0");
+        tracker.on_event(&event("docker0", 4, false));
+        assert_eq!(tracker.on_event(&event("docker0", 9, true)), Some(9));
+    }
+
+    #[test]
+    fn test_other_interfaces_ignored() {
+        let mut tracker = LinkTracker::new("eth1");
+        assert_eq!(tracker.on_event(&event("eth0", 2, false)), None);
+        assert_eq!(tracker.on_event(&event("eth0", 2, true)), None);
+    }
+
+    #[test]
+    fn test_link_event_from_message() {
+        let mut message = LinkMessage::default();
+        message.header.index = 3;
+        message.header.flags = vec![LinkFlag::Up, LinkFlag::Running, LinkFlag::LowerUp];
+        message
+            .attributes
+            .push(LinkAttribute::IfName("eth1".to_string()));
+
+        assert_eq!(link_event(&message, false), Some(event("eth1", 3, true)));
+        assert_eq!(link_event(&message, true), Some(event("eth1", 3, false)));
+
+        message.header.flags = vec![LinkFlag::Up];
+        assert_eq!(link_event(&message, false), Some(event("eth1", 3, false)));
+    }
+}
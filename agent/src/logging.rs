@@ -0,0 +1,108 @@
+use anyhow::{Context, Result, anyhow};
+use std::path::Path;
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Installs the global `tracing` subscriber.
+///
+/// Logs go to stderr unless `log_file` is set, in which case they are
+/// written to a file rotated daily (`<name>.YYYY-MM-DD`) and only copied to
+/// stderr when `log_stderr` is set. Writes are synchronous, so no background
+/// thread is started and `--daemonize` can still fork afterwards.
+///
+/// `RUST_LOG` takes precedence over `level` when set, so per-module
+/// directives keep working.
+pub fn init(log_file: Option<&Path>, log_stderr: bool, level: Option<Level>) -> Result<()> {
+    let file_layer = log_file
+        .map(|path| {
+            file_appender(path).map(|appender| fmt::layer().with_ansi(false).with_writer(appender))
+        })
+        .transpose()?;
+    let stderr_layer =
+        (file_layer.is_none() || log_stderr).then(|| fmt::layer().with_writer(std::io::stderr));
+
+    tracing_subscriber::registry()
+        .with(env_filter(level))
+        .with(file_layer)
+        .with(stderr_layer)
+        .try_init()
+        .context("Failed to initialize logging")
+}
+
+/// Builds the filter from `RUST_LOG` if it is set and non-empty, else from
+/// `level`. Without either, only errors are logged as before.
+fn env_filter(level: Option<Level>) -> EnvFilter {
+    let env_set = std::env::var_os(EnvFilter::DEFAULT_ENV).is_some_and(|value| !value.is_empty());
+    match level {
+        Some(level) if !env_set => EnvFilter::new(level_directive(level)),
+        _ => EnvFilter::from_default_env(),
+    }
+}
+
+/// Filter directive enabling `level` and everything more severe.
+fn level_directive(level: Level) -> String {
+    level.as_str().to_ascii_lowercase()
+}
+
+/// Opens a daily rotating appender whose files are named after `path`.
+fn file_appender(path: &Path) -> Result<RollingFileAppender> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Log file has no file name: {}", path.display()))?;
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(file_name.to_string_lossy())
+        .build(dir)
+        .with_context(|| format!("Failed to open log file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write};
+    use tracing_subscriber::filter::LevelFilter;
+
+    #[test]
+    fn test_level_directive() {
+        for (level, directive, filter) in [
+            (Level::TRACE, "trace", LevelFilter::TRACE),
+            (Level::DEBUG, "debug", LevelFilter::DEBUG),
+            (Level::INFO, "info", LevelFilter::INFO),
+            (Level::WARN, "warn", LevelFilter::WARN),
+            (Level::ERROR, "error", LevelFilter::ERROR),
+        ] {
+            assert_eq!(level_directive(level), directive);
+            assert_eq!(
+                EnvFilter::new(level_directive(level)).max_level_hint(),
+                Some(filter)
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_appender_writes_dated_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let mut appender = file_appender(&dir.path().join("agent.log")).unwrap();
+        appender.write_all(b"hello\n").unwrap();
+        appender.flush().unwrap();
+
+        let names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 1);
+        assert!(names[0].starts_with("agent.log."), "{}", names[0]);
+    }
+
+    #[test]
+    fn test_file_appender_rejects_missing_dir() {
+        assert!(file_appender(Path::new("/nonexistent/dir/agent.log")).is_err());
+        assert!(file_appender(Path::new("/")).is_err());
+    }
+}
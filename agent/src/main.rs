@@ -7,159 +7,81 @@
 //! - Parse configuration from `config.toml`
 //! - Run gRPC server for session management
 //!
+//! The agent itself lives in the `aegis_agent` library; this binary only
+//! sets up logging and parses the command line.
+//!
 //! ## Usage
 //!
 //! ```sh
 //! sudo ./aegis-agent
+//! sudo ./aegis-agent --grpc-uds /run/aegis/agent.sock
+//! sudo ./aegis-agent --daemonize --pidfile /run/aegis-agent.pid
+//! sudo ./aegis-agent --daemonize --log-file /var/log/aegis/agent.log
+//! sudo ./aegis-agent --netns /var/run/netns/tenant1
+//! ./aegis-agent --version
+//! sudo ./aegis-agent list
+//! sudo ./aegis-agent add 10.0.0.1 172.21.0.10 8080
 //! ```
 
-mod benchmark;
-mod bpf;
-mod cap;
-mod config;
-mod grpc_server;
-mod hostname_to_ip;
-
-use crate::grpc_server::session::{Session, SessionList};
-use crate::{bpf::Bpf, config::Config, grpc_server::start_grpc_server};
+use aegis_agent::{
+    Config,
+    cli::Args,
+    command,
+    daemon::{self, PidFile},
+    logging, netns, version,
+};
 use anyhow::{Context, Result};
-use nix::net::if_::if_nametoindex;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
-use tokio::sync::{Mutex, broadcast};
-use tracing::{debug, error, info, warn};
+use tracing::info;
 
 /// Main entry point - initializes the agent and starts serving requests.
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+fn main() -> Result<()> {
+    let mut args = Args::parse()?;
+    // Relative paths mean the start directory even after --daemonize moves to /
+    let workdir = std::env::current_dir().context("Failed to read working directory")?;
+    args.resolve_paths(&workdir);
+
+    // Initialize logging before anything else can log
+    logging::init(args.log_file.as_deref(), args.log_stderr, args.log_level)?;
+
+    if args.version {
+        println!("{}", version::format_version(&version::version_info()));
+        return Ok(());
+    }
+
+    // Subcommands work on the pinned map and never start the agent
+    if let Some(cmd) = &args.command {
+        args.apply_early();
+        let mut config = Config::load()?;
+        args.apply(&mut config);
+        return command::run(cmd, &config);
+    }
 
     info!("Aegis Agent starting...");
 
-    // Verify we have necessary privileges
-    debug!("Checking capabilities...");
-    cap::check_capabilities().with_context(|| "Missing required capabilities")?;
-    info!("Capabilities verified");
-
-    // Load configuration
-    let config = Config::load()?;
-    debug!("Configuration: {:?}", config);
-
-    // Resolve network interface
-    let interface_index = if_nametoindex(config.iface_name.as_str())
-        .with_context(|| format!("Interface '{}' not found", config.iface_name))?
-        as i32;
-
-    info!(
-        "Interface: {} (index: {})",
-        config.iface_name, interface_index
-    );
-
-    // Load and attach BPF program
-    debug!("Loading XDP program...");
-    let bpf = Arc::new(std::sync::Mutex::new(Bpf::new(interface_index, &config)?));
-    info!("XDP program attached");
-
-    // Show active policy
-    warn!("Zero-trust policy active on {}", config.iface_name);
-    warn!(
-        "Allowing only controller traffic ({}:{}) and authorized sessions",
-        config.controller_ip, config.controller_port
-    );
-
-    let (monitor_tx, _) = broadcast::channel(config.broadcast_channel_size);
-    let monitor_tx_loop = monitor_tx.clone();
-    let bpf_cleanup = bpf.clone();
-    let rule_timeout_ns = config.rule_timeout_ns;
-    let cleanup_interval_sec = config.cleanup_interval_sec;
-
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(cleanup_interval_sec));
-        loop {
-            interval.tick().await;
-            debug!("Running periodic eBPF rule cleanup...");
-            match bpf_cleanup.lock() {
-                Ok(bpf) => {
-                    match bpf.cleanup_ebpf_rules(rule_timeout_ns) {
-                        Ok(count) => {
-                            if count > 0 {
-                                debug!("Cleaned up {} stale rules", count);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to cleanup stale rules: {}", e);
-                        }
-                    }
-                    match bpf.list_rules(rule_timeout_ns) {
-                        Ok(rules) => {
-                            let proto_sessions: Vec<Session> = rules
-                                .into_iter()
-                                .map(|(src, dst, port, time)| Session {
-                                    src_ip: u32::from_be(src),
-                                    dst_ip: u32::from_be(dst),
-                                    dst_port: u16::from_be(port) as u32,
-                                    time_left: time,
-                                })
-                                .collect();
-
-                            let session_list = SessionList {
-                                sessions: proto_sessions,
-                            };
-
-                            let _ = monitor_tx_loop.send(Ok(session_list));
-                        }
-                        Err(e) => {
-                            error!("Failed to list active rules: {}", e);
-                            let _ = monitor_tx_loop.send(Err(tonic::Status::internal("BPF error")));
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to acquire BPF lock for cleanup: {}", e);
-                }
-            }
-        }
-    });
-
-    // Start gRPC server
-    let server_addr = SocketAddr::from(([0, 0, 0, 0], config.grpc_server_port));
-    info!("Starting gRPC server on {}", server_addr);
-
-    let bpf_grpc = bpf.clone();
-    let modify_rule_handler = Arc::new(Mutex::new(
-        move |is_add: bool, dest_ip: u32, src_ip: u32, dest_port: u16| -> Result<()> {
-            let bpf = bpf_grpc
-                .lock()
-                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
-
-            if is_add {
-                bpf.add_rule(dest_ip.to_be(), src_ip.to_be(), dest_port.to_be())
-            } else {
-                bpf.remove_rule(dest_ip.to_be(), src_ip.to_be(), dest_port.to_be())
-            }
-        },
-    ));
-
-    let bpf_ip_update = bpf.clone();
-    let update_ip_handler = Arc::new(Mutex::new(
-        move |old_dest_ip: u32, new_dest_ip: u32| -> Result<usize> {
-            let bpf = bpf_ip_update
-                .lock()
-                .map_err(|_| anyhow::anyhow!("BPF mutex poisoned"))?;
-            bpf.update_dest_ip(old_dest_ip.to_be(), new_dest_ip.to_be())
-        },
-    ));
-
-    start_grpc_server(
-        &config,
-        server_addr,
-        modify_rule_handler,
-        update_ip_handler,
-        monitor_tx,
-    )
-    .await?;
-
-    Ok(())
+    // Refuse to start next to another agent while errors still reach the
+    // terminal. The lock is inherited across the forks and held until exit.
+    let pidfile = args.pidfile.as_deref().map(PidFile::lock).transpose()?;
+
+    // Switch namespaces while single-threaded so every runtime thread inherits it
+    if let Some(path) = &args.netns {
+        netns::enter(path)?;
+        info!("Entered network namespace {}", path.display());
+    }
+
+    // Fork before the runtime spawns worker threads
+    if args.daemonize {
+        daemon::daemonize()?;
+        info!("Daemonized (pid {})", std::process::id());
+    }
+
+    // Removed when main returns, including after a signal-driven shutdown
+    if let Some(pidfile) = &pidfile {
+        pidfile.write_pid()?;
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to build Tokio runtime")?
+        .block_on(aegis_agent::run(args, &workdir))
 }
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result, anyhow, bail};
+use nix::{
+    errno::Errno,
+    sched::{CloneFlags, setns},
+};
+use std::{fs::File, io, path::Path};
+
+/// Moves the process into the network namespace at `path`, e.g.
+/// `/var/run/netns/<name>` as created by `ip netns add`.
+///
+/// Must be called before the Tokio runtime starts: `setns` only switches the
+/// calling thread, and threads spawned afterwards inherit its namespace.
+/// Interface lookups, the XDP attachment, link monitoring and the gRPC
+/// listener then all use the namespace, while pinned maps are unaffected
+/// since bpffs belongs to the mount namespace.
+pub fn enter(path: &Path) -> Result<()> {
+    let file = File::open(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => {
+            anyhow!("Network namespace {} does not exist", path.display())
+        }
+        _ => anyhow::Error::new(e).context(format!(
+            "Failed to open network namespace {}",
+            path.display()
+        )),
+    })?;
+    match setns(&file, CloneFlags::CLONE_NEWNET) {
+        Ok(()) => Ok(()),
+        Err(Errno::EPERM) => bail!(
+            "Not permitted to enter network namespace {} (needs CAP_SYS_ADMIN)",
+            path.display()
+        ),
+        Err(Errno::EINVAL) => bail!("{} is not a network namespace", path.display()),
+        Err(e) => {
+            Err(e).with_context(|| format!("Failed to enter network namespace {}", path.display()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_namespace() {
+        let err = enter(Path::new("/var/run/netns/aegis-missing")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Network namespace /var/run/netns/aegis-missing does not exist"
+        );
+    }
+
+    #[test]
+    fn test_regular_file_is_rejected() {
+        let file = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+        // EINVAL with CAP_SYS_ADMIN, EPERM without; never entered
+        assert!(enter(file.path()).is_err());
+    }
+}
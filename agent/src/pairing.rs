@@ -0,0 +1,132 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fs, net::SocketAddrV4, str::FromStr};
+use tracing::{debug, info};
+use x509_parser::{extensions::GeneralName, pem::parse_x509_pem};
+
+use crate::config::Config;
+
+/// URI scheme used in the CA certificate's SAN to embed the controller endpoint,
+/// e.g. `URI:aegis://172.21.0.5:443`.
+const PAIRING_URI_SCHEME: &str = "aegis://";
+
+/// Extracts the controller endpoint embedded in a PEM certificate's SAN.
+/// Returns `None` if the certificate carries no `aegis://` URI.
+pub fn controller_endpoint_from_cert(pem: &[u8]) -> Result<Option<SocketAddrV4>> {
+    let (_, pem) = parse_x509_pem(pem).map_err(|e| anyhow!("Invalid PEM: {}", e))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| anyhow!("Invalid X.509 certificate: {}", e))?;
+
+    let Some(san) = cert
+        .subject_alternative_name()
+        .map_err(|e| anyhow!("Invalid subjectAltName extension: {}", e))?
+    else {
+        return Ok(None);
+    };
+
+    for name in &san.value.general_names {
+        if let GeneralName::URI(uri) = name
+            && let Some(endpoint) = uri.strip_prefix(PAIRING_URI_SCHEME)
+        {
+            let addr = SocketAddrV4::from_str(endpoint)
+                .with_context(|| format!("Invalid controller endpoint in SAN: {}", uri))?;
+            return Ok(Some(addr));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Overrides the controller IP and port with the endpoint embedded in the CA
+/// certificate. Leaves the configured values untouched if none is present.
+pub fn pair_from_cert(config: &mut Config) -> Result<()> {
+    let ca_pem = fs::read(&config.ca_file).context("Failed to read CA certificate")?;
+
+    match controller_endpoint_from_cert(&ca_pem)? {
+        Some(endpoint) => {
+            info!("Paired with controller {} from CA certificate", endpoint);
+            config.controller_ip = *endpoint.ip();
+            config.controller_port = endpoint.port();
+            config.controller_host = None;
+        }
+        None => debug!(
+            "No controller endpoint in CA certificate, using {}:{}",
+            config.controller_ip, config.controller_port
+        ),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    /// Self-signed CA with `subjectAltName = URI:aegis://10.0.0.5:8443`
+    const CA_WITH_ENDPOINT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBqTCCAU+gAwIBAgIUXUfZR6mhIehK1/3gGs8Px/x7xD4wCgYIKoZIzj0EAwIw
+GDEWMBQGA1UEAwwNQWVnaXMtVGVzdC1DQTAgFw0yNjEwMTYwNzQ0NDRaGA8yMTI2
+MDkyMjA3NDQ0NFowGDEWMBQGA1UEAwwNQWVnaXMtVGVzdC1DQTBZMBMGByqGSM49
+AgEGCCqGSM49AwEHA0IABMVn4VWBQDuVGJNKWsxbIr9xcqCjgp/oyDBxb32MfC01
+/m2hUEOTVXNko5kGCbpJtrBmfNrqbG6ugpQWbM05hhmjdTBzMB0GA1UdDgQWBBS3
+jDEvcI1pgSsgDQOoXX23hI+nijAfBgNVHSMEGDAWgBS3jDEvcI1pgSsgDQOoXX23
+hI+nijAPBgNVHRMBAf8EBTADAQH/MCAGA1UdEQQZMBeGFWFlZ2lzOi8vMTAuMC4w
+LjU6ODQ0MzAKBggqhkjOPQQDAgNIADBFAiEA/soDQVSkOccLC1g35mrlg+RK9y69
+ciqQxFvOqu7YZ7cCIEkl1cl0qyUm64u9hKziK3A2EFHg8cwn7IqJNOrwOQaG
+-----END CERTIFICATE-----
+";
+
+    /// Self-signed CA without a subjectAltName extension
+    const CA_WITHOUT_ENDPOINT: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBiDCCAS2gAwIBAgIUa8wMoH9Ob9dXl4g93Wcg4g34t74wCgYIKoZIzj0EAwIw
+GDEWMBQGA1UEAwwNQWVnaXMtVGVzdC1DQTAgFw0yNjEwMTYwNzQ0NDRaGA8yMTI2
+MDkyMjA3NDQ0NFowGDEWMBQGA1UEAwwNQWVnaXMtVGVzdC1DQTBZMBMGByqGSM49
+AgEGCCqGSM49AwEHA0IABPJ5aedDbKgXCHbx7lAgPwwmyUIldix4PgKOs5Py6VMm
+eVD57851TpwFuuF0DUSszrLPmqrrdAPfkrxGNTjwiB6jUzBRMB0GA1UdDgQWBBTg
+FtF8XQNMjO8QPw62IfwJ2cL1VzAfBgNVHSMEGDAWgBTgFtF8XQNMjO8QPw62IfwJ
+2cL1VzAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQCZppdHoFrY
+d2+JosQkgNWYxvACdHqr4l/WMv5YOoKodwIhAIgnNFdGKRDBhhXEJBgFXWcU8jnz
+X+8B5E7rOVu8gL6h
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_endpoint_from_cert() {
+        let endpoint = controller_endpoint_from_cert(CA_WITH_ENDPOINT.as_bytes())
+            .expect("Failed to parse certificate");
+        assert_eq!(
+            endpoint,
+            Some(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 5), 8443))
+        );
+    }
+
+    #[test]
+    fn test_no_endpoint_in_cert() {
+        let endpoint = controller_endpoint_from_cert(CA_WITHOUT_ENDPOINT.as_bytes())
+            .expect("Failed to parse certificate");
+        assert_eq!(endpoint, None);
+    }
+
+    #[test]
+    fn test_invalid_pem_fails() {
+        assert!(controller_endpoint_from_cert(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_pair_falls_back_to_config() {
+        let mut f = tempfile::NamedTempFile::new().expect("Failed to create temp file");
+        std::io::Write::write_all(&mut f, CA_WITHOUT_ENDPOINT.as_bytes())
+            .expect("Failed to write temp file");
+        let mut config = Config {
+            ca_file: f.path().to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+
+        pair_from_cert(&mut config).expect("Pairing should fall back");
+        assert_eq!(config.controller_ip, Ipv4Addr::new(172, 21, 0, 5));
+        assert_eq!(config.controller_port, 443);
+    }
+}
@@ -0,0 +1,369 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    task::{self, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::{Connected, TcpConnectInfo};
+use tracing::{debug, warn};
+
+use crate::types::Ipv4Net;
+
+/// Signature that starts every PROXY protocol v2 header.
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Connection opened by the proxy itself, e.g. a health check.
+const CMD_LOCAL: u8 = 0x0;
+/// Connection relayed on behalf of a client.
+const CMD_PROXY: u8 = 0x1;
+
+/// TCP over IPv4.
+const FAMILY_TCP4: u8 = 0x11;
+/// TCP over IPv6.
+const FAMILY_TCP6: u8 = 0x21;
+
+/// How long a new connection may take to send its PROXY header.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections with a parsed header waiting to be picked up by the server.
+const BACKLOG: usize = 64;
+
+/// TCP connection whose peer address was taken from a PROXY protocol header.
+pub struct ProxiedStream {
+    inner: TcpStream,
+    source: SocketAddr,
+}
+
+impl Connected for ProxiedStream {
+    type ConnectInfo = TcpConnectInfo;
+
+    /// Reports the proxied client as the remote address, so
+    /// `Request::remote_addr()` and the interceptors see it instead of the
+    /// load balancer.
+    fn connect_info(&self) -> TcpConnectInfo {
+        TcpConnectInfo {
+            local_addr: self.inner.local_addr().ok(),
+            remote_addr: Some(self.source),
+        }
+    }
+}
+
+impl AsyncRead for ProxiedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ProxiedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Accepts connections on `listener` and yields them once their PROXY
+/// protocol v2 header has been read, before the TLS handshake starts.
+///
+/// Only peers inside `trusted` may send a header; connections from anyone
+/// else are dropped before it is read, since the header would let them claim
+/// any address. Headers are read on their own tasks so a slow or silent
+/// client cannot hold up other connections. Connections without a valid
+/// header within [`HEADER_TIMEOUT`] are dropped with a warning.
+pub fn incoming(
+    listener: TcpListener,
+    trusted: Vec<Ipv4Net>,
+) -> ReceiverStream<io::Result<ProxiedStream>> {
+    let (tx, rx) = mpsc::channel(BACKLOG);
+    tokio::spawn(async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                () = tx.closed() => break,
+            };
+            let (mut stream, peer) = match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    // Let the server decide, as it does for a plain listener
+                    if tx.send(Err(e)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            if !is_trusted(peer, &trusted) {
+                warn!(
+                    "Dropping connection from {}: not in grpc.trusted_proxies",
+                    peer
+                );
+                continue;
+            }
+
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HEADER_TIMEOUT, read_header(&mut stream, peer)).await {
+                    Ok(Ok(source)) => {
+                        debug!("PROXY header from {}: client {}", peer, source);
+                        let _ = tx
+                            .send(Ok(ProxiedStream {
+                                inner: stream,
+                                source,
+                            }))
+                            .await;
+                    }
+                    Ok(Err(e)) => warn!("Dropping connection from {}: {:#}", peer, e),
+                    Err(_) => warn!(
+                        "Dropping connection from {}: no PROXY header within {:?}",
+                        peer, HEADER_TIMEOUT
+                    ),
+                }
+            });
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Whether `peer` is one of the load balancers allowed to send a header.
+fn is_trusted(peer: SocketAddr, trusted: &[Ipv4Net]) -> bool {
+    match peer.ip() {
+        IpAddr::V4(ip) => trusted.iter().any(|net| net.contains(ip)),
+        IpAddr::V6(_) => false,
+    }
+}
+
+/// Reads a PROXY protocol v2 header from `stream`, leaving it positioned at
+/// the first byte sent by the client, and returns the client address.
+///
+/// `LOCAL` connections, which the proxy opens on its own behalf, keep
+/// `peer`. Version 1 (text) headers and non-TCP address families are
+/// rejected.
+async fn read_header<R: AsyncRead + Unpin>(stream: &mut R, peer: SocketAddr) -> Result<SocketAddr> {
+    let mut header = [0u8; 16];
+    stream
+        .read_exact(&mut header)
+        .await
+        .context("Failed to read PROXY header")?;
+    let len = address_len(&header)?;
+
+    let mut addresses = vec![0u8; len];
+    stream
+        .read_exact(&mut addresses)
+        .await
+        .context("Failed to read PROXY addresses")?;
+    source_addr(&header, &addresses, peer)
+}
+
+/// Validates the fixed part of a header and returns the length of the
+/// address block that follows it.
+fn address_len(header: &[u8; 16]) -> Result<usize> {
+    if header[..12] != SIGNATURE {
+        bail!("Missing PROXY protocol v2 signature");
+    }
+    let version = header[12] >> 4;
+    if version != 2 {
+        bail!("Unsupported PROXY protocol version {}", version);
+    }
+    Ok(u16::from_be_bytes([header[14], header[15]]).into())
+}
+
+/// Extracts the client address from the address block. TLVs after the
+/// addresses are ignored.
+fn source_addr(header: &[u8; 16], addresses: &[u8], peer: SocketAddr) -> Result<SocketAddr> {
+    match header[12] & 0x0f {
+        CMD_LOCAL => return Ok(peer),
+        CMD_PROXY => {}
+        command => bail!("Unknown PROXY command {}", command),
+    }
+
+    let truncated = || anyhow!("Truncated PROXY address block");
+    match header[13] {
+        FAMILY_TCP4 => {
+            // src addr, dst addr, src port, dst port
+            let block = addresses.get(..12).ok_or_else(truncated)?;
+            let ip = Ipv4Addr::new(block[0], block[1], block[2], block[3]);
+            let port = u16::from_be_bytes([block[8], block[9]]);
+            Ok(SocketAddr::new(IpAddr::V4(ip), port))
+        }
+        FAMILY_TCP6 => {
+            let block = addresses.get(..36).ok_or_else(truncated)?;
+            let mut ip = [0u8; 16];
+            ip.copy_from_slice(&block[..16]);
+            let port = u16::from_be_bytes([block[32], block[33]]);
+            Ok(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port))
+        }
+        family => bail!("Unsupported PROXY address family {:#04x}", family),
+    }
+}
+
+/// Encodes a `PROXY` header for a TCP over IPv4 connection.
+#[cfg(test)]
+pub(crate) fn encode_tcp4(source: std::net::SocketAddrV4, dest: std::net::SocketAddrV4) -> Vec<u8> {
+    let mut header = SIGNATURE.to_vec();
+    header.extend([0x20 | CMD_PROXY, FAMILY_TCP4, 0, 12]);
+    header.extend(source.ip().octets());
+    header.extend(dest.ip().octets());
+    header.extend(source.port().to_be_bytes());
+    header.extend(dest.port().to_be_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddrV4;
+
+    fn peer() -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, 1], 45000))
+    }
+
+    #[tokio::test]
+    async fn test_read_header_tcp4() {
+        let source = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 40000);
+        let dest = SocketAddrV4::new(Ipv4Addr::new(172, 21, 0, 10), 50001);
+        let mut bytes = encode_tcp4(source, dest);
+        // Append a TLV (type 0x04, NOOP) and account for it in the length
+        bytes[15] += 4;
+        bytes.extend([0x04, 0x00, 0x01, 0x00]);
+        bytes.extend(b"\x16\x03\x01");
+
+        let mut stream = bytes.as_slice();
+        let addr = read_header(&mut stream, peer()).await.unwrap();
+        assert_eq!(addr, SocketAddr::V4(source));
+        // The TLS ClientHello that follows is left untouched
+        assert_eq!(stream, b"\x16\x03\x01");
+    }
+
+    #[tokio::test]
+    async fn test_read_header_tcp6() {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend([0x21, FAMILY_TCP6, 0, 36]);
+        bytes.extend(Ipv6Addr::LOCALHOST.octets());
+        bytes.extend(Ipv6Addr::UNSPECIFIED.octets());
+        bytes.extend(443u16.to_be_bytes());
+        bytes.extend(50001u16.to_be_bytes());
+
+        let addr = read_header(&mut bytes.as_slice(), peer()).await.unwrap();
+        assert_eq!(addr, SocketAddr::from((Ipv6Addr::LOCALHOST, 443)));
+    }
+
+    #[tokio::test]
+    async fn test_local_command_keeps_peer() {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend([0x20 | CMD_LOCAL, 0x00, 0, 0]);
+
+        let addr = read_header(&mut bytes.as_slice(), peer()).await.unwrap();
+        assert_eq!(addr, peer());
+    }
+
+    #[tokio::test]
+    async fn test_read_header_rejects_malformed() {
+        let valid = encode_tcp4(
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 40000),
+            SocketAddrV4::new(Ipv4Addr::new(172, 21, 0, 10), 50001),
+        );
+
+        // Version 1 text header
+        let v1 = b"PROXY TCP4 10.0.0.1 172.21.0.10 40000 50001\r\n";
+        assert!(read_header(&mut v1.as_slice(), peer()).await.is_err());
+
+        let mut version = valid.clone();
+        version[12] = 0x11;
+        assert!(read_header(&mut version.as_slice(), peer()).await.is_err());
+
+        let mut command = valid.clone();
+        command[12] = 0x2f;
+        assert!(read_header(&mut command.as_slice(), peer()).await.is_err());
+
+        // UDP over IPv4
+        let mut udp = valid.clone();
+        udp[13] = 0x12;
+        assert!(read_header(&mut udp.as_slice(), peer()).await.is_err());
+
+        // Length shorter than an IPv4 address block
+        let mut short = valid.clone();
+        short[15] = 8;
+        assert!(read_header(&mut short.as_slice(), peer()).await.is_err());
+
+        // Connection closed mid-header
+        assert!(read_header(&mut &valid[..20], peer()).await.is_err());
+    }
+
+    #[test]
+    fn test_is_trusted() {
+        let trusted = vec!["192.0.2.0/28".parse().unwrap()];
+        assert!(is_trusted(peer(), &trusted));
+        assert!(!is_trusted(
+            SocketAddr::from(([192, 0, 2, 17], 45000)),
+            &trusted
+        ));
+        assert!(!is_trusted(peer(), &[]));
+        assert!(!is_trusted(
+            SocketAddr::from((Ipv6Addr::LOCALHOST, 45000)),
+            &trusted
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_peer_dropped() {
+        use tokio::io::AsyncWriteExt;
+        use tokio_stream::StreamExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut incoming = incoming(listener, vec!["192.0.2.0/24".parse().unwrap()]);
+
+        // A client on 127.0.0.1 claims to be the Controller
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let header = encode_tcp4(
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 40000),
+            SocketAddrV4::new(Ipv4Addr::new(172, 21, 0, 10), 50001),
+        );
+        client.write_all(&header).await.unwrap();
+
+        // The agent closes the connection without reading the header
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .expect("connection should be closed");
+        assert!(matches!(read, Ok(0) | Err(_)));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), incoming.next())
+                .await
+                .is_err()
+        );
+    }
+}
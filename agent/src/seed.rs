@@ -0,0 +1,110 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fs, path::Path};
+use tracing::warn;
+
+use crate::{cli::Args, types::SessionKey};
+
+/// Reads sessions to restore at startup from a CSV file.
+///
+/// Each line is `<src>[:<src_port>],<dst>,<port>`, using the same syntax as
+/// the `add` subcommand (`*` for any source or every port). Blank lines and
+/// lines starting with `#` are ignored. Malformed lines are skipped with a
+/// warning so one bad entry cannot keep the rest from being restored.
+pub fn read_seed_file(path: &Path) -> Result<Vec<SessionKey>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read seed file: {}", path.display()))?;
+
+    let keys = contents
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            match parse_seed_line(line) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    warn!(
+                        "Skipping seed entry {}:{}: {:#}",
+                        path.display(),
+                        index + 1,
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
+    Ok(keys)
+}
+
+/// Parses one `<src>[:<src_port>],<dst>,<port>` line.
+fn parse_seed_line(line: &str) -> Result<SessionKey> {
+    let mut fields = line.split(',').map(|field| field.trim().to_string());
+    let key = Args::parse_session_key("seed entry", &mut fields)?;
+    if let Some(extra) = fields.next() {
+        return Err(anyhow!("Unexpected extra field: {}", extra));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf::{ANY_SRC_IP, ANY_SRC_PORT, WILDCARD_PORT};
+    use std::{io::Write, net::Ipv4Addr};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_seed_line() {
+        let key = parse_seed_line("10.0.0.1, 172.21.0.10, 8080").unwrap();
+        assert_eq!(
+            key,
+            SessionKey {
+                src_ip: Ipv4Addr::new(10, 0, 0, 1),
+                dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+                dest_port: 8080,
+                src_port: ANY_SRC_PORT,
+            }
+        );
+
+        let key = parse_seed_line("10.0.0.1:1021,172.21.0.10,*").unwrap();
+        assert_eq!(key.src_port, 1021);
+        assert_eq!(key.dest_port, WILDCARD_PORT);
+
+        let key = parse_seed_line("*,172.21.0.10,443").unwrap();
+        assert_eq!(key.src_ip, ANY_SRC_IP);
+    }
+
+    #[test]
+    fn test_parse_seed_line_rejects_malformed() {
+        assert!(parse_seed_line("10.0.0.1,172.21.0.10").is_err());
+        assert!(parse_seed_line("10.0.0.1,172.21.0.10,8080,tcp").is_err());
+        assert!(parse_seed_line("10.0.0.1,172.21.0.10,70000").is_err());
+        assert!(parse_seed_line("10.0.0.300,172.21.0.10,8080").is_err());
+    }
+
+    #[test]
+    fn test_read_seed_file_skips_bad_lines() {
+        let mut f = NamedTempFile::new().expect("Failed to create temp file");
+        f.write_all(
+            b"# src,dst,port\n\
+              10.0.0.1,172.21.0.10,8080\n\
+              \n\
+              not,a,session\n\
+              10.0.0.2,172.21.0.10,22\n",
+        )
+        .expect("Failed to write temp file");
+
+        let keys = read_seed_file(f.path()).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].src_ip, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(keys[1].dest_port, 22);
+    }
+
+    #[test]
+    fn test_read_missing_seed_file_fails() {
+        assert!(read_seed_file(Path::new("/nonexistent/seed.csv")).is_err());
+    }
+}
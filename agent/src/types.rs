@@ -0,0 +1,343 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fmt, net::Ipv4Addr, str::FromStr};
+
+use crate::{
+    bpf::{
+        ANY_SRC_IP, ANY_SRC_PORT, NO_SRC_MAC, WILDCARD_PORT,
+        agent_skel::types::{session_key, session_val},
+    },
+    ip,
+};
+
+/// Identifies an authorized flow in the session map.
+///
+/// Fields are in host byte order, matching the integer IPs and ports carried
+/// in the gRPC messages (`10.0.0.1` is `0x0A000001`). The conversions to and
+/// from the raw eBPF key are the only place network byte order is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionKey {
+    /// Source IP, or [`ANY_SRC_IP`] to admit every source
+    pub src_ip: Ipv4Addr,
+    pub dest_ip: Ipv4Addr,
+    /// Destination port, or [`WILDCARD_PORT`] for every port on `dest_ip`
+    pub dest_port: u16,
+    /// Source port, or [`ANY_SRC_PORT`] to accept any source port
+    pub src_port: u16,
+}
+
+impl SessionKey {
+    /// Returns true if this key authorizes every port on the destination.
+    pub fn is_wildcard(&self) -> bool {
+        self.dest_port == WILDCARD_PORT
+    }
+
+    /// Returns true if this key admits every source IP.
+    pub fn is_any_source(&self) -> bool {
+        self.src_ip == ANY_SRC_IP
+    }
+
+    /// Checks that the session can be bound to `src_mac` for
+    /// `network.bind_src_mac`: it must fit in 48 bits, and any-source
+    /// sessions have no single host to bind to. 0 means unbound.
+    pub fn check_src_mac(&self, src_mac: u64) -> Result<()> {
+        if src_mac >> 48 != 0 {
+            return Err(anyhow!("Source MAC {:#x} does not fit in 48 bits", src_mac));
+        }
+        if src_mac != NO_SRC_MAC && self.is_any_source() {
+            return Err(anyhow!(
+                "An any-source session cannot be bound to a source MAC"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns true if this key only matches a specific source port.
+    pub fn has_src_port(&self) -> bool {
+        self.src_port != ANY_SRC_PORT
+    }
+}
+
+/// Formats a session as `src[:sport] -> dst:port`, with `*` for any source
+/// and for wildcard ports.
+impl fmt::Display for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_any_source() {
+            write!(f, "*")?;
+        } else {
+            write!(f, "{}", self.src_ip)?;
+        }
+        if self.has_src_port() {
+            write!(f, ":{}", self.src_port)?;
+        }
+        if self.is_wildcard() {
+            write!(f, " -> {}:*", self.dest_ip)
+        } else {
+            write!(f, " -> {}:{}", self.dest_ip, self.dest_port)
+        }
+    }
+}
+
+impl From<session_key> for SessionKey {
+    fn from(key: session_key) -> Self {
+        let (src_ip, dest_ip, dest_port, src_port) =
+            (key.src_ip, key.dest_ip, key.dest_port, key.src_port);
+        Self {
+            src_ip: ip::from_network(src_ip),
+            dest_ip: ip::from_network(dest_ip),
+            dest_port: u16::from_be(dest_port),
+            src_port: u16::from_be(src_port),
+        }
+    }
+}
+
+impl From<SessionKey> for session_key {
+    fn from(key: SessionKey) -> Self {
+        Self {
+            src_ip: ip::to_network(key.src_ip),
+            dest_ip: ip::to_network(key.dest_ip),
+            dest_port: key.dest_port.to_be(),
+            src_port: key.src_port.to_be(),
+        }
+    }
+}
+
+/// Timestamps stored for an authorized flow, the controller epoch that
+/// last submitted it, the source MAC it is bound to, and its flags.
+///
+/// Both timestamps are `CLOCK_MONOTONIC` nanoseconds, the clock the XDP
+/// program reads with `bpf_ktime_get_ns()`. `src_mac` holds the MAC in its
+/// low 48 bits and is 0 until the controller or `network.bind_src_mac` binds
+/// the session.
+/// `flags` holds bits such as [`SESSION_SEEN`](crate::bpf::SESSION_SEEN).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionVal {
+    pub created_at_ns: u64,
+    pub last_seen_ns: u64,
+    pub epoch: u64,
+    pub src_mac: u64,
+    pub flags: u64,
+}
+
+impl From<session_val> for SessionVal {
+    fn from(val: session_val) -> Self {
+        Self {
+            created_at_ns: val.created_at_ns,
+            last_seen_ns: val.last_seen_ns,
+            epoch: val.epoch,
+            src_mac: val.src_mac,
+            flags: val.flags,
+        }
+    }
+}
+
+impl From<SessionVal> for session_val {
+    fn from(val: SessionVal) -> Self {
+        Self {
+            created_at_ns: val.created_at_ns,
+            last_seen_ns: val.last_seen_ns,
+            epoch: val.epoch,
+            src_mac: val.src_mac,
+            flags: val.flags,
+        }
+    }
+}
+
+/// IPv4 prefix such as `10.0.0.0/28`, used to authorize a group of
+/// controllers instead of a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Net {
+    network: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Ipv4Net {
+    /// Creates a prefix, clearing any host bits of `addr`.
+    /// The prefix length must be between 1 and 32; `/0` would admit everyone.
+    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Result<Self> {
+        if !(1..=32).contains(&prefix_len) {
+            return Err(anyhow!(
+                "Invalid prefix length /{}, expected 1 to 32",
+                prefix_len
+            ));
+        }
+        let mask = u32::MAX << (32 - prefix_len);
+        Ok(Self {
+            network: Ipv4Addr::from(u32::from(addr) & mask),
+            prefix_len,
+        })
+    }
+
+    /// Network address with the host bits cleared.
+    pub fn network(&self) -> Ipv4Addr {
+        self.network
+    }
+
+    /// Netmask, e.g. `255.255.255.240` for a `/28`.
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::MAX << (32 - self.prefix_len))
+    }
+
+    /// Returns true if `ip` lies within the prefix.
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & u32::from(self.netmask()) == u32::from(self.network)
+    }
+}
+
+impl FromStr for Ipv4Net {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Expected <ip>/<prefix length>, got '{}'", s))?;
+        let addr = addr
+            .parse()
+            .with_context(|| format!("Invalid network address: {}", addr))?;
+        let prefix_len = prefix_len
+            .parse()
+            .with_context(|| format!("Invalid prefix length: {}", prefix_len))?;
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for Ipv4Net {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf::SESSION_SEEN;
+
+    #[test]
+    fn test_check_src_mac() {
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        assert!(key.check_src_mac(0x02_42_ac_15_00_0a).is_ok());
+        assert!(key.check_src_mac(NO_SRC_MAC).is_ok());
+        assert!(key.check_src_mac(1 << 48).is_err());
+        let any_source = SessionKey {
+            src_ip: ANY_SRC_IP,
+            ..key
+        };
+        assert!(any_source.check_src_mac(NO_SRC_MAC).is_ok());
+        assert!(any_source.check_src_mac(0x02_42_ac_15_00_0a).is_err());
+    }
+
+    #[test]
+    fn test_ipv4_net_contains() {
+        let net: Ipv4Net = "10.0.0.0/28".parse().unwrap();
+        assert_eq!(net.netmask(), Ipv4Addr::new(255, 255, 255, 240));
+        assert!(net.contains(Ipv4Addr::new(10, 0, 0, 1)));
+        assert!(net.contains(Ipv4Addr::new(10, 0, 0, 15)));
+        assert!(!net.contains(Ipv4Addr::new(10, 0, 0, 16)));
+        assert!(!net.contains(Ipv4Addr::new(10, 0, 1, 1)));
+
+        // Host bits are cleared, and a /32 only matches itself
+        let net: Ipv4Net = "10.0.0.7/29".parse().unwrap();
+        assert_eq!(net.to_string(), "10.0.0.0/29");
+        let net: Ipv4Net = "10.0.0.7/32".parse().unwrap();
+        assert!(net.contains(Ipv4Addr::new(10, 0, 0, 7)));
+        assert!(!net.contains(Ipv4Addr::new(10, 0, 0, 6)));
+    }
+
+    #[test]
+    fn test_ipv4_net_rejects_invalid() {
+        assert!("10.0.0.0".parse::<Ipv4Net>().is_err());
+        assert!("10.0.0.0/0".parse::<Ipv4Net>().is_err());
+        assert!("10.0.0.0/33".parse::<Ipv4Net>().is_err());
+        assert!("10.0.0/28".parse::<Ipv4Net>().is_err());
+    }
+
+    #[test]
+    fn test_session_key_round_trip() {
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        let raw = session_key::from(key);
+        assert_eq!(SessionKey::from(raw), key);
+        assert!(!key.has_src_port());
+    }
+
+    #[test]
+    fn test_session_key_round_trip_with_src_port() {
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 2049,
+            src_port: 1021,
+        };
+        let raw = session_key::from(key);
+        assert_eq!(SessionKey::from(raw), key);
+        assert!(key.has_src_port());
+    }
+
+    #[test]
+    fn test_session_key_uses_network_byte_order() {
+        let raw = session_key::from(SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: 1021,
+        });
+        let (src_ip, dest_port, src_port) = (raw.src_ip, raw.dest_port, raw.src_port);
+        assert_eq!(src_ip.to_ne_bytes(), [10, 0, 0, 1]);
+        assert_eq!(dest_port.to_ne_bytes(), 8080u16.to_be_bytes());
+        assert_eq!(src_port.to_ne_bytes(), 1021u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_wildcard_key() {
+        let key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: WILDCARD_PORT,
+            src_port: ANY_SRC_PORT,
+        };
+        assert!(key.is_wildcard());
+    }
+
+    #[test]
+    fn test_session_key_display() {
+        let mut key = SessionKey {
+            src_ip: Ipv4Addr::new(10, 0, 0, 1),
+            dest_ip: Ipv4Addr::new(172, 21, 0, 10),
+            dest_port: 8080,
+            src_port: ANY_SRC_PORT,
+        };
+        assert_eq!(key.to_string(), "10.0.0.1 -> 172.21.0.10:8080");
+
+        key.src_port = 1021;
+        assert_eq!(key.to_string(), "10.0.0.1:1021 -> 172.21.0.10:8080");
+
+        key.src_port = ANY_SRC_PORT;
+        key.dest_port = WILDCARD_PORT;
+        assert_eq!(key.to_string(), "10.0.0.1 -> 172.21.0.10:*");
+
+        key.src_ip = ANY_SRC_IP;
+        key.dest_port = 443;
+        assert!(key.is_any_source());
+        assert_eq!(key.to_string(), "* -> 172.21.0.10:443");
+    }
+
+    #[test]
+    fn test_session_val_round_trip() {
+        let val = SessionVal {
+            created_at_ns: 1_000,
+            last_seen_ns: 2_000,
+            epoch: 7,
+            src_mac: 0x02_42_ac_15_00_0a,
+            flags: SESSION_SEEN,
+        };
+        assert_eq!(SessionVal::from(session_val::from(val)), val);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+
+use crate::bpf::{BpfFeatures, probe_features};
+#[cfg(feature = "grpc")]
+pub use crate::grpc_server::session::VersionInfo;
+
+/// Build and runtime versions, as returned by the `GetVersion` RPC.
+#[cfg(not(feature = "grpc"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub git_sha: String,
+    pub kernel: String,
+    pub libbpf: String,
+    pub batch_delete: bool,
+    pub ringbuf: bool,
+    pub btf: bool,
+    pub xdp: bool,
+    pub lru_hash: bool,
+    pub percpu_array: bool,
+}
+
+/// Crate version from `Cargo.toml`
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short git SHA embedded by `build.rs`, or `unknown`
+pub const GIT_SHA: &str = env!("AEGIS_GIT_SHA");
+
+/// Collects build and runtime version information for this agent.
+pub fn version_info() -> VersionInfo {
+    let features = probe_features();
+    VersionInfo {
+        version: VERSION.to_string(),
+        git_sha: GIT_SHA.to_string(),
+        kernel: kernel_release(),
+        libbpf: libbpf_version(),
+        batch_delete: features.batch_delete,
+        ringbuf: features.ringbuf,
+        btf: features.btf,
+        xdp: features.xdp,
+        lru_hash: features.lru_hash,
+        percpu_array: features.percpu_array,
+    }
+}
+
+/// Formats version information for `--version`.
+pub fn format_version(info: &VersionInfo) -> String {
+    let features = BpfFeatures {
+        batch_delete: info.batch_delete,
+        ringbuf: info.ringbuf,
+        btf: info.btf,
+        xdp: info.xdp,
+        lru_hash: info.lru_hash,
+        percpu_array: info.percpu_array,
+    };
+    format!(
+        "aegis-agent {} ({})\nkernel: {}\nlibbpf: {}\nfeatures: {}",
+        info.version, info.git_sha, info.kernel, info.libbpf, features
+    )
+}
+
+/// Returns the running kernel release, e.g. `6.8.0-45-generic`.
+fn kernel_release() -> String {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|release| release.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Returns the libbpf version linked into the agent.
+fn libbpf_version() -> String {
+    // SAFETY: plain getters without arguments or side effects.
+    let (major, minor) = unsafe {
+        (
+            libbpf_sys::libbpf_major_version(),
+            libbpf_sys::libbpf_minor_version(),
+        )
+    };
+    format!("{}.{}", major, minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_info() {
+        let info = version_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_sha.is_empty());
+        assert!(!info.kernel.is_empty());
+        assert!(info.libbpf.starts_with("1."));
+    }
+
+    #[test]
+    fn test_format_version() {
+        let info = VersionInfo {
+            version: "1.2.2".to_string(),
+            git_sha: "abc1234".to_string(),
+            kernel: "6.8.0".to_string(),
+            libbpf: "1.5".to_string(),
+            batch_delete: true,
+            ringbuf: true,
+            btf: false,
+            xdp: true,
+            lru_hash: true,
+            percpu_array: true,
+        };
+        assert_eq!(
+            format_version(&info),
+            "aegis-agent 1.2.2 (abc1234)\nkernel: 6.8.0\nlibbpf: 1.5\n\
+             features: batch_delete=yes ringbuf=yes btf=no xdp=yes lru_hash=yes percpu_array=yes"
+        );
+    }
+}
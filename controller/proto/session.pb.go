@@ -22,11 +22,26 @@ const (
 )
 
 type LoginEvent struct {
-	state         protoimpl.MessageState `protogen:"open.v1"`
-	SrcIp         uint32                 `protobuf:"varint,1,opt,name=src_ip,json=srcIp,proto3" json:"src_ip,omitempty"`
-	DstIp         uint32                 `protobuf:"varint,2,opt,name=dst_ip,json=dstIp,proto3" json:"dst_ip,omitempty"`
-	DstPort       uint32                 `protobuf:"varint,3,opt,name=dst_port,json=dstPort,proto3" json:"dst_port,omitempty"`
-	Activate      bool                   `protobuf:"varint,4,opt,name=activate,proto3" json:"activate,omitempty"`
+	state protoimpl.MessageState `protogen:"open.v1"`
+	// 0 admits every source to dst_ip:dst_port; requires a specific dst_port
+	// and src_port 0.
+	SrcIp uint32 `protobuf:"varint,1,opt,name=src_ip,json=srcIp,proto3" json:"src_ip,omitempty"`
+	DstIp uint32 `protobuf:"varint,2,opt,name=dst_ip,json=dstIp,proto3" json:"dst_ip,omitempty"`
+	// 0 is reserved and authorizes every port on dst_ip. A session covers both
+	// TCP and UDP to the port.
+	DstPort  uint32 `protobuf:"varint,3,opt,name=dst_port,json=dstPort,proto3" json:"dst_port,omitempty"`
+	Activate bool   `protobuf:"varint,4,opt,name=activate,proto3" json:"activate,omitempty"`
+	// 0 accepts any source port; a specific value is matched first.
+	SrcPort uint32 `protobuf:"varint,5,opt,name=src_port,json=srcPort,proto3" json:"src_port,omitempty"`
+	// Controller incarnation submitting the session, stored with it and moved
+	// to the new value when an existing session is refreshed. 0 means none.
+	Epoch uint64 `protobuf:"varint,6,opt,name=epoch,proto3" json:"epoch,omitempty"`
+	// Ethernet MAC of the client in the low 48 bits (02:42:ac:15:00:0a is
+	// 0x0242ac15000a), binding the session to it when the agent runs with
+	// bind_src_mac; replaces an earlier binding on refresh. 0 leaves the agent
+	// to bind the MAC of the first packet that passes. Must be 0 when src_ip
+	// is 0.
+	SrcMac        uint64 `protobuf:"varint,7,opt,name=src_mac,json=srcMac,proto3" json:"src_mac,omitempty"`
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
@@ -89,9 +104,44 @@ func (x *LoginEvent) GetActivate() bool {
 	return false
 }
 
+func (x *LoginEvent) GetSrcPort() uint32 {
+	if x != nil {
+		return x.SrcPort
+	}
+	return 0
+}
+
+func (x *LoginEvent) GetEpoch() uint64 {
+	if x != nil {
+		return x.Epoch
+	}
+	return 0
+}
+
+func (x *LoginEvent) GetSrcMac() uint64 {
+	if x != nil {
+		return x.SrcMac
+	}
+	return 0
+}
+
 type Ack struct {
-	state         protoimpl.MessageState `protogen:"open.v1"`
-	Success       bool                   `protobuf:"varint,1,opt,name=success,proto3" json:"success,omitempty"`
+	state   protoimpl.MessageState `protogen:"open.v1"`
+	Success bool                   `protobuf:"varint,1,opt,name=success,proto3" json:"success,omitempty"`
+	// Sessions in the map after a successful SubmitSession, so the controller
+	// can detect drift. Unset for other RPCs.
+	ActiveSessions *uint32 `protobuf:"varint,2,opt,name=active_sessions,json=activeSessions,proto3,oneof" json:"active_sessions,omitempty"`
+	// Whether the session was already in the map before a successful
+	// SubmitSession: a refresh rather than a new session on activate, and
+	// false when there was nothing to remove on deactivate. Unset for other RPCs.
+	PreviousExisted *bool `protobuf:"varint,3,opt,name=previous_existed,json=previousExisted,proto3,oneof" json:"previous_existed,omitempty"`
+	// Sessions removed by a successful PruneStaleEpochs. Unset for other RPCs.
+	Pruned *uint64 `protobuf:"varint,4,opt,name=pruned,proto3,oneof" json:"pruned,omitempty"`
+	// Outcome of every change in an IpChange request, in request order, so
+	// the controller can retry only the failed ones. Empty for other RPCs.
+	IpChanges []*IpChangeResult `protobuf:"bytes,5,rep,name=ip_changes,json=ipChanges,proto3" json:"ip_changes,omitempty"`
+	// Sessions removed by a successful FlushAll. Unset for other RPCs.
+	Flushed       *uint64 `protobuf:"varint,6,opt,name=flushed,proto3,oneof" json:"flushed,omitempty"`
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
@@ -133,6 +183,119 @@ func (x *Ack) GetSuccess() bool {
 	return false
 }
 
+func (x *Ack) GetActiveSessions() uint32 {
+	if x != nil && x.ActiveSessions != nil {
+		return *x.ActiveSessions
+	}
+	return 0
+}
+
+func (x *Ack) GetPreviousExisted() bool {
+	if x != nil && x.PreviousExisted != nil {
+		return *x.PreviousExisted
+	}
+	return false
+}
+
+func (x *Ack) GetPruned() uint64 {
+	if x != nil && x.Pruned != nil {
+		return *x.Pruned
+	}
+	return 0
+}
+
+func (x *Ack) GetIpChanges() []*IpChangeResult {
+	if x != nil {
+		return x.IpChanges
+	}
+	return nil
+}
+
+func (x *Ack) GetFlushed() uint64 {
+	if x != nil && x.Flushed != nil {
+		return *x.Flushed
+	}
+	return 0
+}
+
+type IpChangeResult struct {
+	state   protoimpl.MessageState `protogen:"open.v1"`
+	OldIp   uint32                 `protobuf:"varint,1,opt,name=old_ip,json=oldIp,proto3" json:"old_ip,omitempty"`
+	NewIp   uint32                 `protobuf:"varint,2,opt,name=new_ip,json=newIp,proto3" json:"new_ip,omitempty"`
+	Success bool                   `protobuf:"varint,3,opt,name=success,proto3" json:"success,omitempty"`
+	// Sessions moved from old_ip to new_ip.
+	Updated uint64 `protobuf:"varint,4,opt,name=updated,proto3" json:"updated,omitempty"`
+	// Why the change failed; empty on success.
+	Error         string `protobuf:"bytes,5,opt,name=error,proto3" json:"error,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *IpChangeResult) Reset() {
+	*x = IpChangeResult{}
+	mi := &file_proto_session_proto_msgTypes[2]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *IpChangeResult) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*IpChangeResult) ProtoMessage() {}
+
+func (x *IpChangeResult) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[2]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use IpChangeResult.ProtoReflect.Descriptor instead.
+func (*IpChangeResult) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{2}
+}
+
+func (x *IpChangeResult) GetOldIp() uint32 {
+	if x != nil {
+		return x.OldIp
+	}
+	return 0
+}
+
+func (x *IpChangeResult) GetNewIp() uint32 {
+	if x != nil {
+		return x.NewIp
+	}
+	return 0
+}
+
+func (x *IpChangeResult) GetSuccess() bool {
+	if x != nil {
+		return x.Success
+	}
+	return false
+}
+
+func (x *IpChangeResult) GetUpdated() uint64 {
+	if x != nil {
+		return x.Updated
+	}
+	return 0
+}
+
+func (x *IpChangeResult) GetError() string {
+	if x != nil {
+		return x.Error
+	}
+	return ""
+}
+
 type Empty struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
 	unknownFields protoimpl.UnknownFields
@@ -141,7 +304,7 @@ type Empty struct {
 
 func (x *Empty) Reset() {
 	*x = Empty{}
-	mi := &file_proto_session_proto_msgTypes[2]
+	mi := &file_proto_session_proto_msgTypes[3]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -153,7 +316,7 @@ func (x *Empty) String() string {
 func (*Empty) ProtoMessage() {}
 
 func (x *Empty) ProtoReflect() protoreflect.Message {
-	mi := &file_proto_session_proto_msgTypes[2]
+	mi := &file_proto_session_proto_msgTypes[3]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -166,19 +329,25 @@ func (x *Empty) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Empty.ProtoReflect.Descriptor instead.
 func (*Empty) Descriptor() ([]byte, []int) {
-	return file_proto_session_proto_rawDescGZIP(), []int{2}
+	return file_proto_session_proto_rawDescGZIP(), []int{3}
 }
 
 type SessionList struct {
-	state         protoimpl.MessageState `protogen:"open.v1"`
-	Sessions      []*Session             `protobuf:"bytes,1,rep,name=sessions,proto3" json:"sessions,omitempty"`
+	state    protoimpl.MessageState `protogen:"open.v1"`
+	Sessions []*Session             `protobuf:"bytes,1,rep,name=sessions,proto3" json:"sessions,omitempty"`
+	// Set on the first list sent after this subscriber fell behind and missed
+	// updates. Each list is a full snapshot, so it replaces the earlier view.
+	Resync bool `protobuf:"varint,2,opt,name=resync,proto3" json:"resync,omitempty"`
+	// Set on the last message before the agent shuts down. The stream ends
+	// right after it; reconnect with backoff instead of treating it as an error.
+	Closing       bool `protobuf:"varint,3,opt,name=closing,proto3" json:"closing,omitempty"`
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
 
 func (x *SessionList) Reset() {
 	*x = SessionList{}
-	mi := &file_proto_session_proto_msgTypes[3]
+	mi := &file_proto_session_proto_msgTypes[4]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -190,7 +359,7 @@ func (x *SessionList) String() string {
 func (*SessionList) ProtoMessage() {}
 
 func (x *SessionList) ProtoReflect() protoreflect.Message {
-	mi := &file_proto_session_proto_msgTypes[3]
+	mi := &file_proto_session_proto_msgTypes[4]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -203,7 +372,7 @@ func (x *SessionList) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use SessionList.ProtoReflect.Descriptor instead.
 func (*SessionList) Descriptor() ([]byte, []int) {
-	return file_proto_session_proto_rawDescGZIP(), []int{3}
+	return file_proto_session_proto_rawDescGZIP(), []int{4}
 }
 
 func (x *SessionList) GetSessions() []*Session {
@@ -213,19 +382,39 @@ func (x *SessionList) GetSessions() []*Session {
 	return nil
 }
 
+func (x *SessionList) GetResync() bool {
+	if x != nil {
+		return x.Resync
+	}
+	return false
+}
+
+func (x *SessionList) GetClosing() bool {
+	if x != nil {
+		return x.Closing
+	}
+	return false
+}
+
 type Session struct {
-	state         protoimpl.MessageState `protogen:"open.v1"`
-	SrcIp         uint32                 `protobuf:"varint,1,opt,name=src_ip,json=srcIp,proto3" json:"src_ip,omitempty"`
-	DstIp         uint32                 `protobuf:"varint,2,opt,name=dst_ip,json=dstIp,proto3" json:"dst_ip,omitempty"`
-	DstPort       uint32                 `protobuf:"varint,3,opt,name=dst_port,json=dstPort,proto3" json:"dst_port,omitempty"`
-	TimeLeft      int32                  `protobuf:"varint,4,opt,name=time_left,json=timeLeft,proto3" json:"time_left,omitempty"`
+	state protoimpl.MessageState `protogen:"open.v1"`
+	SrcIp uint32                 `protobuf:"varint,1,opt,name=src_ip,json=srcIp,proto3" json:"src_ip,omitempty"`
+	DstIp uint32                 `protobuf:"varint,2,opt,name=dst_ip,json=dstIp,proto3" json:"dst_ip,omitempty"`
+	// 0 means the session covers every port on dst_ip.
+	DstPort uint32 `protobuf:"varint,3,opt,name=dst_port,json=dstPort,proto3" json:"dst_port,omitempty"`
+	// Seconds until the session idles out. Negative for a session that has
+	// expired but not been reaped yet (how long ago it expired). Widened from
+	// int32, which is wire compatible.
+	TimeLeft int64 `protobuf:"varint,4,opt,name=time_left,json=timeLeft,proto3" json:"time_left,omitempty"`
+	// 0 means the session accepts any source port.
+	SrcPort       uint32 `protobuf:"varint,5,opt,name=src_port,json=srcPort,proto3" json:"src_port,omitempty"`
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
 
 func (x *Session) Reset() {
 	*x = Session{}
-	mi := &file_proto_session_proto_msgTypes[4]
+	mi := &file_proto_session_proto_msgTypes[5]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -237,7 +426,7 @@ func (x *Session) String() string {
 func (*Session) ProtoMessage() {}
 
 func (x *Session) ProtoReflect() protoreflect.Message {
-	mi := &file_proto_session_proto_msgTypes[4]
+	mi := &file_proto_session_proto_msgTypes[5]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -250,7 +439,7 @@ func (x *Session) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Session.ProtoReflect.Descriptor instead.
 func (*Session) Descriptor() ([]byte, []int) {
-	return file_proto_session_proto_rawDescGZIP(), []int{4}
+	return file_proto_session_proto_rawDescGZIP(), []int{5}
 }
 
 func (x *Session) GetSrcIp() uint32 {
@@ -274,13 +463,235 @@ func (x *Session) GetDstPort() uint32 {
 	return 0
 }
 
-func (x *Session) GetTimeLeft() int32 {
+func (x *Session) GetTimeLeft() int64 {
 	if x != nil {
 		return x.TimeLeft
 	}
 	return 0
 }
 
+func (x *Session) GetSrcPort() uint32 {
+	if x != nil {
+		return x.SrcPort
+	}
+	return 0
+}
+
+type BlockRequest struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	Ip            uint32                 `protobuf:"varint,1,opt,name=ip,proto3" json:"ip,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *BlockRequest) Reset() {
+	*x = BlockRequest{}
+	mi := &file_proto_session_proto_msgTypes[6]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *BlockRequest) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*BlockRequest) ProtoMessage() {}
+
+func (x *BlockRequest) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[6]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use BlockRequest.ProtoReflect.Descriptor instead.
+func (*BlockRequest) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{6}
+}
+
+func (x *BlockRequest) GetIp() uint32 {
+	if x != nil {
+		return x.Ip
+	}
+	return 0
+}
+
+type PruneRequest struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	Epoch         uint64                 `protobuf:"varint,1,opt,name=epoch,proto3" json:"epoch,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *PruneRequest) Reset() {
+	*x = PruneRequest{}
+	mi := &file_proto_session_proto_msgTypes[7]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *PruneRequest) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*PruneRequest) ProtoMessage() {}
+
+func (x *PruneRequest) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[7]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use PruneRequest.ProtoReflect.Descriptor instead.
+func (*PruneRequest) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{7}
+}
+
+func (x *PruneRequest) GetEpoch() uint64 {
+	if x != nil {
+		return x.Epoch
+	}
+	return 0
+}
+
+type MapStats struct {
+	state protoimpl.MessageState `protogen:"open.v1"`
+	// Sessions in the map, including expired ones not yet reaped.
+	Used uint64 `protobuf:"varint,1,opt,name=used,proto3" json:"used,omitempty"`
+	// Maximum number of sessions the map holds.
+	Capacity uint64 `protobuf:"varint,2,opt,name=capacity,proto3" json:"capacity,omitempty"`
+	// SubmitSession requests rejected since the agent started, one entry per
+	// reason.
+	RejectedSessions []*RejectCount `protobuf:"bytes,3,rep,name=rejected_sessions,json=rejectedSessions,proto3" json:"rejected_sessions,omitempty"`
+	// Session lists skipped by lagging MonitorSessions subscribers since the
+	// agent started; each skip was followed by a resync.
+	MonitorLagged uint64 `protobuf:"varint,4,opt,name=monitor_lagged,json=monitorLagged,proto3" json:"monitor_lagged,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *MapStats) Reset() {
+	*x = MapStats{}
+	mi := &file_proto_session_proto_msgTypes[8]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *MapStats) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*MapStats) ProtoMessage() {}
+
+func (x *MapStats) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[8]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use MapStats.ProtoReflect.Descriptor instead.
+func (*MapStats) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{8}
+}
+
+func (x *MapStats) GetUsed() uint64 {
+	if x != nil {
+		return x.Used
+	}
+	return 0
+}
+
+func (x *MapStats) GetCapacity() uint64 {
+	if x != nil {
+		return x.Capacity
+	}
+	return 0
+}
+
+func (x *MapStats) GetRejectedSessions() []*RejectCount {
+	if x != nil {
+		return x.RejectedSessions
+	}
+	return nil
+}
+
+func (x *MapStats) GetMonitorLagged() uint64 {
+	if x != nil {
+		return x.MonitorLagged
+	}
+	return 0
+}
+
+type RejectCount struct {
+	state protoimpl.MessageState `protogen:"open.v1"`
+	// e.g. "invalid_dst_port" or "rate_limited".
+	Reason        string `protobuf:"bytes,1,opt,name=reason,proto3" json:"reason,omitempty"`
+	Count         uint64 `protobuf:"varint,2,opt,name=count,proto3" json:"count,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *RejectCount) Reset() {
+	*x = RejectCount{}
+	mi := &file_proto_session_proto_msgTypes[9]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *RejectCount) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*RejectCount) ProtoMessage() {}
+
+func (x *RejectCount) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[9]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use RejectCount.ProtoReflect.Descriptor instead.
+func (*RejectCount) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{9}
+}
+
+func (x *RejectCount) GetReason() string {
+	if x != nil {
+		return x.Reason
+	}
+	return ""
+}
+
+func (x *RejectCount) GetCount() uint64 {
+	if x != nil {
+		return x.Count
+	}
+	return 0
+}
+
 type IpChangeList struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
 	IpChanges     []*IpChangeEvent       `protobuf:"bytes,1,rep,name=ip_changes,json=ipChanges,proto3" json:"ip_changes,omitempty"`
@@ -290,7 +701,7 @@ type IpChangeList struct {
 
 func (x *IpChangeList) Reset() {
 	*x = IpChangeList{}
-	mi := &file_proto_session_proto_msgTypes[5]
+	mi := &file_proto_session_proto_msgTypes[10]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -302,7 +713,7 @@ func (x *IpChangeList) String() string {
 func (*IpChangeList) ProtoMessage() {}
 
 func (x *IpChangeList) ProtoReflect() protoreflect.Message {
-	mi := &file_proto_session_proto_msgTypes[5]
+	mi := &file_proto_session_proto_msgTypes[10]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -315,7 +726,7 @@ func (x *IpChangeList) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use IpChangeList.ProtoReflect.Descriptor instead.
 func (*IpChangeList) Descriptor() ([]byte, []int) {
-	return file_proto_session_proto_rawDescGZIP(), []int{5}
+	return file_proto_session_proto_rawDescGZIP(), []int{10}
 }
 
 func (x *IpChangeList) GetIpChanges() []*IpChangeEvent {
@@ -335,7 +746,7 @@ type IpChangeEvent struct {
 
 func (x *IpChangeEvent) Reset() {
 	*x = IpChangeEvent{}
-	mi := &file_proto_session_proto_msgTypes[6]
+	mi := &file_proto_session_proto_msgTypes[11]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -347,7 +758,7 @@ func (x *IpChangeEvent) String() string {
 func (*IpChangeEvent) ProtoMessage() {}
 
 func (x *IpChangeEvent) ProtoReflect() protoreflect.Message {
-	mi := &file_proto_session_proto_msgTypes[6]
+	mi := &file_proto_session_proto_msgTypes[11]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -360,7 +771,7 @@ func (x *IpChangeEvent) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use IpChangeEvent.ProtoReflect.Descriptor instead.
 func (*IpChangeEvent) Descriptor() ([]byte, []int) {
-	return file_proto_session_proto_rawDescGZIP(), []int{6}
+	return file_proto_session_proto_rawDescGZIP(), []int{11}
 }
 
 func (x *IpChangeEvent) GetOldIp() uint32 {
@@ -377,37 +788,453 @@ func (x *IpChangeEvent) GetNewIp() uint32 {
 	return 0
 }
 
+type VersionInfo struct {
+	state   protoimpl.MessageState `protogen:"open.v1"`
+	Version string                 `protobuf:"bytes,1,opt,name=version,proto3" json:"version,omitempty"`
+	GitSha  string                 `protobuf:"bytes,2,opt,name=git_sha,json=gitSha,proto3" json:"git_sha,omitempty"`
+	Kernel  string                 `protobuf:"bytes,3,opt,name=kernel,proto3" json:"kernel,omitempty"`
+	Libbpf  string                 `protobuf:"bytes,4,opt,name=libbpf,proto3" json:"libbpf,omitempty"`
+	// Kernel BPF features detected by the agent.
+	BatchDelete   bool `protobuf:"varint,5,opt,name=batch_delete,json=batchDelete,proto3" json:"batch_delete,omitempty"`
+	Ringbuf       bool `protobuf:"varint,6,opt,name=ringbuf,proto3" json:"ringbuf,omitempty"`
+	Btf           bool `protobuf:"varint,7,opt,name=btf,proto3" json:"btf,omitempty"`
+	Xdp           bool `protobuf:"varint,8,opt,name=xdp,proto3" json:"xdp,omitempty"`
+	LruHash       bool `protobuf:"varint,9,opt,name=lru_hash,json=lruHash,proto3" json:"lru_hash,omitempty"`
+	PercpuArray   bool `protobuf:"varint,10,opt,name=percpu_array,json=percpuArray,proto3" json:"percpu_array,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *VersionInfo) Reset() {
+	*x = VersionInfo{}
+	mi := &file_proto_session_proto_msgTypes[12]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *VersionInfo) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*VersionInfo) ProtoMessage() {}
+
+func (x *VersionInfo) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[12]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use VersionInfo.ProtoReflect.Descriptor instead.
+func (*VersionInfo) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{12}
+}
+
+func (x *VersionInfo) GetVersion() string {
+	if x != nil {
+		return x.Version
+	}
+	return ""
+}
+
+func (x *VersionInfo) GetGitSha() string {
+	if x != nil {
+		return x.GitSha
+	}
+	return ""
+}
+
+func (x *VersionInfo) GetKernel() string {
+	if x != nil {
+		return x.Kernel
+	}
+	return ""
+}
+
+func (x *VersionInfo) GetLibbpf() string {
+	if x != nil {
+		return x.Libbpf
+	}
+	return ""
+}
+
+func (x *VersionInfo) GetBatchDelete() bool {
+	if x != nil {
+		return x.BatchDelete
+	}
+	return false
+}
+
+func (x *VersionInfo) GetRingbuf() bool {
+	if x != nil {
+		return x.Ringbuf
+	}
+	return false
+}
+
+func (x *VersionInfo) GetBtf() bool {
+	if x != nil {
+		return x.Btf
+	}
+	return false
+}
+
+func (x *VersionInfo) GetXdp() bool {
+	if x != nil {
+		return x.Xdp
+	}
+	return false
+}
+
+func (x *VersionInfo) GetLruHash() bool {
+	if x != nil {
+		return x.LruHash
+	}
+	return false
+}
+
+func (x *VersionInfo) GetPercpuArray() bool {
+	if x != nil {
+		return x.PercpuArray
+	}
+	return false
+}
+
+// Settings in effect on the agent, after config.toml and command-line
+// overrides. Durations keep the units of config.toml. TLS material is never
+// included.
+type AgentConfig struct {
+	state protoimpl.MessageState `protogen:"open.v1"`
+	Iface string                 `protobuf:"bytes,1,opt,name=iface,proto3" json:"iface,omitempty"`
+	// "xdp" or "tc".
+	AttachHook           string   `protobuf:"bytes,2,opt,name=attach_hook,json=attachHook,proto3" json:"attach_hook,omitempty"`
+	ControllerIp         uint32   `protobuf:"varint,3,opt,name=controller_ip,json=controllerIp,proto3" json:"controller_ip,omitempty"`
+	ControllerPort       uint32   `protobuf:"varint,4,opt,name=controller_port,json=controllerPort,proto3" json:"controller_port,omitempty"`
+	ControllerExtraPorts []uint32 `protobuf:"varint,5,rep,packed,name=controller_extra_ports,json=controllerExtraPorts,proto3" json:"controller_extra_ports,omitempty"`
+	ControllerExtraIps   []uint32 `protobuf:"varint,6,rep,packed,name=controller_extra_ips,json=controllerExtraIps,proto3" json:"controller_extra_ips,omitempty"`
+	RuleTimeoutNs        uint64   `protobuf:"varint,7,opt,name=rule_timeout_ns,json=ruleTimeoutNs,proto3" json:"rule_timeout_ns,omitempty"`
+	LazyUpdateTimeoutNs  uint64   `protobuf:"varint,8,opt,name=lazy_update_timeout_ns,json=lazyUpdateTimeoutNs,proto3" json:"lazy_update_timeout_ns,omitempty"`
+	MaxSessionAgeNs      uint64   `protobuf:"varint,9,opt,name=max_session_age_ns,json=maxSessionAgeNs,proto3" json:"max_session_age_ns,omitempty"`
+	CleanupIntervalSec   uint64   `protobuf:"varint,10,opt,name=cleanup_interval_sec,json=cleanupIntervalSec,proto3" json:"cleanup_interval_sec,omitempty"`
+	// Whether rejected traffic is dropped; false during the startup grace
+	// period. Unset if the agent could not read it.
+	Enforcing           *bool  `protobuf:"varint,11,opt,name=enforcing,proto3,oneof" json:"enforcing,omitempty"`
+	EnforcementGraceSec uint64 `protobuf:"varint,12,opt,name=enforcement_grace_sec,json=enforcementGraceSec,proto3" json:"enforcement_grace_sec,omitempty"`
+	StrictTcp           bool   `protobuf:"varint,13,opt,name=strict_tcp,json=strictTcp,proto3" json:"strict_tcp,omitempty"`
+	BindSrcMac          bool   `protobuf:"varint,14,opt,name=bind_src_mac,json=bindSrcMac,proto3" json:"bind_src_mac,omitempty"`
+	// "drop" or "track".
+	FragmentPolicy   string   `protobuf:"bytes,15,opt,name=fragment_policy,json=fragmentPolicy,proto3" json:"fragment_policy,omitempty"`
+	AlwaysAllowPorts []uint32 `protobuf:"varint,16,rep,packed,name=always_allow_ports,json=alwaysAllowPorts,proto3" json:"always_allow_ports,omitempty"`
+	MaxConnsPerSrc   uint32   `protobuf:"varint,17,opt,name=max_conns_per_src,json=maxConnsPerSrc,proto3" json:"max_conns_per_src,omitempty"`
+	GrpcPort         uint32   `protobuf:"varint,18,opt,name=grpc_port,json=grpcPort,proto3" json:"grpc_port,omitempty"`
+	// Whether every mutating RPC is rejected.
+	ReadOnly bool `protobuf:"varint,19,opt,name=read_only,json=readOnly,proto3" json:"read_only,omitempty"`
+	// Concurrent MonitorSessions streams allowed; 0 means no limit.
+	MaxMonitors uint32 `protobuf:"varint,20,opt,name=max_monitors,json=maxMonitors,proto3" json:"max_monitors,omitempty"`
+	// SubmitSession calls allowed per second per caller; 0 means no limit.
+	SubmitRateLimit uint32 `protobuf:"varint,21,opt,name=submit_rate_limit,json=submitRateLimit,proto3" json:"submit_rate_limit,omitempty"`
+	unknownFields   protoimpl.UnknownFields
+	sizeCache       protoimpl.SizeCache
+}
+
+func (x *AgentConfig) Reset() {
+	*x = AgentConfig{}
+	mi := &file_proto_session_proto_msgTypes[13]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *AgentConfig) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*AgentConfig) ProtoMessage() {}
+
+func (x *AgentConfig) ProtoReflect() protoreflect.Message {
+	mi := &file_proto_session_proto_msgTypes[13]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use AgentConfig.ProtoReflect.Descriptor instead.
+func (*AgentConfig) Descriptor() ([]byte, []int) {
+	return file_proto_session_proto_rawDescGZIP(), []int{13}
+}
+
+func (x *AgentConfig) GetIface() string {
+	if x != nil {
+		return x.Iface
+	}
+	return ""
+}
+
+func (x *AgentConfig) GetAttachHook() string {
+	if x != nil {
+		return x.AttachHook
+	}
+	return ""
+}
+
+func (x *AgentConfig) GetControllerIp() uint32 {
+	if x != nil {
+		return x.ControllerIp
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetControllerPort() uint32 {
+	if x != nil {
+		return x.ControllerPort
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetControllerExtraPorts() []uint32 {
+	if x != nil {
+		return x.ControllerExtraPorts
+	}
+	return nil
+}
+
+func (x *AgentConfig) GetControllerExtraIps() []uint32 {
+	if x != nil {
+		return x.ControllerExtraIps
+	}
+	return nil
+}
+
+func (x *AgentConfig) GetRuleTimeoutNs() uint64 {
+	if x != nil {
+		return x.RuleTimeoutNs
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetLazyUpdateTimeoutNs() uint64 {
+	if x != nil {
+		return x.LazyUpdateTimeoutNs
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetMaxSessionAgeNs() uint64 {
+	if x != nil {
+		return x.MaxSessionAgeNs
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetCleanupIntervalSec() uint64 {
+	if x != nil {
+		return x.CleanupIntervalSec
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetEnforcing() bool {
+	if x != nil && x.Enforcing != nil {
+		return *x.Enforcing
+	}
+	return false
+}
+
+func (x *AgentConfig) GetEnforcementGraceSec() uint64 {
+	if x != nil {
+		return x.EnforcementGraceSec
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetStrictTcp() bool {
+	if x != nil {
+		return x.StrictTcp
+	}
+	return false
+}
+
+func (x *AgentConfig) GetBindSrcMac() bool {
+	if x != nil {
+		return x.BindSrcMac
+	}
+	return false
+}
+
+func (x *AgentConfig) GetFragmentPolicy() string {
+	if x != nil {
+		return x.FragmentPolicy
+	}
+	return ""
+}
+
+func (x *AgentConfig) GetAlwaysAllowPorts() []uint32 {
+	if x != nil {
+		return x.AlwaysAllowPorts
+	}
+	return nil
+}
+
+func (x *AgentConfig) GetMaxConnsPerSrc() uint32 {
+	if x != nil {
+		return x.MaxConnsPerSrc
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetGrpcPort() uint32 {
+	if x != nil {
+		return x.GrpcPort
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetReadOnly() bool {
+	if x != nil {
+		return x.ReadOnly
+	}
+	return false
+}
+
+func (x *AgentConfig) GetMaxMonitors() uint32 {
+	if x != nil {
+		return x.MaxMonitors
+	}
+	return 0
+}
+
+func (x *AgentConfig) GetSubmitRateLimit() uint32 {
+	if x != nil {
+		return x.SubmitRateLimit
+	}
+	return 0
+}
+
 var File_proto_session_proto protoreflect.FileDescriptor
 
 const file_proto_session_proto_rawDesc = "" +
 	"\n" +
-	"\x13proto/session.proto\x12\asession\"q\n" +
+	"\x13proto/session.proto\x12\asession\"\xbb\x01\n" +
 	"\n" +
 	"LoginEvent\x12\x15\n" +
 	"\x06src_ip\x18\x01 \x01(\rR\x05srcIp\x12\x15\n" +
 	"\x06dst_ip\x18\x02 \x01(\rR\x05dstIp\x12\x19\n" +
 	"\bdst_port\x18\x03 \x01(\rR\adstPort\x12\x1a\n" +
-	"\bactivate\x18\x04 \x01(\bR\bactivate\"\x1f\n" +
+	"\bactivate\x18\x04 \x01(\bR\bactivate\x12\x19\n" +
+	"\bsrc_port\x18\x05 \x01(\rR\asrcPort\x12\x14\n" +
+	"\x05epoch\x18\x06 \x01(\x04R\x05epoch\x12\x17\n" +
+	"\asrc_mac\x18\a \x01(\x04R\x06srcMac\"\xb1\x02\n" +
 	"\x03Ack\x12\x18\n" +
-	"\asuccess\x18\x01 \x01(\bR\asuccess\"\a\n" +
-	"\x05Empty\";\n" +
+	"\asuccess\x18\x01 \x01(\bR\asuccess\x12,\n" +
+	"\x0factive_sessions\x18\x02 \x01(\rH\x00R\x0eactiveSessions\x88\x01\x01\x12.\n" +
+	"\x10previous_existed\x18\x03 \x01(\bH\x01R\x0fpreviousExisted\x88\x01\x01\x12\x1b\n" +
+	"\x06pruned\x18\x04 \x01(\x04H\x02R\x06pruned\x88\x01\x01\x126\n" +
+	"\n" +
+	"ip_changes\x18\x05 \x03(\v2\x17.session.IpChangeResultR\tipChanges\x12\x1d\n" +
+	"\aflushed\x18\x06 \x01(\x04H\x03R\aflushed\x88\x01\x01B\x12\n" +
+	"\x10_active_sessionsB\x13\n" +
+	"\x11_previous_existedB\t\n" +
+	"\a_prunedB\n" +
+	"\n" +
+	"\b_flushed\"\x88\x01\n" +
+	"\x0eIpChangeResult\x12\x15\n" +
+	"\x06old_ip\x18\x01 \x01(\rR\x05oldIp\x12\x15\n" +
+	"\x06new_ip\x18\x02 \x01(\rR\x05newIp\x12\x18\n" +
+	"\asuccess\x18\x03 \x01(\bR\asuccess\x12\x18\n" +
+	"\aupdated\x18\x04 \x01(\x04R\aupdated\x12\x14\n" +
+	"\x05error\x18\x05 \x01(\tR\x05error\"\a\n" +
+	"\x05Empty\"m\n" +
 	"\vSessionList\x12,\n" +
-	"\bsessions\x18\x01 \x03(\v2\x10.session.SessionR\bsessions\"o\n" +
+	"\bsessions\x18\x01 \x03(\v2\x10.session.SessionR\bsessions\x12\x16\n" +
+	"\x06resync\x18\x02 \x01(\bR\x06resync\x12\x18\n" +
+	"\aclosing\x18\x03 \x01(\bR\aclosing\"\x8a\x01\n" +
 	"\aSession\x12\x15\n" +
 	"\x06src_ip\x18\x01 \x01(\rR\x05srcIp\x12\x15\n" +
 	"\x06dst_ip\x18\x02 \x01(\rR\x05dstIp\x12\x19\n" +
 	"\bdst_port\x18\x03 \x01(\rR\adstPort\x12\x1b\n" +
-	"\ttime_left\x18\x04 \x01(\x05R\btimeLeft\"E\n" +
+	"\ttime_left\x18\x04 \x01(\x03R\btimeLeft\x12\x19\n" +
+	"\bsrc_port\x18\x05 \x01(\rR\asrcPort\"\x1e\n" +
+	"\fBlockRequest\x12\x0e\n" +
+	"\x02ip\x18\x01 \x01(\rR\x02ip\"$\n" +
+	"\fPruneRequest\x12\x14\n" +
+	"\x05epoch\x18\x01 \x01(\x04R\x05epoch\"\xa4\x01\n" +
+	"\bMapStats\x12\x12\n" +
+	"\x04used\x18\x01 \x01(\x04R\x04used\x12\x1a\n" +
+	"\bcapacity\x18\x02 \x01(\x04R\bcapacity\x12A\n" +
+	"\x11rejected_sessions\x18\x03 \x03(\v2\x14.session.RejectCountR\x10rejectedSessions\x12%\n" +
+	"\x0emonitor_lagged\x18\x04 \x01(\x04R\rmonitorLagged\";\n" +
+	"\vRejectCount\x12\x16\n" +
+	"\x06reason\x18\x01 \x01(\tR\x06reason\x12\x14\n" +
+	"\x05count\x18\x02 \x01(\x04R\x05count\"E\n" +
 	"\fIpChangeList\x125\n" +
 	"\n" +
 	"ip_changes\x18\x01 \x03(\v2\x16.session.IpChangeEventR\tipChanges\"=\n" +
 	"\rIpChangeEvent\x12\x15\n" +
 	"\x06old_ip\x18\x01 \x01(\rR\x05oldIp\x12\x15\n" +
-	"\x06new_ip\x18\x02 \x01(\rR\x05newIp2\xb0\x01\n" +
+	"\x06new_ip\x18\x02 \x01(\rR\x05newIp\"\x8f\x02\n" +
+	"\vVersionInfo\x12\x18\n" +
+	"\aversion\x18\x01 \x01(\tR\aversion\x12\x17\n" +
+	"\agit_sha\x18\x02 \x01(\tR\x06gitSha\x12\x16\n" +
+	"\x06kernel\x18\x03 \x01(\tR\x06kernel\x12\x16\n" +
+	"\x06libbpf\x18\x04 \x01(\tR\x06libbpf\x12!\n" +
+	"\fbatch_delete\x18\x05 \x01(\bR\vbatchDelete\x12\x18\n" +
+	"\aringbuf\x18\x06 \x01(\bR\aringbuf\x12\x10\n" +
+	"\x03btf\x18\a \x01(\bR\x03btf\x12\x10\n" +
+	"\x03xdp\x18\b \x01(\bR\x03xdp\x12\x19\n" +
+	"\blru_hash\x18\t \x01(\bR\alruHash\x12!\n" +
+	"\fpercpu_array\x18\n" +
+	" \x01(\bR\vpercpuArray\"\xe7\x06\n" +
+	"\vAgentConfig\x12\x14\n" +
+	"\x05iface\x18\x01 \x01(\tR\x05iface\x12\x1f\n" +
+	"\vattach_hook\x18\x02 \x01(\tR\n" +
+	"attachHook\x12#\n" +
+	"\rcontroller_ip\x18\x03 \x01(\rR\fcontrollerIp\x12'\n" +
+	"\x0fcontroller_port\x18\x04 \x01(\rR\x0econtrollerPort\x124\n" +
+	"\x16controller_extra_ports\x18\x05 \x03(\rR\x14controllerExtraPorts\x120\n" +
+	"\x14controller_extra_ips\x18\x06 \x03(\rR\x12controllerExtraIps\x12&\n" +
+	"\x0frule_timeout_ns\x18\a \x01(\x04R\rruleTimeoutNs\x123\n" +
+	"\x16lazy_update_timeout_ns\x18\b \x01(\x04R\x13lazyUpdateTimeoutNs\x12+\n" +
+	"\x12max_session_age_ns\x18\t \x01(\x04R\x0fmaxSessionAgeNs\x120\n" +
+	"\x14cleanup_interval_sec\x18\n" +
+	" \x01(\x04R\x12cleanupIntervalSec\x12!\n" +
+	"\tenforcing\x18\v \x01(\bH\x00R\tenforcing\x88\x01\x01\x122\n" +
+	"\x15enforcement_grace_sec\x18\f \x01(\x04R\x13enforcementGraceSec\x12\x1d\n" +
+	"\n" +
+	"strict_tcp\x18\r \x01(\bR\tstrictTcp\x12 \n" +
+	"\fbind_src_mac\x18\x0e \x01(\bR\n" +
+	"bindSrcMac\x12'\n" +
+	"\x0ffragment_policy\x18\x0f \x01(\tR\x0efragmentPolicy\x12,\n" +
+	"\x12always_allow_ports\x18\x10 \x03(\rR\x10alwaysAllowPorts\x12)\n" +
+	"\x11max_conns_per_src\x18\x11 \x01(\rR\x0emaxConnsPerSrc\x12\x1b\n" +
+	"\tgrpc_port\x18\x12 \x01(\rR\bgrpcPort\x12\x1b\n" +
+	"\tread_only\x18\x13 \x01(\bR\breadOnly\x12!\n" +
+	"\fmax_monitors\x18\x14 \x01(\rR\vmaxMonitors\x12*\n" +
+	"\x11submit_rate_limit\x18\x15 \x01(\rR\x0fsubmitRateLimitB\f\n" +
+	"\n" +
+	"_enforcing2\xc3\x04\n" +
 	"\x0eSessionManager\x122\n" +
 	"\rSubmitSession\x12\x13.session.LoginEvent\x1a\f.session.Ack\x129\n" +
-	"\x0fMonitorSessions\x12\x0e.session.Empty\x1a\x14.session.SessionList0\x01\x12/\n" +
-	"\bIpChange\x12\x15.session.IpChangeList\x1a\f.session.AckB\x18Z\x16Aegis/controller/protob\x06proto3"
+	"\x0fMonitorSessions\x12\x0e.session.Empty\x1a\x14.session.SessionList0\x01\x123\n" +
+	"\n" +
+	"GetSession\x12\x13.session.LoginEvent\x1a\x10.session.Session\x12/\n" +
+	"\bIpChange\x12\x15.session.IpChangeList\x1a\f.session.Ack\x12(\n" +
+	"\bFlushAll\x12\x0e.session.Empty\x1a\f.session.Ack\x12.\n" +
+	"\aBlockIp\x12\x15.session.BlockRequest\x1a\f.session.Ack\x120\n" +
+	"\tUnblockIp\x12\x15.session.BlockRequest\x1a\f.session.Ack\x122\n" +
+	"\n" +
+	"GetVersion\x12\x0e.session.Empty\x1a\x14.session.VersionInfo\x121\n" +
+	"\tGetConfig\x12\x0e.session.Empty\x1a\x14.session.AgentConfig\x120\n" +
+	"\vGetMapStats\x12\x0e.session.Empty\x1a\x11.session.MapStats\x127\n" +
+	"\x10PruneStaleEpochs\x12\x15.session.PruneRequest\x1a\f.session.AckB\x18Z\x16Aegis/controller/protob\x06proto3"
 
 var (
 	file_proto_session_proto_rawDescOnce sync.Once
@@ -421,30 +1248,55 @@ func file_proto_session_proto_rawDescGZIP() []byte {
 	return file_proto_session_proto_rawDescData
 }
 
-var file_proto_session_proto_msgTypes = make([]protoimpl.MessageInfo, 7)
+var file_proto_session_proto_msgTypes = make([]protoimpl.MessageInfo, 14)
 var file_proto_session_proto_goTypes = []any{
-	(*LoginEvent)(nil),    // 0: session.LoginEvent
-	(*Ack)(nil),           // 1: session.Ack
-	(*Empty)(nil),         // 2: session.Empty
-	(*SessionList)(nil),   // 3: session.SessionList
-	(*Session)(nil),       // 4: session.Session
-	(*IpChangeList)(nil),  // 5: session.IpChangeList
-	(*IpChangeEvent)(nil), // 6: session.IpChangeEvent
+	(*LoginEvent)(nil),     // 0: session.LoginEvent
+	(*Ack)(nil),            // 1: session.Ack
+	(*IpChangeResult)(nil), // 2: session.IpChangeResult
+	(*Empty)(nil),          // 3: session.Empty
+	(*SessionList)(nil),    // 4: session.SessionList
+	(*Session)(nil),        // 5: session.Session
+	(*BlockRequest)(nil),   // 6: session.BlockRequest
+	(*PruneRequest)(nil),   // 7: session.PruneRequest
+	(*MapStats)(nil),       // 8: session.MapStats
+	(*RejectCount)(nil),    // 9: session.RejectCount
+	(*IpChangeList)(nil),   // 10: session.IpChangeList
+	(*IpChangeEvent)(nil),  // 11: session.IpChangeEvent
+	(*VersionInfo)(nil),    // 12: session.VersionInfo
+	(*AgentConfig)(nil),    // 13: session.AgentConfig
 }
 var file_proto_session_proto_depIdxs = []int32{
-	4, // 0: session.SessionList.sessions:type_name -> session.Session
-	6, // 1: session.IpChangeList.ip_changes:type_name -> session.IpChangeEvent
-	0, // 2: session.SessionManager.SubmitSession:input_type -> session.LoginEvent
-	2, // 3: session.SessionManager.MonitorSessions:input_type -> session.Empty
-	5, // 4: session.SessionManager.IpChange:input_type -> session.IpChangeList
-	1, // 5: session.SessionManager.SubmitSession:output_type -> session.Ack
-	3, // 6: session.SessionManager.MonitorSessions:output_type -> session.SessionList
-	1, // 7: session.SessionManager.IpChange:output_type -> session.Ack
-	5, // [5:8] is the sub-list for method output_type
-	2, // [2:5] is the sub-list for method input_type
-	2, // [2:2] is the sub-list for extension type_name
-	2, // [2:2] is the sub-list for extension extendee
-	0, // [0:2] is the sub-list for field type_name
+	2,  // 0: session.Ack.ip_changes:type_name -> session.IpChangeResult
+	5,  // 1: session.SessionList.sessions:type_name -> session.Session
+	9,  // 2: session.MapStats.rejected_sessions:type_name -> session.RejectCount
+	11, // 3: session.IpChangeList.ip_changes:type_name -> session.IpChangeEvent
+	0,  // 4: session.SessionManager.SubmitSession:input_type -> session.LoginEvent
+	3,  // 5: session.SessionManager.MonitorSessions:input_type -> session.Empty
+	0,  // 6: session.SessionManager.GetSession:input_type -> session.LoginEvent
+	10, // 7: session.SessionManager.IpChange:input_type -> session.IpChangeList
+	3,  // 8: session.SessionManager.FlushAll:input_type -> session.Empty
+	6,  // 9: session.SessionManager.BlockIp:input_type -> session.BlockRequest
+	6,  // 10: session.SessionManager.UnblockIp:input_type -> session.BlockRequest
+	3,  // 11: session.SessionManager.GetVersion:input_type -> session.Empty
+	3,  // 12: session.SessionManager.GetConfig:input_type -> session.Empty
+	3,  // 13: session.SessionManager.GetMapStats:input_type -> session.Empty
+	7,  // 14: session.SessionManager.PruneStaleEpochs:input_type -> session.PruneRequest
+	1,  // 15: session.SessionManager.SubmitSession:output_type -> session.Ack
+	4,  // 16: session.SessionManager.MonitorSessions:output_type -> session.SessionList
+	5,  // 17: session.SessionManager.GetSession:output_type -> session.Session
+	1,  // 18: session.SessionManager.IpChange:output_type -> session.Ack
+	1,  // 19: session.SessionManager.FlushAll:output_type -> session.Ack
+	1,  // 20: session.SessionManager.BlockIp:output_type -> session.Ack
+	1,  // 21: session.SessionManager.UnblockIp:output_type -> session.Ack
+	12, // 22: session.SessionManager.GetVersion:output_type -> session.VersionInfo
+	13, // 23: session.SessionManager.GetConfig:output_type -> session.AgentConfig
+	8,  // 24: session.SessionManager.GetMapStats:output_type -> session.MapStats
+	1,  // 25: session.SessionManager.PruneStaleEpochs:output_type -> session.Ack
+	15, // [15:26] is the sub-list for method output_type
+	4,  // [4:15] is the sub-list for method input_type
+	4,  // [4:4] is the sub-list for extension type_name
+	4,  // [4:4] is the sub-list for extension extendee
+	0,  // [0:4] is the sub-list for field type_name
 }
 
 func init() { file_proto_session_proto_init() }
@@ -452,13 +1304,15 @@ func file_proto_session_proto_init() {
 	if File_proto_session_proto != nil {
 		return
 	}
+	file_proto_session_proto_msgTypes[1].OneofWrappers = []any{}
+	file_proto_session_proto_msgTypes[13].OneofWrappers = []any{}
 	type x struct{}
 	out := protoimpl.TypeBuilder{
 		File: protoimpl.DescBuilder{
 			GoPackagePath: reflect.TypeOf(x{}).PkgPath(),
 			RawDescriptor: unsafe.Slice(unsafe.StringData(file_proto_session_proto_rawDesc), len(file_proto_session_proto_rawDesc)),
 			NumEnums:      0,
-			NumMessages:   7,
+			NumMessages:   14,
 			NumExtensions: 0,
 			NumServices:   1,
 		},
@@ -19,9 +19,17 @@ import (
 const _ = grpc.SupportPackageIsVersion9
 
 const (
-	SessionManager_SubmitSession_FullMethodName   = "/session.SessionManager/SubmitSession"
-	SessionManager_MonitorSessions_FullMethodName = "/session.SessionManager/MonitorSessions"
-	SessionManager_IpChange_FullMethodName        = "/session.SessionManager/IpChange"
+	SessionManager_SubmitSession_FullMethodName    = "/session.SessionManager/SubmitSession"
+	SessionManager_MonitorSessions_FullMethodName  = "/session.SessionManager/MonitorSessions"
+	SessionManager_GetSession_FullMethodName       = "/session.SessionManager/GetSession"
+	SessionManager_IpChange_FullMethodName         = "/session.SessionManager/IpChange"
+	SessionManager_FlushAll_FullMethodName         = "/session.SessionManager/FlushAll"
+	SessionManager_BlockIp_FullMethodName          = "/session.SessionManager/BlockIp"
+	SessionManager_UnblockIp_FullMethodName        = "/session.SessionManager/UnblockIp"
+	SessionManager_GetVersion_FullMethodName       = "/session.SessionManager/GetVersion"
+	SessionManager_GetConfig_FullMethodName        = "/session.SessionManager/GetConfig"
+	SessionManager_GetMapStats_FullMethodName      = "/session.SessionManager/GetMapStats"
+	SessionManager_PruneStaleEpochs_FullMethodName = "/session.SessionManager/PruneStaleEpochs"
 )
 
 // SessionManagerClient is the client API for SessionManager service.
@@ -30,7 +38,26 @@ const (
 type SessionManagerClient interface {
 	SubmitSession(ctx context.Context, in *LoginEvent, opts ...grpc.CallOption) (*Ack, error)
 	MonitorSessions(ctx context.Context, in *Empty, opts ...grpc.CallOption) (grpc.ServerStreamingClient[SessionList], error)
+	// Looks up the session for exactly this key; NOT_FOUND if there is none.
+	// Only src_ip, dst_ip, dst_port and src_port are used.
+	GetSession(ctx context.Context, in *LoginEvent, opts ...grpc.CallOption) (*Session, error)
 	IpChange(ctx context.Context, in *IpChangeList, opts ...grpc.CallOption) (*Ack, error)
+	// Removes every session at once, e.g. during a security incident.
+	FlushAll(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*Ack, error)
+	// Drops all traffic from an IP, overriding its sessions, until unblocked.
+	BlockIp(ctx context.Context, in *BlockRequest, opts ...grpc.CallOption) (*Ack, error)
+	UnblockIp(ctx context.Context, in *BlockRequest, opts ...grpc.CallOption) (*Ack, error)
+	GetVersion(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*VersionInfo, error)
+	// Effective configuration, to check an agent is set up the way the
+	// controller expects without logging in to it.
+	GetConfig(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*AgentConfig, error)
+	// Session map occupancy, for alerting before the map fills up and the
+	// least recently used sessions start being evicted.
+	GetMapStats(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*MapStats, error)
+	// Removes every session whose epoch is not `epoch`, including sessions
+	// submitted without one. After a restart the controller picks a new epoch,
+	// re-submits the sessions it still wants, then prunes the rest.
+	PruneStaleEpochs(ctx context.Context, in *PruneRequest, opts ...grpc.CallOption) (*Ack, error)
 }
 
 type sessionManagerClient struct {
@@ -70,6 +97,16 @@ func (c *sessionManagerClient) MonitorSessions(ctx context.Context, in *Empty, o
 // This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
 type SessionManager_MonitorSessionsClient = grpc.ServerStreamingClient[SessionList]
 
+func (c *sessionManagerClient) GetSession(ctx context.Context, in *LoginEvent, opts ...grpc.CallOption) (*Session, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(Session)
+	err := c.cc.Invoke(ctx, SessionManager_GetSession_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
 func (c *sessionManagerClient) IpChange(ctx context.Context, in *IpChangeList, opts ...grpc.CallOption) (*Ack, error) {
 	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
 	out := new(Ack)
@@ -80,13 +117,102 @@ func (c *sessionManagerClient) IpChange(ctx context.Context, in *IpChangeList, o
 	return out, nil
 }
 
+func (c *sessionManagerClient) FlushAll(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*Ack, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(Ack)
+	err := c.cc.Invoke(ctx, SessionManager_FlushAll_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) BlockIp(ctx context.Context, in *BlockRequest, opts ...grpc.CallOption) (*Ack, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(Ack)
+	err := c.cc.Invoke(ctx, SessionManager_BlockIp_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) UnblockIp(ctx context.Context, in *BlockRequest, opts ...grpc.CallOption) (*Ack, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(Ack)
+	err := c.cc.Invoke(ctx, SessionManager_UnblockIp_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) GetVersion(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*VersionInfo, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(VersionInfo)
+	err := c.cc.Invoke(ctx, SessionManager_GetVersion_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) GetConfig(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*AgentConfig, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(AgentConfig)
+	err := c.cc.Invoke(ctx, SessionManager_GetConfig_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) GetMapStats(ctx context.Context, in *Empty, opts ...grpc.CallOption) (*MapStats, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(MapStats)
+	err := c.cc.Invoke(ctx, SessionManager_GetMapStats_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *sessionManagerClient) PruneStaleEpochs(ctx context.Context, in *PruneRequest, opts ...grpc.CallOption) (*Ack, error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	out := new(Ack)
+	err := c.cc.Invoke(ctx, SessionManager_PruneStaleEpochs_FullMethodName, in, out, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
 // SessionManagerServer is the server API for SessionManager service.
 // All implementations must embed UnimplementedSessionManagerServer
 // for forward compatibility.
 type SessionManagerServer interface {
 	SubmitSession(context.Context, *LoginEvent) (*Ack, error)
 	MonitorSessions(*Empty, grpc.ServerStreamingServer[SessionList]) error
+	// Looks up the session for exactly this key; NOT_FOUND if there is none.
+	// Only src_ip, dst_ip, dst_port and src_port are used.
+	GetSession(context.Context, *LoginEvent) (*Session, error)
 	IpChange(context.Context, *IpChangeList) (*Ack, error)
+	// Removes every session at once, e.g. during a security incident.
+	FlushAll(context.Context, *Empty) (*Ack, error)
+	// Drops all traffic from an IP, overriding its sessions, until unblocked.
+	BlockIp(context.Context, *BlockRequest) (*Ack, error)
+	UnblockIp(context.Context, *BlockRequest) (*Ack, error)
+	GetVersion(context.Context, *Empty) (*VersionInfo, error)
+	// Effective configuration, to check an agent is set up the way the
+	// controller expects without logging in to it.
+	GetConfig(context.Context, *Empty) (*AgentConfig, error)
+	// Session map occupancy, for alerting before the map fills up and the
+	// least recently used sessions start being evicted.
+	GetMapStats(context.Context, *Empty) (*MapStats, error)
+	// Removes every session whose epoch is not `epoch`, including sessions
+	// submitted without one. After a restart the controller picks a new epoch,
+	// re-submits the sessions it still wants, then prunes the rest.
+	PruneStaleEpochs(context.Context, *PruneRequest) (*Ack, error)
 	mustEmbedUnimplementedSessionManagerServer()
 }
 
@@ -103,9 +229,33 @@ func (UnimplementedSessionManagerServer) SubmitSession(context.Context, *LoginEv
 func (UnimplementedSessionManagerServer) MonitorSessions(*Empty, grpc.ServerStreamingServer[SessionList]) error {
 	return status.Error(codes.Unimplemented, "method MonitorSessions not implemented")
 }
+func (UnimplementedSessionManagerServer) GetSession(context.Context, *LoginEvent) (*Session, error) {
+	return nil, status.Error(codes.Unimplemented, "method GetSession not implemented")
+}
 func (UnimplementedSessionManagerServer) IpChange(context.Context, *IpChangeList) (*Ack, error) {
 	return nil, status.Error(codes.Unimplemented, "method IpChange not implemented")
 }
+func (UnimplementedSessionManagerServer) FlushAll(context.Context, *Empty) (*Ack, error) {
+	return nil, status.Error(codes.Unimplemented, "method FlushAll not implemented")
+}
+func (UnimplementedSessionManagerServer) BlockIp(context.Context, *BlockRequest) (*Ack, error) {
+	return nil, status.Error(codes.Unimplemented, "method BlockIp not implemented")
+}
+func (UnimplementedSessionManagerServer) UnblockIp(context.Context, *BlockRequest) (*Ack, error) {
+	return nil, status.Error(codes.Unimplemented, "method UnblockIp not implemented")
+}
+func (UnimplementedSessionManagerServer) GetVersion(context.Context, *Empty) (*VersionInfo, error) {
+	return nil, status.Error(codes.Unimplemented, "method GetVersion not implemented")
+}
+func (UnimplementedSessionManagerServer) GetConfig(context.Context, *Empty) (*AgentConfig, error) {
+	return nil, status.Error(codes.Unimplemented, "method GetConfig not implemented")
+}
+func (UnimplementedSessionManagerServer) GetMapStats(context.Context, *Empty) (*MapStats, error) {
+	return nil, status.Error(codes.Unimplemented, "method GetMapStats not implemented")
+}
+func (UnimplementedSessionManagerServer) PruneStaleEpochs(context.Context, *PruneRequest) (*Ack, error) {
+	return nil, status.Error(codes.Unimplemented, "method PruneStaleEpochs not implemented")
+}
 func (UnimplementedSessionManagerServer) mustEmbedUnimplementedSessionManagerServer() {}
 func (UnimplementedSessionManagerServer) testEmbeddedByValue()                        {}
 
@@ -156,6 +306,24 @@ func _SessionManager_MonitorSessions_Handler(srv interface{}, stream grpc.Server
 // This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
 type SessionManager_MonitorSessionsServer = grpc.ServerStreamingServer[SessionList]
 
+func _SessionManager_GetSession_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(LoginEvent)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).GetSession(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_GetSession_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).GetSession(ctx, req.(*LoginEvent))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
 func _SessionManager_IpChange_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
 	in := new(IpChangeList)
 	if err := dec(in); err != nil {
@@ -174,6 +342,132 @@ func _SessionManager_IpChange_Handler(srv interface{}, ctx context.Context, dec
 	return interceptor(ctx, in, info, handler)
 }
 
+func _SessionManager_FlushAll_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(Empty)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).FlushAll(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_FlushAll_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).FlushAll(ctx, req.(*Empty))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_BlockIp_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(BlockRequest)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).BlockIp(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_BlockIp_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).BlockIp(ctx, req.(*BlockRequest))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_UnblockIp_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(BlockRequest)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).UnblockIp(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_UnblockIp_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).UnblockIp(ctx, req.(*BlockRequest))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_GetVersion_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(Empty)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).GetVersion(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_GetVersion_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).GetVersion(ctx, req.(*Empty))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_GetConfig_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(Empty)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).GetConfig(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_GetConfig_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).GetConfig(ctx, req.(*Empty))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_GetMapStats_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(Empty)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).GetMapStats(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_GetMapStats_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).GetMapStats(ctx, req.(*Empty))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _SessionManager_PruneStaleEpochs_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(PruneRequest)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(SessionManagerServer).PruneStaleEpochs(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: SessionManager_PruneStaleEpochs_FullMethodName,
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(SessionManagerServer).PruneStaleEpochs(ctx, req.(*PruneRequest))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
 // SessionManager_ServiceDesc is the grpc.ServiceDesc for SessionManager service.
 // It's only intended for direct use with grpc.RegisterService,
 // and not to be introspected or modified (even as a copy)
@@ -185,10 +479,42 @@ var SessionManager_ServiceDesc = grpc.ServiceDesc{
 			MethodName: "SubmitSession",
 			Handler:    _SessionManager_SubmitSession_Handler,
 		},
+		{
+			MethodName: "GetSession",
+			Handler:    _SessionManager_GetSession_Handler,
+		},
 		{
 			MethodName: "IpChange",
 			Handler:    _SessionManager_IpChange_Handler,
 		},
+		{
+			MethodName: "FlushAll",
+			Handler:    _SessionManager_FlushAll_Handler,
+		},
+		{
+			MethodName: "BlockIp",
+			Handler:    _SessionManager_BlockIp_Handler,
+		},
+		{
+			MethodName: "UnblockIp",
+			Handler:    _SessionManager_UnblockIp_Handler,
+		},
+		{
+			MethodName: "GetVersion",
+			Handler:    _SessionManager_GetVersion_Handler,
+		},
+		{
+			MethodName: "GetConfig",
+			Handler:    _SessionManager_GetConfig_Handler,
+		},
+		{
+			MethodName: "GetMapStats",
+			Handler:    _SessionManager_GetMapStats_Handler,
+		},
+		{
+			MethodName: "PruneStaleEpochs",
+			Handler:    _SessionManager_PruneStaleEpochs_Handler,
+		},
 	},
 	Streams: []grpc.StreamDesc{
 		{
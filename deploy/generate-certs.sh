@@ -6,6 +6,8 @@ CA_CN="Aegis-Root-CA"
 AGENT_CN="aegis-agent"
 CONTROLLER_CN="aegis-controller"
 WEB_CN="localhost"
+# Optional controller endpoint (ip:port) embedded in the CA for agent --pair-from-cert
+PAIR_CONTROLLER="${PAIR_CONTROLLER:-}"
 
 # Create certs directory
 mkdir -p "$CERT_DIR"
@@ -15,9 +17,14 @@ echo "Generating Aegis Certificates in $CERT_DIR..."
 
 # Generate root certificate authority (CA)
 echo "-> Generating Root CA..."
+CA_EXT=()
+if [ -n "$PAIR_CONTROLLER" ]; then
+  CA_EXT=(-addext "subjectAltName=URI:aegis://$PAIR_CONTROLLER")
+fi
 openssl req -x509 -newkey rsa:4096 -days 3650 -nodes \
   -keyout ca.key -out ca.pem \
-  -subj "/C=IN/ST=Karnataka/L=Bengaluru/O=Aegis/CN=$CA_CN"
+  -subj "/C=IN/ST=Karnataka/L=Bengaluru/O=Aegis/CN=$CA_CN" \
+  "${CA_EXT[@]}"
 
 # Generate agent gRPC server sertificates (mTLS)
 echo "-> Generating Agent Server Certificates..."
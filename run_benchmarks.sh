@@ -41,20 +41,23 @@ else
     # Run all benchmarks
     echo -e "${BLUE}Running all benchmarks...${NC}"
     
-    echo -e "\n${YELLOW}1/5: Attack Scenario (Dropped Packets)${NC}"
+    echo -e "\n${YELLOW}1/6: Attack Scenario (Dropped Packets)${NC}"
     cargo test benchmark_attack_scenario_dropped_packets -- --ignored --nocapture
     
-    echo -e "\n${YELLOW}2/5: Legitimate Traffic (Accepted Packets)${NC}"
+    echo -e "\n${YELLOW}2/6: Legitimate Traffic (Accepted Packets)${NC}"
     cargo test benchmark_legitimate_traffic_accepted_packets -- --ignored --nocapture
     
-    echo -e "\n${YELLOW}3/5: Mixed Traffic (Attack + Legitimate)${NC}"
+    echo -e "\n${YELLOW}3/6: Mixed Traffic (Attack + Legitimate)${NC}"
     cargo test benchmark_mixed_traffic -- --ignored --nocapture
     
-    echo -e "\n${YELLOW}4/5: Map Operations Performance${NC}"
+    echo -e "\n${YELLOW}4/6: Map Operations Performance${NC}"
     cargo test benchmark_map_operations -- --ignored --nocapture
     
-    echo -e "\n${YELLOW}5/5: Scalability with Varying Map Sizes${NC}"
+    echo -e "\n${YELLOW}5/6: Scalability with Varying Map Sizes${NC}"
     cargo test benchmark_scalability_varying_map_sizes -- --ignored --nocapture
+
+    echo -e "\n${YELLOW}6/6: Submit Session End-to-End${NC}"
+    cargo test benchmark_submit_session_throughput -- --ignored --nocapture
 fi
 
 echo -e "\n${GREEN}"